@@ -0,0 +1,6 @@
+ALTER TABLE requests ADD COLUMN deadline_at TIMESTAMP WITH TIME ZONE;
+
+ALTER TABLE requests DROP CONSTRAINT requests_status_check;
+ALTER TABLE requests ADD CONSTRAINT requests_status_check CHECK (status IN ('open', 'in-progress', 'completed', 'expired'));
+
+CREATE INDEX idx_requests_deadline_at ON requests(deadline_at) WHERE deadline_at IS NOT NULL;
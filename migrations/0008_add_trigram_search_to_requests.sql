@@ -0,0 +1,4 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX idx_requests_place_name_trgm ON requests USING GIN (place_name gin_trgm_ops);
+CREATE INDEX idx_requests_description_trgm ON requests USING GIN (description gin_trgm_ops);
@@ -0,0 +1,25 @@
+CREATE TABLE request_comments (
+    id SERIAL PRIMARY KEY,
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE NOT NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    body TEXT NOT NULL,
+    pinned_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_request_comments_request_id ON request_comments(request_id, created_at);
+-- ピン留めできるコメントはリクエストごとに1件まで
+CREATE UNIQUE INDEX idx_request_comments_pinned ON request_comments(request_id) WHERE pinned_at IS NOT NULL;
+
+-- コメント通知の購読者（リクエスト作成者とコメント投稿者）
+CREATE TABLE request_comment_subscriptions (
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE NOT NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    unsubscribe_token VARCHAR(255) UNIQUE NOT NULL,
+    unsubscribed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (request_id, user_id)
+);
+
+CREATE INDEX idx_request_comment_subscriptions_user_id ON request_comment_subscriptions(user_id);
@@ -0,0 +1,26 @@
+CREATE TABLE watch_areas (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    lat DOUBLE PRECISION NOT NULL,
+    lng DOUBLE PRECISION NOT NULL,
+    radius_m DOUBLE PRECISION NOT NULL CHECK (radius_m > 0),
+    unsubscribe_token VARCHAR(255) UNIQUE NOT NULL,
+    unsubscribed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_watch_areas_user_id ON watch_areas(user_id);
+
+-- 監視エリア内に作成されたリクエスト。notified_at が NULL のものが未通知
+CREATE TABLE watch_area_matches (
+    id SERIAL PRIMARY KEY,
+    watch_area_id INTEGER REFERENCES watch_areas(id) ON DELETE CASCADE NOT NULL,
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE NOT NULL,
+    notified_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (watch_area_id, request_id)
+);
+
+CREATE INDEX idx_watch_area_matches_pending ON watch_area_matches(watch_area_id) WHERE notified_at IS NULL;
+CREATE INDEX idx_watch_area_matches_notified_at ON watch_area_matches(watch_area_id, notified_at) WHERE notified_at IS NOT NULL;
@@ -0,0 +1,6 @@
+-- 逆ジオコーディングで解決した都道府県・市区町村
+ALTER TABLE requests
+    ADD COLUMN prefecture VARCHAR(100),
+    ADD COLUMN city VARCHAR(200);
+
+CREATE INDEX idx_requests_prefecture_city ON requests (prefecture, city);
@@ -0,0 +1,29 @@
+-- リクエストのカテゴリ（値の一覧はアプリ側の REQUEST_CATEGORIES と揃える）
+ALTER TABLE requests
+    ADD COLUMN category VARCHAR(30)
+    CHECK (category IN ('landmark', 'food', 'nature', 'event', 'real-estate', 'street', 'shop', 'other'));
+
+CREATE INDEX idx_requests_category ON requests(category);
+
+-- 正規化済みの自由入力タグ
+CREATE TABLE tags (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(30) UNIQUE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE request_tags (
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE NOT NULL,
+    tag_id INTEGER REFERENCES tags(id) ON DELETE CASCADE NOT NULL,
+    PRIMARY KEY (request_id, tag_id)
+);
+
+CREATE INDEX idx_request_tags_tag_id ON request_tags(tag_id);
+
+-- リクエストに付いたタグ名を名前順の配列で返す（リクエストを返す各クエリから使う）
+CREATE FUNCTION request_tag_names(target_request_id INTEGER) RETURNS TEXT[] AS $$
+    SELECT COALESCE(array_agg(t.name ORDER BY t.name), '{}')
+    FROM request_tags rt
+    JOIN tags t ON t.id = rt.tag_id
+    WHERE rt.request_id = target_request_id
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,10 @@
+-- 作成者以外に公開する位置の精度（exact 以外は位置をぼかして返す）
+ALTER TABLE requests
+    ADD COLUMN location_precision VARCHAR(10) NOT NULL DEFAULT 'exact'
+    CHECK (location_precision IN ('exact', '100m', '1km'));
+
+-- リクエストを引き受けた撮影者（引き受けている間は status が in-progress になる）
+ALTER TABLE requests
+    ADD COLUMN claimed_by INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_requests_claimed_by ON requests(claimed_by);
@@ -0,0 +1,8 @@
+-- 投稿数の上限はリクエストごとに作成者が設定する（従来どおり既定は1人1枚、全体は無制限）
+ALTER TABLE requests
+    ADD COLUMN max_submissions_per_user INTEGER NOT NULL DEFAULT 1 CHECK (max_submissions_per_user > 0),
+    ADD COLUMN max_total_submissions INTEGER CHECK (max_total_submissions > 0);
+
+ALTER TABLE pictures DROP CONSTRAINT pictures_user_request_unique;
+
+CREATE INDEX idx_pictures_request_id_user_id ON pictures(request_id, user_id);
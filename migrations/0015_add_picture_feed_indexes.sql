@@ -0,0 +1,4 @@
+-- 写真一覧のキーセットページネーション用インデックス
+CREATE INDEX idx_pictures_created_at_id ON pictures(created_at DESC, id DESC);
+CREATE INDEX idx_pictures_user_id_created_at_id ON pictures(user_id, created_at DESC, id DESC);
+CREATE INDEX idx_pictures_request_id_created_at_id ON pictures(request_id, created_at DESC, id DESC);
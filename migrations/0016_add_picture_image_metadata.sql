@@ -0,0 +1,5 @@
+-- アップロード時にファイルの中身から判定した画像の形式とサイズ（既存の行は不明のため NULL）
+ALTER TABLE pictures
+    ADD COLUMN width INTEGER CHECK (width > 0),
+    ADD COLUMN height INTEGER CHECK (height > 0),
+    ADD COLUMN mime_type VARCHAR(20);
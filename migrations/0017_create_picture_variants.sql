@@ -0,0 +1,25 @@
+-- 一覧表示用に縮小した画像。長辺のピクセル数と形式ごとに1つ
+CREATE TABLE picture_variants (
+    id SERIAL PRIMARY KEY,
+    picture_id INTEGER NOT NULL REFERENCES pictures(id) ON DELETE CASCADE,
+    size INTEGER NOT NULL CHECK (size > 0),
+    format VARCHAR(10) NOT NULL CHECK (format IN ('jpeg', 'webp')),
+    width INTEGER NOT NULL CHECK (width > 0),
+    height INTEGER NOT NULL CHECK (height > 0),
+    image_url TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CONSTRAINT picture_variants_picture_size_format_unique UNIQUE (picture_id, size, format)
+);
+
+-- 写真のレスポンスに埋め込む `{"256_webp": {...}, ...}` 形式の一覧
+CREATE FUNCTION picture_variants_json(target_picture_id INTEGER) RETURNS JSONB AS $$
+    SELECT COALESCE(
+        jsonb_object_agg(
+            pv.size || '_' || pv.format,
+            jsonb_build_object('url', pv.image_url, 'width', pv.width, 'height', pv.height)
+        ),
+        '{}'::jsonb
+    )
+    FROM picture_variants pv
+    WHERE pv.picture_id = target_picture_id
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,8 @@
+-- アップロード時に EXIF から取り出した撮影情報。ファイル自体からは向き以外のメタデータを取り除いて保存する
+ALTER TABLE pictures
+    ADD COLUMN taken_at TIMESTAMP,
+    ADD COLUMN camera_make VARCHAR(100),
+    ADD COLUMN camera_model VARCHAR(100),
+    ADD COLUMN orientation SMALLINT CHECK (orientation BETWEEN 1 AND 8),
+    ADD COLUMN gps_lat DOUBLE PRECISION CHECK (gps_lat BETWEEN -90 AND 90),
+    ADD COLUMN gps_lng DOUBLE PRECISION CHECK (gps_lng BETWEEN -180 AND 180);
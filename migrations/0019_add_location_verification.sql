@@ -0,0 +1,11 @@
+-- 投稿写真の撮影位置がリクエストの地点からどれだけ離れていてよいか（メートル）
+ALTER TABLE requests
+    ADD COLUMN location_tolerance_m INTEGER NOT NULL DEFAULT 200 CHECK (location_tolerance_m > 0);
+
+-- リクエストへの投稿時に、EXIF の撮影位置かクライアントが送った現在地でリクエストの地点との距離を確かめた結果。
+-- リクエストに紐づかない写真では NULL
+ALTER TABLE pictures
+    ADD COLUMN reported_lat DOUBLE PRECISION CHECK (reported_lat BETWEEN -90 AND 90),
+    ADD COLUMN reported_lng DOUBLE PRECISION CHECK (reported_lng BETWEEN -180 AND 180),
+    ADD COLUMN location_verified BOOLEAN,
+    ADD COLUMN location_distance_m DOUBLE PRECISION;
@@ -0,0 +1,14 @@
+-- クライアントが署名付き URL でストレージへ直接アップロードしている途中の写真。
+-- 完了の通知で pictures に移し、期限までに完了しなかったものはバックグラウンドで削除する
+CREATE TABLE picture_uploads (
+    id UUID PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE,
+    object_key TEXT NOT NULL UNIQUE,
+    content_type VARCHAR(20) NOT NULL,
+    content_length BIGINT NOT NULL CHECK (content_length > 0),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_picture_uploads_expires_at ON picture_uploads (expires_at);
@@ -0,0 +1,54 @@
+-- 画像の保存先を URL ではなくストレージのオブジェクトのキーで持つ。
+-- URL は読み出すときに今の設定から組み立てるため、エンドポイントや CDN を変えても古い写真が壊れない。
+-- image_url はストレージの外にある画像を指す写真にだけ残す
+ALTER TABLE pictures
+    ADD COLUMN storage_key TEXT,
+    ADD COLUMN storage_backend VARCHAR(20),
+    ALTER COLUMN image_url DROP NOT NULL;
+
+ALTER TABLE picture_variants
+    ADD COLUMN storage_key TEXT,
+    ALTER COLUMN image_url DROP NOT NULL;
+
+-- 既存の URL（非公開バケットではキーそのもの）から、アップロード時に付けた `pictures/{ユーザーID}/` 以降をキーとして取り出す。
+-- エンドポイントやバケット名の形に左右されないよう、キーの形だけを見る
+UPDATE pictures
+SET storage_key = substring(image_url FROM '(?:^|/)(pictures/[0-9]+/[^?#]+)(?:[?#].*)?$');
+
+UPDATE pictures
+SET storage_backend = 's3', image_url = NULL
+WHERE storage_key IS NOT NULL;
+
+UPDATE picture_variants
+SET storage_key = substring(image_url FROM '(?:^|/)(pictures/[0-9]+/[^?#]+)(?:[?#].*)?$');
+
+UPDATE picture_variants
+SET image_url = NULL
+WHERE storage_key IS NOT NULL;
+
+ALTER TABLE pictures
+    ADD CONSTRAINT pictures_storage_check CHECK (
+        (storage_key IS NULL) = (storage_backend IS NULL)
+        AND (storage_key IS NOT NULL OR image_url IS NOT NULL)
+    );
+
+ALTER TABLE picture_variants
+    ADD CONSTRAINT picture_variants_storage_check CHECK (storage_key IS NOT NULL OR image_url IS NOT NULL);
+
+-- url にはキーか外部の URL が入る。キーがあればアプリケーションが URL に置き換える
+CREATE OR REPLACE FUNCTION picture_variants_json(target_picture_id INTEGER) RETURNS JSONB AS $$
+    SELECT COALESCE(
+        jsonb_object_agg(
+            pv.size || '_' || pv.format,
+            jsonb_build_object(
+                'url', COALESCE(pv.image_url, pv.storage_key),
+                'storage_key', pv.storage_key,
+                'width', pv.width,
+                'height', pv.height
+            )
+        ),
+        '{}'::jsonb
+    )
+    FROM picture_variants pv
+    WHERE pv.picture_id = target_picture_id
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,27 @@
+-- tus プロトコルで複数のリクエストに分けて送られている途中の写真。
+-- 受け取ったデータはストレージのマルチパートアップロードのパートとして置き、
+-- パートの大きさに満たない末尾だけを別のオブジェクトに置いておく
+CREATE TABLE resumable_uploads (
+    id UUID PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    request_id INTEGER REFERENCES requests(id) ON DELETE CASCADE,
+    reported_lat DOUBLE PRECISION,
+    reported_lng DOUBLE PRECISION,
+    object_key TEXT NOT NULL UNIQUE,
+    content_type VARCHAR(20) NOT NULL,
+    upload_length BIGINT NOT NULL CHECK (upload_length > 0),
+    upload_offset BIGINT NOT NULL DEFAULT 0 CHECK (upload_offset >= 0 AND upload_offset <= upload_length),
+    multipart_upload_id TEXT NOT NULL,
+    part_size BIGINT NOT NULL CHECK (part_size > 0),
+    parts JSONB NOT NULL DEFAULT '[]',
+    -- 受け取り中のリクエストが持つロック。期限が切れたら別のリクエストが引き継げる
+    lock_token UUID,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    -- 写真として保存し終えたら設定する
+    picture_id INTEGER REFERENCES pictures(id) ON DELETE SET NULL,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_resumable_uploads_expires_at ON resumable_uploads (expires_at);
@@ -0,0 +1,15 @@
+-- ストレージ上のオブジェクトと DB の行が食い違わないよう、オブジェクトの書き込みと削除を記録しておく送信箱。
+-- 'upload' はオブジェクトを置く前に記録し、行を作るのと同じトランザクションで取り除く。
+-- 猶予を過ぎても残っていれば、行を作れなかったオブジェクトとして消す。
+-- 'delete' は行を消すのと同じトランザクションで記録し、オブジェクトを消し終えたら取り除く
+CREATE TABLE storage_operations (
+    id BIGSERIAL PRIMARY KEY,
+    operation VARCHAR(10) NOT NULL CHECK (operation IN ('upload', 'delete')),
+    object_key TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_storage_operations_next_attempt_at ON storage_operations (next_attempt_at);
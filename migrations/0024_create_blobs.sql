@@ -0,0 +1,11 @@
+-- 写真の元画像と縮小画像は、内容の SHA-256 から決まるキーに置き、同じ内容のファイルを何度アップロードされても1つだけ持つ。
+-- ref_count はそのオブジェクトを指している pictures と picture_variants の行の数で、
+-- 0 になった行は、送信箱の片付けがオブジェクトを消すときに一緒に消す。
+-- 内容から決まるキーを使う前に置いたオブジェクトはここに記録せず、これまでどおり行を消すときに消す
+CREATE TABLE blobs (
+    storage_key TEXT PRIMARY KEY,
+    sha256 CHAR(64) NOT NULL,
+    size BIGINT NOT NULL CHECK (size >= 0),
+    ref_count INTEGER NOT NULL DEFAULT 0 CHECK (ref_count >= 0),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
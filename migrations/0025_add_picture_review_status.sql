@@ -0,0 +1,5 @@
+-- リクエストへの投稿写真を依頼者が確認した結果（値の一覧はアプリ側の REVIEW_STATUSES と揃える）。
+-- リクエストに紐づかない写真では使わない
+ALTER TABLE pictures
+    ADD COLUMN review_status VARCHAR(20) NOT NULL DEFAULT 'pending'
+    CHECK (review_status IN ('pending', 'accepted', 'rejected'));
@@ -0,0 +1,17 @@
+-- 完了を通知された直接アップロードは、バックグラウンドで写真として保存する。
+-- 一時的な失敗は next_attempt_at まで待って再試行し、画像として受け付けられなかったものは failed_at を記録する。
+-- 保留中の行は写真の行を作るのと同じトランザクションで削除する
+ALTER TABLE picture_uploads
+    ADD COLUMN reported_lat DOUBLE PRECISION CHECK (reported_lat BETWEEN -90 AND 90),
+    ADD COLUMN reported_lng DOUBLE PRECISION CHECK (reported_lng BETWEEN -180 AND 180),
+    ADD COLUMN completed_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN last_error TEXT,
+    ADD COLUMN next_attempt_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN failed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_picture_uploads_next_attempt_at ON picture_uploads (next_attempt_at)
+    WHERE completed_at IS NOT NULL AND failed_at IS NULL;
+
+-- 写真の元になった直接アップロード。完了を通知したクライアントが、保存された写真を確かめるのに使う
+ALTER TABLE pictures ADD COLUMN upload_id UUID UNIQUE;
@@ -0,0 +1,2 @@
+-- 非公開のリクエストへの投稿写真は、投稿者と依頼者にだけ画像の URL を返す
+ALTER TABLE requests ADD COLUMN is_private BOOLEAN NOT NULL DEFAULT false;
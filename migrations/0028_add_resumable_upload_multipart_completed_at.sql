@@ -0,0 +1,3 @@
+-- パートをつなげ終えたマルチパートアップロードは、もう一度つなげたり中断したりできない。
+-- 写真として保存する前に止まった場合に、再試行や掃除でつなげ直さないよう記録する
+ALTER TABLE resumable_uploads ADD COLUMN multipart_completed_at TIMESTAMP WITH TIME ZONE;
@@ -1,15 +1,35 @@
 use axum::{response::Html, routing::get, Router};
 
 use crate::{
-  domains::{picture::rest::picture_routes, request::rest::request_routes, user::rest::user_routes},
+  domains::{
+    comment::rest::comment_routes, picture::rest::picture_routes, request::rest::request_routes, tag::rest::tag_routes,
+    upload::rest::upload_routes, user::rest::user_routes, watch_area::rest::watch_area_routes,
+  },
   state::SharedAppState,
 };
 
 pub fn create_app(state: SharedAppState) -> Router {
-  Router::new()
+  // ローカルのストレージでは、アプリ自身がファイルの配信とアップロードの受け取りを行う
+  let storage_routes = state.storage.routes();
+
+  let app = Router::new()
     .route("/", get(hello_world_handler))
-    .nest("/api/v1", user_routes().merge(picture_routes()).merge(request_routes()))
-    .with_state(state)
+    .nest(
+      "/api/v1",
+      user_routes()
+        .merge(picture_routes())
+        .merge(request_routes())
+        .merge(comment_routes())
+        .merge(watch_area_routes())
+        .merge(tag_routes())
+        .merge(upload_routes()),
+    )
+    .with_state(state);
+
+  match storage_routes {
+    Some(routes) => app.merge(routes),
+    None => app,
+  }
 }
 
 pub async fn hello_world_handler() -> Html<String> {
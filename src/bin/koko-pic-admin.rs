@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use dotenvy::dotenv;
+
+use koko_pic_api::db::pool::create_pool;
+use koko_pic_api::domains::storage_operation::{
+  model::{BackfillReport, GcAction, GcOptions, GcReport},
+  service::{StorageOperationService, QUARANTINE_PREFIX},
+};
+use koko_pic_api::storage::init_storage;
+
+const USAGE: &str = "\
+Usage: koko-pic-admin storage gc [--delete | --quarantine] [--dry-run] [--min-age-secs <SECS>]
+       koko-pic-admin storage backfill-keys [--dry-run]
+
+Compares objects under pictures/ in the bucket with rows in pictures and picture_variants,
+and reports objects no row points at and rows whose object is missing.
+
+Options:
+  --delete              Delete orphan objects and rows whose object is missing
+  --quarantine          Move orphan objects under quarantine/ (rows are only reported)
+  --dry-run             Report what would be changed without changing anything
+  --min-age-secs <SECS> Ignore objects newer than this (default: 86400)
+
+backfill-keys replaces image_url with storage_key on rows whose URL points at the configured
+bucket. Rows pointing elsewhere keep their URL and are listed.
+
+Options:
+  --dry-run             Report what would be converted without changing anything";
+
+enum Command {
+  Gc(GcOptions),
+  BackfillKeys { dry_run: bool },
+}
+
+/// 行を作っている途中のオブジェクトを消さないよう、これより新しいオブジェクトは既定で対象にしない
+const DEFAULT_MIN_AGE_SECS: u64 = 24 * 60 * 60;
+const PICTURE_PREFIX: &str = "pictures/";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  dotenv().ok();
+
+  tracing_subscriber::fmt::init();
+
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let command = match args.split_first_chunk::<2>() {
+    Some(([group, command], rest)) if group == "storage" && command == "gc" => Command::Gc(parse_gc_options(rest)?),
+    Some(([group, command], rest)) if group == "storage" && command == "backfill-keys" => Command::BackfillKeys {
+      dry_run: parse_backfill_options(rest)?,
+    },
+    _ => {
+      eprintln!("{}", USAGE);
+      std::process::exit(2);
+    }
+  };
+
+  let pool = create_pool().await?;
+  let storage = init_storage().await?;
+  let service = StorageOperationService::new(pool, storage);
+
+  match command {
+    Command::Gc(options) => {
+      let report = service.garbage_collect(&options).await?;
+      print_report(&options, &report);
+    }
+    Command::BackfillKeys { dry_run } => {
+      let report = service.backfill_storage_keys(dry_run).await?;
+      print_backfill_report(dry_run, &report);
+    }
+  }
+
+  Ok(())
+}
+
+fn parse_gc_options(args: &[String]) -> Result<GcOptions> {
+  let mut options = GcOptions {
+    prefix: PICTURE_PREFIX.to_string(),
+    action: GcAction::Report,
+    dry_run: false,
+    min_age: Duration::from_secs(DEFAULT_MIN_AGE_SECS),
+  };
+
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--delete" | "--quarantine" if options.action != GcAction::Report => {
+        bail!("--delete and --quarantine cannot be used together")
+      }
+      "--delete" => options.action = GcAction::Delete,
+      "--quarantine" => options.action = GcAction::Quarantine,
+      "--dry-run" => options.dry_run = true,
+      "--min-age-secs" => {
+        let secs = args
+          .next()
+          .and_then(|value| value.parse().ok())
+          .ok_or_else(|| anyhow::anyhow!("--min-age-secs requires a number of seconds"))?;
+        options.min_age = Duration::from_secs(secs);
+      }
+      other => bail!("Unknown option: {}\n\n{}", other, USAGE),
+    }
+  }
+
+  Ok(options)
+}
+
+fn parse_backfill_options(args: &[String]) -> Result<bool> {
+  match args {
+    [] => Ok(false),
+    [flag] if flag == "--dry-run" => Ok(true),
+    [other, ..] => bail!("Unknown option: {}\n\n{}", other, USAGE),
+  }
+}
+
+fn print_backfill_report(dry_run: bool, report: &BackfillReport) {
+  if dry_run {
+    println!("Dry run: {} rows would be converted to storage keys", report.converted);
+  } else {
+    println!("Converted {} rows to storage keys", report.converted);
+  }
+
+  println!("Rows left with URLs outside this storage: {}", report.skipped.len());
+  for reference in &report.skipped {
+    match reference.variant_id {
+      Some(variant_id) => println!(
+        "  picture {} variant {}: {}",
+        reference.picture_id, variant_id, reference.image_url
+      ),
+      None => println!("  picture {}: {}", reference.picture_id, reference.image_url),
+    }
+  }
+}
+
+fn print_report(options: &GcOptions, report: &GcReport) {
+  println!(
+    "Scanned {} objects under {} ({} skipped as recent or pending)",
+    report.scanned_objects, options.prefix, report.skipped_objects
+  );
+
+  println!("Orphan objects: {}", report.orphan_objects.len());
+  for key in &report.orphan_objects {
+    println!("  {}", key);
+  }
+
+  println!("Rows pointing at missing objects: {}", report.missing_objects.len());
+  for reference in &report.missing_objects {
+    match reference.variant_id {
+      Some(variant_id) => println!(
+        "  picture {} variant {}: {}",
+        reference.picture_id, variant_id, reference.storage_key
+      ),
+      None => println!("  picture {}: {}", reference.picture_id, reference.storage_key),
+    }
+  }
+
+  match (options.action, options.dry_run) {
+    (GcAction::Report, _) => {}
+    (GcAction::Delete, true) => println!("Dry run: nothing was deleted"),
+    (GcAction::Quarantine, true) => println!("Dry run: nothing was moved"),
+    (GcAction::Delete, false) => println!(
+      "Deleted {} objects and {} rows",
+      report.removed_objects, report.removed_rows
+    ),
+    (GcAction::Quarantine, false) => println!("Moved {} objects under {}", report.removed_objects, QUARANTINE_PREFIX),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+  }
+
+  #[test]
+  fn test_parse_gc_options() {
+    let options = parse_gc_options(&args(&["--delete", "--dry-run", "--min-age-secs", "60"])).unwrap();
+    assert_eq!(options.action, GcAction::Delete);
+    assert!(options.dry_run);
+    assert_eq!(options.min_age, Duration::from_secs(60));
+
+    let options = parse_gc_options(&[]).unwrap();
+    assert_eq!(options.action, GcAction::Report);
+    assert_eq!(options.min_age, Duration::from_secs(DEFAULT_MIN_AGE_SECS));
+
+    assert!(parse_gc_options(&args(&["--delete", "--quarantine"])).is_err());
+    assert!(parse_gc_options(&args(&["--min-age-secs"])).is_err());
+    assert!(parse_gc_options(&args(&["--force"])).is_err());
+  }
+
+  #[test]
+  fn test_parse_backfill_options() {
+    assert!(!parse_backfill_options(&[]).unwrap());
+    assert!(parse_backfill_options(&args(&["--dry-run"])).unwrap());
+    assert!(parse_backfill_options(&args(&["--delete"])).is_err());
+    assert!(parse_backfill_options(&args(&["--dry-run", "--dry-run"])).is_err());
+  }
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct Comment {
+  pub id: i32,
+  pub request_id: i32,
+  pub user_id: i32,
+  pub body: String,
+  pub pinned_at: Option<DateTime<Utc>>,
+  pub created_at: Option<DateTime<Utc>>,
+  pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommentsResponse {
+  pub comments: Vec<Comment>,
+  pub total: i64,
+  pub page: i64,
+  pub per_page: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
+pub struct CreateCommentRequest {
+  #[validate(length(min = 1, max = 2000, message = "コメントは1文字以上2000文字以内である必要があります"))]
+  pub body: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
+pub struct UpdateCommentRequest {
+  #[validate(length(min = 1, max = 2000, message = "コメントは1文字以上2000文字以内である必要があります"))]
+  pub body: String,
+}
+
+/// コメント通知の送信先
+#[derive(Debug, Clone, FromRow)]
+pub struct CommentSubscriber {
+  pub user_id: i32,
+  pub email: String,
+  pub unsubscribe_token: String,
+}
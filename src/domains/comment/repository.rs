@@ -0,0 +1,403 @@
+use sqlx::{Executor, PgPool, Postgres};
+
+use super::model::{Comment, CommentSubscriber};
+
+pub async fn find_by_request_id(
+  db: &PgPool,
+  request_id: i32,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<Comment>, sqlx::Error> {
+  find_by_request_id_with_executor(db, request_id, limit, offset).await
+}
+
+pub async fn find_by_request_id_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<Comment>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  // ピン留めされたコメントを先頭に、残りは投稿順に並べる
+  let comments = sqlx::query_as!(
+    Comment,
+    r#"
+      SELECT id, request_id, user_id, body, pinned_at, created_at, updated_at
+      FROM request_comments
+      WHERE request_id = $1
+      ORDER BY pinned_at IS NULL, created_at ASC, id ASC
+      LIMIT $2 OFFSET $3
+    "#,
+    request_id,
+    limit,
+    offset
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(comments)
+}
+
+pub async fn count_by_request_id(db: &PgPool, request_id: i32) -> Result<i64, sqlx::Error> {
+  count_by_request_id_with_executor(db, request_id).await
+}
+
+pub async fn count_by_request_id_with_executor<'e, E>(executor: E, request_id: i32) -> Result<i64, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let count = sqlx::query_scalar!(
+    r#"SELECT COUNT(*) as "count!" FROM request_comments WHERE request_id = $1"#,
+    request_id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(count)
+}
+
+pub async fn create(db: &PgPool, request_id: i32, user_id: i32, body: &str) -> Result<Comment, sqlx::Error> {
+  create_with_executor(db, request_id, user_id, body).await
+}
+
+pub async fn create_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  user_id: i32,
+  body: &str,
+) -> Result<Comment, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let comment = sqlx::query_as!(
+    Comment,
+    r#"
+      INSERT INTO request_comments (request_id, user_id, body)
+      VALUES ($1, $2, $3)
+      RETURNING id, request_id, user_id, body, pinned_at, created_at, updated_at
+    "#,
+    request_id,
+    user_id,
+    body
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(comment)
+}
+
+pub async fn find_by_id(db: &PgPool, id: i32) -> Result<Option<Comment>, sqlx::Error> {
+  find_by_id_with_executor(db, id).await
+}
+
+pub async fn find_by_id_with_executor<'e, E>(executor: E, id: i32) -> Result<Option<Comment>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let comment = sqlx::query_as!(
+    Comment,
+    r#"
+      SELECT id, request_id, user_id, body, pinned_at, created_at, updated_at
+      FROM request_comments
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(comment)
+}
+
+pub async fn update_body(db: &PgPool, id: i32, body: &str) -> Result<Comment, sqlx::Error> {
+  update_body_with_executor(db, id, body).await
+}
+
+pub async fn update_body_with_executor<'e, E>(executor: E, id: i32, body: &str) -> Result<Comment, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let comment = sqlx::query_as!(
+    Comment,
+    r#"
+      UPDATE request_comments
+      SET body = $2, updated_at = NOW()
+      WHERE id = $1
+      RETURNING id, request_id, user_id, body, pinned_at, created_at, updated_at
+    "#,
+    id,
+    body
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(comment)
+}
+
+pub async fn delete(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+  delete_with_executor(db, id).await
+}
+
+pub async fn delete_with_executor<'e, E>(executor: E, id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      DELETE FROM request_comments
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn clear_pin_with_executor<'e, E>(executor: E, request_id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE request_comments
+      SET pinned_at = NULL
+      WHERE request_id = $1 AND pinned_at IS NOT NULL
+    "#,
+    request_id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn set_pinned_with_executor<'e, E>(executor: E, id: i32, pinned: bool) -> Result<Comment, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let comment = sqlx::query_as!(
+    Comment,
+    r#"
+      UPDATE request_comments
+      SET pinned_at = CASE WHEN $2 THEN NOW() ELSE NULL END
+      WHERE id = $1
+      RETURNING id, request_id, user_id, body, pinned_at, created_at, updated_at
+    "#,
+    id,
+    pinned
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(comment)
+}
+
+/// 購読済み・購読解除済みの行がある場合は何もしない（解除した利用者を勝手に再購読させない）
+pub async fn add_subscriber_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  user_id: i32,
+  unsubscribe_token: &str,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      INSERT INTO request_comment_subscriptions (request_id, user_id, unsubscribe_token)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (request_id, user_id) DO NOTHING
+    "#,
+    request_id,
+    user_id,
+    unsubscribe_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn subscribe(db: &PgPool, request_id: i32, user_id: i32, unsubscribe_token: &str) -> Result<(), sqlx::Error> {
+  subscribe_with_executor(db, request_id, user_id, unsubscribe_token).await
+}
+
+pub async fn subscribe_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  user_id: i32,
+  unsubscribe_token: &str,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      INSERT INTO request_comment_subscriptions (request_id, user_id, unsubscribe_token)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (request_id, user_id) DO UPDATE SET unsubscribed_at = NULL
+    "#,
+    request_id,
+    user_id,
+    unsubscribe_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn unsubscribe(db: &PgPool, request_id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+  unsubscribe_with_executor(db, request_id, user_id).await
+}
+
+pub async fn unsubscribe_with_executor<'e, E>(executor: E, request_id: i32, user_id: i32) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE request_comment_subscriptions
+      SET unsubscribed_at = COALESCE(unsubscribed_at, NOW())
+      WHERE request_id = $1 AND user_id = $2
+    "#,
+    request_id,
+    user_id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn unsubscribe_by_token(db: &PgPool, unsubscribe_token: &str) -> Result<bool, sqlx::Error> {
+  unsubscribe_by_token_with_executor(db, unsubscribe_token).await
+}
+
+pub async fn unsubscribe_by_token_with_executor<'e, E>(
+  executor: E,
+  unsubscribe_token: &str,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE request_comment_subscriptions
+      SET unsubscribed_at = COALESCE(unsubscribed_at, NOW())
+      WHERE unsubscribe_token = $1
+    "#,
+    unsubscribe_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// 通知対象の購読者を取得する（投稿者本人と購読解除済みの利用者は除く）
+pub async fn find_active_subscribers(
+  db: &PgPool,
+  request_id: i32,
+  exclude_user_id: i32,
+) -> Result<Vec<CommentSubscriber>, sqlx::Error> {
+  find_active_subscribers_with_executor(db, request_id, exclude_user_id).await
+}
+
+pub async fn find_active_subscribers_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  exclude_user_id: i32,
+) -> Result<Vec<CommentSubscriber>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let subscribers = sqlx::query_as!(
+    CommentSubscriber,
+    r#"
+      SELECT s.user_id, u.email, s.unsubscribe_token
+      FROM request_comment_subscriptions s
+      JOIN users u ON u.id = s.user_id
+      WHERE s.request_id = $1
+        AND s.user_id <> $2
+        AND s.unsubscribed_at IS NULL
+      ORDER BY s.created_at ASC
+    "#,
+    request_id,
+    exclude_user_id
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(subscribers)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::request::repository as request_repository;
+  use crate::test_support::request_payload;
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn pinned_comment_is_listed_first(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "comment-pin@example.com", "Comment Pin", "password123").await?;
+    let request =
+      request_repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "説明")).await?;
+
+    let first = create(&pool, request.id, user.id, "最初のコメント").await?;
+    let second = create(&pool, request.id, user.id, "二番目のコメント").await?;
+    set_pinned_with_executor(&pool, second.id, true).await?;
+
+    let comments = find_by_request_id(&pool, request.id, 10, 0).await?;
+    let ids: Vec<i32> = comments.iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![second.id, first.id]);
+    assert_eq!(count_by_request_id(&pool, request.id).await?, 2);
+
+    let page = find_by_request_id(&pool, request.id, 1, 1).await?;
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].id, first.id);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn unsubscribed_users_are_not_notified(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let owner =
+      crate::domains::user::model::User::create(&pool, "sub-owner@example.com", "Sub Owner", "password123").await?;
+    let commenter =
+      crate::domains::user::model::User::create(&pool, "sub-commenter@example.com", "Sub Commenter", "password123")
+        .await?;
+    let request =
+      request_repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "説明")).await?;
+
+    add_subscriber_with_executor(&pool, request.id, owner.id, "owner-token").await?;
+    add_subscriber_with_executor(&pool, request.id, commenter.id, "commenter-token").await?;
+
+    let subscribers = find_active_subscribers(&pool, request.id, commenter.id).await?;
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].email, "sub-owner@example.com");
+
+    assert!(unsubscribe_by_token(&pool, "owner-token").await?);
+    assert!(find_active_subscribers(&pool, request.id, commenter.id)
+      .await?
+      .is_empty());
+
+    // 再度コメントしても購読解除の状態は維持される
+    add_subscriber_with_executor(&pool, request.id, owner.id, "another-token").await?;
+    assert!(find_active_subscribers(&pool, request.id, commenter.id)
+      .await?
+      .is_empty());
+
+    subscribe(&pool, request.id, owner.id, "another-token").await?;
+    assert_eq!(find_active_subscribers(&pool, request.id, commenter.id).await?.len(), 1);
+
+    assert!(!unsubscribe_by_token(&pool, "unknown-token").await?);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,376 @@
+use axum::{
+  extract::{Json, Path, Query, State},
+  http::HeaderMap,
+  response::Json as JsonResponse,
+  routing::{get, patch, put},
+  Router,
+};
+use validator::Validate;
+
+use super::model::{Comment, CommentsResponse, CreateCommentRequest, UpdateCommentRequest};
+use crate::{
+  middleware::auth::auth_middleware,
+  state::{AppState, SharedAppState},
+  utils::pagination::{Page, PageQuery},
+  AppError,
+};
+
+pub fn comment_routes() -> Router<SharedAppState> {
+  Router::new()
+    .route(
+      "/requests/{request_id}/comments",
+      get(list_comments_handler).post(create_comment_handler),
+    )
+    .route(
+      "/requests/{request_id}/comments/subscription",
+      put(subscribe_comments_handler).delete(unsubscribe_comments_handler),
+    )
+    .route(
+      "/requests/{request_id}/comments/{comment_id}",
+      patch(update_comment_handler).delete(delete_comment_handler),
+    )
+    .route(
+      "/requests/{request_id}/comments/{comment_id}/pin",
+      put(pin_comment_handler).delete(unpin_comment_handler),
+    )
+    .route(
+      "/comment-subscriptions/unsubscribe/{token}",
+      get(unsubscribe_comments_by_token_handler),
+    )
+}
+
+pub async fn list_comments_handler(
+  State(state): State<SharedAppState>,
+  Path(request_id): Path<i32>,
+  Query(query): Query<PageQuery>,
+) -> Result<JsonResponse<CommentsResponse>, AppError> {
+  let page = Page::try_from(query).map_err(AppError::bad_request)?;
+
+  state
+    .list_comments(request_id, page)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn create_comment_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+  Json(payload): Json<CreateCommentRequest>,
+) -> Result<JsonResponse<Comment>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .create_comment(request_id, user_id, payload)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn update_comment_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path((request_id, comment_id)): Path<(i32, i32)>,
+  Json(payload): Json<UpdateCommentRequest>,
+) -> Result<JsonResponse<Comment>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .update_comment(request_id, comment_id, user_id, payload)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn delete_comment_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path((request_id, comment_id)): Path<(i32, i32)>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.delete_comment(request_id, comment_id, user_id).await?;
+
+  Ok(())
+}
+
+pub async fn pin_comment_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path((request_id, comment_id)): Path<(i32, i32)>,
+) -> Result<JsonResponse<Comment>, AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .pin_comment(request_id, comment_id, user_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn unpin_comment_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path((request_id, comment_id)): Path<(i32, i32)>,
+) -> Result<JsonResponse<Comment>, AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .unpin_comment(request_id, comment_id, user_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn subscribe_comments_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.subscribe_comments(request_id, user_id).await?;
+
+  Ok(())
+}
+
+pub async fn unsubscribe_comments_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.unsubscribe_comments(request_id, user_id).await?;
+
+  Ok(())
+}
+
+pub async fn unsubscribe_comments_by_token_handler(
+  State(state): State<SharedAppState>,
+  Path(token): Path<String>,
+) -> Result<(), AppError> {
+  state.unsubscribe_comments_by_token(token).await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::model::{Comment, CommentsResponse, CreateCommentRequest, UpdateCommentRequest};
+  use crate::test_support::{
+    app_with_pool, delete_with_auth, get, login_verified_user, patch_json_with_auth, post_json_with_auth,
+    request_payload, send_with_auth,
+  };
+  use axum::http::StatusCode;
+
+  fn comment_payload(body: &str) -> CreateCommentRequest {
+    CreateCommentRequest { body: body.to_string() }
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_and_list_comments(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "cmt-owner@example.com", "Owner", "password123").await?;
+    let _photographer =
+      crate::domains::user::model::User::create(&pool, "cmt-photo@example.com", "Photographer", "password123").await?;
+    let request = crate::domains::request::repository::create(
+      &pool,
+      owner.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "丸の内側から"),
+    )
+    .await?;
+    let photo_token = login_verified_user(app.clone(), &pool, "cmt-photo@example.com").await?;
+
+    let uri = format!("/api/v1/requests/{}/comments", request.id);
+    for body in ["どちら側の建物ですか？", "夜景でも大丈夫ですか？", "三脚は使えますか？"]
+    {
+      let (status, _) = post_json_with_auth(app.clone(), &uri, &comment_payload(body), &photo_token).await;
+      assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = get(app.clone(), &format!("{}?page=2&per_page=2", uri)).await;
+    assert_eq!(status, StatusCode::OK);
+    let response: CommentsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.total, 3);
+    assert_eq!(response.comments.len(), 1);
+    assert_eq!(response.comments[0].body, "三脚は使えますか？");
+
+    // 作成者と投稿者の双方が購読者として登録される
+    let subscriber_count = sqlx::query_scalar!(
+      "SELECT COUNT(*) FROM request_comment_subscriptions WHERE request_id = $1",
+      request.id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(subscriber_count, Some(2));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_comment_request_not_found(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user = crate::domains::user::model::User::create(&pool, "cmt-404@example.com", "User", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "cmt-404@example.com").await?;
+
+    let (status, _) = post_json_with_auth(
+      app,
+      "/api/v1/requests/99999/comments",
+      &comment_payload("質問です"),
+      &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_comment_only_by_author(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "cmt-upd-owner@example.com", "Owner", "password123").await?;
+    let author =
+      crate::domains::user::model::User::create(&pool, "cmt-upd-author@example.com", "Author", "password123").await?;
+    let request =
+      crate::domains::request::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "説明"))
+        .await?;
+    let comment = super::super::repository::create(&pool, request.id, author.id, "元のコメント").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "cmt-upd-owner@example.com").await?;
+    let author_token = login_verified_user(app.clone(), &pool, "cmt-upd-author@example.com").await?;
+
+    let uri = format!("/api/v1/requests/{}/comments/{}", request.id, comment.id);
+    let payload = UpdateCommentRequest {
+      body: "編集後のコメント".to_string(),
+    };
+
+    let (status, _) = patch_json_with_auth(app.clone(), &uri, &payload, &owner_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = patch_json_with_auth(app, &uri, &payload, &author_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let updated: Comment = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(updated.body, "編集後のコメント");
+    assert!(updated.updated_at.is_some());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_comment_by_requester(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "cmt-del-owner@example.com", "Owner", "password123").await?;
+    let author =
+      crate::domains::user::model::User::create(&pool, "cmt-del-author@example.com", "Author", "password123").await?;
+    let _other =
+      crate::domains::user::model::User::create(&pool, "cmt-del-other@example.com", "Other", "password123").await?;
+    let request =
+      crate::domains::request::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "説明"))
+        .await?;
+    let comment = super::super::repository::create(&pool, request.id, author.id, "不要なコメント").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "cmt-del-owner@example.com").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "cmt-del-other@example.com").await?;
+
+    let uri = format!("/api/v1/requests/{}/comments/{}", request.id, comment.id);
+
+    let (status, _) = delete_with_auth(app.clone(), &uri, &other_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = delete_with_auth(app, &uri, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(super::super::repository::find_by_id(&pool, comment.id).await?.is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn pin_comment_replaces_previous_pin(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "cmt-pin-owner@example.com", "Owner", "password123").await?;
+    let author =
+      crate::domains::user::model::User::create(&pool, "cmt-pin-author@example.com", "Author", "password123").await?;
+    let request =
+      crate::domains::request::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "説明"))
+        .await?;
+    let first = super::super::repository::create(&pool, request.id, author.id, "一つ目").await?;
+    let second = super::super::repository::create(&pool, request.id, author.id, "二つ目").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "cmt-pin-owner@example.com").await?;
+    let author_token = login_verified_user(app.clone(), &pool, "cmt-pin-author@example.com").await?;
+
+    let pin_uri = |comment_id: i32| format!("/api/v1/requests/{}/comments/{}/pin", request.id, comment_id);
+
+    let (status, _) = send_with_auth(app.clone(), "PUT", &pin_uri(first.id), &author_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send_with_auth(app.clone(), "PUT", &pin_uri(first.id), &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send_with_auth(app.clone(), "PUT", &pin_uri(second.id), &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = get(app.clone(), &format!("/api/v1/requests/{}/comments", request.id)).await;
+    let response: CommentsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.comments[0].id, second.id);
+    assert!(response.comments[0].pinned_at.is_some());
+    assert!(response.comments[1].pinned_at.is_none());
+
+    let (status, body) = send_with_auth(app, "DELETE", &pin_uri(second.id), &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let unpinned: Comment = serde_json::from_slice(&body).expect("deserialize response");
+    assert!(unpinned.pinned_at.is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn unsubscribe_by_token(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "cmt-unsub@example.com", "Owner", "password123").await?;
+    let request =
+      crate::domains::request::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "説明"))
+        .await?;
+    super::super::repository::add_subscriber_with_executor(&pool, request.id, owner.id, "unsub-token").await?;
+
+    let (status, _) = get(app.clone(), "/api/v1/comment-subscriptions/unsubscribe/unsub-token").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let unsubscribed_at = sqlx::query_scalar!(
+      "SELECT unsubscribed_at FROM request_comment_subscriptions WHERE unsubscribe_token = $1",
+      "unsub-token"
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(unsubscribed_at.is_some());
+
+    let (status, _) = get(app, "/api/v1/comment-subscriptions/unsubscribe/unknown").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,254 @@
+use sqlx::PgPool;
+use std::error::Error;
+use uuid::Uuid;
+
+use crate::domains::comment::{
+  model::{Comment, CommentsResponse, CreateCommentRequest, UpdateCommentRequest},
+  repository,
+};
+use crate::domains::request::{model::Request, repository as request_repository};
+use crate::email::EmailService;
+use crate::impl_service_error_conversions;
+use crate::utils::pagination::Page;
+
+#[derive(Debug)]
+pub enum CommentServiceError {
+  InternalServerError(String),
+  BadRequest(String),
+  NotFound(String),
+  Forbidden(String),
+}
+
+impl Error for CommentServiceError {}
+
+impl std::fmt::Display for CommentServiceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      CommentServiceError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
+      CommentServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+      CommentServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+      CommentServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+    }
+  }
+}
+
+impl_service_error_conversions!(CommentServiceError, InternalServerError);
+
+pub struct CommentService {
+  pool: PgPool,
+  email_service: EmailService,
+}
+
+impl CommentService {
+  pub fn new(pool: PgPool, email_service: EmailService) -> Self {
+    Self { pool, email_service }
+  }
+
+  pub async fn list_comments(&self, request_id: i32, page: Page) -> Result<CommentsResponse, CommentServiceError> {
+    self.find_request(request_id).await?;
+
+    let comments = repository::find_by_request_id(&self.pool, request_id, page.limit(), page.offset()).await?;
+    let total = repository::count_by_request_id(&self.pool, request_id).await?;
+
+    Ok(CommentsResponse {
+      comments,
+      total,
+      page: page.page,
+      per_page: page.per_page,
+    })
+  }
+
+  pub async fn create_comment(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: CreateCommentRequest,
+  ) -> Result<Comment, CommentServiceError> {
+    let request = self.find_request(request_id).await?;
+
+    let mut tx = self.pool.begin().await?;
+    let comment = repository::create_with_executor(&mut *tx.as_mut(), request_id, user_id, &req.body).await?;
+
+    // リクエスト作成者とコメント投稿者をスレッドの参加者として購読させる
+    for participant_id in [request.user_id, user_id] {
+      repository::add_subscriber_with_executor(
+        &mut *tx.as_mut(),
+        request_id,
+        participant_id,
+        &Uuid::new_v4().to_string(),
+      )
+      .await?;
+    }
+
+    tx.commit().await?;
+
+    self.notify_subscribers(&request, &comment).await?;
+
+    Ok(comment)
+  }
+
+  pub async fn update_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+    req: UpdateCommentRequest,
+  ) -> Result<Comment, CommentServiceError> {
+    let comment = self.find_comment(request_id, comment_id).await?;
+
+    if comment.user_id != user_id {
+      return Err(CommentServiceError::Forbidden(
+        "You do not have permission to update this comment".to_string(),
+      ));
+    }
+
+    let updated = repository::update_body(&self.pool, comment_id, &req.body).await?;
+    Ok(updated)
+  }
+
+  pub async fn delete_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> Result<(), CommentServiceError> {
+    let request = self.find_request(request_id).await?;
+    let comment = self.find_comment(request_id, comment_id).await?;
+
+    // 投稿者本人に加えて、リクエスト作成者も自分のスレッドのコメントを削除できる
+    if comment.user_id != user_id && request.user_id != user_id {
+      return Err(CommentServiceError::Forbidden(
+        "You do not have permission to delete this comment".to_string(),
+      ));
+    }
+
+    repository::delete(&self.pool, comment_id).await?;
+    Ok(())
+  }
+
+  /// コメントをピン留めする。既にピン留めされたコメントがあれば置き換える
+  pub async fn pin_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> Result<Comment, CommentServiceError> {
+    self.set_pinned(request_id, comment_id, user_id, true).await
+  }
+
+  pub async fn unpin_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> Result<Comment, CommentServiceError> {
+    self.set_pinned(request_id, comment_id, user_id, false).await
+  }
+
+  pub async fn subscribe(&self, request_id: i32, user_id: i32) -> Result<(), CommentServiceError> {
+    self.find_request(request_id).await?;
+    repository::subscribe(&self.pool, request_id, user_id, &Uuid::new_v4().to_string()).await?;
+    Ok(())
+  }
+
+  pub async fn unsubscribe(&self, request_id: i32, user_id: i32) -> Result<(), CommentServiceError> {
+    self.find_request(request_id).await?;
+    repository::unsubscribe(&self.pool, request_id, user_id).await?;
+    Ok(())
+  }
+
+  pub async fn unsubscribe_by_token(&self, token: &str) -> Result<(), CommentServiceError> {
+    if !repository::unsubscribe_by_token(&self.pool, token).await? {
+      return Err(CommentServiceError::NotFound("Subscription not found".to_string()));
+    }
+    Ok(())
+  }
+
+  async fn set_pinned(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+    pinned: bool,
+  ) -> Result<Comment, CommentServiceError> {
+    let mut tx = self.pool.begin().await?;
+
+    // 同じリクエストへの同時ピン留めを直列化するため、リクエスト行をロックする
+    let request = request_repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id)
+      .await?
+      .ok_or_else(|| CommentServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.user_id != user_id {
+      return Err(CommentServiceError::Forbidden(
+        "Only the requester can pin comments".to_string(),
+      ));
+    }
+
+    let comment = repository::find_by_id_with_executor(&mut *tx.as_mut(), comment_id)
+      .await?
+      .filter(|c| c.request_id == request_id)
+      .ok_or_else(|| CommentServiceError::NotFound(format!("Comment with id {} not found", comment_id)))?;
+
+    if pinned {
+      repository::clear_pin_with_executor(&mut *tx.as_mut(), request_id).await?;
+    }
+    let updated = repository::set_pinned_with_executor(&mut *tx.as_mut(), comment.id, pinned).await?;
+
+    tx.commit().await?;
+
+    Ok(updated)
+  }
+
+  async fn find_request(&self, request_id: i32) -> Result<Request, CommentServiceError> {
+    request_repository::find_by_id(&self.pool, request_id)
+      .await?
+      .ok_or_else(|| CommentServiceError::NotFound(format!("Request with id {} not found", request_id)))
+  }
+
+  async fn find_comment(&self, request_id: i32, comment_id: i32) -> Result<Comment, CommentServiceError> {
+    repository::find_by_id(&self.pool, comment_id)
+      .await?
+      .filter(|c| c.request_id == request_id)
+      .ok_or_else(|| CommentServiceError::NotFound(format!("Comment with id {} not found", comment_id)))
+  }
+
+  /// 投稿者以外の参加者へ新着コメントを通知する。送信は投稿のレスポンスを待たせないよう別タスクで行う
+  async fn notify_subscribers(&self, request: &Request, comment: &Comment) -> Result<(), CommentServiceError> {
+    let subscribers = repository::find_active_subscribers(&self.pool, request.id, comment.user_id).await?;
+    if subscribers.is_empty() {
+      return Ok(());
+    }
+
+    let email_service = self.email_service.clone();
+    let request_id = request.id;
+    let place_name = request.place_name.clone();
+    let comment_body = comment.body.clone();
+
+    tokio::spawn(async move {
+      let subject = format!("「{}」に新しいコメントがあります", place_name);
+
+      for subscriber in subscribers {
+        let body = EmailService::build_comment_notification_email_body(
+          request_id,
+          &place_name,
+          &comment_body,
+          &subscriber.unsubscribe_token,
+        );
+
+        if let Err(e) = email_service
+          .send_simple_text_email(&subscriber.email, &subject, &body)
+          .await
+        {
+          tracing::error!(
+            "Failed to send comment notification for request {} to user {}: {:?}",
+            request_id,
+            subscriber.user_id,
+            e
+          );
+        }
+      }
+    });
+
+    Ok(())
+  }
+}
@@ -1,3 +1,8 @@
+pub mod comment;
 pub mod picture;
 pub mod request;
+pub mod storage_operation;
+pub mod tag;
+pub mod upload;
 pub mod user;
+pub mod watch_area;
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 写真として保存するためにメモリへ読み込んでいる大きさの合計を抑える。
+///
+/// 形式の検証・デコード・メタデータの除去はファイル全体を必要とし、保存するキーもメタデータを取り除いた後の内容から決まるため、
+/// 保存の前に一度はファイル全体を読み込む。デコードしたピクセルはファイルよりずっと大きくなるので、別の予算で数える。
+/// 同時に届いたアップロードの数だけメモリが増えないよう、読み込む前に大きさの分を確保し、使い終えるまで返さない
+pub struct BufferBudget {
+  semaphore: Arc<Semaphore>,
+  capacity: usize,
+  usage: Arc<Usage>,
+}
+
+#[derive(Default)]
+struct Usage {
+  in_use: AtomicUsize,
+  peak: AtomicUsize,
+}
+
+impl BufferBudget {
+  pub fn new(capacity: usize) -> Self {
+    // 1回に確保できる数は u32 に収まる範囲に限られる
+    let capacity = capacity.clamp(1, u32::MAX as usize);
+    Self {
+      semaphore: Arc::new(Semaphore::new(capacity)),
+      capacity,
+      usage: Arc::default(),
+    }
+  }
+
+  /// `bytes` を確保できるまで待つ。上限より大きければ上限の分だけ確保する
+  pub async fn reserve(&self, bytes: usize) -> BufferReservation {
+    let bytes = bytes.clamp(1, self.capacity);
+    let permit = self
+      .semaphore
+      .clone()
+      .acquire_many_owned(bytes as u32)
+      .await
+      .expect("buffer budget semaphore is never closed");
+
+    let in_use = self.usage.in_use.fetch_add(bytes, Ordering::SeqCst) + bytes;
+    self.usage.peak.fetch_max(in_use, Ordering::SeqCst);
+
+    BufferReservation {
+      usage: self.usage.clone(),
+      bytes,
+      _permit: permit,
+    }
+  }
+
+  /// これまでに同時に確保された大きさの最大値
+  pub fn peak(&self) -> usize {
+    self.usage.peak.load(Ordering::SeqCst)
+  }
+}
+
+/// 確保した分。読み込んだファイルと一緒に保存まで持ち回れるよう、予算を借用しない。手放すと待っている読み込みに回る
+pub struct BufferReservation {
+  usage: Arc<Usage>,
+  bytes: usize,
+  _permit: OwnedSemaphorePermit,
+}
+
+impl std::fmt::Debug for BufferReservation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("BufferReservation").field("bytes", &self.bytes).finish()
+  }
+}
+
+impl Drop for BufferReservation {
+  fn drop(&mut self) {
+    self.usage.in_use.fetch_sub(self.bytes, Ordering::SeqCst);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_reservations_wait_for_capacity() {
+    let budget = BufferBudget::new(100);
+
+    let first = budget.reserve(60).await;
+    assert!(
+      tokio::time::timeout(std::time::Duration::from_millis(20), budget.reserve(60))
+        .await
+        .is_err()
+    );
+
+    drop(first);
+    drop(budget.reserve(60).await);
+    assert_eq!(budget.peak(), 60);
+
+    // 上限を超える分は上限までに抑える
+    drop(budget.reserve(1000).await);
+    assert_eq!(budget.peak(), 100);
+  }
+}
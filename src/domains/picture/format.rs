@@ -0,0 +1,324 @@
+use std::io::Cursor;
+
+use image::ImageReader;
+
+pub const DEFAULT_MAX_UPLOAD_BYTES: usize = 20 * 1024 * 1024;
+pub const DEFAULT_MAX_IMAGE_PIXELS: u64 = 50_000_000;
+
+/// アップロードを受け付ける画像の上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLimits {
+  pub max_bytes: usize,
+  pub max_pixels: u64,
+}
+
+impl ImageLimits {
+  /// `MAX_UPLOAD_BYTES` と `MAX_IMAGE_PIXELS` から読む。未設定や不正な値なら既定値を使う
+  pub fn from_env() -> Self {
+    let max_bytes = std::env::var("MAX_UPLOAD_BYTES")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|&bytes| bytes > 0)
+      .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
+    let max_pixels = std::env::var("MAX_IMAGE_PIXELS")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|&pixels| pixels > 0)
+      .unwrap_or(DEFAULT_MAX_IMAGE_PIXELS);
+
+    Self { max_bytes, max_pixels }
+  }
+}
+
+impl Default for ImageLimits {
+  fn default() -> Self {
+    Self {
+      max_bytes: DEFAULT_MAX_UPLOAD_BYTES,
+      max_pixels: DEFAULT_MAX_IMAGE_PIXELS,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+  Jpeg,
+  Png,
+  Webp,
+  Heic,
+}
+
+impl ImageFormat {
+  pub fn mime_type(&self) -> &'static str {
+    match self {
+      ImageFormat::Jpeg => "image/jpeg",
+      ImageFormat::Png => "image/png",
+      ImageFormat::Webp => "image/webp",
+      ImageFormat::Heic => "image/heic",
+    }
+  }
+
+  pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+    match mime_type {
+      "image/jpeg" => Some(ImageFormat::Jpeg),
+      "image/png" => Some(ImageFormat::Png),
+      "image/webp" => Some(ImageFormat::Webp),
+      "image/heic" => Some(ImageFormat::Heic),
+      _ => None,
+    }
+  }
+
+  /// `image` クレートでデコードできる形式。HEIC は純粋な Rust のデコーダーがないため `None`
+  pub fn decoder_format(&self) -> Option<image::ImageFormat> {
+    match self {
+      ImageFormat::Jpeg => Some(image::ImageFormat::Jpeg),
+      ImageFormat::Png => Some(image::ImageFormat::Png),
+      ImageFormat::Webp => Some(image::ImageFormat::WebP),
+      ImageFormat::Heic => None,
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ImageFormat::Jpeg => "jpg",
+      ImageFormat::Png => "png",
+      ImageFormat::Webp => "webp",
+      ImageFormat::Heic => "heic",
+    }
+  }
+}
+
+/// ファイルの中身から判定した画像の形式とサイズ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+  pub format: ImageFormat,
+  pub width: u32,
+  pub height: u32,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+  TooLarge { max_bytes: usize },
+  UnsupportedFormat,
+  Malformed(String),
+  TooManyPixels { max_pixels: u64 },
+}
+
+impl std::fmt::Display for ImageError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ImageError::TooLarge { max_bytes } => write!(f, "Image must be at most {} bytes", max_bytes),
+      ImageError::UnsupportedFormat => write!(f, "Unsupported image format. Use JPEG, PNG, WebP or HEIC"),
+      ImageError::Malformed(reason) => write!(f, "Malformed image: {}", reason),
+      ImageError::TooManyPixels { max_pixels } => write!(f, "Image must be at most {} pixels", max_pixels),
+    }
+  }
+}
+
+/// 先頭のマジックバイトから形式を判定する。クライアントの Content-Type やファイル名は信用しない
+pub fn sniff_format(data: &[u8]) -> Option<ImageFormat> {
+  if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+    return Some(ImageFormat::Jpeg);
+  }
+  if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+    return Some(ImageFormat::Png);
+  }
+  if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+    return Some(ImageFormat::Webp);
+  }
+  if is_heic(data) {
+    return Some(ImageFormat::Heic);
+  }
+
+  None
+}
+
+/// 形式とサイズを調べ、上限を超えていないか確認する。ピクセルのデコードはせずヘッダーだけを読む
+pub fn inspect(data: &[u8], limits: &ImageLimits) -> Result<ImageInfo, ImageError> {
+  if data.len() > limits.max_bytes {
+    return Err(ImageError::TooLarge {
+      max_bytes: limits.max_bytes,
+    });
+  }
+
+  let format = sniff_format(data).ok_or(ImageError::UnsupportedFormat)?;
+  let (width, height) = match format.decoder_format() {
+    Some(decoder_format) => read_dimensions(data, decoder_format)?,
+    None => heic_dimensions(data).ok_or_else(|| ImageError::Malformed("missing HEIC image size".to_string()))?,
+  };
+
+  if width == 0 || height == 0 {
+    return Err(ImageError::Malformed("image has no pixels".to_string()));
+  }
+  if u64::from(width) * u64::from(height) > limits.max_pixels {
+    return Err(ImageError::TooManyPixels {
+      max_pixels: limits.max_pixels,
+    });
+  }
+
+  Ok(ImageInfo { format, width, height })
+}
+
+fn read_dimensions(data: &[u8], format: image::ImageFormat) -> Result<(u32, u32), ImageError> {
+  ImageReader::with_format(Cursor::new(data), format)
+    .into_dimensions()
+    .map_err(|e| ImageError::Malformed(e.to_string()))
+}
+
+const HEIC_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx"];
+
+/// ftyp ボックスのメジャーブランドか互換ブランドに HEVC 系のブランドがあるか
+fn is_heic(data: &[u8]) -> bool {
+  let Some((b"ftyp", body)) = next_box(data).map(|(kind, body, _)| (kind, body)) else {
+    return false;
+  };
+  if body.len() < 8 {
+    return false;
+  }
+
+  // メジャーブランド(4) + マイナーバージョン(4) の後に互換ブランドが並ぶ
+  std::iter::once(&body[0..4])
+    .chain(body[8..].chunks_exact(4))
+    .any(|brand| HEIC_BRANDS.contains(&brand))
+}
+
+/// meta/iprp/ipco 内の ispe プロパティから幅と高さを読む。
+/// サムネイルなど複数ある場合は最も大きいものを本体の画像とみなす
+fn heic_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+  let meta = find_box(data, b"meta")?;
+  // meta はフルボックスのため、バージョンとフラグの4バイトを飛ばす
+  let ipco = find_box(find_box(meta.get(4..)?, b"iprp")?, b"ipco")?;
+
+  let mut largest: Option<(u32, u32)> = None;
+  let mut rest = ipco;
+  while let Some((kind, body, next)) = next_box(rest) {
+    if kind == b"ispe" && body.len() >= 12 {
+      let width = u32::from_be_bytes(body[4..8].try_into().ok()?);
+      let height = u32::from_be_bytes(body[8..12].try_into().ok()?);
+      let area = u64::from(width) * u64::from(height);
+      if largest.is_none_or(|(w, h)| area > u64::from(w) * u64::from(h)) {
+        largest = Some((width, height));
+      }
+    }
+    rest = next;
+  }
+
+  largest
+}
+
+pub(super) fn find_box<'a>(mut data: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+  while let Some((kind, body, next)) = next_box(data) {
+    if kind == wanted {
+      return Some(body);
+    }
+    data = next;
+  }
+
+  None
+}
+
+/// ISO BMFF のボックスを1つ読み、(種類, 中身, 残り) を返す
+pub(super) fn next_box(data: &[u8]) -> Option<(&[u8; 4], &[u8], &[u8])> {
+  let size = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?) as u64;
+  let kind: &[u8; 4] = data.get(4..8)?.try_into().ok()?;
+
+  let (header_len, box_len) = match size {
+    // 64bit のサイズが続く
+    1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+    // ファイルの終わりまで
+    0 => (8, data.len() as u64),
+    _ => (8, size),
+  };
+  let box_len = usize::try_from(box_len).ok()?;
+  if box_len < header_len || box_len > data.len() {
+    return None;
+  }
+
+  Some((kind, &data[header_len..box_len], &data[box_len..]))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  pub(crate) fn sample_png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbImage::new(width, height)
+      .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+      .unwrap();
+    data
+  }
+
+  pub(crate) fn bmff_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data
+  }
+
+  fn sample_heic(width: u32, height: u32) -> Vec<u8> {
+    let ftyp = bmff_box(b"ftyp", b"mif1\0\0\0\0mif1heic");
+    let mut ispe_body = vec![0; 4];
+    ispe_body.extend_from_slice(&width.to_be_bytes());
+    ispe_body.extend_from_slice(&height.to_be_bytes());
+    let thumbnail = {
+      let mut body = vec![0; 4];
+      body.extend_from_slice(&320u32.to_be_bytes());
+      body.extend_from_slice(&240u32.to_be_bytes());
+      bmff_box(b"ispe", &body)
+    };
+    let ipco = bmff_box(b"ipco", &[thumbnail, bmff_box(b"ispe", &ispe_body)].concat());
+    let meta = bmff_box(b"meta", &[vec![0; 4], bmff_box(b"iprp", &ipco)].concat());
+
+    [ftyp, meta].concat()
+  }
+
+  #[test]
+  fn test_inspect_png() {
+    let info = inspect(&sample_png(3, 2), &ImageLimits::default()).unwrap();
+    assert_eq!(
+      info,
+      ImageInfo {
+        format: ImageFormat::Png,
+        width: 3,
+        height: 2
+      }
+    );
+  }
+
+  #[test]
+  fn test_inspect_heic_uses_largest_image_size() {
+    let info = inspect(&sample_heic(4032, 3024), &ImageLimits::default()).unwrap();
+    assert_eq!(info.format, ImageFormat::Heic);
+    assert_eq!((info.width, info.height), (4032, 3024));
+  }
+
+  #[test]
+  fn test_inspect_rejects_unsupported_and_malformed() {
+    let limits = ImageLimits::default();
+    assert_eq!(inspect(b"GIF89a....", &limits), Err(ImageError::UnsupportedFormat));
+    assert!(matches!(
+      inspect(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0], &limits),
+      Err(ImageError::Malformed(_))
+    ));
+    assert!(matches!(
+      inspect(&bmff_box(b"ftyp", b"heic\0\0\0\0"), &limits),
+      Err(ImageError::Malformed(_))
+    ));
+  }
+
+  #[test]
+  fn test_inspect_enforces_limits() {
+    let png = sample_png(10, 10);
+    let limits = ImageLimits {
+      max_bytes: png.len() - 1,
+      max_pixels: 1000,
+    };
+    assert!(matches!(inspect(&png, &limits), Err(ImageError::TooLarge { .. })));
+
+    let limits = ImageLimits {
+      max_bytes: png.len(),
+      max_pixels: 99,
+    };
+    assert!(matches!(inspect(&png, &limits), Err(ImageError::TooManyPixels { .. })));
+  }
+}
@@ -0,0 +1,660 @@
+use chrono::NaiveDateTime;
+use exif::{In, Reader, Tag, Value};
+use std::io::Cursor;
+
+use super::format::{find_box, next_box, ImageError, ImageFormat};
+
+const MAX_CAMERA_CHARS: usize = 100;
+
+/// EXIF から取り出して保存する撮影情報
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaptureMetadata {
+  /// 撮影地の現地時刻（EXIF の DateTimeOriginal にはタイムゾーンがない）
+  pub taken_at: Option<NaiveDateTime>,
+  pub camera_make: Option<String>,
+  pub camera_model: Option<String>,
+  /// EXIF の Orientation（1〜8）
+  pub orientation: Option<u16>,
+  pub gps: Option<(f64, f64)>,
+}
+
+/// EXIF を読む。EXIF がない、または壊れている場合は空の撮影情報を返す
+pub fn extract(data: &[u8]) -> CaptureMetadata {
+  let Ok(exif) = Reader::new().read_from_container(&mut Cursor::new(data)) else {
+    return CaptureMetadata::default();
+  };
+
+  let ascii = |tag: Tag| {
+    exif.get_field(tag, In::PRIMARY).and_then(|field| match &field.value {
+      Value::Ascii(values) => values
+        .first()
+        .map(|value| {
+          String::from_utf8_lossy(value)
+            .trim()
+            .chars()
+            .take(MAX_CAMERA_CHARS)
+            .collect::<String>()
+        })
+        .filter(|value| !value.is_empty()),
+      _ => None,
+    })
+  };
+
+  let taken_at = [Tag::DateTimeOriginal, Tag::DateTime]
+    .into_iter()
+    .find_map(&ascii)
+    .and_then(|value| NaiveDateTime::parse_from_str(&value, "%Y:%m:%d %H:%M:%S").ok());
+
+  let orientation = exif
+    .get_field(Tag::Orientation, In::PRIMARY)
+    .and_then(|field| field.value.get_uint(0))
+    .and_then(|value| u16::try_from(value).ok())
+    .filter(|value| (1..=8).contains(value));
+
+  let coordinate = |tag: Tag, ref_tag: Tag, negative: &str, max: f64| {
+    let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+      Value::Rational(values) if values.len() >= 3 => {
+        values[0].to_f64() + values[1].to_f64() / 60.0 + values[2].to_f64() / 3600.0
+      }
+      _ => return None,
+    };
+    let sign = if ascii(ref_tag).is_some_and(|value| value.eq_ignore_ascii_case(negative)) {
+      -1.0
+    } else {
+      1.0
+    };
+    Some(sign * degrees).filter(|value| value.is_finite() && value.abs() <= max)
+  };
+  let gps = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S", 90.0).zip(coordinate(
+    Tag::GPSLongitude,
+    Tag::GPSLongitudeRef,
+    "W",
+    180.0,
+  ));
+
+  CaptureMetadata {
+    taken_at,
+    camera_make: ascii(Tag::Make),
+    camera_model: ascii(Tag::Model),
+    orientation,
+    gps,
+  }
+}
+
+/// 画像データはそのままに、向き以外のメタデータ（EXIF・XMP・IPTC・コメントなど）を取り除く
+pub fn strip(data: Vec<u8>, format: ImageFormat, orientation: Option<u16>) -> Result<Vec<u8>, ImageError> {
+  match format {
+    ImageFormat::Jpeg => strip_jpeg(&data, orientation),
+    ImageFormat::Png => strip_png(&data, orientation),
+    ImageFormat::Webp => strip_webp(&data, orientation),
+    // HEIC の向きは EXIF ではなく irot/imir プロパティで表されるため、EXIF と XMP を消すだけでよい
+    ImageFormat::Heic => strip_heic(data),
+  }
+}
+
+/// Orientation だけを持つビッグエンディアンの TIFF
+fn orientation_tiff(orientation: u16) -> Vec<u8> {
+  let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+  tiff.extend_from_slice(&1u16.to_be_bytes());
+  tiff.extend_from_slice(&0x0112u16.to_be_bytes());
+  tiff.extend_from_slice(&3u16.to_be_bytes());
+  tiff.extend_from_slice(&1u32.to_be_bytes());
+  tiff.extend_from_slice(&orientation.to_be_bytes());
+  tiff.extend_from_slice(&[0, 0]);
+  tiff.extend_from_slice(&0u32.to_be_bytes());
+  tiff
+}
+
+fn malformed(reason: &str) -> ImageError {
+  ImageError::Malformed(reason.to_string())
+}
+
+fn strip_jpeg(data: &[u8], orientation: Option<u16>) -> Result<Vec<u8>, ImageError> {
+  let mut out = Vec::with_capacity(data.len());
+  out.extend_from_slice(&[0xFF, 0xD8]);
+
+  let mut inserted = false;
+  let mut pos = 2;
+  while pos < data.len() {
+    if data[pos] != 0xFF {
+      return Err(malformed("invalid JPEG marker"));
+    }
+    // マーカー前の埋め草の 0xFF を飛ばす
+    while data.get(pos + 1) == Some(&0xFF) {
+      pos += 1;
+    }
+    let marker = *data.get(pos + 1).ok_or_else(|| malformed("truncated JPEG"))?;
+
+    match marker {
+      // 以降に連結されている別の画像（MPF のサブ画像など）は捨てる
+      0xD9 => break,
+      0x01 | 0xD0..=0xD7 => {
+        out.extend_from_slice(&data[pos..pos + 2]);
+        pos += 2;
+        continue;
+      }
+      _ => {}
+    }
+
+    let length = data
+      .get(pos + 2..pos + 4)
+      .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+      .ok_or_else(|| malformed("truncated JPEG"))?;
+    let end = pos + 2 + length;
+    if length < 2 || end > data.len() {
+      return Err(malformed("truncated JPEG segment"));
+    }
+    let payload = &data[pos + 4..end];
+
+    // JFIF の APP0 は SOI の直後にある必要があるため、その後ろに向きの EXIF を入れる
+    if !inserted && marker != 0xE0 {
+      if let Some(orientation) = orientation {
+        let tiff = orientation_tiff(orientation);
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        out.extend_from_slice(b"Exif\x00\x00");
+        out.extend_from_slice(&tiff);
+      }
+      inserted = true;
+    }
+
+    let keep = match marker {
+      // APP1: EXIF/XMP, APP13: IPTC, COM: コメント
+      0xE1 | 0xED | 0xFE => false,
+      // APP2 の ICC プロファイルは色の再現に必要なので残し、MPF は捨てる
+      0xE2 => !payload.starts_with(b"MPF\x00"),
+      _ => true,
+    };
+    if keep {
+      out.extend_from_slice(&data[pos..end]);
+    }
+    pos = end;
+
+    if marker == 0xDA {
+      // スキャンデータは次のマーカー（0xFF00 のスタッフィングと RST を除く）まで
+      let start = pos;
+      while pos < data.len() {
+        if data[pos] == 0xFF
+          && data
+            .get(pos + 1)
+            .is_some_and(|&next| next != 0x00 && !(0xD0..=0xD7).contains(&next))
+        {
+          break;
+        }
+        pos += 1;
+      }
+      out.extend_from_slice(&data[start..pos]);
+    }
+  }
+
+  out.extend_from_slice(&[0xFF, 0xD9]);
+  Ok(out)
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_METADATA_CHUNKS: &[&[u8; 4]] = &[b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+fn strip_png(data: &[u8], orientation: Option<u16>) -> Result<Vec<u8>, ImageError> {
+  let mut out = Vec::with_capacity(data.len());
+  out.extend_from_slice(PNG_SIGNATURE);
+
+  let mut inserted = false;
+  let mut pos = PNG_SIGNATURE.len();
+  while pos < data.len() {
+    let header = data.get(pos..pos + 8).ok_or_else(|| malformed("truncated PNG chunk"))?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let kind: &[u8; 4] = header[4..8].try_into().map_err(|_| malformed("truncated PNG chunk"))?;
+    let end = pos + 12 + length;
+    if end > data.len() {
+      return Err(malformed("truncated PNG chunk"));
+    }
+
+    // eXIf は IDAT より前に置く必要がある
+    if kind == b"IDAT" && !inserted {
+      if let Some(orientation) = orientation {
+        write_png_chunk(&mut out, b"eXIf", &orientation_tiff(orientation));
+      }
+      inserted = true;
+    }
+    if !PNG_METADATA_CHUNKS.contains(&kind) {
+      out.extend_from_slice(&data[pos..end]);
+    }
+    pos = end;
+
+    if kind == b"IEND" {
+      break;
+    }
+  }
+
+  Ok(out)
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+  out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+  out.extend_from_slice(kind);
+  out.extend_from_slice(body);
+  out.extend_from_slice(&crc32(&[kind.as_slice(), body].concat()).to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+  let mut crc = 0xFFFF_FFFFu32;
+  for &byte in data {
+    crc ^= u32::from(byte);
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 {
+        (crc >> 1) ^ 0xEDB8_8320
+      } else {
+        crc >> 1
+      };
+    }
+  }
+  !crc
+}
+
+const WEBP_XMP_FLAG: u8 = 0x04;
+const WEBP_EXIF_FLAG: u8 = 0x08;
+
+fn strip_webp(data: &[u8], orientation: Option<u16>) -> Result<Vec<u8>, ImageError> {
+  // メタデータを持てるのは VP8X で始まる拡張形式だけ
+  if data.get(12..16) != Some(b"VP8X".as_slice()) {
+    return Ok(data.to_vec());
+  }
+
+  let mut out = Vec::with_capacity(data.len());
+  out.extend_from_slice(&data[0..12]);
+
+  let mut pos = 12;
+  while pos + 8 <= data.len() {
+    let kind = &data[pos..pos + 4];
+    let size = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+    let end = (pos + 8 + size + size % 2).min(data.len());
+    if pos + 8 + size > data.len() {
+      return Err(malformed("truncated WebP chunk"));
+    }
+
+    if kind != b"EXIF" && kind != b"XMP " {
+      out.extend_from_slice(&data[pos..end]);
+    }
+    pos = end;
+  }
+
+  if let Some(orientation) = orientation {
+    let tiff = orientation_tiff(orientation);
+    out.extend_from_slice(b"EXIF");
+    out.extend_from_slice(&(tiff.len() as u32).to_le_bytes());
+    out.extend_from_slice(&tiff);
+    if tiff.len() % 2 == 1 {
+      out.push(0);
+    }
+  }
+
+  // VP8X のフラグと RIFF 全体のサイズを書き直す
+  out[20] &= !(WEBP_XMP_FLAG | WEBP_EXIF_FLAG);
+  if orientation.is_some() {
+    out[20] |= WEBP_EXIF_FLAG;
+  }
+  let riff_size = (out.len() - 8) as u32;
+  out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+  Ok(out)
+}
+
+/// EXIF と XMP のアイテムの中身をゼロで埋める。オフセットが変わらないよう、ファイルの長さは変えない
+fn strip_heic(mut data: Vec<u8>) -> Result<Vec<u8>, ImageError> {
+  let ranges = heic_metadata_ranges(&data).ok_or_else(|| malformed("unsupported HEIC metadata layout"))?;
+  for (start, end) in ranges {
+    data[start..end].fill(0);
+  }
+
+  Ok(data)
+}
+
+fn heic_metadata_ranges(data: &[u8]) -> Option<Vec<(usize, usize)>> {
+  let meta = find_box(data, b"meta")?.get(4..)?;
+  let Some(iinf) = find_box(meta, b"iinf") else {
+    return Some(Vec::new());
+  };
+
+  // iinf: バージョンとフラグ(4) + 件数(v0 は2バイト、それ以外は4バイト)の後に infe が並ぶ
+  let mut entries = iinf.get(if *iinf.first()? == 0 { 6 } else { 8 }..)?;
+  let mut metadata_items = Vec::new();
+  while let Some((kind, body, next)) = next_box(entries) {
+    if kind == b"infe" {
+      if let Some(item_id) = metadata_item_id(body) {
+        metadata_items.push(item_id);
+      }
+    }
+    entries = next;
+  }
+  if metadata_items.is_empty() {
+    return Some(Vec::new());
+  }
+
+  let iloc = find_box(meta, b"iloc")?;
+  // construction_method 1 のオフセットは meta 内の idat の中身が基準
+  let idat_start = find_box(meta, b"idat").map(|idat| idat.as_ptr() as usize - data.as_ptr() as usize);
+
+  let mut reader = BoxReader { data: iloc, pos: 0 };
+  let version = reader.read(1)?;
+  reader.read(3)?;
+  let sizes = reader.read(2)?;
+  let (offset_size, length_size, base_offset_size) = ((sizes >> 12) & 0xF, (sizes >> 8) & 0xF, (sizes >> 4) & 0xF);
+  let index_size = if version >= 1 { sizes & 0xF } else { 0 };
+  let item_count = reader.read(if version < 2 { 2 } else { 4 })?;
+
+  let mut ranges = Vec::new();
+  for _ in 0..item_count {
+    let item_id = reader.read(if version < 2 { 2 } else { 4 })?;
+    let construction_method = if version >= 1 { reader.read(2)? & 0xF } else { 0 };
+    reader.read(2)?;
+    let base_offset = reader.read(base_offset_size as usize)?;
+    let extent_count = reader.read(2)?;
+
+    let is_metadata = metadata_items.contains(&item_id);
+    let origin = match (is_metadata, construction_method) {
+      (false, _) => 0,
+      (true, 0) => 0,
+      (true, 1) => idat_start? as u64,
+      _ => return None,
+    };
+
+    for _ in 0..extent_count {
+      reader.read(index_size as usize)?;
+      let offset = reader.read(offset_size as usize)?;
+      let length = reader.read(length_size as usize)?;
+      if !is_metadata {
+        continue;
+      }
+
+      // 値はアップロードされたファイルから読んだものなので、桁あふれするものは壊れたファイルとして扱う
+      let start = origin.checked_add(base_offset)?.checked_add(offset)?;
+      let end = if length == 0 {
+        data.len()
+      } else {
+        usize::try_from(start.checked_add(length)?).ok()?
+      };
+      let start = usize::try_from(start).ok()?;
+      if start > end || end > data.len() {
+        return None;
+      }
+      ranges.push((start, end));
+    }
+  }
+
+  Some(ranges)
+}
+
+/// infe が EXIF か XMP のアイテムならそのIDを返す
+fn metadata_item_id(infe: &[u8]) -> Option<u64> {
+  let mut reader = BoxReader { data: infe, pos: 0 };
+  let version = reader.read(1)?;
+  reader.read(3)?;
+  if version < 2 {
+    return None;
+  }
+  let item_id = reader.read(if version == 2 { 2 } else { 4 })?;
+  reader.read(2)?;
+  let item_type = infe.get(reader.pos..reader.pos + 4)?;
+
+  let is_metadata = match item_type {
+    b"Exif" => true,
+    b"mime" => {
+      // item_name と content_type はどちらも NUL 終端の文字列
+      let rest = infe.get(reader.pos + 4..)?;
+      let name_end = rest.iter().position(|&byte| byte == 0)?;
+      let content_type = &rest[name_end + 1..];
+      let content_type = &content_type[..content_type
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(content_type.len())];
+      content_type == b"application/rdf+xml"
+    }
+    _ => false,
+  };
+
+  is_metadata.then_some(item_id)
+}
+
+/// ビッグエンディアンの可変長の整数を順に読む
+struct BoxReader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl BoxReader<'_> {
+  fn read(&mut self, size: usize) -> Option<u64> {
+    let bytes = self.data.get(self.pos..self.pos + size)?;
+    self.pos += size;
+    Some(bytes.iter().fold(0u64, |value, &byte| (value << 8) | u64::from(byte)))
+  }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use crate::domains::picture::format::tests::{bmff_box, sample_png};
+  use exif::{experimental::Writer, Field, Rational};
+  use image::{codecs::jpeg::JpegEncoder, RgbImage};
+
+  fn sample_exif() -> Vec<u8> {
+    let ascii = |tag, value: &str| Field {
+      tag,
+      ifd_num: In::PRIMARY,
+      value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+    };
+    let dms = |d, m, s| {
+      Value::Rational(vec![
+        Rational::from((d, 1)),
+        Rational::from((m, 1)),
+        Rational::from((s, 1)),
+      ])
+    };
+
+    let fields = vec![
+      ascii(Tag::Make, "Koko"),
+      ascii(Tag::Model, "Koko Cam X"),
+      ascii(Tag::BodySerialNumber, "SERIAL-123"),
+      ascii(Tag::DateTimeOriginal, "2026:05:01 10:20:30"),
+      Field {
+        tag: Tag::Orientation,
+        ifd_num: In::PRIMARY,
+        value: Value::Short(vec![6]),
+      },
+      ascii(Tag::GPSLatitudeRef, "N"),
+      Field {
+        tag: Tag::GPSLatitude,
+        ifd_num: In::PRIMARY,
+        value: dms(35, 40, 48),
+      },
+      ascii(Tag::GPSLongitudeRef, "E"),
+      Field {
+        tag: Tag::GPSLongitude,
+        ifd_num: In::PRIMARY,
+        value: dms(139, 46, 12),
+      },
+    ];
+
+    let mut writer = Writer::new();
+    for field in &fields {
+      writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+    tiff.into_inner()
+  }
+
+  /// EXIF・XMP・コメントを含む JPEG
+  pub(crate) fn sample_jpeg() -> Vec<u8> {
+    let mut encoded = Vec::new();
+    RgbImage::new(8, 4)
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, 90))
+      .unwrap();
+
+    let segment = |marker: u8, payload: &[u8]| {
+      let mut segment = vec![0xFF, marker];
+      segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+      segment.extend_from_slice(payload);
+      segment
+    };
+
+    [
+      encoded[..2].to_vec(),
+      segment(0xE1, &[b"Exif\x00\x00".as_slice(), &sample_exif()].concat()),
+      segment(0xE1, b"http://ns.adobe.com/xap/1.0/\x00<x:xmpmeta/>"),
+      segment(0xFE, b"shot at home"),
+      encoded[2..].to_vec(),
+    ]
+    .concat()
+  }
+
+  #[test]
+  fn test_extract_capture_metadata() {
+    let metadata = extract(&sample_jpeg());
+
+    assert_eq!(
+      metadata.taken_at,
+      NaiveDateTime::parse_from_str("2026-05-01 10:20:30", "%Y-%m-%d %H:%M:%S").ok()
+    );
+    assert_eq!(metadata.camera_make.as_deref(), Some("Koko"));
+    assert_eq!(metadata.camera_model.as_deref(), Some("Koko Cam X"));
+    assert_eq!(metadata.orientation, Some(6));
+    let (lat, lng) = metadata.gps.unwrap();
+    assert!((lat - 35.68).abs() < 1e-9);
+    assert!((lng - 139.77).abs() < 1e-9);
+
+    assert_eq!(extract(b"not an image"), CaptureMetadata::default());
+  }
+
+  #[test]
+  fn test_strip_jpeg_keeps_only_orientation() {
+    let original = sample_jpeg();
+    let stripped = strip(original.clone(), ImageFormat::Jpeg, Some(6)).unwrap();
+
+    assert_eq!(
+      extract(&stripped),
+      CaptureMetadata {
+        orientation: Some(6),
+        ..Default::default()
+      }
+    );
+    for secret in [b"SERIAL-123".as_slice(), b"xmpmeta", b"shot at home"] {
+      assert!(original.windows(secret.len()).any(|window| window == secret));
+      assert!(!stripped.windows(secret.len()).any(|window| window == secret));
+    }
+
+    let decoded = image::load_from_memory(&stripped).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (8, 4));
+  }
+
+  #[test]
+  fn test_strip_png_and_webp_metadata() {
+    let png = sample_png(4, 4);
+    let iend = png.len() - 12;
+    let mut with_text = png[..iend].to_vec();
+    write_png_chunk(&mut with_text, b"tEXt", b"Comment\x00secret");
+    with_text.extend_from_slice(&png[iend..]);
+
+    let stripped = strip(with_text, ImageFormat::Png, Some(3)).unwrap();
+    assert!(!stripped.windows(6).any(|window| window == b"secret"));
+    assert_eq!(extract(&stripped).orientation, Some(3));
+    assert!(image::load_from_memory(&stripped).is_ok());
+
+    // VP8X の拡張形式に EXIF を付けた WebP
+    let mut webp = Vec::new();
+    image::DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4))
+      .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut webp))
+      .unwrap();
+    let exif = sample_exif();
+    let mut vp8x = b"VP8X".to_vec();
+    vp8x.extend_from_slice(&10u32.to_le_bytes());
+    vp8x.extend_from_slice(&[WEBP_EXIF_FLAG, 0, 0, 0, 3, 0, 0, 3, 0, 0]);
+    let mut extended = [b"RIFF\x00\x00\x00\x00WEBP".as_slice(), &vp8x, &webp[12..], b"EXIF"].concat();
+    extended.extend_from_slice(&(exif.len() as u32).to_le_bytes());
+    extended.extend_from_slice(&exif);
+    if exif.len() % 2 == 1 {
+      extended.push(0);
+    }
+    let riff_size = (extended.len() - 8) as u32;
+    extended[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    assert!(extract(&extended).gps.is_some());
+
+    let stripped = strip(extended, ImageFormat::Webp, None).unwrap();
+    assert_eq!(extract(&stripped), CaptureMetadata::default());
+    assert_eq!(stripped[20] & WEBP_EXIF_FLAG, 0);
+    assert!(image::load_from_memory(&stripped).is_ok());
+  }
+
+  /// EXIF のアイテムを1つ持つ iinf
+  fn exif_iinf() -> Vec<u8> {
+    let mut infe = vec![2, 0, 0, 0];
+    infe.extend_from_slice(&1u16.to_be_bytes());
+    infe.extend_from_slice(&0u16.to_be_bytes());
+    infe.extend_from_slice(b"Exif\x00");
+    bmff_box(b"iinf", &[vec![0, 0, 0, 0, 0, 1], bmff_box(b"infe", &infe)].concat())
+  }
+
+  #[test]
+  fn test_strip_heic_rejects_overflowing_extents() {
+    let ftyp = bmff_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+    let build = |base_offset: u64, offset: u64, length: u64| {
+      // iloc v0: offset_size=8, length_size=8, base_offset_size=8
+      let mut iloc = vec![0, 0, 0, 0, 0x88, 0x80];
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&0u16.to_be_bytes());
+      iloc.extend_from_slice(&base_offset.to_be_bytes());
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&offset.to_be_bytes());
+      iloc.extend_from_slice(&length.to_be_bytes());
+      let meta = bmff_box(b"meta", &[vec![0; 4], exif_iinf(), bmff_box(b"iloc", &iloc)].concat());
+      [ftyp.clone(), meta, bmff_box(b"mdat", b"Exif")].concat()
+    };
+
+    // どれか1つでも上限近くの値なら、桁あふれかファイルの外を指すため壊れたファイルになる
+    let values = [0, 1, 64, u64::MAX / 2 + 1, u64::MAX - 64, u64::MAX - 1, u64::MAX];
+    for base_offset in values {
+      for offset in values {
+        for length in values {
+          if [base_offset, offset, length].iter().all(|&value| value <= 64) {
+            continue;
+          }
+          let heic = build(base_offset, offset, length);
+          assert!(
+            strip(heic, ImageFormat::Heic, None).is_err(),
+            "base_offset={} offset={} length={}",
+            base_offset,
+            offset,
+            length
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_strip_heic_zeroes_exif_item() {
+    let exif = [b"\x00\x00\x00\x06Exif\x00\x00".as_slice(), &sample_exif()].concat();
+    let iinf = exif_iinf();
+
+    let ftyp = bmff_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+    let build = |offset: u32| {
+      // iloc v0: offset_size=4, length_size=4, base_offset_size=0
+      let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00];
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&0u16.to_be_bytes());
+      iloc.extend_from_slice(&1u16.to_be_bytes());
+      iloc.extend_from_slice(&offset.to_be_bytes());
+      iloc.extend_from_slice(&(exif.len() as u32).to_be_bytes());
+      let meta = bmff_box(b"meta", &[vec![0; 4], iinf.clone(), bmff_box(b"iloc", &iloc)].concat());
+      [ftyp.clone(), meta, bmff_box(b"mdat", &exif)].concat()
+    };
+    let offset = (build(0).len() - exif.len()) as u32;
+    let heic = build(offset);
+
+    let stripped = strip(heic.clone(), ImageFormat::Heic, None).unwrap();
+    assert_eq!(stripped.len(), heic.len());
+    assert_eq!(&stripped[..offset as usize], &heic[..offset as usize]);
+    assert!(stripped[offset as usize..].iter().all(|&byte| byte == 0));
+  }
+}
@@ -1,4 +1,9 @@
+pub mod buffer;
+pub mod format;
+pub mod metadata;
 pub mod model;
 pub mod repository;
 pub mod rest;
 pub mod service;
+pub mod variant;
+pub mod worker;
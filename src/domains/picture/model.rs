@@ -1,16 +1,415 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{types::Json, FromRow};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use super::buffer::BufferReservation;
+use super::format::ImageInfo;
+use super::metadata::CaptureMetadata;
+use crate::domains::request::{
+  model::Request,
+  privacy::{can_view_exact_location, precision_radius_m, public_location, round_distance, PUBLIC_DISTANCE_UNIT_M},
+};
+use crate::storage::ObjectStorage;
+use crate::utils::geo::haversine_distance;
+
+/// 依頼者が投稿写真を確認した結果（migrations の CHECK 制約と揃える）
+pub const REVIEW_STATUSES: &[&str] = &["pending", "accepted", "rejected"];
+
+pub fn validate_review_status(status: &str) -> Result<(), ValidationError> {
+  if !REVIEW_STATUSES.contains(&status) {
+    return Err(ValidationError::new("確認結果が不正です"));
+  }
+
+  Ok(())
+}
 
 #[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
 pub struct Picture {
   pub id: i32,
   pub user_id: i32,
+  /// 画像の URL。ストレージにある画像では読み出したあとにキーから組み立てる。見せられない相手には空にして返さない
+  #[serde(default, skip_serializing_if = "String::is_empty")]
   pub image_url: String,
+  /// ストレージ上のオブジェクトのキー。ストレージの外にある画像では `None`
+  #[serde(skip)]
+  pub storage_key: Option<String>,
+  #[serde(skip)]
+  pub storage_backend: Option<String>,
+  pub width: Option<i32>,
+  pub height: Option<i32>,
+  pub mime_type: Option<String>,
+  /// 撮影地の現地時刻
+  pub taken_at: Option<NaiveDateTime>,
+  pub camera_make: Option<String>,
+  pub camera_model: Option<String>,
+  /// EXIF の Orientation（1〜8）。保存したファイルにもこの値だけは残している
+  pub orientation: Option<i16>,
+  /// 撮影位置は撮影者の居場所が分かるため公開しない
+  #[serde(skip)]
+  pub gps_lat: Option<f64>,
+  #[serde(skip)]
+  pub gps_lng: Option<f64>,
+  /// 投稿時にクライアントが送った現在地。撮影位置と同じく公開しない
+  #[serde(skip)]
+  pub reported_lat: Option<f64>,
+  #[serde(skip)]
+  pub reported_lng: Option<f64>,
+  /// 撮影位置がリクエストの地点から許容距離内だったか。リクエストへの投稿でなければ `None`。
+  /// 位置をぼかしたリクエストでは、依頼者と撮影者以外にも `None` にする
+  pub location_verified: Option<bool>,
+  /// 照合に使った位置とリクエストの地点との距離（メートル）。依頼者と撮影者以外には丸めて返す
+  pub location_distance_m: Option<f64>,
+  pub variants: Json<PictureVariants>,
   pub created_at: Option<DateTime<Utc>>,
 }
 
+impl Picture {
+  /// ストレージ上の元画像と縮小画像すべてのキー。`storage backfill-keys` がまだキーに置き換えていない行は、
+  /// 保存している URL が `storage` を指していればそこからキーを取り出す
+  pub fn storage_keys(&self, storage: &dyn ObjectStorage) -> Vec<String> {
+    let original = (self.storage_key.clone()).or_else(|| storage.key_for_stored_url(&self.image_url));
+    let variants = self
+      .variants
+      .values()
+      .filter_map(|variant| (variant.storage_key.clone()).or_else(|| storage.key_for_stored_url(&variant.url)));
+    original.into_iter().chain(variants).collect()
+  }
+
+  /// 画像を見せられない相手に返すときに、元画像と縮小画像の URL を取り除く
+  pub fn hide_urls(&mut self) {
+    self.image_url.clear();
+    self.variants.clear();
+  }
+
+  /// 位置の照合に使った位置。`LocationCheck::evaluate` と同じく EXIF の撮影位置を優先する
+  fn checked_location(&self) -> Option<(f64, f64)> {
+    self
+      .gps_lat
+      .zip(self.gps_lng)
+      .or(self.reported_lat.zip(self.reported_lng))
+  }
+
+  /// 位置をぼかしたリクエストへの投稿では、送る位置を変えて何度も投稿すれば正確な地点との距離から元の位置を割り出せる。
+  /// 正確な位置を見られない `viewer_id` には、ぼかした地点からの距離を精度の単位に丸めて返し、照合結果は伏せる。
+  /// 正確な位置のリクエストでも、公開されている地点からの細かな距離は撮影者の居場所を明かすため、撮影者以外には丸めて返す
+  pub fn mask_location_check(&mut self, request: &Request, viewer_id: Option<i32>) {
+    if can_view_exact_location(request.user_id, request.claimed_by, viewer_id) {
+      return;
+    }
+    let Some(radius_m) = precision_radius_m(&request.location_precision) else {
+      if viewer_id != Some(self.user_id) {
+        self.location_distance_m = self
+          .location_distance_m
+          .map(|distance| round_distance(distance, PUBLIC_DISTANCE_UNIT_M));
+      }
+      return;
+    };
+
+    let (lat, lng) = public_location(request);
+    self.location_verified = None;
+    self.location_distance_m = self.checked_location().map(|(checked_lat, checked_lng)| {
+      round_distance(haversine_distance(lat, lng, checked_lat, checked_lng), radius_m)
+    });
+  }
+}
+
+/// アップロードされた画像から作成する写真
+#[derive(Debug, Clone, Copy)]
+pub struct NewPicture<'a> {
+  pub user_id: i32,
+  pub storage_key: &'a str,
+  pub storage_backend: &'a str,
+  pub request_id: Option<i32>,
+  pub image: &'a ImageInfo,
+  pub capture: &'a CaptureMetadata,
+  pub reported_location: Option<(f64, f64)>,
+  pub location_check: Option<LocationCheck>,
+  /// 元になった保留中のアップロード。写真の行を作るのと同じトランザクションで保留中の行を消す
+  pub upload_id: Option<Uuid>,
+}
+
+/// 投稿写真の撮影位置とリクエストの地点との照合結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocationCheck {
+  pub verified: bool,
+  pub distance_m: Option<f64>,
+}
+
+impl LocationCheck {
+  /// 偽装しにくい EXIF の撮影位置を優先し、なければクライアントが送った現在地で照合する。
+  /// どちらもなければ未確認とする
+  pub fn evaluate(
+    target: (f64, f64),
+    tolerance_m: i32,
+    exif: Option<(f64, f64)>,
+    reported: Option<(f64, f64)>,
+  ) -> Self {
+    let Some((lat, lng)) = exif.or(reported) else {
+      return Self {
+        verified: false,
+        distance_m: None,
+      };
+    };
+
+    let distance_m = haversine_distance(target.0, target.1, lat, lng);
+    Self {
+      verified: distance_m <= f64::from(tolerance_m),
+      distance_m: Some(distance_m),
+    }
+  }
+}
+
+/// 一覧表示用に縮小した画像
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PictureVariant {
+  pub url: String,
+  #[serde(default, skip_serializing)]
+  pub storage_key: Option<String>,
+  pub width: i32,
+  pub height: i32,
+}
+
+/// `{長辺のピクセル数}_{形式}`（例: `256_webp`）をキーにした縮小画像の一覧
+pub type PictureVariants = BTreeMap<String, PictureVariant>;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PicturesResponse {
   pub pictures: Vec<Picture>,
 }
+
+/// 写真の投稿者の公開プロフィール
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PictureAuthor {
+  pub id: i32,
+  pub display_name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PictureWithAuthor {
+  #[serde(flatten)]
+  pub picture: Picture,
+  pub request_id: Option<i32>,
+  /// 依頼者による確認結果。リクエストへの投稿でなければ `None`
+  pub review_status: Option<String>,
+  /// 投稿先のリクエストの依頼者
+  #[serde(skip)]
+  pub request_owner_id: Option<i32>,
+  #[serde(skip)]
+  pub request_is_private: bool,
+  pub author: PictureAuthor,
+}
+
+impl PictureWithAuthor {
+  /// 画像を `viewer_id` に見せてよいか。投稿者と依頼者には常に見せ、
+  /// それ以外には公開のリクエストへの投稿のうち却下されていないものだけを見せる
+  pub fn is_visible_to(&self, viewer_id: Option<i32>) -> bool {
+    if viewer_id.is_some_and(|viewer_id| viewer_id == self.picture.user_id || Some(viewer_id) == self.request_owner_id)
+    {
+      return true;
+    }
+    if self.request_id.is_none() {
+      return true;
+    }
+
+    !self.request_is_private && self.review_status.as_deref() != Some("rejected")
+  }
+}
+
+/// 写真一覧の絞り込み条件。どちらも未指定なら全体のフィード
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PictureFilter {
+  pub user_id: Option<i32>,
+  pub request_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PictureFeedResponse {
+  pub pictures: Vec<PictureWithAuthor>,
+  pub next_cursor: Option<String>,
+}
+
+/// リクエストごとの投稿数と最新の投稿写真
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubmissionSummary {
+  pub request_id: i32,
+  pub submission_count: i64,
+  pub latest: Picture,
+}
+
+/// リクエストへの投稿数（全体と特定ユーザーの分）
+#[derive(Debug, Clone, Copy)]
+pub struct SubmissionCounts {
+  pub total: i64,
+  pub by_user: i64,
+}
+
+/// 投稿写真が応えているリクエストの概要
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubmissionRequest {
+  pub id: i32,
+  pub place_name: String,
+  pub status: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Submission {
+  #[serde(flatten)]
+  pub picture: Picture,
+  pub request: Option<SubmissionRequest>,
+  /// 依頼者による確認結果。リクエストへの投稿でなければ `None`
+  pub review_status: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubmissionsResponse {
+  pub submissions: Vec<Submission>,
+  pub total: i64,
+  pub page: i64,
+  pub per_page: i64,
+}
+
+/// 依頼者が投稿写真の確認結果を更新する
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct ReviewSubmissionRequest {
+  #[validate(custom(function = validate_review_status))]
+  pub review_status: String,
+}
+
+/// 署名付き URL でストレージへ直接アップロードしている途中の写真
+#[derive(Debug, Clone, FromRow)]
+pub struct PictureUpload {
+  pub id: Uuid,
+  pub user_id: i32,
+  pub request_id: Option<i32>,
+  pub object_key: String,
+  pub content_type: String,
+  pub content_length: i64,
+  pub expires_at: DateTime<Utc>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// サーバー経由で受け取り、メモリに読み込んだファイル。
+/// メタデータを取り除いたファイルを保存先のキーへ一度だけ置くよう、ストレージの一時的な場所には置かない。
+/// 保存を終えるか捨てるまで、読み込みに確保した予算を持ち続ける
+#[derive(Debug)]
+pub struct StagedUpload {
+  pub user_id: i32,
+  pub data: Vec<u8>,
+  pub(crate) reservation: BufferReservation,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+pub struct CreatePictureUploadRequest {
+  /// JPEG・PNG・WebP・HEIC の MIME タイプ
+  pub content_type: String,
+  #[validate(range(min = 1, message = "ファイルサイズは1バイト以上である必要があります"))]
+  pub content_length: i64,
+  pub request_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PictureUploadResponse {
+  pub upload_id: Uuid,
+  pub method: String,
+  pub upload_url: String,
+  /// アップロード時にそのまま送る必要があるヘッダー
+  pub headers: BTreeMap<String, String>,
+  /// この時刻までに完了を通知しなければアップロードは破棄される
+  pub expires_at: DateTime<Utc>,
+}
+
+/// 完了を通知され、バックグラウンドで写真として保存するのを待っている直接アップロード
+#[derive(Debug, Clone)]
+pub struct CompletedUpload {
+  pub upload: PictureUpload,
+  pub reported_location: Option<(f64, f64)>,
+  /// これまでに保存を試みて一時的に失敗した回数
+  pub attempts: i32,
+}
+
+/// 保留中のアップロードの処理の進み具合
+#[derive(Debug, Clone, FromRow)]
+pub struct PictureUploadProgress {
+  pub user_id: i32,
+  pub completed_at: Option<DateTime<Utc>>,
+  pub failed_at: Option<DateTime<Utc>>,
+  pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PictureUploadState {
+  /// まだ完了を通知されていない
+  Pending,
+  /// 完了を通知され、写真として保存するのを待っている
+  Processing,
+  Completed,
+  /// 画像として受け付けられなかった
+  Failed,
+}
+
+/// 直接アップロードの完了を通知したあとに、写真として保存されたかを確かめるための状態
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PictureUploadStatus {
+  pub upload_id: Uuid,
+  pub status: PictureUploadState,
+  pub picture_id: Option<i32>,
+  pub error: Option<String>,
+}
+
+impl PictureUploadStatus {
+  pub fn from_progress(upload_id: Uuid, progress: &PictureUploadProgress) -> Self {
+    let status = match (progress.completed_at, progress.failed_at) {
+      (_, Some(_)) => PictureUploadState::Failed,
+      (Some(_), None) => PictureUploadState::Processing,
+      (None, None) => PictureUploadState::Pending,
+    };
+    Self {
+      upload_id,
+      status,
+      picture_id: None,
+      error: progress.failed_at.and(progress.last_error.clone()),
+    }
+  }
+}
+
+/// アップロード完了時に送る撮影時の端末の現在地
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
+pub struct CompletePictureUploadRequest {
+  #[validate(range(min = -90.0, max = 90.0, message = "緯度は-90から90の範囲である必要があります"))]
+  pub lat: Option<f64>,
+  #[validate(range(min = -180.0, max = 180.0, message = "経度は-180から180の範囲である必要があります"))]
+  pub lng: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TOKYO_STATION: (f64, f64) = (35.6812, 139.7671);
+
+  #[test]
+  fn test_location_check_prefers_exif() {
+    // EXIF は約1km離れているため、近くの現在地が送られていても未確認
+    let check = LocationCheck::evaluate(TOKYO_STATION, 200, Some((35.6902, 139.7671)), Some(TOKYO_STATION));
+    assert!(!check.verified);
+    assert!((check.distance_m.unwrap() - 1000.0).abs() < 10.0);
+
+    let check = LocationCheck::evaluate(TOKYO_STATION, 200, None, Some((35.6821, 139.7671)));
+    assert!(check.verified);
+  }
+
+  #[test]
+  fn test_location_check_without_position() {
+    assert_eq!(
+      LocationCheck::evaluate(TOKYO_STATION, 200, None, None),
+      LocationCheck {
+        verified: false,
+        distance_m: None
+      }
+    );
+  }
+}
@@ -1,29 +1,200 @@
-use sqlx::{Executor, PgPool, Postgres};
+use sqlx::{types::Json, Executor, PgPool, Postgres};
+use std::time::Duration;
+use uuid::Uuid;
 
-use super::model::Picture;
+use crate::utils::pagination::CursorPage;
 
-pub async fn find_all(db: &PgPool) -> Result<Vec<Picture>, sqlx::Error> {
-  find_all_with_executor(db).await
+use super::model::{
+  CompletedUpload, NewPicture, Picture, PictureAuthor, PictureFilter, PictureUpload, PictureUploadProgress,
+  PictureVariants, PictureWithAuthor, Submission, SubmissionCounts, SubmissionRequest, SubmissionSummary,
+};
+use super::variant::RenderedVariant;
+
+pub async fn find_all(
+  db: &PgPool,
+  filter: &PictureFilter,
+  page: &CursorPage,
+) -> Result<Vec<PictureWithAuthor>, sqlx::Error> {
+  find_all_with_executor(db, filter, page).await
 }
 
-pub async fn find_all_with_executor<'e, E>(executor: E) -> Result<Vec<Picture>, sqlx::Error>
+/// 新しい順に写真を返す。`page.after` より後ろの行から最大 `page.fetch_limit()` 件を取得する
+pub async fn find_all_with_executor<'e, E>(
+  executor: E,
+  filter: &PictureFilter,
+  page: &CursorPage,
+) -> Result<Vec<PictureWithAuthor>, sqlx::Error>
 where
   E: Executor<'e, Database = Postgres>,
 {
-  let pictures = sqlx::query_as!(
-    Picture,
+  let rows = sqlx::query!(
     r#"
-      SELECT id, user_id, image_url, created_at
-      FROM pictures
-      ORDER BY created_at DESC
-    "#
+      SELECT
+        p.id,
+        p.user_id,
+        COALESCE(p.image_url, p.storage_key) as "image_url!",
+        p.storage_key,
+        p.storage_backend,
+        p.width,
+        p.height,
+        p.mime_type,
+        p.taken_at,
+        p.camera_make,
+        p.camera_model,
+        p.orientation,
+        p.gps_lat,
+        p.gps_lng,
+        p.reported_lat,
+        p.reported_lng,
+        p.location_verified,
+        p.location_distance_m,
+        picture_variants_json(p.id) as "variants!: Json<PictureVariants>",
+        p.created_at,
+        p.request_id,
+        CASE WHEN p.request_id IS NULL THEN NULL ELSE p.review_status END as review_status,
+        r.user_id as "request_owner_id?",
+        COALESCE(r.is_private, false) as "request_is_private!",
+        u.display_name
+      FROM pictures p
+      JOIN users u ON u.id = p.user_id
+      LEFT JOIN requests r ON r.id = p.request_id
+      WHERE ($1::INTEGER IS NULL OR p.user_id = $1)
+        AND ($2::INTEGER IS NULL OR p.request_id = $2)
+        AND ($3::TIMESTAMPTZ IS NULL OR (p.created_at, p.id) < ($3, $4))
+      ORDER BY p.created_at DESC, p.id DESC
+      LIMIT $5
+    "#,
+    filter.user_id,
+    filter.request_id,
+    page.after.map(|cursor| cursor.created_at),
+    page.after.map(|cursor| cursor.id),
+    page.fetch_limit()
   )
   .fetch_all(executor)
   .await?;
 
+  let pictures = rows
+    .into_iter()
+    .map(|row| PictureWithAuthor {
+      picture: Picture {
+        id: row.id,
+        user_id: row.user_id,
+        image_url: row.image_url,
+        storage_key: row.storage_key,
+        storage_backend: row.storage_backend,
+        width: row.width,
+        height: row.height,
+        mime_type: row.mime_type,
+        taken_at: row.taken_at,
+        camera_make: row.camera_make,
+        camera_model: row.camera_model,
+        orientation: row.orientation,
+        gps_lat: row.gps_lat,
+        gps_lng: row.gps_lng,
+        reported_lat: row.reported_lat,
+        reported_lng: row.reported_lng,
+        location_verified: row.location_verified,
+        location_distance_m: row.location_distance_m,
+        variants: row.variants,
+        created_at: Some(row.created_at),
+      },
+      request_id: row.request_id,
+      review_status: row.review_status,
+      request_owner_id: row.request_owner_id,
+      request_is_private: row.request_is_private,
+      author: PictureAuthor {
+        id: row.user_id,
+        display_name: row.display_name,
+      },
+    })
+    .collect();
+
   Ok(pictures)
 }
 
+pub async fn find_with_author_by_id(db: &PgPool, id: i32) -> Result<Option<PictureWithAuthor>, sqlx::Error> {
+  find_with_author_by_id_with_executor(db, id).await
+}
+
+pub async fn find_with_author_by_id_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+) -> Result<Option<PictureWithAuthor>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let row = sqlx::query!(
+    r#"
+      SELECT
+        p.id,
+        p.user_id,
+        COALESCE(p.image_url, p.storage_key) as "image_url!",
+        p.storage_key,
+        p.storage_backend,
+        p.width,
+        p.height,
+        p.mime_type,
+        p.taken_at,
+        p.camera_make,
+        p.camera_model,
+        p.orientation,
+        p.gps_lat,
+        p.gps_lng,
+        p.reported_lat,
+        p.reported_lng,
+        p.location_verified,
+        p.location_distance_m,
+        picture_variants_json(p.id) as "variants!: Json<PictureVariants>",
+        p.created_at,
+        p.request_id,
+        CASE WHEN p.request_id IS NULL THEN NULL ELSE p.review_status END as review_status,
+        r.user_id as "request_owner_id?",
+        COALESCE(r.is_private, false) as "request_is_private!",
+        u.display_name
+      FROM pictures p
+      JOIN users u ON u.id = p.user_id
+      LEFT JOIN requests r ON r.id = p.request_id
+      WHERE p.id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(row.map(|row| PictureWithAuthor {
+    picture: Picture {
+      id: row.id,
+      user_id: row.user_id,
+      image_url: row.image_url,
+      storage_key: row.storage_key,
+      storage_backend: row.storage_backend,
+      width: row.width,
+      height: row.height,
+      mime_type: row.mime_type,
+      taken_at: row.taken_at,
+      camera_make: row.camera_make,
+      camera_model: row.camera_model,
+      orientation: row.orientation,
+      gps_lat: row.gps_lat,
+      gps_lng: row.gps_lng,
+      reported_lat: row.reported_lat,
+      reported_lng: row.reported_lng,
+      location_verified: row.location_verified,
+      location_distance_m: row.location_distance_m,
+      variants: row.variants,
+      created_at: Some(row.created_at),
+    },
+    request_id: row.request_id,
+    review_status: row.review_status,
+    request_owner_id: row.request_owner_id,
+    request_is_private: row.request_is_private,
+    author: PictureAuthor {
+      id: row.user_id,
+      display_name: row.display_name,
+    },
+  }))
+}
+
 pub async fn create(db: &PgPool, user_id: i32, image_url: &str) -> Result<Picture, sqlx::Error> {
   create_with_executor(db, user_id, image_url).await
 }
@@ -37,7 +208,11 @@ where
     r#"
       INSERT INTO pictures (user_id, image_url)
       VALUES ($1, $2)
-      RETURNING id, user_id, image_url, created_at
+      RETURNING id, user_id, COALESCE(image_url, storage_key) as "image_url!", storage_key, storage_backend,
+        width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>", created_at
     "#,
     user_id,
     image_url
@@ -48,6 +223,119 @@ where
   Ok(picture)
 }
 
+/// アップロードされた画像から写真を作成する。`request_id` があればそのリクエストへの投稿になる
+pub async fn create_uploaded(db: &PgPool, picture: &NewPicture<'_>) -> Result<Picture, sqlx::Error> {
+  create_uploaded_with_executor(db, picture).await
+}
+
+pub async fn create_uploaded_with_executor<'e, E>(executor: E, picture: &NewPicture<'_>) -> Result<Picture, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let capture = picture.capture;
+  let picture = sqlx::query_as!(
+    Picture,
+    r#"
+      INSERT INTO pictures (
+        user_id, storage_key, storage_backend, request_id, width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m, upload_id
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+      RETURNING id, user_id, COALESCE(image_url, storage_key) as "image_url!", storage_key, storage_backend,
+        width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>", created_at
+    "#,
+    picture.user_id,
+    picture.storage_key,
+    picture.storage_backend,
+    picture.request_id,
+    picture.image.width as i32,
+    picture.image.height as i32,
+    picture.image.format.mime_type(),
+    capture.taken_at,
+    capture.camera_make.as_deref(),
+    capture.camera_model.as_deref(),
+    capture.orientation.map(|orientation| orientation as i16),
+    capture.gps.map(|(lat, _)| lat),
+    capture.gps.map(|(_, lng)| lng),
+    picture.reported_location.map(|(lat, _)| lat),
+    picture.reported_location.map(|(_, lng)| lng),
+    picture.location_check.map(|check| check.verified),
+    picture.location_check.and_then(|check| check.distance_m),
+    picture.upload_id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(picture)
+}
+
+pub async fn create_variant(
+  db: &PgPool,
+  picture_id: i32,
+  variant: &RenderedVariant,
+  storage_key: &str,
+) -> Result<(), sqlx::Error> {
+  create_variant_with_executor(db, picture_id, variant, storage_key).await
+}
+
+pub async fn create_variant_with_executor<'e, E>(
+  executor: E,
+  picture_id: i32,
+  variant: &RenderedVariant,
+  storage_key: &str,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      INSERT INTO picture_variants (picture_id, size, format, width, height, storage_key)
+      VALUES ($1, $2, $3, $4, $5, $6)
+    "#,
+    picture_id,
+    variant.size as i32,
+    variant.format.name(),
+    variant.width as i32,
+    variant.height as i32,
+    storage_key
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// リクエストへの投稿数を、全体と指定ユーザーの分で数える
+pub async fn count_submissions_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  user_id: i32,
+) -> Result<SubmissionCounts, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let counts = sqlx::query_as!(
+    SubmissionCounts,
+    r#"
+      SELECT
+        COUNT(*) as "total!",
+        COUNT(*) FILTER (WHERE user_id = $2) as "by_user!"
+      FROM pictures
+      WHERE request_id = $1
+    "#,
+    request_id,
+    user_id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(counts)
+}
+
 pub async fn find_by_id(db: &PgPool, id: i32) -> Result<Option<Picture>, sqlx::Error> {
   find_by_id_with_executor(db, id).await
 }
@@ -59,7 +347,11 @@ where
   let picture = sqlx::query_as!(
     Picture,
     r#"
-      SELECT id, user_id, image_url, created_at
+      SELECT id, user_id, COALESCE(image_url, storage_key) as "image_url!", storage_key, storage_backend,
+        width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>", created_at
       FROM pictures
       WHERE id = $1
     "#,
@@ -71,6 +363,259 @@ where
   Ok(picture)
 }
 
+pub async fn find_by_request_id(db: &PgPool, request_id: i32) -> Result<Vec<Picture>, sqlx::Error> {
+  find_by_request_id_with_executor(db, request_id).await
+}
+
+pub async fn find_by_request_id_with_executor<'e, E>(executor: E, request_id: i32) -> Result<Vec<Picture>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let pictures = sqlx::query_as!(
+    Picture,
+    r#"
+      SELECT id, user_id, COALESCE(image_url, storage_key) as "image_url!", storage_key, storage_backend,
+        width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>", created_at
+      FROM pictures
+      WHERE request_id = $1
+      ORDER BY created_at DESC
+    "#,
+    request_id
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(pictures)
+}
+
+pub async fn summarize_by_request_ids(db: &PgPool, request_ids: &[i32]) -> Result<Vec<SubmissionSummary>, sqlx::Error> {
+  summarize_by_request_ids_with_executor(db, request_ids).await
+}
+
+pub async fn summarize_by_request_ids_with_executor<'e, E>(
+  executor: E,
+  request_ids: &[i32],
+) -> Result<Vec<SubmissionSummary>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  // リクエストごとに最新の1枚だけを残し、投稿数はウィンドウ関数で数える
+  let rows = sqlx::query!(
+    r#"
+      SELECT DISTINCT ON (request_id)
+        request_id as "request_id!",
+        COUNT(*) OVER (PARTITION BY request_id) as "submission_count!",
+        id,
+        user_id,
+        COALESCE(image_url, storage_key) as "image_url!",
+        storage_key,
+        storage_backend,
+        width,
+        height,
+        mime_type,
+        taken_at,
+        camera_make,
+        camera_model,
+        orientation,
+        gps_lat,
+        gps_lng,
+        reported_lat,
+        reported_lng,
+        location_verified,
+        location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>",
+        created_at
+      FROM pictures
+      WHERE request_id = ANY($1)
+      ORDER BY request_id, created_at DESC, id DESC
+    "#,
+    request_ids
+  )
+  .fetch_all(executor)
+  .await?;
+
+  let summaries = rows
+    .into_iter()
+    .map(|row| SubmissionSummary {
+      request_id: row.request_id,
+      submission_count: row.submission_count,
+      latest: Picture {
+        id: row.id,
+        user_id: row.user_id,
+        image_url: row.image_url,
+        storage_key: row.storage_key,
+        storage_backend: row.storage_backend,
+        width: row.width,
+        height: row.height,
+        mime_type: row.mime_type,
+        taken_at: row.taken_at,
+        camera_make: row.camera_make,
+        camera_model: row.camera_model,
+        orientation: row.orientation,
+        gps_lat: row.gps_lat,
+        gps_lng: row.gps_lng,
+        reported_lat: row.reported_lat,
+        reported_lng: row.reported_lng,
+        location_verified: row.location_verified,
+        location_distance_m: row.location_distance_m,
+        variants: row.variants,
+        created_at: Some(row.created_at),
+      },
+    })
+    .collect();
+
+  Ok(summaries)
+}
+
+pub async fn find_submissions_by_user_id(
+  db: &PgPool,
+  user_id: i32,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<Submission>, sqlx::Error> {
+  find_submissions_by_user_id_with_executor(db, user_id, limit, offset).await
+}
+
+pub async fn find_submissions_by_user_id_with_executor<'e, E>(
+  executor: E,
+  user_id: i32,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<Submission>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let rows = sqlx::query!(
+    r#"
+      SELECT
+        p.id,
+        p.user_id,
+        COALESCE(p.image_url, p.storage_key) as "image_url!",
+        p.storage_key,
+        p.storage_backend,
+        p.width,
+        p.height,
+        p.mime_type,
+        p.taken_at,
+        p.camera_make,
+        p.camera_model,
+        p.orientation,
+        p.gps_lat,
+        p.gps_lng,
+        p.reported_lat,
+        p.reported_lng,
+        p.location_verified,
+        p.location_distance_m,
+        picture_variants_json(p.id) as "variants!: Json<PictureVariants>",
+        p.created_at,
+        r.id as "request_id?",
+        r.place_name as "request_place_name?",
+        r.status as "request_status?",
+        CASE WHEN p.request_id IS NULL THEN NULL ELSE p.review_status END as review_status
+      FROM pictures p
+      LEFT JOIN requests r ON r.id = p.request_id
+      WHERE p.user_id = $1
+      ORDER BY p.created_at DESC, p.id DESC
+      LIMIT $2 OFFSET $3
+    "#,
+    user_id,
+    limit,
+    offset
+  )
+  .fetch_all(executor)
+  .await?;
+
+  let submissions = rows
+    .into_iter()
+    .map(|row| {
+      let request = match (row.request_id, row.request_place_name, row.request_status) {
+        (Some(id), Some(place_name), Some(status)) => Some(SubmissionRequest { id, place_name, status }),
+        _ => None,
+      };
+
+      Submission {
+        picture: Picture {
+          id: row.id,
+          user_id: row.user_id,
+          image_url: row.image_url,
+          storage_key: row.storage_key,
+          storage_backend: row.storage_backend,
+          width: row.width,
+          height: row.height,
+          mime_type: row.mime_type,
+          taken_at: row.taken_at,
+          camera_make: row.camera_make,
+          camera_model: row.camera_model,
+          orientation: row.orientation,
+          gps_lat: row.gps_lat,
+          gps_lng: row.gps_lng,
+          reported_lat: row.reported_lat,
+          reported_lng: row.reported_lng,
+          location_verified: row.location_verified,
+          location_distance_m: row.location_distance_m,
+          variants: row.variants,
+          created_at: Some(row.created_at),
+        },
+        request,
+        review_status: row.review_status,
+      }
+    })
+    .collect();
+
+  Ok(submissions)
+}
+
+/// `request_id` への投稿写真 `id` の確認結果を更新する。そのリクエストへの投稿でなければ `false`
+pub async fn update_review_status(db: &PgPool, id: i32, request_id: i32, status: &str) -> Result<bool, sqlx::Error> {
+  update_review_status_with_executor(db, id, request_id, status).await
+}
+
+pub async fn update_review_status_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+  request_id: i32,
+  status: &str,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE pictures
+      SET review_status = $3
+      WHERE id = $1 AND request_id = $2
+    "#,
+    id,
+    request_id,
+    status
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn count_by_user_id(db: &PgPool, user_id: i32) -> Result<i64, sqlx::Error> {
+  count_by_user_id_with_executor(db, user_id).await
+}
+
+pub async fn count_by_user_id_with_executor<'e, E>(executor: E, user_id: i32) -> Result<i64, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let count = sqlx::query_scalar!(
+    r#"SELECT COUNT(*) as "count!" FROM pictures WHERE user_id = $1"#,
+    user_id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(count)
+}
+
 pub async fn delete(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
   delete_with_executor(db, id).await
 }
@@ -91,3 +636,339 @@ where
 
   Ok(())
 }
+
+pub async fn delete_variant(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+  delete_variant_with_executor(db, id).await
+}
+
+pub async fn delete_variant_with_executor<'e, E>(executor: E, id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      DELETE FROM picture_variants
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn create_upload(db: &PgPool, upload: &PictureUpload) -> Result<(), sqlx::Error> {
+  create_upload_with_executor(db, upload).await
+}
+
+pub async fn create_upload_with_executor<'e, E>(executor: E, upload: &PictureUpload) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      INSERT INTO picture_uploads (id, user_id, request_id, object_key, content_type, content_length, expires_at)
+      VALUES ($1, $2, $3, $4, $5, $6, $7)
+    "#,
+    upload.id,
+    upload.user_id,
+    upload.request_id,
+    upload.object_key,
+    upload.content_type,
+    upload.content_length,
+    upload.expires_at
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn find_upload_by_id(db: &PgPool, id: Uuid) -> Result<Option<PictureUpload>, sqlx::Error> {
+  find_upload_by_id_with_executor(db, id).await
+}
+
+pub async fn find_upload_by_id_with_executor<'e, E>(executor: E, id: Uuid) -> Result<Option<PictureUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let upload = sqlx::query_as!(
+    PictureUpload,
+    r#"
+      SELECT id, user_id, request_id, object_key, content_type, content_length, expires_at, created_at
+      FROM picture_uploads
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(upload)
+}
+
+/// 直接アップロードの完了を記録し、バックグラウンドでの保存を待つ。すでに完了を記録していた場合は `false`
+pub async fn mark_upload_completed(
+  db: &PgPool,
+  id: Uuid,
+  reported_location: Option<(f64, f64)>,
+) -> Result<bool, sqlx::Error> {
+  mark_upload_completed_with_executor(db, id, reported_location).await
+}
+
+pub async fn mark_upload_completed_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  reported_location: Option<(f64, f64)>,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE picture_uploads
+      SET completed_at = NOW(), next_attempt_at = NOW(), reported_lat = $2, reported_lng = $3
+      WHERE id = $1 AND completed_at IS NULL
+    "#,
+    id,
+    reported_location.map(|(lat, _)| lat),
+    reported_location.map(|(_, lng)| lng)
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// 保存を待っている直接アップロードを取り出し、`lease` の間はほかの処理が取り出さないようにする
+pub async fn claim_completed_uploads(
+  db: &PgPool,
+  limit: i64,
+  lease: Duration,
+) -> Result<Vec<CompletedUpload>, sqlx::Error> {
+  claim_completed_uploads_with_executor(db, limit, lease).await
+}
+
+pub async fn claim_completed_uploads_with_executor<'e, E>(
+  executor: E,
+  limit: i64,
+  lease: Duration,
+) -> Result<Vec<CompletedUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let rows = sqlx::query!(
+    r#"
+      UPDATE picture_uploads
+      SET next_attempt_at = NOW() + make_interval(secs => $2)
+      WHERE id IN (
+        SELECT id
+        FROM picture_uploads
+        WHERE completed_at IS NOT NULL AND failed_at IS NULL AND next_attempt_at <= NOW()
+        ORDER BY next_attempt_at
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+      )
+      RETURNING id, user_id, request_id, object_key, content_type, content_length, expires_at, created_at,
+        reported_lat, reported_lng, attempts
+    "#,
+    limit,
+    lease.as_secs_f64()
+  )
+  .fetch_all(executor)
+  .await?;
+
+  let uploads = rows
+    .into_iter()
+    .map(|row| CompletedUpload {
+      upload: PictureUpload {
+        id: row.id,
+        user_id: row.user_id,
+        request_id: row.request_id,
+        object_key: row.object_key,
+        content_type: row.content_type,
+        content_length: row.content_length,
+        expires_at: row.expires_at,
+        created_at: row.created_at,
+      },
+      reported_location: row.reported_lat.zip(row.reported_lng),
+      attempts: row.attempts,
+    })
+    .collect();
+
+  Ok(uploads)
+}
+
+/// 保存に一時的に失敗したことを記録し、`retry_after` 後に再試行する
+pub async fn record_upload_failure(
+  db: &PgPool,
+  id: Uuid,
+  error: &str,
+  retry_after: Duration,
+) -> Result<(), sqlx::Error> {
+  record_upload_failure_with_executor(db, id, error, retry_after).await
+}
+
+pub async fn record_upload_failure_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  error: &str,
+  retry_after: Duration,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE picture_uploads
+      SET attempts = attempts + 1, last_error = $2, next_attempt_at = NOW() + make_interval(secs => $3)
+      WHERE id = $1
+    "#,
+    id,
+    error,
+    retry_after.as_secs_f64()
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// 写真として保存できなかったことを記録する。クライアントが結果を確かめられるよう、行は `retain` の間残してから掃除する
+pub async fn mark_upload_failed(db: &PgPool, id: Uuid, error: &str, retain: Duration) -> Result<(), sqlx::Error> {
+  mark_upload_failed_with_executor(db, id, error, retain).await
+}
+
+pub async fn mark_upload_failed_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  error: &str,
+  retain: Duration,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE picture_uploads
+      SET failed_at = NOW(), last_error = $2, expires_at = NOW() + make_interval(secs => $3)
+      WHERE id = $1
+    "#,
+    id,
+    error,
+    retain.as_secs_f64()
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn find_upload_progress(db: &PgPool, id: Uuid) -> Result<Option<PictureUploadProgress>, sqlx::Error> {
+  find_upload_progress_with_executor(db, id).await
+}
+
+pub async fn find_upload_progress_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+) -> Result<Option<PictureUploadProgress>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let progress = sqlx::query_as!(
+    PictureUploadProgress,
+    r#"
+      SELECT user_id, completed_at, failed_at, last_error
+      FROM picture_uploads
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(progress)
+}
+
+/// 直接アップロードから保存された写真を取得する
+pub async fn find_by_upload_id(db: &PgPool, upload_id: Uuid) -> Result<Option<Picture>, sqlx::Error> {
+  find_by_upload_id_with_executor(db, upload_id).await
+}
+
+pub async fn find_by_upload_id_with_executor<'e, E>(
+  executor: E,
+  upload_id: Uuid,
+) -> Result<Option<Picture>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let picture = sqlx::query_as!(
+    Picture,
+    r#"
+      SELECT id, user_id, COALESCE(image_url, storage_key) as "image_url!", storage_key, storage_backend,
+        width, height, mime_type,
+        taken_at, camera_make, camera_model, orientation, gps_lat, gps_lng,
+        reported_lat, reported_lng, location_verified, location_distance_m,
+        picture_variants_json(id) as "variants!: Json<PictureVariants>", created_at
+      FROM pictures
+      WHERE upload_id = $1
+    "#,
+    upload_id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(picture)
+}
+
+/// 期限切れのアップロードを古い順に取得する。保存を待っているものは期限が切れていても残す
+pub async fn find_expired_uploads(db: &PgPool, limit: i64) -> Result<Vec<PictureUpload>, sqlx::Error> {
+  find_expired_uploads_with_executor(db, limit).await
+}
+
+pub async fn find_expired_uploads_with_executor<'e, E>(
+  executor: E,
+  limit: i64,
+) -> Result<Vec<PictureUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let uploads = sqlx::query_as!(
+    PictureUpload,
+    r#"
+      SELECT id, user_id, request_id, object_key, content_type, content_length, expires_at, created_at
+      FROM picture_uploads
+      WHERE expires_at <= NOW() AND (completed_at IS NULL OR failed_at IS NOT NULL)
+      ORDER BY expires_at
+      LIMIT $1
+    "#,
+    limit
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(uploads)
+}
+
+/// 削除できた場合は `true`。同じアップロードから写真を2枚作らないよう、写真の行を作るトランザクションでこの結果を確かめる
+pub async fn delete_upload(db: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+  delete_upload_with_executor(db, id).await
+}
+
+pub async fn delete_upload_with_executor<'e, E>(executor: E, id: Uuid) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      DELETE FROM picture_uploads
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
@@ -1,23 +1,80 @@
+use async_trait::async_trait;
 use axum::{
-  extract::{Multipart, Path, State},
-  http::HeaderMap,
+  body::Bytes,
+  extract::{multipart::Field, DefaultBodyLimit, Json, Multipart, Path, Query, State},
+  http::{HeaderMap, StatusCode},
   response::Json as JsonResponse,
-  routing::{delete, post},
+  routing::{get, patch, post},
   Router,
 };
+use uuid::Uuid;
+use validator::Validate;
 
 use crate::{
-  middleware::auth::auth_middleware,
+  middleware::auth::{auth_middleware, optional_auth_middleware},
   state::{AppState, SharedAppState},
+  utils::pagination::{CursorPage, CursorQuery, Page, PageQuery},
   AppError,
 };
 
-use super::model::Picture;
+use super::format::ImageLimits;
+use super::model::{
+  CompletePictureUploadRequest, CreatePictureUploadRequest, Picture, PictureFeedResponse, PictureUploadResponse,
+  PictureUploadState, PictureUploadStatus, PictureWithAuthor, ReviewSubmissionRequest, StagedUpload,
+  SubmissionsResponse,
+};
+use super::service::{FileChunks, PictureServiceError};
+
+/// multipart の境界や request_id などファイル以外の部分に見込む余裕
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
 
 pub fn picture_routes() -> Router<SharedAppState> {
+  let body_limit = ImageLimits::from_env().max_bytes + MULTIPART_OVERHEAD_BYTES;
+
   Router::new()
-    .route("/pictures", post(create_picture_handler))
-    .route("/pictures/{picture_id}", delete(delete_picture_handler))
+    .route(
+      "/pictures",
+      get(get_pictures_handler)
+        .post(create_picture_handler)
+        .layer(DefaultBodyLimit::max(body_limit)),
+    )
+    .route("/pictures/uploads", post(create_picture_upload_handler))
+    .route("/pictures/uploads/{upload_id}", get(get_picture_upload_status_handler))
+    .route(
+      "/pictures/uploads/{upload_id}/complete",
+      post(complete_picture_upload_handler),
+    )
+    .route(
+      "/pictures/{picture_id}",
+      get(get_picture_handler).delete(delete_picture_handler),
+    )
+    .route("/users/me/submissions", get(get_my_submissions_handler))
+    .route("/users/{user_id}/pictures", get(get_user_pictures_handler))
+    .route("/requests/{request_id}/pictures", get(get_request_pictures_handler))
+    .route(
+      "/requests/{request_id}/pictures/{picture_id}",
+      patch(review_submission_handler),
+    )
+}
+
+/// multipart のファイルのフィールドを、全体をメモリに載せずに少しずつ読む
+struct MultipartFile<'a>(Field<'a>);
+
+#[async_trait]
+impl FileChunks for MultipartFile<'_> {
+  async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError> {
+    // 本文の上限を超えた場合は 413、形式の不正や途中での切断は 400 になるよう、multipart のエラーのステータスで分ける
+    self.0.chunk().await.map_err(|e| match e.status() {
+      StatusCode::PAYLOAD_TOO_LARGE => PictureServiceError::PayloadTooLarge(format!("Failed to read file data: {}", e)),
+      _ => PictureServiceError::BadRequest(format!("Failed to read file data: {}", e)),
+    })
+  }
+}
+
+/// 写真のアップロードでファイルと一緒に送るフィールド
+struct PictureForm {
+  request_id: Option<i32>,
+  reported_location: Option<(f64, f64)>,
 }
 
 async fn create_picture_handler(
@@ -28,35 +85,142 @@ async fn create_picture_handler(
   let claims = auth_middleware(headers).await?;
   let user_id = claims.user_id;
 
-  let mut file_data: Option<Vec<u8>> = None;
-  let mut file_name: Option<String> = None;
-  let mut content_type: Option<String> = None;
+  // ファイルより後のフィールドが不正だった場合、読み込んだファイルはここで捨てられる
+  let mut staged = None;
+  let form = read_picture_form(&state, user_id, &mut multipart, &mut staged).await?;
+  let staged = staged.ok_or_else(|| AppError::bad_request("No file provided".to_string()))?;
 
+  state
+    .upload_and_create_picture(user_id, staged, form.request_id, form.reported_location)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+/// フィールドを順に読む。ファイルは読んだ時点で検証しながらメモリに読み込み、`staged` に残す
+async fn read_picture_form(
+  state: &SharedAppState,
+  user_id: i32,
+  multipart: &mut Multipart,
+  staged: &mut Option<StagedUpload>,
+) -> Result<PictureForm, AppError> {
+  let mut request_id: Option<i32> = None;
+  let mut lat: Option<f64> = None;
+  let mut lng: Option<f64> = None;
+
+  // 本文の上限を超えた場合は 413、形式の不正は 400 になるよう、multipart のエラーのステータスをそのまま使う
   while let Some(field) = multipart
     .next_field()
     .await
-    .map_err(|e| AppError::bad_request(format!("Failed to read multipart field: {}", e)))?
+    .map_err(|e| AppError::new(e.status(), format!("Failed to read multipart field: {}", e)))?
   {
     let name = field.name().unwrap_or("").to_string();
 
     if name == "file" {
-      file_name = field.file_name().map(|s| s.to_string());
-      content_type = field.content_type().map(|s| s.to_string());
-
-      let data = field
-        .bytes()
+      if staged.is_some() {
+        return Err(AppError::bad_request("Only one file can be uploaded".to_string()));
+      }
+      *staged = Some(state.stage_picture_upload(user_id, &mut MultipartFile(field)).await?);
+    } else if name == "request_id" || name == "lat" || name == "lng" {
+      let value = field
+        .text()
         .await
-        .map_err(|e| AppError::bad_request(format!("Failed to read file data: {}", e)))?;
-      file_data = Some(data.to_vec());
+        .map_err(|e| AppError::bad_request(format!("Failed to read {}: {}", name, e)))?;
+      let value = value.trim();
+
+      match name.as_str() {
+        "request_id" => {
+          request_id = Some(
+            value
+              .parse()
+              .map_err(|_| AppError::bad_request("request_id must be an integer".to_string()))?,
+          )
+        }
+        "lat" => lat = Some(parse_coordinate(value, "lat", 90.0)?),
+        _ => lng = Some(parse_coordinate(value, "lng", 180.0)?),
+      }
     }
   }
 
-  let file_data = file_data.ok_or_else(|| AppError::bad_request("No file provided".to_string()))?;
-  let file_name = file_name.ok_or_else(|| AppError::bad_request("No file name provided".to_string()))?;
-  let content_type = content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+  Ok(PictureForm {
+    request_id,
+    reported_location: reported_location(lat, lng)?,
+  })
+}
+
+/// 撮影者が送った現在地の緯度・経度を検証する
+pub(crate) fn parse_coordinate(value: &str, name: &str, max: f64) -> Result<f64, AppError> {
+  value
+    .parse::<f64>()
+    .ok()
+    .filter(|coordinate| coordinate.is_finite() && coordinate.abs() <= max)
+    .ok_or_else(|| AppError::bad_request(format!("{} must be a number between -{} and {}", name, max, max)))
+}
+
+/// 緯度と経度はそろっている場合だけ現在地として扱う
+pub(crate) fn reported_location(lat: Option<f64>, lng: Option<f64>) -> Result<Option<(f64, f64)>, AppError> {
+  match (lat, lng) {
+    (Some(lat), Some(lng)) => Ok(Some((lat, lng))),
+    (None, None) => Ok(None),
+    _ => Err(AppError::bad_request(
+      "lat and lng must be provided together".to_string(),
+    )),
+  }
+}
+
+async fn create_picture_upload_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Json(payload): Json<CreatePictureUploadRequest>,
+) -> Result<JsonResponse<PictureUploadResponse>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
 
   state
-    .upload_and_create_picture(user_id, file_data, file_name, content_type)
+    .create_picture_upload(claims.user_id, payload)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn complete_picture_upload_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(upload_id): Path<Uuid>,
+  payload: Option<Json<CompletePictureUploadRequest>>,
+) -> Result<(StatusCode, JsonResponse<PictureUploadStatus>), AppError> {
+  let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+  let reported_location = reported_location(payload.lat, payload.lng)?;
+
+  let claims = auth_middleware(headers).await?;
+
+  let status = state
+    .complete_picture_upload(claims.user_id, upload_id, reported_location)
+    .await?;
+  // 写真として保存するのはバックグラウンドの処理なので、保存を待っている間は 202 を返す
+  let code = match status.status {
+    PictureUploadState::Pending | PictureUploadState::Processing => StatusCode::ACCEPTED,
+    PictureUploadState::Completed | PictureUploadState::Failed => StatusCode::OK,
+  };
+
+  Ok((code, JsonResponse(status)))
+}
+
+async fn get_picture_upload_status_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(upload_id): Path<Uuid>,
+) -> Result<JsonResponse<PictureUploadStatus>, AppError> {
+  let claims = auth_middleware(headers).await?;
+
+  state
+    .get_picture_upload_status(claims.user_id, upload_id)
     .await
     .map(JsonResponse)
     .map_err(Into::into)
@@ -75,12 +239,119 @@ async fn delete_picture_handler(
   Ok(())
 }
 
+async fn get_my_submissions_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Query(query): Query<PageQuery>,
+) -> Result<JsonResponse<SubmissionsResponse>, AppError> {
+  let page = Page::try_from(query).map_err(AppError::bad_request)?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .get_my_submissions(user_id, page)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn get_pictures_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Query(query): Query<CursorQuery>,
+) -> Result<JsonResponse<PictureFeedResponse>, AppError> {
+  let page = CursorPage::try_from(query).map_err(AppError::bad_request)?;
+  // ログインしていれば、自分の投稿と自分のリクエストへの投稿は非公開でも画像を返す
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_pictures(page, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn get_picture_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(picture_id): Path<i32>,
+) -> Result<JsonResponse<PictureWithAuthor>, AppError> {
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_picture(picture_id, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn get_user_pictures_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(user_id): Path<i32>,
+  Query(query): Query<CursorQuery>,
+) -> Result<JsonResponse<PictureFeedResponse>, AppError> {
+  let page = CursorPage::try_from(query).map_err(AppError::bad_request)?;
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_user_pictures(user_id, page, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn get_request_pictures_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+  Query(query): Query<CursorQuery>,
+) -> Result<JsonResponse<PictureFeedResponse>, AppError> {
+  let page = CursorPage::try_from(query).map_err(AppError::bad_request)?;
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_request_pictures(request_id, page, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+async fn review_submission_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path((request_id, picture_id)): Path<(i32, i32)>,
+  Json(payload): Json<ReviewSubmissionRequest>,
+) -> Result<JsonResponse<PictureWithAuthor>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
+
+  state
+    .review_submission(request_id, picture_id, claims.user_id, &payload.review_status)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
 #[cfg(test)]
 mod tests {
-  use crate::test_support::{app_with_pool, delete_with_auth, post_json};
+  use crate::domains::picture::format::{tests::sample_png, ImageLimits};
+  use crate::domains::picture::metadata::tests::sample_jpeg;
+  use crate::domains::picture::service::{PictureService, PictureServiceImpl};
+  use crate::storage::{MemoryStorage, ObjectStorage};
+  use crate::test_support::{
+    app_with_pool, app_with_storage, create_test_storage, delete_with_auth, get, get_with_auth, login_verified_user,
+    patch_json_with_auth, post_json, post_json_with_auth, request_payload, send_with_auth,
+  };
   use axum::http::StatusCode;
+  use std::sync::Arc;
 
   #[sqlx::test(migrations = "./migrations")]
+  #[allow(clippy::useless_format)]
   async fn create_picture_unauthorized(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
     let app = app_with_pool(pool).await;
 
@@ -127,25 +398,886 @@ mod tests {
       serde_json::from_slice(&login_body).expect("deserialize login response");
     let token = login_response.token;
 
-    let boundary = "----WebKitFormBoundary7MA4YWxkTrZu0gW";
-    let body_content = format!(
-      "------WebKitFormBoundary7MA4YWxkTrZu0gW\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.jpg\"\r\nContent-Type: image/jpeg\r\n\r\nfake-image-data\r\n------WebKitFormBoundary7MA4YWxkTrZu0gW--\r\n"
+    // クライアントが申告する形式ではなく、ファイルの中身から判定した形式とサイズが保存される
+    let (status, body) = upload_picture(app, &token, None, &sample_png(3, 2)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let picture: super::super::model::Picture = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(picture.user_id, user.id);
+    assert_eq!(picture.mime_type.as_deref(), Some("image/png"));
+    assert_eq!((picture.width, picture.height), (Some(3), Some(2)));
+    assert!(picture.image_url.ends_with(".png"));
+
+    Ok(())
+  }
+
+  /// ファイル名と Content-Type は常に JPEG と申告する
+  async fn upload_picture(
+    app: axum::Router,
+    token: &str,
+    request_id: Option<i32>,
+    file: &[u8],
+  ) -> (StatusCode, axum::body::Bytes) {
+    let request_id = request_id.map(|id| id.to_string());
+    let fields: Vec<(&str, &str)> = request_id.iter().map(|id| ("request_id", id.as_str())).collect();
+    upload_picture_with_fields(app, token, &fields, file).await
+  }
+
+  async fn upload_picture_with_fields(
+    app: axum::Router,
+    token: &str,
+    fields: &[(&str, &str)],
+    file: &[u8],
+  ) -> (StatusCode, axum::body::Bytes) {
+    let boundary = "----KokoPicBoundary";
+    let mut body = Vec::new();
+    for (name, value) in fields {
+      body.extend_from_slice(
+        format!(
+          "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+          boundary, name, value
+        )
+        .as_bytes(),
+      );
+    }
+    body.extend_from_slice(
+      format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.jpg\"\r\nContent-Type: image/jpeg\r\n\r\n",
+        boundary
+      )
+      .as_bytes(),
     );
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
 
     let request = axum::http::Request::builder()
       .method("POST")
       .uri("/api/v1/pictures")
       .header("authorization", format!("Bearer {}", token))
       .header("content-type", format!("multipart/form-data; boundary={}", boundary))
-      .body(axum::body::Body::from(body_content))
+      .body(axum::body::Body::from(body))
       .unwrap();
 
     let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
-    assert_eq!(response.status(), StatusCode::OK);
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, body)
+  }
 
-    let body_bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
-    let picture: super::super::model::Picture = serde_json::from_slice(&body_bytes).expect("deserialize response");
-    assert_eq!(picture.user_id, user.id);
+  async fn upload_for_request(app: axum::Router, token: &str, request_id: i32) -> StatusCode {
+    upload_picture(app, token, Some(request_id), &sample_png(2, 2)).await.0
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_generates_variants(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "variants@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "variants@example.com").await?;
+
+    let (status, body) = upload_picture(app.clone(), &token, None, &sample_png(600, 400)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let picture_id = picture["id"].as_i64().unwrap();
+
+    // 元画像より大きいサイズは作らない
+    let variants = picture["variants"].as_object().unwrap();
+    let mut keys: Vec<&str> = variants.keys().map(String::as_str).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["256_jpeg", "256_webp"]);
+    assert_eq!(variants["256_webp"]["width"], 256);
+    assert!(variants["256_webp"]["url"].as_str().unwrap().ends_with(".webp"));
+
+    let (status, body) = get(app.clone(), &format!("/api/v1/pictures/{}", picture_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let detail: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(detail["variants"], picture["variants"]);
+
+    let (status, _) = delete_with_auth(app, &format!("/api/v1/pictures/{}", picture_id), &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let remaining = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM picture_variants"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(remaining, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_records_capture_metadata(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "exif@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "exif@example.com").await?;
+
+    let (status, body) = upload_picture(app, &token, None, &sample_jpeg()).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["taken_at"], "2026-05-01T10:20:30");
+    assert_eq!(picture["camera_model"], "Koko Cam X");
+    assert_eq!(picture["orientation"], 6);
+    // 撮影位置は保存するがレスポンスには含めない
+    assert!(picture.get("gps_lat").is_none());
+
+    let gps_lat = sqlx::query_scalar!(
+      "SELECT gps_lat FROM pictures WHERE id = $1",
+      picture["id"].as_i64().unwrap() as i32
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(gps_lat.is_some());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_verifies_location(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "location-owner@example.com", "Owner", "password123").await?;
+    let payload = crate::domains::request::model::CreateRequestRequest {
+      max_submissions_per_user: Some(5),
+      location_tolerance_m: Some(200),
+      ..request_payload(35.6812, 139.7671, "東京駅", "駅舎")
+    };
+    let request = crate::domains::request::repository::create(&pool, owner.id, &payload).await?;
+    let request_id = request.id.to_string();
+
+    crate::domains::user::model::User::create(&pool, "location@example.com", "Photographer", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "location@example.com").await?;
+    let png = sample_png(2, 2);
+
+    // 端末の現在地が許容範囲内
+    let fields = [
+      ("request_id", request_id.as_str()),
+      ("lat", "35.6815"),
+      ("lng", "139.7670"),
+    ];
+    let (status, body) = upload_picture_with_fields(app.clone(), &token, &fields, &png).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["location_verified"], true);
+    assert!(picture["location_distance_m"].as_f64().unwrap() < 200.0);
+    assert!(picture.get("reported_lat").is_none());
+
+    // 許容範囲外
+    let fields = [
+      ("request_id", request_id.as_str()),
+      ("lat", "35.6900"),
+      ("lng", "139.7000"),
+    ];
+    let (status, body) = upload_picture_with_fields(app.clone(), &token, &fields, &png).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["location_verified"], false);
+
+    // 位置情報がなければ未確認
+    let (status, body) = upload_picture(app.clone(), &token, Some(request.id), &png).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["location_verified"], false);
+    assert!(picture["location_distance_m"].is_null());
+
+    // EXIF の撮影位置 (東京駅から約300m) が端末の現在地より優先される
+    let fields = [
+      ("request_id", request_id.as_str()),
+      ("lat", "35.6812"),
+      ("lng", "139.7671"),
+    ];
+    let (status, body) = upload_picture_with_fields(app.clone(), &token, &fields, &sample_jpeg()).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["location_verified"], false);
+
+    // 片方だけ、範囲外の値は 400
+    let fields = [("request_id", request_id.as_str()), ("lat", "35.6812")];
+    let (status, _) = upload_picture_with_fields(app.clone(), &token, &fields, &png).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let fields = [("request_id", request_id.as_str()), ("lat", "95"), ("lng", "139.7671")];
+    let (status, _) = upload_picture_with_fields(app, &token, &fields, &png).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn exact_request_rounds_distance_for_others(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    use crate::utils::geo::haversine_distance;
+
+    let app = app_with_pool(pool.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "exact-check-owner@example.com", "Owner", "password123").await?;
+    crate::domains::user::model::User::create(&pool, "exact-check@example.com", "Photographer", "password123").await?;
+    crate::domains::user::model::User::create(&pool, "exact-check-other@example.com", "Other", "password123").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "exact-check-owner@example.com").await?;
+    let token = login_verified_user(app.clone(), &pool, "exact-check@example.com").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "exact-check-other@example.com").await?;
+
+    let payload = crate::domains::request::model::CreateRequestRequest {
+      location_tolerance_m: Some(200),
+      ..request_payload(35.6812, 139.7671, "東京駅", "駅舎")
+    };
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let request: crate::domains::request::model::Request = serde_json::from_slice(&body).unwrap();
+
+    // 地点から約155mの現在地。撮影者自身には正確な距離を返す
+    let (reported_lat, reported_lng) = (35.6826, 139.7671);
+    let exact_distance = haversine_distance(request.lat, request.lng, reported_lat, reported_lng);
+    let request_id = request.id.to_string();
+    let (reported_lat_field, reported_lng_field) = (reported_lat.to_string(), reported_lng.to_string());
+    let fields = [
+      ("request_id", request_id.as_str()),
+      ("lat", reported_lat_field.as_str()),
+      ("lng", reported_lng_field.as_str()),
+    ];
+    let (status, body) = upload_picture_with_fields(app.clone(), &token, &fields, &sample_png(2, 2)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!((picture["location_distance_m"].as_f64().unwrap() - exact_distance).abs() < 0.01);
+
+    // 依頼者には正確な距離を、それ以外には 100m 単位に丸めた距離を返す
+    let uri = format!("/api/v1/pictures/{}", picture["id"]);
+    let (status, body) = get_with_auth(app.clone(), &uri, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!((picture["location_distance_m"].as_f64().unwrap() - exact_distance).abs() < 0.01);
+    for response in [
+      get(app.clone(), &uri).await,
+      get_with_auth(app.clone(), &uri, &other_token).await,
+    ] {
+      assert_eq!(response.0, StatusCode::OK);
+      let picture: serde_json::Value = serde_json::from_slice(&response.1).unwrap();
+      assert_eq!(picture["location_verified"], true);
+      assert_eq!(picture["location_distance_m"].as_f64().unwrap(), 200.0);
+    }
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn fuzzed_request_hides_exact_location_check(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    use crate::domains::request::privacy::round_distance;
+    use crate::utils::geo::haversine_distance;
+
+    let app = app_with_pool(pool.clone()).await;
+
+    let _owner =
+      crate::domains::user::model::User::create(&pool, "fuzz-check-owner@example.com", "Owner", "password123").await?;
+    crate::domains::user::model::User::create(&pool, "fuzz-check@example.com", "Photographer", "password123").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "fuzz-check-owner@example.com").await?;
+    let token = login_verified_user(app.clone(), &pool, "fuzz-check@example.com").await?;
+
+    let payload = crate::domains::request::model::CreateRequestRequest {
+      location_precision: Some("1km".to_string()),
+      max_submissions_per_user: Some(5),
+      location_tolerance_m: Some(200),
+      ..request_payload(35.6812, 139.7671, "自宅前", "玄関の写真")
+    };
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let request: crate::domains::request::model::Request = serde_json::from_slice(&body).unwrap();
+    let (status, body) = get_with_auth(app.clone(), &format!("/api/v1/requests/{}", request.id), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let fuzzed: crate::domains::request::model::Request = serde_json::from_slice(&body).unwrap();
+
+    // 正確な地点から約30mの現在地を送っても、投稿者には照合結果も正確な距離も返さない
+    let (reported_lat, reported_lng) = (35.6815, 139.7671);
+    let request_id = request.id.to_string();
+    let (reported_lat_field, reported_lng_field) = (reported_lat.to_string(), reported_lng.to_string());
+    let fields = [
+      ("request_id", request_id.as_str()),
+      ("lat", reported_lat_field.as_str()),
+      ("lng", reported_lng_field.as_str()),
+    ];
+    let (status, body) = upload_picture_with_fields(app.clone(), &token, &fields, &sample_png(2, 2)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(picture["location_verified"].is_null());
+    // 公開されているぼかした地点から計算できる距離だけを返す
+    let public_distance = round_distance(
+      haversine_distance(fuzzed.lat, fuzzed.lng, reported_lat, reported_lng),
+      1000.0,
+    );
+    assert_eq!(picture["location_distance_m"].as_f64().unwrap(), public_distance);
+
+    let uri = format!("/api/v1/pictures/{}", picture["id"]);
+    for response in [
+      get(app.clone(), &uri).await,
+      get_with_auth(app.clone(), &uri, &token).await,
+    ] {
+      assert_eq!(response.0, StatusCode::OK);
+      let picture: serde_json::Value = serde_json::from_slice(&response.1).unwrap();
+      assert!(picture["location_verified"].is_null());
+      assert_eq!(picture["location_distance_m"].as_f64().unwrap(), public_distance);
+    }
+    let (status, body) = get_with_auth(app.clone(), "/api/v1/users/me/submissions", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let submissions: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+      submissions["submissions"][0]["location_distance_m"].as_f64().unwrap(),
+      public_distance
+    );
+
+    // 依頼者には正確な地点との照合結果を返す
+    let (status, body) = get_with_auth(app, &uri, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["location_verified"], true);
+    let exact_distance = haversine_distance(request.lat, request.lng, reported_lat, reported_lng);
+    assert!((picture["location_distance_m"].as_f64().unwrap() - exact_distance).abs() < 0.01);
+
+    Ok(())
+  }
+
+  /// クライアントの代わりに、署名付き URL の先へファイルを置く
+  async fn put_pending_upload(
+    pool: &sqlx::PgPool,
+    storage: &dyn ObjectStorage,
+    upload_id: uuid::Uuid,
+    file: &[u8],
+  ) -> Result<String, sqlx::Error> {
+    let object_key = sqlx::query_scalar!("SELECT object_key FROM picture_uploads WHERE id = $1", upload_id)
+      .fetch_one(pool)
+      .await?;
+    storage.put(&object_key, file.to_vec(), "image/png").await.unwrap();
+    Ok(object_key)
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn direct_upload_with_presigned_url(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = create_test_storage();
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "direct@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "direct@example.com").await?;
+    crate::domains::user::model::User::create(&pool, "direct-other@example.com", "Other", "password123").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "direct-other@example.com").await?;
+
+    let payload = serde_json::json!({ "content_type": "image/png", "content_length": 1024 });
+    let (status, _) = post_json(app.clone(), "/api/v1/pictures/uploads", &payload).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let gif = serde_json::json!({ "content_type": "image/gif", "content_length": 1024 });
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/pictures/uploads", &gif, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let huge = serde_json::json!({ "content_type": "image/png", "content_length": 1i64 << 40 });
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/pictures/uploads", &huge, &token).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/pictures/uploads", &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload["method"], "PUT");
+    let upload_url = upload["upload_url"].as_str().unwrap();
+    assert!(upload_url.starts_with("http://storage.invalid/uploads/"));
+    assert!(upload_url.contains("signature="));
+    assert_eq!(upload["headers"]["content-type"], "image/png");
+    let upload_id: uuid::Uuid = upload["upload_id"].as_str().unwrap().parse().unwrap();
+    let complete_uri = format!("/api/v1/pictures/uploads/{}/complete", upload_id);
+
+    // ファイルが届く前に完了させることはできない
+    let (status, _) = send_with_auth(app.clone(), "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let object_key = put_pending_upload(&pool, storage.as_ref(), upload_id, &sample_png(4, 3)).await?;
+
+    let (status, _) = send_with_auth(app.clone(), "POST", &complete_uri, &other_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 完了の通知では形式だけを確かめ、写真として保存するのはバックグラウンドの処理に任せる
+    let (status, body) = send_with_auth(app.clone(), "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let upload_status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload_status["status"], "processing");
+    let status_uri = format!("/api/v1/pictures/uploads/{}", upload_id);
+    let (status, _) = get_with_auth(app.clone(), &status_uri, &other_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 一時的にファイルを読み出せなくても、保留中のアップロードは残して再試行する
+    storage.delete(&object_key).await.unwrap();
+    let service = PictureServiceImpl::new(pool.clone(), storage.clone(), ImageLimits::default());
+    assert_eq!(service.process_completed_uploads().await.unwrap(), 0);
+    let (status, body) = get_with_auth(app.clone(), &status_uri, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload_status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload_status["status"], "processing");
+
+    put_pending_upload(&pool, storage.as_ref(), upload_id, &sample_png(4, 3)).await?;
+    sqlx::query!(
+      "UPDATE picture_uploads SET next_attempt_at = NOW() WHERE id = $1",
+      upload_id
+    )
+    .execute(&pool)
+    .await?;
+    assert_eq!(service.process_completed_uploads().await.unwrap(), 1);
+
+    let (status, body) = get_with_auth(app.clone(), &status_uri, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload_status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload_status["status"], "completed");
+    let picture_uri = format!("/api/v1/pictures/{}", upload_status["picture_id"]);
+    let (status, body) = get(app.clone(), &picture_uri).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["mime_type"], "image/png");
+    assert_eq!(picture["width"], 4);
+
+    // 完了の通知を再送しても同じ写真を返し、一時ファイルは残らない
+    let (status, body) = send_with_auth(app, "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let resent: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(resent["picture_id"], upload_status["picture_id"]);
+    assert!(storage.head(&object_key).await.unwrap().is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn direct_upload_rejects_non_images_and_expires(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = create_test_storage();
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "direct-bad@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "direct-bad@example.com").await?;
+
+    let payload = serde_json::json!({ "content_type": "image/png", "content_length": 16 });
+    let mut upload_ids = Vec::new();
+    for _ in 0..3 {
+      let (status, body) = post_json_with_auth(app.clone(), "/api/v1/pictures/uploads", &payload, &token).await;
+      assert_eq!(status, StatusCode::OK);
+      let upload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+      upload_ids.push(upload["upload_id"].as_str().unwrap().parse::<uuid::Uuid>().unwrap());
+    }
+
+    // Content-Type を偽った画像以外のファイル
+    put_pending_upload(&pool, storage.as_ref(), upload_ids[0], b"fake-image-data").await?;
+    let complete_uri = format!("/api/v1/pictures/uploads/{}/complete", upload_ids[0]);
+    let (status, _) = send_with_auth(app.clone(), "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // 先頭だけが PNG の壊れたファイルは、バックグラウンドの処理で失敗として記録してファイルを消す
+    let mut broken = sample_png(2, 2);
+    broken.truncate(40);
+    let broken_key = put_pending_upload(&pool, storage.as_ref(), upload_ids[2], &broken).await?;
+    let complete_uri = format!("/api/v1/pictures/uploads/{}/complete", upload_ids[2]);
+    let (status, _) = send_with_auth(app.clone(), "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let service = PictureServiceImpl::new(pool.clone(), storage.clone(), ImageLimits::default());
+    assert_eq!(service.process_completed_uploads().await.unwrap(), 0);
+    let status_uri = format!("/api/v1/pictures/uploads/{}", upload_ids[2]);
+    let (status, body) = get_with_auth(app.clone(), &status_uri, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload_status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload_status["status"], "failed");
+    assert!(upload_status["error"].is_string());
+    assert!(storage.head(&broken_key).await.unwrap().is_none());
+
+    // 期限切れのアップロードは完了できず、掃除でファイルごと消える
+    let object_key = put_pending_upload(&pool, storage.as_ref(), upload_ids[1], &sample_png(2, 2)).await?;
+    sqlx::query!(
+      "UPDATE picture_uploads SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1",
+      upload_ids[1]
+    )
+    .execute(&pool)
+    .await?;
+    let complete_uri = format!("/api/v1/pictures/uploads/{}/complete", upload_ids[1]);
+    let (status, _) = send_with_auth(app, "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // 失敗したアップロードも結果を確かめられる期間を過ぎれば掃除する
+    sqlx::query!(
+      "UPDATE picture_uploads SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1",
+      upload_ids[2]
+    )
+    .execute(&pool)
+    .await?;
+    assert_eq!(service.cleanup_expired_uploads().await.unwrap(), 2);
+    assert!(storage.head(&object_key).await.unwrap().is_none());
+
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM picture_uploads"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(count, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn direct_upload_grown_after_completion_is_not_buffered(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = create_test_storage();
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "direct-grown@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "direct-grown@example.com").await?;
+
+    let png = sample_png(4, 3);
+    let payload = serde_json::json!({ "content_type": "image/png", "content_length": png.len() });
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/pictures/uploads", &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let upload_id: uuid::Uuid = upload["upload_id"].as_str().unwrap().parse().unwrap();
+    put_pending_upload(&pool, storage.as_ref(), upload_id, &png).await?;
+    let complete_uri = format!("/api/v1/pictures/uploads/{}/complete", upload_id);
+    let (status, _) = send_with_auth(app.clone(), "POST", &complete_uri, &token).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    // 完了の通知の後で上限を超える大きさに差し替えられたファイルは、上限までしか読まずに失敗させる
+    let mut grown = png.clone();
+    grown.resize(png.len() + 1024, 0);
+    let object_key = put_pending_upload(&pool, storage.as_ref(), upload_id, &grown).await?;
+    let limits = ImageLimits {
+      max_bytes: png.len() + 16,
+      ..ImageLimits::default()
+    };
+    let service = PictureServiceImpl::new(pool.clone(), storage.clone(), limits);
+    assert_eq!(service.process_completed_uploads().await.unwrap(), 0);
+
+    let status_uri = format!("/api/v1/pictures/uploads/{}", upload_id);
+    let (status, body) = get_with_auth(app, &status_uri, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let upload_status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload_status["status"], "failed");
+    assert!(storage.head(&object_key).await.unwrap().is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn private_bucket_serves_presigned_urls(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage: Arc<dyn ObjectStorage> =
+      Arc::new(MemoryStorage::new().into_private(std::time::Duration::from_secs(120)));
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "private@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "private@example.com").await?;
+
+    let (status, body) = upload_picture(app.clone(), &token, None, &sample_png(600, 400)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let picture_id = picture["id"].as_i64().unwrap() as i32;
+    let signed = |url: &serde_json::Value| {
+      let url = url.as_str().unwrap();
+      url.contains("expires=") && url.contains("signature=")
+    };
+    assert!(signed(&picture["image_url"]));
+    assert!(signed(&picture["variants"]["256_webp"]["url"]));
+
+    // DB にはキーだけを保存し、取得のたびに署名する
+    let stored = sqlx::query!(
+      "SELECT image_url, storage_key, storage_backend FROM pictures WHERE id = $1",
+      picture_id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert!(stored.image_url.is_none());
+    assert_eq!(stored.storage_backend.as_deref(), Some("memory"));
+    let stored = stored.storage_key.unwrap();
+    assert!(stored.starts_with("pictures/"));
+
+    let (status, body) = get(app.clone(), &format!("/api/v1/pictures/{}", picture_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(signed(&picture["image_url"]));
+    let (_, body) = get(app.clone(), "/api/v1/pictures").await;
+    let feed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(signed(&feed["pictures"][0]["image_url"]));
+
+    let (status, _) = delete_with_auth(app, &format!("/api/v1/pictures/{}", picture_id), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(storage.head(&stored).await.unwrap().is_none());
+
+    Ok(())
+  }
+
+  /// 写真の詳細に画像の URL が含まれるか
+  async fn picture_has_url(app: axum::Router, picture_id: i64, token: Option<&str>) -> bool {
+    let uri = format!("/api/v1/pictures/{}", picture_id);
+    let (status, body) = match token {
+      Some(token) => get_with_auth(app, &uri, token).await,
+      None => get(app, &uri).await,
+    };
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    picture.get("image_url").is_some()
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn private_bucket_signs_urls_only_for_allowed_viewers(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage: Arc<dyn ObjectStorage> =
+      Arc::new(MemoryStorage::new().into_private(std::time::Duration::from_secs(120)));
+    let app = app_with_storage(pool.clone(), storage).await;
+
+    let requester =
+      crate::domains::user::model::User::create(&pool, "viewer-requester@example.com", "Requester", "password123")
+        .await?;
+    crate::domains::user::model::User::create(&pool, "viewer-photo@example.com", "Photographer", "password123").await?;
+    crate::domains::user::model::User::create(&pool, "viewer-other@example.com", "Other", "password123").await?;
+    let requester_token = login_verified_user(app.clone(), &pool, "viewer-requester@example.com").await?;
+    let photographer_token = login_verified_user(app.clone(), &pool, "viewer-photo@example.com").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "viewer-other@example.com").await?;
+
+    let public_request = crate::domains::request::repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京タワー", "夜景"),
+    )
+    .await?;
+    let private_request = crate::domains::request::repository::create(
+      &pool,
+      requester.id,
+      &crate::domains::request::model::CreateRequestRequest {
+        max_submissions_per_user: Some(2),
+        is_private: Some(true),
+        ..request_payload(35.6812, 139.7671, "東京駅", "駅舎")
+      },
+    )
+    .await?;
+
+    let mut picture_ids = Vec::new();
+    for request_id in [public_request.id, private_request.id] {
+      let (status, body) = upload_picture(app.clone(), &photographer_token, Some(request_id), &sample_png(3, 2)).await;
+      assert_eq!(status, StatusCode::OK);
+      let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+      picture_ids.push(picture["id"].as_i64().unwrap());
+    }
+    let (public_picture, private_picture) = (picture_ids[0], picture_ids[1]);
+
+    let has_url = |picture: &serde_json::Value| picture.get("image_url").is_some();
+
+    // 非公開のリクエストへの投稿は、投稿者と依頼者にだけ署名付き URL を返す
+    assert!(picture_has_url(app.clone(), public_picture, None).await);
+    assert!(!picture_has_url(app.clone(), private_picture, None).await);
+    assert!(!picture_has_url(app.clone(), private_picture, Some(&other_token)).await);
+    assert!(picture_has_url(app.clone(), private_picture, Some(&photographer_token)).await);
+    assert!(picture_has_url(app.clone(), private_picture, Some(&requester_token)).await);
+
+    let (_, body) = get(
+      app.clone(),
+      &format!("/api/v1/requests/{}/pictures", private_request.id),
+    )
+    .await;
+    let feed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(!has_url(&feed["pictures"][0]));
+    assert!(feed["pictures"][0]["variants"].as_object().unwrap().is_empty());
+
+    // 却下された投稿も、投稿者と依頼者以外には見せない
+    let uri = format!("/api/v1/requests/{}/pictures/{}", public_request.id, public_picture);
+    let rejected = serde_json::json!({ "review_status": "rejected" });
+    let (status, _) = patch_json_with_auth(app.clone(), &uri, &rejected, &requester_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!picture_has_url(app.clone(), public_picture, None).await);
+    assert!(!picture_has_url(app.clone(), public_picture, Some(&other_token)).await);
+    assert!(picture_has_url(app.clone(), public_picture, Some(&photographer_token)).await);
+
+    let (_, body) = get_with_auth(app, "/api/v1/pictures", &other_token).await;
+    let feed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(feed["pictures"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .all(|picture| !has_url(picture)));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_picture_removes_objects_by_key(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = create_test_storage();
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "by-key@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "by-key@example.com").await?;
+
+    // 別のエンドポイントで公開していた頃の写真をバックフィルした状態
+    let key = format!("pictures/{}/legacy.png", user.id);
+    storage.put(&key, sample_png(2, 2), "image/png").await.unwrap();
+    let picture_id = sqlx::query_scalar!(
+      "INSERT INTO pictures (user_id, storage_key, storage_backend) VALUES ($1, $2, 's3') RETURNING id",
+      user.id,
+      key
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    // URL は今の設定から組み立てる
+    let (status, body) = get(app.clone(), &format!("/api/v1/pictures/{}", picture_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(picture["image_url"], format!("http://storage.invalid/{}", key));
+    assert!(picture.get("storage_key").is_none());
+
+    let (status, _) = delete_with_auth(app, &format!("/api/v1/pictures/{}", picture_id), &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(storage.head(&key).await.unwrap().is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_rejects_non_images(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "not-image@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "not-image@example.com").await?;
+
+    let (status, body) = upload_picture(app.clone(), &token, None, b"fake-image-data").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(json["error"].as_str().unwrap().contains("Unsupported image format"));
+
+    // PNG のシグネチャだけで中身が壊れているもの
+    let (status, _) = upload_picture(app, &token, None, b"\x89PNG\r\n\x1a\nbroken").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM pictures"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(count, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_streams_upload_without_leaving_staged_files(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = create_test_storage();
+    let app = app_with_storage(pool.clone(), storage.clone()).await;
+
+    crate::domains::user::model::User::create(&pool, "stream@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "stream@example.com").await?;
+
+    let (status, _) = upload_picture(app.clone(), &token, None, &sample_png(4, 3)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = upload_picture(app.clone(), &token, None, b"fake-image-data").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // ファイルの後に届いたフィールドが不正なら、受け取ったファイルは残さない
+    let boundary = "----KokoPicBoundary";
+    let mut body = format!(
+      "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test.png\"\r\n\r\n",
+      boundary
+    )
+    .into_bytes();
+    body.extend_from_slice(&sample_png(2, 2));
+    body.extend_from_slice(
+      format!(
+        "\r\n--{}\r\nContent-Disposition: form-data; name=\"lat\"\r\n\r\n95\r\n--{}--\r\n",
+        boundary, boundary
+      )
+      .as_bytes(),
+    );
+    let request = axum::http::Request::builder()
+      .method("POST")
+      .uri("/api/v1/pictures")
+      .header("authorization", format!("Bearer {}", token))
+      .header("content-type", format!("multipart/form-data; boundary={}", boundary))
+      .body(axum::body::Body::from(body))
+      .unwrap();
+    let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM picture_uploads"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(count, 0);
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM pictures"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(count, 1);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_picture_enforces_submission_limits(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "limit-owner@example.com", "Owner", "password123").await?;
+    let payload = crate::domains::request::model::CreateRequestRequest {
+      max_submissions_per_user: Some(2),
+      max_total_submissions: Some(3),
+      ..request_payload(35.6812, 139.7671, "東京駅", "駅舎")
+    };
+    let request = crate::domains::request::repository::create(&pool, owner.id, &payload).await?;
+
+    let mut tokens = Vec::new();
+    for email in ["limit-a@example.com", "limit-b@example.com"] {
+      crate::domains::user::model::User::create(&pool, email, "Photographer", "password123").await?;
+      tokens.push(login_verified_user(app.clone(), &pool, email).await?);
+    }
+
+    // 1人2枚まで
+    assert_eq!(
+      upload_for_request(app.clone(), &tokens[0], request.id).await,
+      StatusCode::OK
+    );
+    assert_eq!(
+      upload_for_request(app.clone(), &tokens[0], request.id).await,
+      StatusCode::OK
+    );
+    assert_eq!(
+      upload_for_request(app.clone(), &tokens[0], request.id).await,
+      StatusCode::CONFLICT
+    );
+
+    // 全体で3枚まで
+    assert_eq!(
+      upload_for_request(app.clone(), &tokens[1], request.id).await,
+      StatusCode::OK
+    );
+    assert_eq!(
+      upload_for_request(app.clone(), &tokens[1], request.id).await,
+      StatusCode::CONFLICT
+    );
+
+    assert_eq!(upload_for_request(app, &tokens[1], 99999).await, StatusCode::NOT_FOUND);
+
+    let count = sqlx::query_scalar!(
+      r#"SELECT COUNT(*) as "count!" FROM pictures WHERE request_id = $1"#,
+      request.id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(count, 3);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn concurrent_submissions_do_not_exceed_total_limit(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "race-owner@example.com", "Owner", "password123").await?;
+    let payload = crate::domains::request::model::CreateRequestRequest {
+      max_total_submissions: Some(2),
+      ..request_payload(35.6812, 139.7671, "東京駅", "駅舎")
+    };
+    let request = crate::domains::request::repository::create(&pool, owner.id, &payload).await?;
+
+    let mut handles = Vec::new();
+    for i in 0..5 {
+      let email = format!("race-{}@example.com", i);
+      crate::domains::user::model::User::create(&pool, &email, "Photographer", "password123").await?;
+      let token = login_verified_user(app.clone(), &pool, &email).await?;
+      let app = app.clone();
+      handles.push(tokio::spawn(async move {
+        upload_for_request(app, &token, request.id).await
+      }));
+    }
+
+    let mut accepted = 0;
+    for handle in handles {
+      if handle.await.unwrap() == StatusCode::OK {
+        accepted += 1;
+      }
+    }
+    assert_eq!(accepted, 2);
 
     Ok(())
   }
@@ -286,4 +1418,223 @@ mod tests {
 
     Ok(())
   }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_my_submissions_with_request(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let requester =
+      crate::domains::user::model::User::create(&pool, "sub-requester@example.com", "Requester", "password123").await?;
+    let photographer =
+      crate::domains::user::model::User::create(&pool, "sub-photo@example.com", "Photographer", "password123").await?;
+    let request = crate::domains::request::repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京タワー", "夜景"),
+    )
+    .await?;
+
+    sqlx::query!(
+      "INSERT INTO pictures (user_id, image_url) VALUES ($1, $2)",
+      photographer.id,
+      "http://127.0.0.1:9000/test/pictures/free.jpg"
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query!(
+      "INSERT INTO pictures (user_id, image_url, request_id) VALUES ($1, $2, $3)",
+      photographer.id,
+      "http://127.0.0.1:9000/test/pictures/tower.jpg",
+      request.id
+    )
+    .execute(&pool)
+    .await?;
+
+    let token = login_verified_user(app.clone(), &pool, "sub-photo@example.com").await?;
+    let (status, body) = get_with_auth(app, "/api/v1/users/me/submissions", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response: super::super::model::SubmissionsResponse =
+      serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.total, 2);
+    assert_eq!(response.submissions.len(), 2);
+
+    let linked = response
+      .submissions
+      .iter()
+      .find(|s| s.request.is_some())
+      .expect("submission with request");
+    let linked_request = linked.request.as_ref().unwrap();
+    assert_eq!(linked_request.id, request.id);
+    assert_eq!(linked_request.place_name, "東京タワー");
+    assert_eq!(linked_request.status, "open");
+    assert_eq!(linked.review_status.as_deref(), Some("pending"));
+
+    let free = response
+      .submissions
+      .iter()
+      .find(|s| s.request.is_none())
+      .expect("submission without request");
+    assert!(free.review_status.is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn review_submission_by_request_owner(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let requester =
+      crate::domains::user::model::User::create(&pool, "review-requester@example.com", "Requester", "password123")
+        .await?;
+    let photographer =
+      crate::domains::user::model::User::create(&pool, "review-photo@example.com", "Photographer", "password123")
+        .await?;
+    let request = crate::domains::request::repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京タワー", "夜景"),
+    )
+    .await?;
+    let other_request = crate::domains::request::repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "駅舎"),
+    )
+    .await?;
+    let picture_id = sqlx::query_scalar!(
+      "INSERT INTO pictures (user_id, image_url, request_id) VALUES ($1, $2, $3) RETURNING id",
+      photographer.id,
+      "http://127.0.0.1:9000/test/pictures/tower.jpg",
+      request.id
+    )
+    .fetch_one(&pool)
+    .await?;
+
+    let requester_token = login_verified_user(app.clone(), &pool, "review-requester@example.com").await?;
+    let photographer_token = login_verified_user(app.clone(), &pool, "review-photo@example.com").await?;
+    let uri = format!("/api/v1/requests/{}/pictures/{}", request.id, picture_id);
+    let rejected = serde_json::json!({ "review_status": "rejected" });
+
+    let (status, _) = patch_json_with_auth(app.clone(), &uri, &rejected, &photographer_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = patch_json_with_auth(
+      app.clone(),
+      &uri,
+      &serde_json::json!({ "review_status": "approved" }),
+      &requester_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let other_uri = format!("/api/v1/requests/{}/pictures/{}", other_request.id, picture_id);
+    let (status, _) = patch_json_with_auth(app.clone(), &other_uri, &rejected, &requester_token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = patch_json_with_auth(app.clone(), &uri, &rejected, &requester_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let picture: serde_json::Value = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(picture["review_status"], "rejected");
+
+    let (_, body) = get_with_auth(app, "/api/v1/users/me/submissions", &photographer_token).await;
+    let response: super::super::model::SubmissionsResponse =
+      serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.submissions[0].review_status.as_deref(), Some("rejected"));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn picture_feed_paginates_with_cursor(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let alice =
+      crate::domains::user::model::User::create(&pool, "feed-alice@example.com", "Alice", "password123").await?;
+    let bob = crate::domains::user::model::User::create(&pool, "feed-bob@example.com", "Bob", "password123").await?;
+    let request = crate::domains::request::repository::create(
+      &pool,
+      alice.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "駅舎"),
+    )
+    .await?;
+
+    // 同じ作成日時の行があってもIDで順序が決まることを確認する
+    let mut ids = Vec::new();
+    for (user_id, request_id) in [(alice.id, None), (bob.id, Some(request.id)), (bob.id, None)] {
+      let id = sqlx::query_scalar!(
+        r#"
+          INSERT INTO pictures (user_id, image_url, request_id, created_at)
+          VALUES ($1, 'https://example.com/p.jpg', $2, '2026-01-01T00:00:00Z')
+          RETURNING id
+        "#,
+        user_id,
+        request_id
+      )
+      .fetch_one(&pool)
+      .await?;
+      ids.push(id);
+    }
+
+    let (status, body) = get(app.clone(), "/api/v1/pictures?limit=2").await;
+    assert_eq!(status, StatusCode::OK);
+    let first: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let first_ids: Vec<i64> = first["pictures"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|p| p["id"].as_i64().unwrap())
+      .collect();
+    assert_eq!(first_ids, vec![i64::from(ids[2]), i64::from(ids[1])]);
+    assert_eq!(first["pictures"][0]["author"]["display_name"], "Bob");
+    assert_eq!(first["pictures"][1]["request_id"], request.id);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (status, body) = get(app.clone(), &format!("/api/v1/pictures?limit=2&cursor={}", cursor)).await;
+    assert_eq!(status, StatusCode::OK);
+    let second: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(second["pictures"].as_array().unwrap().len(), 1);
+    assert_eq!(second["pictures"][0]["id"], ids[0]);
+    assert!(second["next_cursor"].is_null());
+
+    let (status, body) = get(app.clone(), &format!("/api/v1/users/{}/pictures", bob.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let by_user: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(by_user["pictures"].as_array().unwrap().len(), 2);
+
+    let (status, body) = get(app.clone(), &format!("/api/v1/requests/{}/pictures", request.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let by_request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(by_request["pictures"].as_array().unwrap().len(), 1);
+    assert_eq!(by_request["pictures"][0]["id"], ids[1]);
+
+    let (status, _) = get(app, "/api/v1/pictures?cursor=garbage").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_picture_and_missing_parents(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user = crate::domains::user::model::User::create(&pool, "detail@example.com", "Detail", "password123").await?;
+    let picture = crate::domains::picture::repository::create(&pool, user.id, "https://example.com/p.jpg").await?;
+
+    let (status, body) = get(app.clone(), &format!("/api/v1/pictures/{}", picture.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["author"]["id"], user.id);
+    assert_eq!(json["author"]["display_name"], "Detail");
+    assert!(json["author"].get("email").is_none());
+
+    let (status, _) = get(app.clone(), "/api/v1/pictures/99999").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(app.clone(), "/api/v1/users/99999/pictures").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get(app, "/api/v1/requests/99999/pictures").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    Ok(())
+  }
 }
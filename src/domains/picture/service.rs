@@ -1,13 +1,69 @@
 use async_trait::async_trait;
+use axum::body::Bytes;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::domains::request::{model::Request, repository as request_repository};
+use crate::domains::storage_operation::{
+  model::{OperationKind, StorageOperation},
+  repository as storage_operation_repository,
+  service::{retry_delay, StorageOperationService},
+};
+use crate::domains::user::model::User;
 use crate::impl_service_error_conversions;
-use crate::storage::S3Storage;
+use crate::storage::ObjectStorage;
+use crate::utils::pagination::{Cursor, CursorPage, Page};
 
-use super::model::Picture;
+use super::buffer::BufferBudget;
+use super::format::{self, ImageError, ImageFormat, ImageLimits};
+use super::metadata;
+use super::model::{
+  CompletedUpload, CreatePictureUploadRequest, LocationCheck, NewPicture, Picture, PictureFeedResponse, PictureFilter,
+  PictureUpload, PictureUploadResponse, PictureUploadState, PictureUploadStatus, PictureVariant, PictureWithAuthor,
+  StagedUpload, SubmissionsResponse,
+};
 use super::repository;
+use super::variant::{self, RenderedVariant};
+
+/// 署名付き URL の有効期間
+const PRESIGNED_UPLOAD_TTL: Duration = Duration::from_secs(15 * 60);
+/// アップロードの完了を待つ期間。署名付き URL の期限ぎりぎりに送り終えた場合も完了できるよう長めにとる
+const PENDING_UPLOAD_TTL: Duration = Duration::from_secs(60 * 60);
+/// 1回の掃除で削除する期限切れアップロードの上限
+const EXPIRED_UPLOAD_BATCH_SIZE: i64 = 100;
+/// 保存を待つ直接アップロードを取り出したあと、ほかの処理が取り出さないようにしておく期間
+const UPLOAD_PROCESSING_LEASE: Duration = Duration::from_secs(5 * 60);
+/// 1回の処理で写真として保存する直接アップロードの上限
+const UPLOAD_PROCESSING_BATCH_SIZE: i64 = 10;
+/// 一時的な失敗が続いた直接アップロードを諦めるまでの試行回数
+const MAX_UPLOAD_ATTEMPTS: i32 = 5;
+/// 写真として保存するためにメモリへ同時に読み込めるファイルの合計を、1ファイルの上限の何倍にするか
+const BUFFERED_UPLOADS: usize = 4;
+/// 同時にデコードできる画像の数。デコードしたピクセルの合計の予算を、ピクセル数の上限の画像の何枚分にするか
+const DECODING_UPLOADS: usize = 2;
+/// デコードした画像1枚に使うメモリの、RGBA のピクセル列の何倍とみなすか。
+/// 向きを反映したコピーと、縮小のための中間のバッファーの分を含める
+const DECODED_COPIES: usize = 3;
+/// 形式の判定に使う先頭のバイト数。HEIC の ftyp ボックスの互換ブランドまで読めるだけの長さをとる
+const SNIFF_BYTES: usize = 64;
+
+/// 写真の元画像を置くキー。同じ内容のファイルが同じキーになるよう、内容の SHA-256 から決める
+fn content_key(sha256: &str, extension: &str) -> String {
+  format!("pictures/{}.{}", sha256, extension)
+}
+
+/// `width`×`height` の画像をデコードして縮小画像を作るあいだに使うメモリの見積もり
+fn decoded_bytes(width: u64, height: u64) -> usize {
+  usize::try_from(width.saturating_mul(height).saturating_mul(4))
+    .unwrap_or(usize::MAX)
+    .saturating_mul(DECODED_COPIES)
+}
 
 #[derive(Debug)]
 pub enum PictureServiceError {
@@ -15,6 +71,8 @@ pub enum PictureServiceError {
   BadRequest(String),
   NotFound(String),
   Forbidden(String),
+  Conflict(String),
+  PayloadTooLarge(String),
 }
 
 impl Error for PictureServiceError {}
@@ -26,61 +84,903 @@ impl std::fmt::Display for PictureServiceError {
       PictureServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
       PictureServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
       PictureServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+      PictureServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+      PictureServiceError::PayloadTooLarge(msg) => write!(f, "Payload Too Large: {}", msg),
     }
   }
 }
 
 impl_service_error_conversions!(PictureServiceError, InternalServerError);
 
+impl From<ImageError> for PictureServiceError {
+  fn from(error: ImageError) -> Self {
+    match error {
+      ImageError::TooLarge { .. } | ImageError::TooManyPixels { .. } => {
+        PictureServiceError::PayloadTooLarge(error.to_string())
+      }
+      ImageError::UnsupportedFormat | ImageError::Malformed(_) => PictureServiceError::BadRequest(error.to_string()),
+    }
+  }
+}
+
+/// ストレージにある元画像と縮小画像の URL を、今の設定でキーから組み立てる
+pub async fn resolve_picture_urls(storage: &dyn ObjectStorage, picture: &mut Picture) -> anyhow::Result<()> {
+  if let Some(key) = &picture.storage_key {
+    picture.image_url = storage.url_for(key).await?;
+  }
+  for variant in picture.variants.values_mut() {
+    if let Some(key) = &variant.storage_key {
+      variant.url = storage.url_for(key).await?;
+    }
+  }
+
+  Ok(())
+}
+
+/// 少しずつ届くアップロードファイル。multipart のフィールドなどを、全体をメモリに載せずに読む
+#[async_trait]
+pub trait FileChunks: Send {
+  /// 読み終えたら `None`
+  async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError>;
+}
+
 #[async_trait]
 pub trait PictureService: Send + Sync {
   async fn create_picture(&self, user_id: i32, image_url: String) -> Result<Picture, PictureServiceError>;
+  /// 届いたファイルを検証しながらメモリに読み込む
+  async fn stage_upload(&self, user_id: i32, file: &mut dyn FileChunks) -> Result<StagedUpload, PictureServiceError>;
+  /// 読み込んだファイルを写真として保存する
   async fn upload_and_create_picture(
     &self,
     user_id: i32,
-    file_data: Vec<u8>,
-    file_name: String,
-    content_type: String,
+    staged: StagedUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<Picture, PictureServiceError>;
+  /// 分割して届いたファイルをストレージ上で組み立て終えたあと、サーバー経由のアップロードと同じ処理で写真として保存する。
+  /// 同じアップロードで呼び直すと、前回の試行で保存し終えた写真を返す
+  async fn finalize_assembled_upload(
+    &self,
+    upload: PictureUpload,
+    reported_location: Option<(f64, f64)>,
   ) -> Result<Picture, PictureServiceError>;
+  /// ストレージへ直接アップロードするための署名付き URL を発行する
+  async fn create_upload(
+    &self,
+    user_id: i32,
+    req: CreatePictureUploadRequest,
+  ) -> Result<PictureUploadResponse, PictureServiceError>;
+  /// 直接アップロードされたファイルの大きさと形式を確かめ、バックグラウンドで写真として保存するよう記録する
+  async fn complete_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<PictureUploadStatus, PictureServiceError>;
+  async fn get_upload_status(&self, user_id: i32, upload_id: Uuid) -> Result<PictureUploadStatus, PictureServiceError>;
+  /// 完了を通知された直接アップロードを写真として保存し、保存した件数を返す
+  async fn process_completed_uploads(&self) -> Result<usize, PictureServiceError>;
+  /// 期限までに完了しなかったアップロードをファイルごと削除し、削除した件数を返す
+  async fn cleanup_expired_uploads(&self) -> Result<usize, PictureServiceError>;
   async fn delete_picture(&self, picture_id: i32, user_id: i32) -> Result<(), PictureServiceError>;
+  async fn get_my_submissions(&self, user_id: i32, page: Page) -> Result<SubmissionsResponse, PictureServiceError>;
+  /// `viewer_id` に見せられない写真は画像の URL を除いて返す
+  async fn get_pictures(
+    &self,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError>;
+  async fn get_user_pictures(
+    &self,
+    user_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError>;
+  async fn get_request_pictures(
+    &self,
+    request_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError>;
+  async fn get_picture(
+    &self,
+    picture_id: i32,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureWithAuthor, PictureServiceError>;
+  /// リクエストの依頼者が投稿写真の確認結果を更新する
+  async fn review_submission(
+    &self,
+    request_id: i32,
+    picture_id: i32,
+    user_id: i32,
+    review_status: &str,
+  ) -> Result<PictureWithAuthor, PictureServiceError>;
 }
 
 pub struct PictureServiceImpl {
   db: PgPool,
-  storage: S3Storage,
+  storage: Arc<dyn ObjectStorage>,
+  storage_operations: StorageOperationService,
+  limits: ImageLimits,
+  buffer_budget: BufferBudget,
+  decode_budget: BufferBudget,
 }
 
 impl PictureServiceImpl {
-  pub fn new(db: PgPool, storage: S3Storage) -> Self {
-    Self { db, storage }
+  pub fn new(db: PgPool, storage: Arc<dyn ObjectStorage>, limits: ImageLimits) -> Self {
+    Self {
+      storage_operations: StorageOperationService::new(db.clone(), storage.clone()),
+      db,
+      storage,
+      buffer_budget: BufferBudget::new(limits.max_bytes.saturating_add(1).saturating_mul(BUFFERED_UPLOADS)),
+      decode_budget: BufferBudget::new(decoded_bytes(limits.max_pixels, 1).saturating_mul(DECODING_UPLOADS)),
+      limits,
+    }
+  }
+
+  /// 写真として保存するためにメモリへ同時に読み込めるファイルの合計を変える
+  pub fn with_buffer_budget(mut self, bytes: usize) -> Self {
+    self.buffer_budget = BufferBudget::new(bytes);
+    self
+  }
+
+  /// 写真として保存するためにメモリへ同時に読み込んだファイルの合計の最大値
+  pub fn peak_buffered_bytes(&self) -> usize {
+    self.buffer_budget.peak()
+  }
+
+  /// 同時にデコードできる画像のメモリの見積もりの合計を変える
+  pub fn with_decode_budget(mut self, bytes: usize) -> Self {
+    self.decode_budget = BufferBudget::new(bytes);
+    self
+  }
+
+  /// 同時にデコードした画像のメモリの見積もりの合計の最大値
+  pub fn peak_decoded_bytes(&self) -> usize {
+    self.decode_budget.peak()
+  }
+
+  async fn resolve_urls(&self, picture: &mut Picture) -> Result<(), PictureServiceError> {
+    resolve_picture_urls(self.storage.as_ref(), picture)
+      .await
+      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to resolve picture URL: {}", e)))
+  }
+
+  /// 非公開のバケットでは URL が署名付きになるので、`viewer_id` に見せてよい写真だけ URL を組み立てる
+  async fn resolve_urls_for(
+    &self,
+    picture: &mut PictureWithAuthor,
+    viewer_id: Option<i32>,
+  ) -> Result<(), PictureServiceError> {
+    if !picture.is_visible_to(viewer_id) {
+      picture.picture.hide_urls();
+      return Ok(());
+    }
+
+    self.resolve_urls(&mut picture.picture).await
+  }
+
+  /// 投稿先のリクエストを読み込み、`viewer_id` に見せてよい位置照合の結果だけを残す
+  async fn mask_location_checks(
+    &self,
+    pictures: Vec<(&mut Picture, i32)>,
+    viewer_id: Option<i32>,
+  ) -> Result<(), PictureServiceError> {
+    if pictures.is_empty() {
+      return Ok(());
+    }
+
+    let mut request_ids: Vec<i32> = pictures.iter().map(|(_, request_id)| *request_id).collect();
+    request_ids.sort_unstable();
+    request_ids.dedup();
+    let requests: HashMap<i32, Request> = request_repository::find_by_ids(&self.db, &request_ids)
+      .await?
+      .into_iter()
+      .map(|request| (request.id, request))
+      .collect();
+
+    for (picture, request_id) in pictures {
+      if let Some(request) = requests.get(&request_id) {
+        picture.mask_location_check(request, viewer_id);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// 縮小画像をアップロードして記録する。縮小画像は元画像の代わりに使うだけなので、失敗してもログに残して続ける。
+  /// 縮小画像も元画像と同じく、エンコードした内容から決まるキーに置く
+  async fn store_variants(&self, mut picture: Picture, rendered: Vec<RenderedVariant>) -> Picture {
+    for mut variant in rendered {
+      let name = variant.key();
+      let (width, height) = (variant.width as i32, variant.height as i32);
+      let data = std::mem::take(&mut variant.data);
+
+      let (key, upload) = match self
+        .put_blob(data, variant.format.extension(), variant.format.mime_type())
+        .await
+      {
+        Ok(stored) => stored,
+        Err(e) => {
+          tracing::error!("Failed to upload variant {} of picture {}: {:?}", name, picture.id, e);
+          continue;
+        }
+      };
+
+      if let Err(e) = self.record_variant(picture.id, &variant, &key, &upload).await {
+        tracing::error!("Failed to record variant {}: {:?}", key, e);
+        self.storage_operations.apply(vec![upload]).await;
+        continue;
+      }
+
+      picture.variants.insert(
+        name,
+        PictureVariant {
+          url: key.clone(),
+          storage_key: Some(key),
+          width,
+          height,
+        },
+      );
+    }
+
+    picture
+  }
+
+  /// `data` の SHA-256 から決まるキーへオブジェクトを置き、そのキーを返す。同じ内容のオブジェクトがすでにあれば送らずに使い回す。
+  /// 行を作れないままプロセスが止まってもオブジェクトが残らないよう、置く前に送信箱へ記録する
+  async fn put_blob(
+    &self,
+    data: Vec<u8>,
+    extension: &str,
+    content_type: &str,
+  ) -> Result<(String, StorageOperation), PictureServiceError> {
+    let sha256 = format!("{:x}", Sha256::digest(&data));
+    let key = content_key(&sha256, extension);
+    let upload = self
+      .storage_operations
+      .begin_blob_upload(&key, &sha256, data.len() as i64)
+      .await?
+      .ok_or_else(|| PictureServiceError::InternalServerError(format!("{} is recorded with different content", key)))?;
+
+    let stored = match self.storage.head(&key).await {
+      Ok(Some(_)) => Ok(()),
+      Ok(None) => self.storage.put(&key, data, content_type).await,
+      Err(e) => Err(e),
+    };
+    if let Err(e) = stored {
+      self.storage_operations.apply(vec![upload]).await;
+      return Err(PictureServiceError::InternalServerError(format!(
+        "Failed to upload to storage: {}",
+        e
+      )));
+    }
+
+    Ok((key, upload))
+  }
+
+  /// 縮小画像の行を作り、同じトランザクションでアップロードの記録を取り除く
+  async fn record_variant(
+    &self,
+    picture_id: i32,
+    variant: &RenderedVariant,
+    key: &str,
+    upload: &StorageOperation,
+  ) -> Result<(), sqlx::Error> {
+    let mut tx = self.db.begin().await?;
+    repository::create_variant_with_executor(&mut *tx.as_mut(), picture_id, variant, key).await?;
+    storage_operation_repository::add_blob_references_with_executor(&mut *tx.as_mut(), &[key]).await?;
+    storage_operation_repository::delete_with_executor(&mut *tx.as_mut(), upload.id).await?;
+    tx.commit().await
+  }
+
+  /// 1件多く取得した結果から、次のページがあれば最後の行をカーソルにして返す
+  async fn find_page(
+    &self,
+    filter: PictureFilter,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    let mut pictures = repository::find_all(&self.db, &filter, &page).await?;
+    for picture in &mut pictures {
+      self.resolve_urls_for(picture, viewer_id).await?;
+    }
+    let submissions = pictures
+      .iter_mut()
+      .filter_map(|picture| Some((&mut picture.picture, picture.request_id?)))
+      .collect();
+    self.mask_location_checks(submissions, viewer_id).await?;
+
+    let next_cursor = if pictures.len() as i64 > page.limit {
+      pictures.truncate(page.limit as usize);
+      pictures.last().and_then(|last| {
+        last.picture.created_at.map(|created_at| {
+          Cursor {
+            created_at,
+            id: last.picture.id,
+          }
+          .encode()
+        })
+      })
+    } else {
+      None
+    };
+
+    Ok(PictureFeedResponse { pictures, next_cursor })
+  }
+
+  /// 画像を検証し、メタデータを取り除いてから保存する。直接アップロードでもサーバー経由のアップロードでも同じ処理を通す。
+  /// 直接アップロードでは `source` の保留中のアップロードを写真の行と同じトランザクションで消す
+  async fn store_picture(
+    &self,
+    user_id: i32,
+    source: Option<&PictureUpload>,
+    file_data: Vec<u8>,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<Picture, PictureServiceError> {
+    let image = format::inspect(&file_data, &self.limits)?;
+    let capture = metadata::extract(&file_data);
+    let orientation = capture.orientation;
+
+    // 撮影位置やカメラのシリアル番号が公開されないよう、向き以外のメタデータを取り除いたファイルを保存する。
+    // ヘッダーだけでは分からない壊れた画像もここで弾けるよう、保存する前にデコードして縮小画像を作る。
+    // デコードしたピクセルはファイルよりずっと大きいため、デコードする画像の分も予算から確保する
+    let decoding = match image.format.decoder_format() {
+      Some(_) => Some(
+        self
+          .decode_budget
+          .reserve(decoded_bytes(image.width.into(), image.height.into()))
+          .await,
+      ),
+      None => None,
+    };
+    let (file_data, rendered) = tokio::task::spawn_blocking(move || {
+      let stripped = metadata::strip(file_data, image.format, orientation)?;
+      let rendered = variant::render_variants(&stripped, image.format, orientation)?;
+      Ok::<_, ImageError>((stripped, rendered))
+    })
+    .await
+    .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to process image: {}", e)))??;
+    // デコードしたピクセルは縮小画像をエンコードし終えた時点で手放している
+    drop(decoding);
+
+    // 同じ画像が何度アップロードされてもストレージには1つだけ置くよう、メタデータを取り除いたあとの内容からキーを決める
+    let (storage_key, upload) = self
+      .put_blob(file_data, image.format.extension(), image.format.mime_type())
+      .await?;
+
+    let new_picture = NewPicture {
+      user_id,
+      storage_key: &storage_key,
+      storage_backend: self.storage.backend(),
+      request_id: None,
+      image: &image,
+      capture: &capture,
+      reported_location: None,
+      location_check: None,
+      upload_id: source.map(|source| source.id),
+    };
+    let created = match request_id {
+      None => self.create_picture_row(&new_picture, &upload, source).await,
+      Some(request_id) => {
+        // クライアントが送った現在地はリクエストへの投稿の位置確認にだけ使う
+        let new_picture = NewPicture {
+          reported_location,
+          ..new_picture
+        };
+        self.attach_to_request(new_picture, request_id, &upload, source).await
+      }
+    };
+    let picture = match created {
+      Ok((picture, cleanup)) => {
+        // 直接アップロードで保存した写真はメタデータを取り除いた別のファイルなので、元のファイルは消す
+        self.storage_operations.apply(cleanup).await;
+        picture
+      }
+      Err(e) => {
+        // 上限や DB の障害で行を作れなかった場合、ほかの写真と共有していなければアップロード済みのオブジェクトを残さない
+        self.storage_operations.apply(vec![upload]).await;
+        return Err(e);
+      }
+    };
+
+    let mut picture = self.store_variants(picture, rendered).await;
+    self.resolve_urls(&mut picture).await?;
+    Ok(picture)
+  }
+
+  /// 一時的な場所に届いたファイルを検証して写真として保存する。画像として受け付けられなければ保留中のアップロードをファイルごと消す。
+  /// ストレージや DB の一時的な障害では、呼び出し元がやり直せるようファイルを残し、保留中の行だけを消す
+  async fn finalize_upload(
+    &self,
+    upload: PictureUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<Picture, PictureServiceError> {
+    let result = self.process_upload(&upload, request_id, reported_location).await;
+    match &result {
+      Ok(_) => {}
+      Err(PictureServiceError::InternalServerError(_)) => {
+        if let Err(e) = repository::delete_upload(&self.db, upload.id).await {
+          tracing::error!("Failed to release upload {}: {:?}", upload.id, e);
+        }
+      }
+      Err(_) => self.discard_pending_upload(&upload).await,
+    }
+
+    result
+  }
+
+  /// 保留中のアップロードのファイルを読み出して写真として保存する。保留中の行は写真の行と同じトランザクションで消す
+  async fn process_upload(
+    &self,
+    upload: &PictureUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<Picture, PictureServiceError> {
+    if upload.content_length as u64 > self.limits.max_bytes as u64 {
+      return Err(
+        ImageError::TooLarge {
+          max_bytes: self.limits.max_bytes,
+        }
+        .into(),
+      );
+    }
+
+    // デコードにはファイル全体が必要なため、ここで一度だけ読み込む（`BufferBudget` を参照）。
+    // 完了の通知の後でファイルが差し替えられていても、読み込むのは記録した大きさを 1 バイト超えるところまでにする
+    let read_length = upload.content_length as usize + 1;
+    let _reservation = self.buffer_budget.reserve(read_length).await;
+    let file_data = self
+      .storage
+      .get_range(&upload.object_key, 0, read_length as u64)
+      .await
+      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to download upload: {}", e)))?;
+    if file_data.len() > self.limits.max_bytes {
+      return Err(
+        ImageError::TooLarge {
+          max_bytes: self.limits.max_bytes,
+        }
+        .into(),
+      );
+    }
+    if file_data.len() as i64 > upload.content_length {
+      return Err(PictureServiceError::BadRequest(
+        "Uploaded file is larger than its declared size".to_string(),
+      ));
+    }
+
+    self
+      .store_picture(upload.user_id, Some(upload), file_data, request_id, reported_location)
+      .await
+  }
+
+  /// 保留中のアップロードを消し、同じトランザクションで記録した削除でファイルも消す。ほかの処理がすでに消していれば何もしない
+  async fn discard_pending_upload(&self, upload: &PictureUpload) {
+    // 消せなかったものは期限切れのアップロードとして掃除される
+    if let Err(e) = self.remove_pending_upload(upload).await {
+      tracing::error!("Failed to discard upload {}: {:?}", upload.id, e);
+    }
+  }
+
+  /// 保留中のアップロードの行を消し、同じトランザクションで送信箱にファイルの削除を記録してから消す。
+  /// ファイルを消せなくても送信箱から再試行される。ほかの処理が先に行を消していれば何もせず `false` を返す
+  async fn remove_pending_upload(&self, upload: &PictureUpload) -> Result<bool, sqlx::Error> {
+    let mut tx = self.db.begin().await?;
+    if !repository::delete_upload_with_executor(&mut *tx.as_mut(), upload.id).await? {
+      return Ok(false);
+    }
+    let cleanup = storage_operation_repository::create_with_executor(
+      &mut *tx.as_mut(),
+      OperationKind::Delete,
+      &[upload.object_key.as_str()],
+      Duration::ZERO,
+    )
+    .await?;
+    tx.commit().await?;
+
+    self.storage_operations.apply(cleanup).await;
+    Ok(true)
+  }
+
+  /// 画像として受け付けられなかった直接アップロードを記録し、ファイルを消す。行はクライアントが結果を確かめられるよう残す
+  async fn fail_upload(&self, upload: &PictureUpload, error: &str) {
+    let failed = async {
+      let mut tx = self.db.begin().await?;
+      repository::mark_upload_failed_with_executor(&mut *tx.as_mut(), upload.id, error, PENDING_UPLOAD_TTL).await?;
+      let cleanup = storage_operation_repository::create_with_executor(
+        &mut *tx.as_mut(),
+        OperationKind::Delete,
+        &[upload.object_key.as_str()],
+        Duration::ZERO,
+      )
+      .await?;
+      tx.commit().await?;
+      Ok::<_, sqlx::Error>(cleanup)
+    }
+    .await;
+
+    match failed {
+      Ok(cleanup) => {
+        self.storage_operations.apply(cleanup).await;
+      }
+      Err(e) => tracing::error!("Failed to record failed upload {}: {:?}", upload.id, e),
+    }
+  }
+
+  /// 写真の元になった保留中のアップロードを消し、元のファイルの削除を記録する。
+  /// サーバー経由のアップロードは保留中の行もファイルも持たないので何もしない。
+  /// ほかの処理が先に写真として保存していた場合は `Conflict`
+  async fn consume_upload(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    source: Option<&PictureUpload>,
+  ) -> Result<Vec<StorageOperation>, PictureServiceError> {
+    let Some(source) = source else {
+      return Ok(Vec::new());
+    };
+    if !repository::delete_upload_with_executor(&mut *tx.as_mut(), source.id).await? {
+      return Err(PictureServiceError::Conflict(
+        "This upload has already been processed".to_string(),
+      ));
+    }
+    let cleanup = storage_operation_repository::create_with_executor(
+      &mut *tx.as_mut(),
+      OperationKind::Delete,
+      &[source.object_key.as_str()],
+      Duration::ZERO,
+    )
+    .await?;
+
+    Ok(cleanup)
+  }
+
+  /// 写真の行を作り、同じトランザクションでアップロードの記録と保留中のアップロードを取り除く
+  async fn create_picture_row(
+    &self,
+    picture: &NewPicture<'_>,
+    upload: &StorageOperation,
+    source: Option<&PictureUpload>,
+  ) -> Result<(Picture, Vec<StorageOperation>), PictureServiceError> {
+    let mut tx = self.db.begin().await?;
+    let cleanup = Self::consume_upload(&mut tx, source).await?;
+    let picture = repository::create_uploaded_with_executor(&mut *tx.as_mut(), picture).await?;
+    storage_operation_repository::add_blob_references_with_executor(&mut *tx.as_mut(), &[upload.object_key.as_str()])
+      .await?;
+    storage_operation_repository::delete_with_executor(&mut *tx.as_mut(), upload.id).await?;
+    tx.commit().await?;
+
+    Ok((picture, cleanup))
+  }
+
+  /// 投稿数の上限を確認し、撮影位置をリクエストの地点と照合してからリクエストへの投稿として保存する
+  async fn attach_to_request(
+    &self,
+    mut picture: NewPicture<'_>,
+    request_id: i32,
+    upload: &StorageOperation,
+    source: Option<&PictureUpload>,
+  ) -> Result<(Picture, Vec<StorageOperation>), PictureServiceError> {
+    let user_id = picture.user_id;
+    let mut tx = self.db.begin().await?;
+    let cleanup = Self::consume_upload(&mut tx, source).await?;
+
+    // 同じリクエストへの同時投稿で上限を超えないよう、リクエスト行をロックしてから数える
+    let request = request_repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.status != "open" && request.status != "in-progress" {
+      return Err(PictureServiceError::Conflict(
+        "This request is no longer accepting submissions".to_string(),
+      ));
+    }
+
+    let counts = repository::count_submissions_with_executor(&mut *tx.as_mut(), request_id, user_id).await?;
+    if counts.by_user >= i64::from(request.max_submissions_per_user) {
+      return Err(PictureServiceError::Conflict(format!(
+        "You can submit at most {} pictures to this request",
+        request.max_submissions_per_user
+      )));
+    }
+    if let Some(max_total) = request.max_total_submissions {
+      if counts.total >= i64::from(max_total) {
+        return Err(PictureServiceError::Conflict(
+          "This request has reached its submission limit".to_string(),
+        ));
+      }
+    }
+
+    picture.request_id = Some(request_id);
+    picture.location_check = Some(LocationCheck::evaluate(
+      (request.lat, request.lng),
+      request.location_tolerance_m,
+      picture.capture.gps,
+      picture.reported_location,
+    ));
+
+    let mut picture = repository::create_uploaded_with_executor(&mut *tx.as_mut(), &picture).await?;
+    storage_operation_repository::add_blob_references_with_executor(&mut *tx.as_mut(), &[upload.object_key.as_str()])
+      .await?;
+    storage_operation_repository::delete_with_executor(&mut *tx.as_mut(), upload.id).await?;
+    tx.commit().await?;
+
+    // 投稿者は送る現在地を自由に選べるため、ぼかしたリクエストでは正確な地点との距離を返さない
+    picture.mask_location_check(&request, Some(user_id));
+
+    Ok((picture, cleanup))
   }
 }
 
 #[async_trait]
 impl PictureService for PictureServiceImpl {
   async fn create_picture(&self, user_id: i32, image_url: String) -> Result<Picture, PictureServiceError> {
-    let picture = repository::create(&self.db, user_id, &image_url).await?;
+    let mut picture = repository::create(&self.db, user_id, &image_url).await?;
+    self.resolve_urls(&mut picture).await?;
     Ok(picture)
   }
 
+  async fn stage_upload(&self, user_id: i32, file: &mut dyn FileChunks) -> Result<StagedUpload, PictureServiceError> {
+    // 届く前には大きさが分からないため、上限の分を確保してから読み始める
+    let reservation = self.buffer_budget.reserve(self.limits.max_bytes).await;
+
+    // 画像でないファイルを最後まで受け取らないよう、先頭が届いた時点で形式を判定する
+    let mut data = Vec::new();
+    let mut sniffed = false;
+    while let Some(chunk) = file.next_chunk().await? {
+      if data.len() + chunk.len() > self.limits.max_bytes {
+        return Err(
+          ImageError::TooLarge {
+            max_bytes: self.limits.max_bytes,
+          }
+          .into(),
+        );
+      }
+      data.extend_from_slice(&chunk);
+      if !sniffed && data.len() >= SNIFF_BYTES {
+        format::sniff_format(&data).ok_or(ImageError::UnsupportedFormat)?;
+        sniffed = true;
+      }
+    }
+    if !sniffed {
+      format::sniff_format(&data).ok_or(ImageError::UnsupportedFormat)?;
+    }
+
+    Ok(StagedUpload {
+      user_id,
+      data,
+      reservation,
+    })
+  }
+
   async fn upload_and_create_picture(
     &self,
     user_id: i32,
-    file_data: Vec<u8>,
-    file_name: String,
-    content_type: String,
+    staged: StagedUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
   ) -> Result<Picture, PictureServiceError> {
-    let extension = file_name.split('.').next_back().unwrap_or("jpg");
-    let unique_key = format!("pictures/{}/{}.{}", user_id, Uuid::new_v4(), extension);
+    if staged.user_id != user_id {
+      return Err(PictureServiceError::Forbidden(
+        "You do not have permission to complete this upload".to_string(),
+      ));
+    }
+
+    // 読み込みに確保した予算は、メタデータを取り除いたファイルを置き終えるまで持っておく
+    let StagedUpload {
+      data,
+      reservation: _reservation,
+      ..
+    } = staged;
+    self
+      .store_picture(user_id, None, data, request_id, reported_location)
+      .await
+  }
+
+  async fn finalize_assembled_upload(
+    &self,
+    upload: PictureUpload,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<Picture, PictureServiceError> {
+    // 前回の試行が写真の行を作ったあとで止まっていれば、その写真を返す
+    if let Some(mut picture) = repository::find_by_upload_id(&self.db, upload.id).await? {
+      self.resolve_urls(&mut picture).await?;
+      return Ok(picture);
+    }
+
+    // 保存の途中で止まった場合も期限切れのアップロードとして掃除されるよう、保留中のアップロードとして記録してから進める。
+    // 前回の試行が途中で止まって残した行は作り直す
+    let upload = PictureUpload {
+      expires_at: Utc::now() + PENDING_UPLOAD_TTL,
+      ..upload
+    };
+    repository::delete_upload(&self.db, upload.id).await?;
+    repository::create_upload(&self.db, &upload).await?;
+
+    let request_id = upload.request_id;
+    self.finalize_upload(upload, request_id, reported_location).await
+  }
 
-    let image_url = self
+  async fn create_upload(
+    &self,
+    user_id: i32,
+    req: CreatePictureUploadRequest,
+  ) -> Result<PictureUploadResponse, PictureServiceError> {
+    let format = ImageFormat::from_mime_type(&req.content_type)
+      .ok_or_else(|| PictureServiceError::BadRequest(ImageError::UnsupportedFormat.to_string()))?;
+    if req.content_length as u64 > self.limits.max_bytes as u64 {
+      return Err(
+        ImageError::TooLarge {
+          max_bytes: self.limits.max_bytes,
+        }
+        .into(),
+      );
+    }
+    if let Some(request_id) = req.request_id {
+      request_repository::find_by_id(&self.db, request_id)
+        .await?
+        .ok_or_else(|| PictureServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+    }
+
+    let id = Uuid::new_v4();
+    let object_key = format!("uploads/{}/{}.{}", user_id, id, format.extension());
+    let presigned = self
       .storage
-      .upload_file(&unique_key, file_data, &content_type)
+      .presign_put(&object_key, format.mime_type(), PRESIGNED_UPLOAD_TTL)
       .await
-      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to upload to S3: {}", e)))?;
+      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to presign upload: {}", e)))?;
 
-    let picture = repository::create(&self.db, user_id, &image_url).await?;
-    Ok(picture)
+    let now = Utc::now();
+    let upload = PictureUpload {
+      id,
+      user_id,
+      request_id: req.request_id,
+      object_key,
+      content_type: format.mime_type().to_string(),
+      content_length: req.content_length,
+      expires_at: now + PENDING_UPLOAD_TTL,
+      created_at: now,
+    };
+    repository::create_upload(&self.db, &upload).await?;
+
+    Ok(PictureUploadResponse {
+      upload_id: upload.id,
+      method: presigned.method,
+      upload_url: presigned.url,
+      headers: presigned.headers,
+      expires_at: upload.expires_at,
+    })
+  }
+
+  async fn complete_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<PictureUploadStatus, PictureServiceError> {
+    // 完了の通知を再送しても同じ結果を返す
+    let status = self.get_upload_status(user_id, upload_id).await?;
+    if status.status != PictureUploadState::Pending {
+      return Ok(status);
+    }
+
+    let upload = repository::find_upload_by_id(&self.db, upload_id)
+      .await?
+      .filter(|upload| upload.expires_at > Utc::now())
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Upload with id {} not found", upload_id)))?;
+
+    // API サーバーへファイル全体を取り込まないよう、HEAD で届いた大きさを、範囲指定の GET で先頭の形式だけを確かめる。
+    // デコードして保存するのはバックグラウンドの処理に任せる
+    let object = self
+      .storage
+      .head(&upload.object_key)
+      .await
+      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to check upload: {}", e)))?
+      .ok_or_else(|| PictureServiceError::Conflict("The file has not been uploaded yet".to_string()))?;
+    let head = self
+      .storage
+      .get_range(&upload.object_key, 0, SNIFF_BYTES as u64)
+      .await
+      .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to check upload: {}", e)))?;
+
+    // 申告した大きさではなく、実際に届いたファイルの大きさで上限を確かめる
+    let rejected = if object.size as u64 > self.limits.max_bytes as u64 {
+      Some(ImageError::TooLarge {
+        max_bytes: self.limits.max_bytes,
+      })
+    } else if format::sniff_format(&head).is_none() {
+      Some(ImageError::UnsupportedFormat)
+    } else {
+      None
+    };
+    if let Some(error) = rejected {
+      self.discard_pending_upload(&upload).await;
+      return Err(error.into());
+    }
+
+    repository::mark_upload_completed(&self.db, upload.id, reported_location).await?;
+    self.get_upload_status(user_id, upload_id).await
+  }
+
+  async fn get_upload_status(&self, user_id: i32, upload_id: Uuid) -> Result<PictureUploadStatus, PictureServiceError> {
+    let forbidden = || PictureServiceError::Forbidden("You do not have permission to access this upload".to_string());
+
+    if let Some(progress) = repository::find_upload_progress(&self.db, upload_id).await? {
+      if progress.user_id != user_id {
+        return Err(forbidden());
+      }
+      return Ok(PictureUploadStatus::from_progress(upload_id, &progress));
+    }
+
+    // 写真として保存し終えると保留中の行はなくなるので、写真から結果を返す
+    let picture = repository::find_by_upload_id(&self.db, upload_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Upload with id {} not found", upload_id)))?;
+    if picture.user_id != user_id {
+      return Err(forbidden());
+    }
+
+    Ok(PictureUploadStatus {
+      upload_id,
+      status: PictureUploadState::Completed,
+      picture_id: Some(picture.id),
+      error: None,
+    })
+  }
+
+  async fn process_completed_uploads(&self) -> Result<usize, PictureServiceError> {
+    let uploads =
+      repository::claim_completed_uploads(&self.db, UPLOAD_PROCESSING_BATCH_SIZE, UPLOAD_PROCESSING_LEASE).await?;
+
+    let mut processed = 0;
+    for CompletedUpload {
+      upload,
+      reported_location,
+      attempts,
+    } in uploads
+    {
+      let request_id = upload.request_id;
+      match self.process_upload(&upload, request_id, reported_location).await {
+        Ok(_) => processed += 1,
+        // ストレージや DB の一時的な障害では保留中のアップロードを残し、間隔を空けて再試行する
+        Err(PictureServiceError::InternalServerError(e)) if attempts + 1 < MAX_UPLOAD_ATTEMPTS => {
+          tracing::error!(
+            "Failed to process upload {} (attempt {}): {}",
+            upload.id,
+            attempts + 1,
+            e
+          );
+          if let Err(e) = repository::record_upload_failure(&self.db, upload.id, &e, retry_delay(attempts)).await {
+            tracing::error!("Failed to record upload {}: {:?}", upload.id, e);
+          }
+        }
+        Err(PictureServiceError::InternalServerError(e)) => {
+          tracing::error!(
+            "Giving up on upload {} after {} attempts: {}",
+            upload.id,
+            attempts + 1,
+            e
+          );
+          self.fail_upload(&upload, "The upload could not be processed").await;
+        }
+        Err(e) => self.fail_upload(&upload, &e.to_string()).await,
+      }
+    }
+
+    Ok(processed)
+  }
+
+  async fn cleanup_expired_uploads(&self) -> Result<usize, PictureServiceError> {
+    let uploads = repository::find_expired_uploads(&self.db, EXPIRED_UPLOAD_BATCH_SIZE).await?;
+
+    let mut deleted = 0;
+    for upload in uploads {
+      if self.remove_pending_upload(&upload).await? {
+        deleted += 1;
+      }
+    }
+
+    Ok(deleted)
   }
 
   async fn delete_picture(&self, picture_id: i32, user_id: i32) -> Result<(), PictureServiceError> {
@@ -94,15 +994,133 @@ impl PictureService for PictureServiceImpl {
       ));
     }
 
-    if let Some(key) = self.storage.extract_key_from_url(&picture.image_url) {
+    // 行を消すのと同じトランザクションで参照数を減らしてオブジェクトの削除を記録し、ここで消せなかった分は後で片付ける。
+    // ほかの写真と共有しているオブジェクトは、最後の参照がなくなるまで消さない
+    let keys = picture.storage_keys(self.storage.as_ref());
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let mut tx = self.db.begin().await?;
+    repository::delete_with_executor(&mut *tx.as_mut(), picture_id).await?;
+    storage_operation_repository::remove_blob_references_with_executor(&mut *tx.as_mut(), &keys).await?;
+    let deletes = storage_operation_repository::create_with_executor(
+      &mut *tx.as_mut(),
+      OperationKind::Delete,
+      &keys,
+      Duration::ZERO,
+    )
+    .await?;
+    tx.commit().await?;
+
+    self.storage_operations.apply(deletes).await;
+    Ok(())
+  }
+
+  async fn get_my_submissions(&self, user_id: i32, page: Page) -> Result<SubmissionsResponse, PictureServiceError> {
+    let mut submissions =
+      repository::find_submissions_by_user_id(&self.db, user_id, page.limit(), page.offset()).await?;
+    for submission in &mut submissions {
+      self.resolve_urls(&mut submission.picture).await?;
+    }
+    let pictures = submissions
+      .iter_mut()
+      .filter_map(|submission| {
+        let request_id = submission.request.as_ref()?.id;
+        Some((&mut submission.picture, request_id))
+      })
+      .collect();
+    self.mask_location_checks(pictures, Some(user_id)).await?;
+    let total = repository::count_by_user_id(&self.db, user_id).await?;
+
+    Ok(SubmissionsResponse {
+      submissions,
+      total,
+      page: page.page,
+      per_page: page.per_page,
+    })
+  }
+
+  async fn get_pictures(
+    &self,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    self.find_page(PictureFilter::default(), page, viewer_id).await
+  }
+
+  async fn get_user_pictures(
+    &self,
+    user_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    User::find_by_id(&self.db, user_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("User with id {} not found", user_id)))?;
+
+    let filter = PictureFilter {
+      user_id: Some(user_id),
+      ..Default::default()
+    };
+    self.find_page(filter, page, viewer_id).await
+  }
+
+  async fn get_request_pictures(
+    &self,
+    request_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    request_repository::find_by_id(&self.db, request_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    let filter = PictureFilter {
+      request_id: Some(request_id),
+      ..Default::default()
+    };
+    self.find_page(filter, page, viewer_id).await
+  }
+
+  async fn get_picture(
+    &self,
+    picture_id: i32,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureWithAuthor, PictureServiceError> {
+    let mut picture = repository::find_with_author_by_id(&self.db, picture_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Picture with id {} not found", picture_id)))?;
+    self.resolve_urls_for(&mut picture, viewer_id).await?;
+    if let Some(request_id) = picture.request_id {
       self
-        .storage
-        .delete_file(&key)
-        .await
-        .map_err(|e| PictureServiceError::InternalServerError(format!("Failed to delete from S3: {}", e)))?;
+        .mask_location_checks(vec![(&mut picture.picture, request_id)], viewer_id)
+        .await?;
     }
+    Ok(picture)
+  }
 
-    repository::delete(&self.db, picture_id).await?;
-    Ok(())
+  async fn review_submission(
+    &self,
+    request_id: i32,
+    picture_id: i32,
+    user_id: i32,
+    review_status: &str,
+  ) -> Result<PictureWithAuthor, PictureServiceError> {
+    let request = request_repository::find_by_id(&self.db, request_id)
+      .await?
+      .ok_or_else(|| PictureServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.user_id != user_id {
+      return Err(PictureServiceError::Forbidden(
+        "Only the request owner can review submissions".to_string(),
+      ));
+    }
+
+    if !repository::update_review_status(&self.db, picture_id, request_id, review_status).await? {
+      return Err(PictureServiceError::NotFound(format!(
+        "Picture with id {} not found in request {}",
+        picture_id, request_id
+      )));
+    }
+
+    self.get_picture(picture_id, Some(user_id)).await
   }
 }
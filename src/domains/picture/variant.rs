@@ -0,0 +1,175 @@
+use std::io::Cursor;
+
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage, ImageReader};
+
+use super::format::{ImageError, ImageFormat};
+
+/// 縮小画像の長辺のピクセル数
+pub const VARIANT_SIZES: &[u32] = &[256, 1024, 2048];
+const JPEG_QUALITY: u8 = 80;
+/// 写真の可逆圧縮は元の JPEG より大きくなりがちなため、WebP も非可逆で圧縮する
+const WEBP_QUALITY: f32 = 75.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantFormat {
+  Jpeg,
+  Webp,
+}
+
+impl VariantFormat {
+  pub const ALL: [VariantFormat; 2] = [VariantFormat::Jpeg, VariantFormat::Webp];
+
+  /// picture_variants.format に保存する名前
+  pub fn name(&self) -> &'static str {
+    match self {
+      VariantFormat::Jpeg => "jpeg",
+      VariantFormat::Webp => "webp",
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      VariantFormat::Jpeg => "jpg",
+      VariantFormat::Webp => "webp",
+    }
+  }
+
+  pub fn mime_type(&self) -> &'static str {
+    match self {
+      VariantFormat::Jpeg => "image/jpeg",
+      VariantFormat::Webp => "image/webp",
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedVariant {
+  pub size: u32,
+  pub format: VariantFormat,
+  pub width: u32,
+  pub height: u32,
+  pub data: Vec<u8>,
+}
+
+impl RenderedVariant {
+  /// レスポンスの `variants` で使うキー（例: `256_webp`）
+  pub fn key(&self) -> String {
+    format!("{}_{}", self.size, self.format.name())
+  }
+}
+
+/// 元画像を `VARIANT_SIZES` の長辺に縮小し、JPEG と WebP にエンコードする。
+/// 縮小画像にはメタデータが残らないため、EXIF の向きはピクセルに反映しておく。
+/// 元画像以上のサイズは拡大になるため作らない。HEIC はデコードできないため空を返し、元画像だけを配信する
+pub fn render_variants(
+  data: &[u8],
+  format: ImageFormat,
+  orientation: Option<u16>,
+) -> Result<Vec<RenderedVariant>, ImageError> {
+  let Some(decoder_format) = format.decoder_format() else {
+    return Ok(Vec::new());
+  };
+
+  let mut original = ImageReader::with_format(Cursor::new(data), decoder_format)
+    .decode()
+    .map_err(|e| ImageError::Malformed(e.to_string()))?;
+  if let Some(orientation) = orientation.and_then(|value| Orientation::from_exif(value as u8)) {
+    original.apply_orientation(orientation);
+  }
+  let long_edge = original.width().max(original.height());
+
+  let mut variants = Vec::new();
+  for &size in VARIANT_SIZES.iter().filter(|&&size| size < long_edge) {
+    let resized = original.resize(size, size, FilterType::Lanczos3);
+    for format in VariantFormat::ALL {
+      variants.push(RenderedVariant {
+        size,
+        format,
+        width: resized.width(),
+        height: resized.height(),
+        data: encode(&resized, format)?,
+      });
+    }
+  }
+
+  Ok(variants)
+}
+
+fn encode(image: &DynamicImage, format: VariantFormat) -> Result<Vec<u8>, ImageError> {
+  let mut data = Vec::new();
+  let result = match format {
+    // JPEG はアルファチャンネルを持てない
+    VariantFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)),
+    VariantFormat::Webp => {
+      let rgba = image.to_rgba8();
+      let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+        .encode_simple(false, WEBP_QUALITY)
+        .map_err(|e| ImageError::Malformed(format!("Failed to encode WebP: {:?}", e)))?;
+      return Ok(encoded.to_vec());
+    }
+  };
+  result.map_err(|e| ImageError::Malformed(e.to_string()))?;
+
+  Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::picture::format::{inspect, tests::sample_png, ImageLimits};
+
+  #[test]
+  fn test_render_variants_skips_upscaling() {
+    let variants = render_variants(&sample_png(600, 400), ImageFormat::Png, None).unwrap();
+
+    let keys: Vec<String> = variants.iter().map(RenderedVariant::key).collect();
+    assert_eq!(keys, vec!["256_jpeg", "256_webp"]);
+    for variant in &variants {
+      assert_eq!((variant.width, variant.height), (256, 171));
+      let info = inspect(&variant.data, &ImageLimits::default()).unwrap();
+      assert_eq!(info.format.mime_type(), variant.format.mime_type());
+      assert_eq!((info.width, info.height), (256, 171));
+    }
+
+    assert!(render_variants(&sample_png(200, 100), ImageFormat::Png, None)
+      .unwrap()
+      .is_empty());
+  }
+
+  #[test]
+  fn test_variants_are_smaller_than_photo() {
+    // 写真に近い、なめらかな色の変化に細かなノイズが乗った画像。可逆圧縮の WebP では元の JPEG より大きくなる
+    let photo = image::RgbImage::from_fn(1200, 900, |x, y| {
+      let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104729)) % 16;
+      image::Rgb([
+        (x / 5 + noise) as u8,
+        (y / 4 + noise) as u8,
+        ((x + y) / 9 + noise) as u8,
+      ])
+    });
+    let mut source = Vec::new();
+    DynamicImage::ImageRgb8(photo)
+      .write_with_encoder(JpegEncoder::new_with_quality(&mut source, 90))
+      .unwrap();
+
+    let variants = render_variants(&source, ImageFormat::Jpeg, None).unwrap();
+    assert_eq!(variants.len(), 4);
+    for variant in &variants {
+      assert!(
+        variant.data.len() < source.len(),
+        "{} is {} bytes, source is {} bytes",
+        variant.key(),
+        variant.data.len(),
+        source.len()
+      );
+    }
+  }
+
+  #[test]
+  fn test_render_variants_applies_orientation() {
+    // 6 は時計回りに90度回転して表示する向き
+    let variants = render_variants(&sample_png(600, 400), ImageFormat::Png, Some(6)).unwrap();
+    assert_eq!((variants[0].width, variants[0].height), (171, 256));
+  }
+}
@@ -0,0 +1,62 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use super::service::{PictureService, PictureServiceImpl};
+
+const DEFAULT_UPLOAD_CLEANUP_INTERVAL_SECS: u64 = 600;
+const DEFAULT_UPLOAD_PROCESSING_INTERVAL_SECS: u64 = 5;
+
+pub fn upload_cleanup_interval_from_env() -> Duration {
+  let secs = std::env::var("UPLOAD_CLEANUP_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(DEFAULT_UPLOAD_CLEANUP_INTERVAL_SECS);
+
+  Duration::from_secs(secs)
+}
+
+pub fn upload_processing_interval_from_env() -> Duration {
+  let secs = std::env::var("UPLOAD_PROCESSING_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(DEFAULT_UPLOAD_PROCESSING_INTERVAL_SECS);
+
+  Duration::from_secs(secs)
+}
+
+/// 完了しないまま期限が切れた直接アップロードを定期的に削除するバックグラウンドタスクを起動する
+pub fn spawn_upload_cleanup_worker(picture_service: Arc<PictureServiceImpl>, interval: Duration) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match picture_service.cleanup_expired_uploads().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Deleted {} expired uploads", count),
+        Err(e) => tracing::error!("Failed to delete expired uploads: {}", e),
+      }
+    }
+  })
+}
+
+/// 完了を通知された直接アップロードを定期的に写真として保存するバックグラウンドタスクを起動する
+pub fn spawn_upload_processing_worker(picture_service: Arc<PictureServiceImpl>, interval: Duration) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match picture_service.process_completed_uploads().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Stored {} completed uploads as pictures", count),
+        Err(e) => tracing::error!("Failed to process completed uploads: {}", e),
+      }
+    }
+  })
+}
@@ -1,4 +1,6 @@
 pub mod model;
+pub mod privacy;
 pub mod repository;
 pub mod rest;
 pub mod service;
+pub mod worker;
@@ -1,7 +1,31 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+use super::privacy::validate_location_precision;
+use crate::domains::picture::model::Picture;
+use crate::domains::tag::model::validate_tags;
+
+/// リクエストに付けられるカテゴリ（migrations の CHECK 制約と揃える）
+pub const REQUEST_CATEGORIES: &[&str] = &[
+  "landmark",
+  "food",
+  "nature",
+  "event",
+  "real-estate",
+  "street",
+  "shop",
+  "other",
+];
+
+pub fn validate_category(category: &str) -> Result<(), ValidationError> {
+  if !REQUEST_CATEGORIES.contains(&category) {
+    return Err(ValidationError::new("カテゴリが不正です"));
+  }
+
+  Ok(())
+}
 
 #[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
 pub struct Request {
@@ -12,6 +36,19 @@ pub struct Request {
   pub status: String,
   pub place_name: String,
   pub description: String,
+  pub deadline_at: Option<DateTime<Utc>>,
+  pub prefecture: Option<String>,
+  pub city: Option<String>,
+  pub category: Option<String>,
+  pub tags: Vec<String>,
+  pub location_precision: String,
+  pub claimed_by: Option<i32>,
+  pub max_submissions_per_user: i32,
+  pub max_total_submissions: Option<i32>,
+  /// 投稿写真の撮影位置がこの距離（メートル）以内なら位置を確認済みとする
+  pub location_tolerance_m: i32,
+  /// 非公開のリクエストへの投稿写真は、投稿者と依頼者にだけ見せる
+  pub is_private: bool,
   pub created_at: Option<DateTime<Utc>>,
 }
 
@@ -24,8 +61,27 @@ pub struct RequestWithDistance {
   pub status: String,
   pub place_name: String,
   pub description: String,
+  pub deadline_at: Option<DateTime<Utc>>,
+  pub prefecture: Option<String>,
+  pub city: Option<String>,
+  pub category: Option<String>,
+  pub tags: Vec<String>,
+  pub location_precision: String,
+  pub claimed_by: Option<i32>,
+  pub max_submissions_per_user: i32,
+  pub max_total_submissions: Option<i32>,
+  pub location_tolerance_m: i32,
+  pub is_private: bool,
   pub created_at: Option<DateTime<Utc>>,
   pub distance: Option<f64>,
+  pub rank: Option<f64>,
+  pub highlights: Option<RequestHighlights>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RequestHighlights {
+  pub place_name: Option<String>,
+  pub description: Option<String>,
 }
 
 impl From<Request> for RequestWithDistance {
@@ -38,13 +94,26 @@ impl From<Request> for RequestWithDistance {
       status: req.status,
       place_name: req.place_name,
       description: req.description,
+      deadline_at: req.deadline_at,
+      prefecture: req.prefecture,
+      city: req.city,
+      category: req.category,
+      tags: req.tags,
+      location_precision: req.location_precision,
+      claimed_by: req.claimed_by,
+      max_submissions_per_user: req.max_submissions_per_user,
+      max_total_submissions: req.max_total_submissions,
+      location_tolerance_m: req.location_tolerance_m,
+      is_private: req.is_private,
       created_at: req.created_at,
       distance: None,
+      rank: None,
+      highlights: None,
     }
   }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Validate)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
 pub struct CreateRequestRequest {
   #[validate(range(min = -90.0, max = 90.0, message = "緯度は-90から90の範囲である必要があります"))]
   pub lat: f64,
@@ -54,9 +123,102 @@ pub struct CreateRequestRequest {
   pub place_name: String,
   #[validate(length(min = 1, message = "説明が必要です"))]
   pub description: String,
+  #[validate(custom(function = crate::utils::validate_future_datetime))]
+  pub deadline_at: Option<DateTime<Utc>>,
+  #[validate(custom(function = validate_category))]
+  pub category: Option<String>,
+  #[serde(default)]
+  #[validate(
+    length(max = 10, message = "タグは10個までです"),
+    custom(function = validate_tags)
+  )]
+  pub tags: Vec<String>,
+  /// 未指定の場合は exact
+  #[validate(custom(function = validate_location_precision))]
+  pub location_precision: Option<String>,
+  /// 1人が投稿できる写真の枚数。未指定の場合は1
+  #[validate(range(min = 1, max = 20, message = "1人あたりの投稿数は1から20の範囲である必要があります"))]
+  pub max_submissions_per_user: Option<i32>,
+  /// 全体で受け付ける写真の枚数。未指定の場合は無制限
+  #[validate(range(min = 1, max = 1000, message = "投稿数の上限は1から1000の範囲である必要があります"))]
+  pub max_total_submissions: Option<i32>,
+  /// 投稿写真の位置確認で許容する距離（メートル）。未指定の場合は200
+  #[validate(range(
+    min = 10,
+    max = 5000,
+    message = "位置確認の許容距離は10から5000メートルの範囲である必要があります"
+  ))]
+  pub location_tolerance_m: Option<i32>,
+  /// 投稿写真を投稿者と依頼者にだけ見せる。未指定の場合は false
+  pub is_private: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
+pub struct UpdateRequestRequest {
+  #[validate(range(min = -90.0, max = 90.0, message = "緯度は-90から90の範囲である必要があります"))]
+  pub lat: Option<f64>,
+  #[validate(range(min = -180.0, max = 180.0, message = "経度は-180から180の範囲である必要があります"))]
+  pub lng: Option<f64>,
+  #[validate(length(min = 1, max = 255, message = "場所名は1文字以上255文字以内である必要があります"))]
+  pub place_name: Option<String>,
+  #[validate(length(min = 1, message = "説明が必要です"))]
+  pub description: Option<String>,
+  #[validate(custom(function = validate_category))]
+  pub category: Option<String>,
+  #[validate(
+    length(max = 10, message = "タグは10個までです"),
+    custom(function = validate_tags)
+  )]
+  pub tags: Option<Vec<String>>,
+  #[validate(custom(function = validate_location_precision))]
+  pub location_precision: Option<String>,
+  pub is_private: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RequestFilter {
+  pub include_expired: bool,
+  pub q: Option<String>,
+  pub prefecture: Option<String>,
+  pub city: Option<String>,
+  pub category: Option<String>,
+  pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ExpiredRequest {
+  pub id: i32,
+  pub user_id: i32,
+  pub place_name: String,
+  pub email: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RequestsResponse {
   pub requests: Vec<RequestWithDistance>,
 }
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RequestStatusCounts {
+  pub open: i64,
+  pub in_progress: i64,
+  pub completed: i64,
+  pub expired: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MyRequest {
+  #[serde(flatten)]
+  pub request: Request,
+  pub submission_count: i64,
+  pub latest_submission: Option<Picture>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MyRequestsResponse {
+  pub requests: Vec<MyRequest>,
+  pub status_counts: RequestStatusCounts,
+  pub total: i64,
+  pub page: i64,
+  pub per_page: i64,
+}
@@ -0,0 +1,134 @@
+use sha2::{Digest, Sha256};
+use std::f64::consts::PI;
+use validator::ValidationError;
+
+use super::model::{Request, RequestWithDistance};
+use crate::utils::geo::{haversine_distance, offset_point};
+
+/// 作成者以外に公開する位置の精度（migrations の CHECK 制約と揃える）
+pub const LOCATION_PRECISIONS: &[&str] = &["exact", "100m", "1km"];
+
+pub fn validate_location_precision(precision: &str) -> Result<(), ValidationError> {
+  if !LOCATION_PRECISIONS.contains(&precision) {
+    return Err(ValidationError::new("位置の精度が不正です"));
+  }
+
+  Ok(())
+}
+
+/// 正確な位置のリクエストへの投稿で、依頼者・撮影者以外に返す撮影位置までの距離の単位（メートル）
+pub const PUBLIC_DISTANCE_UNIT_M: f64 = 100.0;
+
+/// 精度ごとのぼかし半径（メートル）。exact の場合は `None`
+pub fn precision_radius_m(precision: &str) -> Option<f64> {
+  match precision {
+    "100m" => Some(100.0),
+    "1km" => Some(1000.0),
+    _ => None,
+  }
+}
+
+/// 正確な位置を見られるのは作成者と引き受けた撮影者だけ
+pub fn can_view_exact_location(owner_id: i32, claimed_by: Option<i32>, viewer_id: Option<i32>) -> bool {
+  viewer_id.is_some_and(|viewer| viewer == owner_id || claimed_by == Some(viewer))
+}
+
+/// 半径 `radius_m` の円内にぼかした位置を返す。
+/// 何度取得しても同じ点になるよう（平均を取って元の位置を推定されないよう）、サーバーの秘密鍵とリクエストIDから決める
+pub fn fuzz_location(secret: &str, request_id: i32, lat: f64, lng: f64, radius_m: f64) -> (f64, f64) {
+  let mut hasher = Sha256::new();
+  hasher.update(secret.as_bytes());
+  hasher.update(b":location:");
+  hasher.update(request_id.to_be_bytes());
+  let hash = hasher.finalize();
+
+  let unit = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap()) as f64 / u32::MAX as f64;
+  let bearing = unit(&hash[0..4]) * 2.0 * PI;
+  // 円内で一様になるよう半径方向は平方根を取る
+  let distance = radius_m * unit(&hash[4..8]).sqrt();
+
+  offset_point(lat, lng, distance, bearing)
+}
+
+/// 距離をぼかし半径の単位に丸める
+pub fn round_distance(distance_m: f64, radius_m: f64) -> f64 {
+  (distance_m / radius_m).round() * radius_m
+}
+
+fn location_secret() -> String {
+  std::env::var("JWT_SECRET").expect("JWT_SECRET environment variable must be set.")
+}
+
+/// 作成者と撮影者以外に見せる位置。exact の場合は元の位置のまま
+pub fn public_location(request: &Request) -> (f64, f64) {
+  match precision_radius_m(&request.location_precision) {
+    Some(radius_m) => fuzz_location(&location_secret(), request.id, request.lat, request.lng, radius_m),
+    None => (request.lat, request.lng),
+  }
+}
+
+/// 閲覧者が正確な位置を見られない場合、位置をぼかす
+pub fn mask_request(request: &mut Request, viewer_id: Option<i32>) {
+  if can_view_exact_location(request.user_id, request.claimed_by, viewer_id) {
+    return;
+  }
+
+  (request.lat, request.lng) = public_location(request);
+}
+
+/// `mask_request` に加え、ユーザー位置 `origin` からの距離もぼかした位置から計算し直して丸める
+pub fn mask_request_with_distance(
+  request: &mut RequestWithDistance,
+  viewer_id: Option<i32>,
+  origin: Option<(f64, f64)>,
+) {
+  let Some(radius_m) = precision_radius_m(&request.location_precision) else {
+    return;
+  };
+  if can_view_exact_location(request.user_id, request.claimed_by, viewer_id) {
+    return;
+  }
+
+  (request.lat, request.lng) = fuzz_location(&location_secret(), request.id, request.lat, request.lng, radius_m);
+  if let (Some((origin_lat, origin_lng)), Some(_)) = (origin, request.distance) {
+    let distance = haversine_distance(origin_lat, origin_lng, request.lat, request.lng);
+    request.distance = Some(round_distance(distance, radius_m));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fuzz_location_is_deterministic_and_within_radius() {
+    let first = fuzz_location("secret", 1, 35.6812, 139.7671, 100.0);
+    let second = fuzz_location("secret", 1, 35.6812, 139.7671, 100.0);
+    assert_eq!(first, second);
+    assert!(haversine_distance(35.6812, 139.7671, first.0, first.1) <= 100.0 + 0.01);
+
+    // 別のリクエストや別の鍵では別の点になる
+    assert_ne!(first, fuzz_location("secret", 2, 35.6812, 139.7671, 100.0));
+    assert_ne!(first, fuzz_location("other", 1, 35.6812, 139.7671, 100.0));
+  }
+
+  #[test]
+  fn test_round_distance() {
+    assert_eq!(round_distance(1234.0, 100.0), 1200.0);
+    assert_eq!(round_distance(1750.0, 1000.0), 2000.0);
+  }
+
+  #[test]
+  fn test_can_view_exact_location() {
+    assert!(can_view_exact_location(1, None, Some(1)));
+    assert!(can_view_exact_location(1, Some(2), Some(2)));
+    assert!(!can_view_exact_location(1, Some(2), Some(3)));
+    assert!(!can_view_exact_location(1, None, None));
+  }
+
+  #[test]
+  fn test_validate_location_precision() {
+    assert!(validate_location_precision("1km").is_ok());
+    assert!(validate_location_precision("10km").is_err());
+  }
+}
@@ -1,26 +1,141 @@
 use sqlx::{Executor, PgPool, Postgres};
 
-use super::model::{Request, RequestWithDistance};
+use super::model::{
+  CreateRequestRequest, ExpiredRequest, Request, RequestFilter, RequestStatusCounts, RequestWithDistance,
+  UpdateRequestRequest,
+};
 
-pub async fn find_all(db: &PgPool) -> Result<Vec<Request>, sqlx::Error> {
-  find_all_with_executor(db).await
+/// トライグラム類似度による検索でヒットとみなす下限値
+const SEARCH_SIMILARITY_THRESHOLD: f32 = 0.3;
+
+/// ILIKE 用に `%` `_` `\` をエスケープして部分一致パターンを作る
+fn like_pattern(q: &str) -> String {
+  let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+  format!("%{}%", escaped)
 }
 
-pub async fn find_all_with_executor<'e, E>(executor: E) -> Result<Vec<Request>, sqlx::Error>
+/// `<%` 演算子がヒットとみなす類似度の下限を、トランザクションの間だけ `SEARCH_SIMILARITY_THRESHOLD` にする
+pub async fn set_search_similarity_threshold_with_executor<'e, E>(executor: E) -> Result<(), sqlx::Error>
 where
   E: Executor<'e, Database = Postgres>,
 {
-  let requests = sqlx::query_as!(
-    Request,
+  sqlx::query_scalar!(
+    r#"
+      SELECT set_config('pg_trgm.word_similarity_threshold', $1::float4::text, true)
+    "#,
+    SEARCH_SIMILARITY_THRESHOLD
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn find_all(db: &PgPool, filter: &RequestFilter) -> Result<Vec<RequestWithDistance>, sqlx::Error> {
+  let mut tx = db.begin().await?;
+  set_search_similarity_threshold_with_executor(&mut *tx).await?;
+  let requests = find_all_with_executor(&mut *tx, filter).await?;
+  tx.commit().await?;
+
+  Ok(requests)
+}
+
+pub async fn find_all_with_executor<'e, E>(
+  executor: E,
+  filter: &RequestFilter,
+) -> Result<Vec<RequestWithDistance>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let pattern = filter.q.as_deref().map(like_pattern);
+
+  // q が指定された場合は部分一致を最優先し、次にトライグラム類似度で順位付けする
+  // `<%` の下限は呼び出し側で `set_search_similarity_threshold_with_executor` により設定しておく
+  let rows = sqlx::query!(
     r#"
-      SELECT id, user_id, lat, lng, status, place_name, description, created_at
+      SELECT
+        id,
+        user_id,
+        lat,
+        lng,
+        status,
+        place_name,
+        description,
+        deadline_at,
+        prefecture,
+        city,
+        category,
+        request_tag_names(id) as "tags!",
+        location_precision,
+        claimed_by,
+        max_submissions_per_user,
+        max_total_submissions,
+        location_tolerance_m,
+        is_private,
+        created_at,
+        CASE WHEN $2::text IS NULL THEN NULL ELSE (
+          GREATEST(word_similarity($2, place_name), word_similarity($2, description))
+          + CASE WHEN place_name ILIKE $3 OR description ILIKE $3 THEN 1 ELSE 0 END
+        )::float8 END as rank
       FROM requests
-      ORDER BY created_at DESC
-    "#
+      WHERE ($1 OR status <> 'expired')
+        AND (
+          $2::text IS NULL
+          OR place_name ILIKE $3
+          OR description ILIKE $3
+          OR $2 <% place_name
+          OR $2 <% description
+        )
+        AND ($4::text IS NULL OR prefecture = $4)
+        AND ($5::text IS NULL OR city = $5)
+        AND ($6::text IS NULL OR category = $6)
+        AND ($7::text IS NULL OR EXISTS (
+          SELECT 1
+          FROM request_tags rt
+          JOIN tags t ON t.id = rt.tag_id
+          WHERE rt.request_id = requests.id AND t.name = $7
+        ))
+      ORDER BY rank DESC NULLS LAST, created_at DESC
+    "#,
+    filter.include_expired,
+    filter.q,
+    pattern,
+    filter.prefecture,
+    filter.city,
+    filter.category,
+    filter.tag
   )
   .fetch_all(executor)
   .await?;
 
+  let requests = rows
+    .into_iter()
+    .map(|row| RequestWithDistance {
+      id: row.id,
+      user_id: row.user_id,
+      lat: row.lat,
+      lng: row.lng,
+      status: row.status,
+      place_name: row.place_name,
+      description: row.description,
+      deadline_at: row.deadline_at,
+      prefecture: row.prefecture,
+      city: row.city,
+      category: row.category,
+      tags: row.tags,
+      location_precision: row.location_precision,
+      claimed_by: row.claimed_by,
+      max_submissions_per_user: row.max_submissions_per_user,
+      max_total_submissions: row.max_total_submissions,
+      location_tolerance_m: row.location_tolerance_m,
+      is_private: row.is_private,
+      created_at: Some(row.created_at),
+      distance: None,
+      rank: row.rank,
+      highlights: None,
+    })
+    .collect();
+
   Ok(requests)
 }
 
@@ -28,19 +143,30 @@ pub async fn find_all_with_distance(
   db: &PgPool,
   user_lat: f64,
   user_lng: f64,
+  filter: &RequestFilter,
 ) -> Result<Vec<RequestWithDistance>, sqlx::Error> {
-  find_all_with_distance_with_executor(db, user_lat, user_lng).await
+  let mut tx = db.begin().await?;
+  set_search_similarity_threshold_with_executor(&mut *tx).await?;
+  let requests = find_all_with_distance_with_executor(&mut *tx, user_lat, user_lng, filter).await?;
+  tx.commit().await?;
+
+  Ok(requests)
 }
 
 pub async fn find_all_with_distance_with_executor<'e, E>(
   executor: E,
   user_lat: f64,
   user_lng: f64,
+  filter: &RequestFilter,
 ) -> Result<Vec<RequestWithDistance>, sqlx::Error>
 where
   E: Executor<'e, Database = Postgres>,
 {
+  let pattern = filter.q.as_deref().map(like_pattern);
+
   // ハヴァサイン公式をSQLで実装
+  // q が指定された場合は関連度順、同じ関連度の中では距離順に並べる
+  // `<%` の下限は呼び出し側で `set_search_similarity_threshold_with_executor` により設定しておく
   let rows = sqlx::query!(
     r#"
       SELECT
@@ -51,19 +177,58 @@ where
         status,
         place_name,
         description,
+        deadline_at,
+        prefecture,
+        city,
+        category,
+        request_tag_names(id) as "tags!",
+        location_precision,
+        claimed_by,
+        max_submissions_per_user,
+        max_total_submissions,
+        location_tolerance_m,
+        is_private,
         created_at,
         (
-          6371000 * acos(
+          6371000 * acos(LEAST(1.0,
             cos(radians($1)) * cos(radians(lat)) *
             cos(radians(lng) - radians($2)) +
             sin(radians($1)) * sin(radians(lat))
-          )
-        ) as distance
+          ))
+        ) as distance,
+        CASE WHEN $4::text IS NULL THEN NULL ELSE (
+          GREATEST(word_similarity($4, place_name), word_similarity($4, description))
+          + CASE WHEN place_name ILIKE $5 OR description ILIKE $5 THEN 1 ELSE 0 END
+        )::float8 END as rank
       FROM requests
-      ORDER BY distance ASC
+      WHERE ($3 OR status <> 'expired')
+        AND (
+          $4::text IS NULL
+          OR place_name ILIKE $5
+          OR description ILIKE $5
+          OR $4 <% place_name
+          OR $4 <% description
+        )
+        AND ($6::text IS NULL OR prefecture = $6)
+        AND ($7::text IS NULL OR city = $7)
+        AND ($8::text IS NULL OR category = $8)
+        AND ($9::text IS NULL OR EXISTS (
+          SELECT 1
+          FROM request_tags rt
+          JOIN tags t ON t.id = rt.tag_id
+          WHERE rt.request_id = requests.id AND t.name = $9
+        ))
+      ORDER BY rank DESC NULLS LAST, distance ASC
     "#,
     user_lat,
-    user_lng
+    user_lng,
+    filter.include_expired,
+    filter.q,
+    pattern,
+    filter.prefecture,
+    filter.city,
+    filter.category,
+    filter.tag
   )
   .fetch_all(executor)
   .await?;
@@ -78,32 +243,162 @@ where
       status: row.status,
       place_name: row.place_name,
       description: row.description,
+      deadline_at: row.deadline_at,
+      prefecture: row.prefecture,
+      city: row.city,
+      category: row.category,
+      tags: row.tags,
+      location_precision: row.location_precision,
+      claimed_by: row.claimed_by,
+      max_submissions_per_user: row.max_submissions_per_user,
+      max_total_submissions: row.max_total_submissions,
+      location_tolerance_m: row.location_tolerance_m,
+      is_private: row.is_private,
       created_at: Some(row.created_at),
       distance: row.distance,
+      rank: row.rank,
+      highlights: None,
     })
     .collect();
 
   Ok(requests)
 }
 
-pub async fn create(
+/// 説明文が似ていて `radius_m` 以内にある募集中のリクエストを、類似度の高い順に重複候補として返す。
+/// 正確な距離で絞り込むと位置を探られるため、位置をぼかしたリクエストは `viewer_id` が正確な位置を見られるものだけを対象にする
+pub async fn find_possible_duplicates(
   db: &PgPool,
-  user_id: i32,
+  viewer_id: i32,
   lat: f64,
   lng: f64,
-  place_name: String,
-  description: String,
-) -> Result<Request, sqlx::Error> {
-  create_with_executor(db, user_id, lat, lng, place_name, description).await
+  description: &str,
+  radius_m: f64,
+  limit: i64,
+) -> Result<Vec<RequestWithDistance>, sqlx::Error> {
+  let mut tx = db.begin().await?;
+  set_search_similarity_threshold_with_executor(&mut *tx).await?;
+  let requests =
+    find_possible_duplicates_with_executor(&mut *tx, viewer_id, lat, lng, description, radius_m, limit).await?;
+  tx.commit().await?;
+
+  Ok(requests)
 }
 
-pub async fn create_with_executor<'e, E>(
+pub async fn find_possible_duplicates_with_executor<'e, E>(
   executor: E,
-  user_id: i32,
+  viewer_id: i32,
   lat: f64,
   lng: f64,
-  place_name: String,
-  description: String,
+  description: &str,
+  radius_m: f64,
+  limit: i64,
+) -> Result<Vec<RequestWithDistance>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let pattern = like_pattern(description);
+
+  // `<%` の下限は呼び出し側で `set_search_similarity_threshold_with_executor` により設定しておく
+  let rows = sqlx::query!(
+    r#"
+      SELECT
+        id,
+        user_id,
+        lat,
+        lng,
+        status,
+        place_name,
+        description,
+        deadline_at,
+        prefecture,
+        city,
+        category,
+        request_tag_names(id) as "tags!",
+        location_precision,
+        claimed_by,
+        max_submissions_per_user,
+        max_total_submissions,
+        location_tolerance_m,
+        is_private,
+        created_at,
+        (
+          6371000 * acos(LEAST(1.0,
+            cos(radians($1)) * cos(radians(lat)) *
+            cos(radians(lng) - radians($2)) +
+            sin(radians($1)) * sin(radians(lat))
+          ))
+        ) as distance,
+        (
+          GREATEST(word_similarity($3, place_name), word_similarity($3, description))
+          + CASE WHEN place_name ILIKE $4 OR description ILIKE $4 THEN 1 ELSE 0 END
+        )::float8 as rank
+      FROM requests
+      WHERE status = 'open'
+        AND (location_precision = 'exact' OR user_id = $5 OR claimed_by = $5)
+        AND (
+          place_name ILIKE $4
+          OR description ILIKE $4
+          OR $3 <% place_name
+          OR $3 <% description
+        )
+        AND 6371000 * acos(LEAST(1.0,
+          cos(radians($1)) * cos(radians(lat)) *
+          cos(radians(lng) - radians($2)) +
+          sin(radians($1)) * sin(radians(lat))
+        )) <= $6
+      ORDER BY rank DESC, distance ASC
+      LIMIT $7
+    "#,
+    lat,
+    lng,
+    description,
+    pattern,
+    viewer_id,
+    radius_m,
+    limit
+  )
+  .fetch_all(executor)
+  .await?;
+
+  let requests = rows
+    .into_iter()
+    .map(|row| RequestWithDistance {
+      id: row.id,
+      user_id: row.user_id,
+      lat: row.lat,
+      lng: row.lng,
+      status: row.status,
+      place_name: row.place_name,
+      description: row.description,
+      deadline_at: row.deadline_at,
+      prefecture: row.prefecture,
+      city: row.city,
+      category: row.category,
+      tags: row.tags,
+      location_precision: row.location_precision,
+      claimed_by: row.claimed_by,
+      max_submissions_per_user: row.max_submissions_per_user,
+      max_total_submissions: row.max_total_submissions,
+      location_tolerance_m: row.location_tolerance_m,
+      is_private: row.is_private,
+      created_at: Some(row.created_at),
+      distance: row.distance,
+      rank: row.rank,
+      highlights: None,
+    })
+    .collect();
+
+  Ok(requests)
+}
+
+pub async fn create(db: &PgPool, user_id: i32, req: &CreateRequestRequest) -> Result<Request, sqlx::Error> {
+  create_with_executor(db, user_id, req).await
+}
+
+pub async fn create_with_executor<'e, E>(
+  executor: E,
+  user_id: i32,
+  req: &CreateRequestRequest,
 ) -> Result<Request, sqlx::Error>
 where
   E: Executor<'e, Database = Postgres>,
@@ -111,15 +406,30 @@ where
   let request = sqlx::query_as!(
     Request,
     r#"
-      INSERT INTO requests (user_id, lat, lng, place_name, description)
-      VALUES ($1, $2, $3, $4, $5)
-      RETURNING id, user_id, lat, lng, status, place_name, description, created_at
+      INSERT INTO requests (
+        user_id, lat, lng, place_name, description, deadline_at, category, location_precision,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private
+      )
+      VALUES (
+        $1, $2, $3, $4, $5, $6, $7, COALESCE($8, 'exact'), COALESCE($9, 1), $10, COALESCE($11, 200),
+        COALESCE($12, false)
+      )
+      RETURNING id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
     "#,
     user_id,
-    lat,
-    lng,
-    place_name,
-    description
+    req.lat,
+    req.lng,
+    req.place_name,
+    req.description,
+    req.deadline_at,
+    req.category,
+    req.location_precision,
+    req.max_submissions_per_user,
+    req.max_total_submissions,
+    req.location_tolerance_m,
+    req.is_private
   )
   .fetch_one(executor)
   .await?;
@@ -138,9 +448,58 @@ where
   let request = sqlx::query_as!(
     Request,
     r#"
-      SELECT id, user_id, lat, lng, status, place_name, description, created_at
+      SELECT id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+      FROM requests
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(request)
+}
+
+pub async fn find_by_ids(db: &PgPool, ids: &[i32]) -> Result<Vec<Request>, sqlx::Error> {
+  find_by_ids_with_executor(db, ids).await
+}
+
+pub async fn find_by_ids_with_executor<'e, E>(executor: E, ids: &[i32]) -> Result<Vec<Request>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let requests = sqlx::query_as!(
+    Request,
+    r#"
+      SELECT id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+      FROM requests
+      WHERE id = ANY($1)
+    "#,
+    ids
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(requests)
+}
+
+pub async fn find_by_id_for_update_with_executor<'e, E>(executor: E, id: i32) -> Result<Option<Request>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let request = sqlx::query_as!(
+    Request,
+    r#"
+      SELECT id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
       FROM requests
       WHERE id = $1
+      FOR UPDATE
     "#,
     id
   )
@@ -150,9 +509,248 @@ where
   Ok(request)
 }
 
+pub async fn update(db: &PgPool, id: i32, req: &UpdateRequestRequest) -> Result<Request, sqlx::Error> {
+  update_with_executor(db, id, req).await
+}
+
+/// 指定されたフィールドだけを更新する（タグは別途 tag::repository で置き換える）
+pub async fn update_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+  req: &UpdateRequestRequest,
+) -> Result<Request, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let request = sqlx::query_as!(
+    Request,
+    r#"
+      UPDATE requests
+      SET
+        lat = COALESCE($2, lat),
+        lng = COALESCE($3, lng),
+        place_name = COALESCE($4, place_name),
+        description = COALESCE($5, description),
+        category = COALESCE($6, category),
+        location_precision = COALESCE($7, location_precision),
+        is_private = COALESCE($8, is_private)
+      WHERE id = $1
+      RETURNING id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+    "#,
+    id,
+    req.lat,
+    req.lng,
+    req.place_name,
+    req.description,
+    req.category,
+    req.location_precision,
+    req.is_private
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(request)
+}
+
+/// 引き受けた撮影者を設定・解除し、ステータスを合わせて更新する
+pub async fn set_claim(db: &PgPool, id: i32, claimed_by: Option<i32>, status: &str) -> Result<Request, sqlx::Error> {
+  set_claim_with_executor(db, id, claimed_by, status).await
+}
+
+pub async fn set_claim_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+  claimed_by: Option<i32>,
+  status: &str,
+) -> Result<Request, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let request = sqlx::query_as!(
+    Request,
+    r#"
+      UPDATE requests
+      SET claimed_by = $2, status = $3
+      WHERE id = $1
+      RETURNING id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+    "#,
+    id,
+    claimed_by,
+    status
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(request)
+}
+
+pub async fn update_location(
+  db: &PgPool,
+  id: i32,
+  prefecture: Option<&str>,
+  city: Option<&str>,
+) -> Result<Request, sqlx::Error> {
+  update_location_with_executor(db, id, prefecture, city).await
+}
+
+pub async fn update_location_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+  prefecture: Option<&str>,
+  city: Option<&str>,
+) -> Result<Request, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let request = sqlx::query_as!(
+    Request,
+    r#"
+      UPDATE requests
+      SET prefecture = $2, city = $3
+      WHERE id = $1
+      RETURNING id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+    "#,
+    id,
+    prefecture,
+    city
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(request)
+}
+
+pub async fn delete(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+  delete_with_executor(db, id).await
+}
+
+pub async fn delete_with_executor<'e, E>(executor: E, id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      DELETE FROM requests
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn find_by_user_id(db: &PgPool, user_id: i32, limit: i64, offset: i64) -> Result<Vec<Request>, sqlx::Error> {
+  find_by_user_id_with_executor(db, user_id, limit, offset).await
+}
+
+pub async fn find_by_user_id_with_executor<'e, E>(
+  executor: E,
+  user_id: i32,
+  limit: i64,
+  offset: i64,
+) -> Result<Vec<Request>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let requests = sqlx::query_as!(
+    Request,
+    r#"
+      SELECT id, user_id, lat, lng, status, place_name, description, deadline_at, prefecture, city, category,
+        request_tag_names(id) as "tags!", location_precision, claimed_by,
+        max_submissions_per_user, max_total_submissions, location_tolerance_m, is_private, created_at
+      FROM requests
+      WHERE user_id = $1
+      ORDER BY created_at DESC, id DESC
+      LIMIT $2 OFFSET $3
+    "#,
+    user_id,
+    limit,
+    offset
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(requests)
+}
+
+pub async fn count_by_status_for_user(db: &PgPool, user_id: i32) -> Result<RequestStatusCounts, sqlx::Error> {
+  count_by_status_for_user_with_executor(db, user_id).await
+}
+
+pub async fn count_by_status_for_user_with_executor<'e, E>(
+  executor: E,
+  user_id: i32,
+) -> Result<RequestStatusCounts, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let rows = sqlx::query!(
+    r#"
+      SELECT status, COUNT(*) as "count!"
+      FROM requests
+      WHERE user_id = $1
+      GROUP BY status
+    "#,
+    user_id
+  )
+  .fetch_all(executor)
+  .await?;
+
+  let mut counts = RequestStatusCounts::default();
+  for row in rows {
+    match row.status.as_str() {
+      "open" => counts.open = row.count,
+      "in-progress" => counts.in_progress = row.count,
+      "completed" => counts.completed = row.count,
+      "expired" => counts.expired = row.count,
+      _ => {}
+    }
+  }
+
+  Ok(counts)
+}
+
+pub async fn expire_overdue(db: &PgPool) -> Result<Vec<ExpiredRequest>, sqlx::Error> {
+  expire_overdue_with_executor(db).await
+}
+
+pub async fn expire_overdue_with_executor<'e, E>(executor: E) -> Result<Vec<ExpiredRequest>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let expired = sqlx::query_as!(
+    ExpiredRequest,
+    r#"
+      WITH expired AS (
+        UPDATE requests
+        SET status = 'expired'
+        WHERE deadline_at < NOW()
+          AND status IN ('open', 'in-progress')
+        RETURNING id, user_id, place_name
+      )
+      SELECT expired.id, expired.user_id, expired.place_name, users.email
+      FROM expired
+      JOIN users ON users.id = expired.user_id
+    "#
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(expired)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::test_support::request_payload;
 
   #[sqlx::test(migrations = "./migrations")]
   async fn create_and_find_request(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
@@ -162,10 +760,7 @@ mod tests {
     let created = create(
       &pool,
       user.id,
-      35.6812,
-      139.7671,
-      "東京タワー".to_string(),
-      "写真をお願いします".to_string(),
+      &request_payload(35.6812, 139.7671, "東京タワー", "写真をお願いします"),
     )
     .await?;
 
@@ -198,37 +793,13 @@ mod tests {
     let user =
       crate::domains::user::model::User::create(&pool, "find-all@example.com", "Find All", "password123").await?;
 
-    let req1 = create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "説明1".to_string(),
-    )
-    .await?;
+    let req1 = create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "説明1")).await?;
     tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-    let req2 = create(
-      &pool,
-      user.id,
-      34.6937,
-      135.5023,
-      "大阪".to_string(),
-      "説明2".to_string(),
-    )
-    .await?;
+    let req2 = create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪", "説明2")).await?;
     tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
-    let req3 = create(
-      &pool,
-      user.id,
-      43.0642,
-      141.3469,
-      "札幌".to_string(),
-      "説明3".to_string(),
-    )
-    .await?;
+    let req3 = create(&pool, user.id, &request_payload(43.0642, 141.3469, "札幌", "説明3")).await?;
 
-    let requests = find_all(&pool).await?;
+    let requests = find_all(&pool, &RequestFilter::default()).await?;
 
     assert!(requests.len() >= 3);
 
@@ -245,23 +816,15 @@ mod tests {
     let user =
       crate::domains::user::model::User::create(&pool, "coords-test@example.com", "Coords Test", "password123").await?;
 
-    let req1 = create(
-      &pool,
-      user.id,
-      -90.0,
-      -180.0,
-      "南極点".to_string(),
-      "最南端".to_string(),
-    )
-    .await?;
+    let req1 = create(&pool, user.id, &request_payload(-90.0, -180.0, "南極点", "最南端")).await?;
     assert_eq!(req1.lat, -90.0);
     assert_eq!(req1.lng, -180.0);
 
-    let req2 = create(&pool, user.id, 90.0, 180.0, "北極点".to_string(), "最北端".to_string()).await?;
+    let req2 = create(&pool, user.id, &request_payload(90.0, 180.0, "北極点", "最北端")).await?;
     assert_eq!(req2.lat, 90.0);
     assert_eq!(req2.lng, 180.0);
 
-    let req3 = create(&pool, user.id, 0.0, 0.0, "赤道".to_string(), "0度0度".to_string()).await?;
+    let req3 = create(&pool, user.id, &request_payload(0.0, 0.0, "赤道", "0度0度")).await?;
     assert_eq!(req3.lat, 0.0);
     assert_eq!(req3.lng, 0.0);
 
@@ -277,10 +840,7 @@ mod tests {
     let created = create(
       &pool,
       user.id,
-      35.6812,
-      139.7671,
-      long_name.clone(),
-      "テスト".to_string(),
+      &request_payload(35.6812, 139.7671, &long_name, "テスト"),
     )
     .await?;
     assert_eq!(created.place_name, long_name);
@@ -295,38 +855,14 @@ mod tests {
         .await?;
 
     // 札幌（最も遠い）
-    create(
-      &pool,
-      user.id,
-      43.0642,
-      141.3469,
-      "札幌".to_string(),
-      "説明1".to_string(),
-    )
-    .await?;
+    create(&pool, user.id, &request_payload(43.0642, 141.3469, "札幌", "説明1")).await?;
     // 東京（最も近い）
-    create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "説明2".to_string(),
-    )
-    .await?;
+    create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "説明2")).await?;
     // 大阪（中間）
-    create(
-      &pool,
-      user.id,
-      34.6937,
-      135.5023,
-      "大阪".to_string(),
-      "説明3".to_string(),
-    )
-    .await?;
+    create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪", "説明3")).await?;
 
     // 東京タワーからの距離で取得
-    let requests = find_all_with_distance(&pool, 35.6812, 139.7671).await?;
+    let requests = find_all_with_distance(&pool, 35.6812, 139.7671, &RequestFilter::default()).await?;
 
     assert!(requests.len() >= 3);
 
@@ -352,4 +888,238 @@ mod tests {
 
     Ok(())
   }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn find_all_with_distance_at_same_point(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "same-point@example.com", "Same Point", "password123").await?;
+
+    // この緯度では同じ地点どうしでも丸め誤差で acos の引数が 1 をわずかに超える
+    create(&pool, user.id, &request_payload(35.0023, 135.7681, "京都", "説明")).await?;
+
+    let requests = find_all_with_distance(&pool, 35.0023, 135.7681, &RequestFilter::default()).await?;
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].distance, Some(0.0));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_only_overwrites_given_fields(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "update-repo@example.com", "Update Repo", "password123").await?;
+
+    let created = create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "説明")).await?;
+
+    let req = UpdateRequestRequest {
+      description: Some("新しい説明".to_string()),
+      ..Default::default()
+    };
+    let updated = update(&pool, created.id, &req).await?;
+    assert_eq!(updated.description, "新しい説明");
+    assert_eq!(updated.place_name, "東京");
+    assert_eq!(updated.lat, 35.6812);
+    assert_eq!(updated.lng, 139.7671);
+
+    let req = UpdateRequestRequest {
+      lat: Some(34.6937),
+      lng: Some(135.5023),
+      place_name: Some("大阪".to_string()),
+      category: Some("landmark".to_string()),
+      ..Default::default()
+    };
+    let moved = update(&pool, created.id, &req).await?;
+    assert_eq!(moved.lat, 34.6937);
+    assert_eq!(moved.lng, 135.5023);
+    assert_eq!(moved.place_name, "大阪");
+    assert_eq!(moved.description, "新しい説明");
+    assert_eq!(moved.category.as_deref(), Some("landmark"));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_removes_request(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "delete-repo@example.com", "Delete Repo", "password123").await?;
+
+    let created = create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "説明")).await?;
+
+    delete(&pool, created.id).await?;
+
+    let found = find_by_id(&pool, created.id).await?;
+    assert!(found.is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn expire_overdue_only_touches_past_deadlines(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "expire-repo@example.com", "Expire Repo", "password123").await?;
+
+    let overdue = create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "期限切れ")).await?;
+    let upcoming = create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪", "期限前")).await?;
+    let completed = create(&pool, user.id, &request_payload(43.0642, 141.3469, "札幌", "完了済み")).await?;
+    sqlx::query!(
+      "UPDATE requests SET deadline_at = NOW() - INTERVAL '1 hour' WHERE id = ANY($1)",
+      &[overdue.id, completed.id]
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query!(
+      "UPDATE requests SET deadline_at = NOW() + INTERVAL '1 hour' WHERE id = $1",
+      upcoming.id
+    )
+    .execute(&pool)
+    .await?;
+    sqlx::query!("UPDATE requests SET status = 'completed' WHERE id = $1", completed.id)
+      .execute(&pool)
+      .await?;
+
+    let expired = expire_overdue(&pool).await?;
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].id, overdue.id);
+    assert_eq!(expired[0].email, "expire-repo@example.com");
+
+    assert_eq!(find_by_id(&pool, overdue.id).await?.unwrap().status, "expired");
+    assert_eq!(find_by_id(&pool, upcoming.id).await?.unwrap().status, "open");
+    assert_eq!(find_by_id(&pool, completed.id).await?.unwrap().status, "completed");
+
+    let visible = find_all(&pool, &RequestFilter::default()).await?;
+    assert!(visible.iter().all(|r| r.id != overdue.id));
+
+    let all = find_all(
+      &pool,
+      &RequestFilter {
+        include_expired: true,
+        ..Default::default()
+      },
+    )
+    .await?;
+    assert!(all.iter().any(|r| r.id == overdue.id));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn search_matches_japanese_substrings_and_ranks_them_first(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "search-repo@example.com", "Search Repo", "password123").await?;
+
+    let skytree = create(
+      &pool,
+      user.id,
+      &request_payload(35.7101, 139.8107, "東京スカイツリー", "展望台からの夜景"),
+    )
+    .await?;
+    let shibuya = create(
+      &pool,
+      user.id,
+      &request_payload(35.6595, 139.7005, "渋谷駅", "スクランブル交差点を上から"),
+    )
+    .await?;
+    create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪城", "天守閣")).await?;
+
+    let filter = RequestFilter {
+      q: Some("スカイツリー".to_string()),
+      ..Default::default()
+    };
+    let results = find_all(&pool, &filter).await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, skytree.id);
+    assert!(results[0].rank.unwrap() >= 1.0);
+
+    let filter = RequestFilter {
+      q: Some("渋谷".to_string()),
+      ..Default::default()
+    };
+    let results = find_all_with_distance(&pool, 35.6812, 139.7671, &filter).await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, shibuya.id);
+    assert!(results[0].distance.is_some());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn search_tolerates_typos_with_trigram_similarity(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "fuzzy-repo@example.com", "Fuzzy Repo", "password123").await?;
+
+    let tower = create(
+      &pool,
+      user.id,
+      &request_payload(35.6586, 139.7454, "Tokyo Tower", "night view"),
+    )
+    .await?;
+    create(
+      &pool,
+      user.id,
+      &request_payload(34.6937, 135.5023, "Osaka Castle", "cherry blossoms"),
+    )
+    .await?;
+
+    let filter = RequestFilter {
+      q: Some("tokyo towr".to_string()),
+      ..Default::default()
+    };
+    let results = find_all(&pool, &filter).await?;
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, tower.id);
+    assert!(results[0].rank.unwrap() < 1.0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn find_possible_duplicates_within_radius_and_hides_fuzzed_requests(
+    pool: sqlx::PgPool,
+  ) -> Result<(), sqlx::Error> {
+    let owner =
+      crate::domains::user::model::User::create(&pool, "dup-repo-owner@example.com", "Owner", "password123").await?;
+    let other =
+      crate::domains::user::model::User::create(&pool, "dup-repo-other@example.com", "Other", "password123").await?;
+
+    let near = create(
+      &pool,
+      owner.id,
+      &request_payload(35.6586, 139.7454, "東京タワー", "Tokyo Tower at night"),
+    )
+    .await?;
+    // 300m ほど離れている
+    create(
+      &pool,
+      owner.id,
+      &request_payload(35.6613, 139.7454, "東京タワー", "Tokyo Tower at night"),
+    )
+    .await?;
+    let fuzzed = create(
+      &pool,
+      owner.id,
+      &CreateRequestRequest {
+        location_precision: Some("1km".to_string()),
+        ..request_payload(35.6587, 139.7455, "東京タワー", "Tokyo Tower at night")
+      },
+    )
+    .await?;
+
+    // pg_trgm の既定の下限（0.6）では一致しない程度の誤字も拾う
+    let ids = |requests: Vec<RequestWithDistance>| requests.into_iter().map(|r| r.id).collect::<Vec<_>>();
+    let found = find_possible_duplicates(&pool, other.id, 35.6586, 139.7454, "Tokio Towre at nite", 200.0, 5).await?;
+    assert_eq!(ids(found), vec![near.id]);
+
+    // 位置をぼかしたリクエストは正確な位置を見られる作成者にだけ候補として返す
+    let mut found =
+      ids(find_possible_duplicates(&pool, owner.id, 35.6586, 139.7454, "Tokio Towre at nite", 200.0, 5).await?);
+    found.sort();
+    assert_eq!(found, vec![near.id, fuzzed.id]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn like_pattern_escapes_wildcards() {
+    assert_eq!(like_pattern("100%_off\\"), "%100\\%\\_off\\\\%");
+  }
 }
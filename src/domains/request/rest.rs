@@ -2,38 +2,114 @@ use axum::{
   extract::{Json, Path, Query, State},
   http::HeaderMap,
   response::Json as JsonResponse,
-  routing::{get, post},
+  routing::{get, post, put},
   Router,
 };
 use serde::Deserialize;
 use validator::Validate;
 
-use super::model::{CreateRequestRequest, Request, RequestsResponse};
+use super::model::{
+  validate_category, CreateRequestRequest, MyRequestsResponse, Request, RequestFilter, RequestsResponse,
+  UpdateRequestRequest,
+};
 use crate::{
-  middleware::auth::auth_middleware,
+  domains::tag::model::normalize_tag,
+  middleware::auth::{auth_middleware, optional_auth_middleware},
   state::{AppState, SharedAppState},
+  utils::pagination::{Page, PageQuery},
   AppError,
 };
 
+const MAX_SEARCH_QUERY_CHARS: usize = 100;
+
 #[derive(Debug, Deserialize)]
 pub struct GetRequestsQuery {
   pub lat: Option<f64>,
   pub lng: Option<f64>,
+  pub include_expired: Option<bool>,
+  pub q: Option<String>,
+  pub prefecture: Option<String>,
+  pub city: Option<String>,
+  pub category: Option<String>,
+  pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequestQuery {
+  /// 重複候補があっても作成する
+  pub force: Option<bool>,
 }
 
 pub fn request_routes() -> Router<SharedAppState> {
   Router::new()
     .route("/requests", get(get_requests_handler))
     .route("/requests", post(create_request_handler))
-    .route("/requests/{request_id}", get(get_request_by_id_handler))
+    .route("/users/me/requests", get(get_my_requests_handler))
+    .route(
+      "/requests/{request_id}",
+      get(get_request_by_id_handler)
+        .patch(update_request_handler)
+        .delete(delete_request_handler),
+    )
+    .route(
+      "/requests/{request_id}/claim",
+      put(claim_request_handler).delete(unclaim_request_handler),
+    )
 }
 
 pub async fn get_requests_handler(
   State(state): State<SharedAppState>,
+  headers: HeaderMap,
   Query(query): Query<GetRequestsQuery>,
 ) -> Result<JsonResponse<RequestsResponse>, AppError> {
+  let q = non_empty(query.q);
+  if q.as_ref().is_some_and(|q| q.chars().count() > MAX_SEARCH_QUERY_CHARS) {
+    return Err(AppError::bad_request(format!(
+      "Search query must be at most {} characters",
+      MAX_SEARCH_QUERY_CHARS
+    )));
+  }
+
+  let category = non_empty(query.category);
+  if let Some(category) = &category {
+    validate_category(category).map_err(|_| AppError::bad_request(format!("Unknown category: {}", category)))?;
+  }
+
+  let filter = RequestFilter {
+    include_expired: query.include_expired.unwrap_or(false),
+    q,
+    prefecture: non_empty(query.prefecture),
+    city: non_empty(query.city),
+    category,
+    tag: query.tag.as_deref().and_then(normalize_tag),
+  };
+
+  // ログインしていれば、自分のリクエストと引き受けたリクエストは正確な位置で返す
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_requests(query.lat, query.lng, filter, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+  value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+pub async fn get_my_requests_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Query(query): Query<PageQuery>,
+) -> Result<JsonResponse<MyRequestsResponse>, AppError> {
+  let page = Page::try_from(query).map_err(AppError::bad_request)?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
   state
-    .get_requests(query.lat, query.lng)
+    .get_my_requests(user_id, page)
     .await
     .map(JsonResponse)
     .map_err(Into::into)
@@ -42,6 +118,7 @@ pub async fn get_requests_handler(
 pub async fn create_request_handler(
   State(state): State<SharedAppState>,
   headers: HeaderMap,
+  Query(query): Query<CreateRequestQuery>,
   Json(payload): Json<CreateRequestRequest>,
 ) -> Result<JsonResponse<Request>, AppError> {
   payload
@@ -52,7 +129,7 @@ pub async fn create_request_handler(
   let user_id = claims.user_id;
 
   state
-    .create_request(user_id, payload)
+    .create_request(user_id, payload, query.force.unwrap_or(false))
     .await
     .map(JsonResponse)
     .map_err(Into::into)
@@ -60,36 +137,96 @@ pub async fn create_request_handler(
 
 pub async fn get_request_by_id_handler(
   State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+) -> Result<JsonResponse<Request>, AppError> {
+  let viewer_id = optional_auth_middleware(headers).await?.map(|claims| claims.user_id);
+
+  state
+    .get_request_by_id(request_id, viewer_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn claim_request_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+) -> Result<JsonResponse<Request>, AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .claim_request(request_id, user_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn unclaim_request_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
   Path(request_id): Path<i32>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.unclaim_request(request_id, user_id).await?;
+
+  Ok(())
+}
+
+pub async fn update_request_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+  Json(payload): Json<UpdateRequestRequest>,
 ) -> Result<JsonResponse<Request>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
   state
-    .get_request_by_id(request_id)
+    .update_request(request_id, user_id, payload)
     .await
     .map(JsonResponse)
     .map_err(Into::into)
 }
 
+pub async fn delete_request_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(request_id): Path<i32>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.delete_request(request_id, user_id).await?;
+
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-  use super::super::model::CreateRequestRequest;
-  use crate::test_support::{app_with_pool, get, post_json};
+  use super::super::model::{CreateRequestRequest, UpdateRequestRequest};
+  use crate::test_support::{
+    app_with_pool, delete_with_auth, get, get_with_auth, login_verified_user, patch_json_with_auth, post_json,
+    post_json_with_auth, request_payload, send_with_auth,
+  };
   use axum::http::StatusCode;
 
   #[sqlx::test(migrations = "./migrations")]
+  #[allow(clippy::len_zero)]
   async fn get_requests_success(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
     let app = app_with_pool(pool.clone()).await;
 
     let user =
       crate::domains::user::model::User::create(&pool, "get-req@example.com", "Get Req", "password123").await?;
-    super::super::repository::create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "テスト".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
 
     let (status, body) = get(app, "/api/v1/requests").await;
     assert_eq!(status, StatusCode::OK);
@@ -109,6 +246,7 @@ mod tests {
       lng: 139.7671,
       place_name: "東京".to_string(),
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -154,6 +292,7 @@ mod tests {
       lng: 139.7671,
       place_name: "東京".to_string(),
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -208,6 +347,7 @@ mod tests {
       lng: 139.7671,
       place_name: "東京".to_string(),
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -254,6 +394,7 @@ mod tests {
       lng: 181.0, // Invalid: > 180
       place_name: "東京".to_string(),
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -300,6 +441,7 @@ mod tests {
       lng: 139.7671,
       place_name: "".to_string(), // Invalid: empty
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -346,6 +488,7 @@ mod tests {
       lng: 139.7671,
       place_name: "東京".to_string(),
       description: "".to_string(), // Invalid: empty
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -392,6 +535,7 @@ mod tests {
       lng: 139.7671,
       place_name: "a".repeat(256), // Invalid: > 255
       description: "テスト説明".to_string(),
+      ..Default::default()
     };
 
     let request = axum::http::Request::builder()
@@ -409,43 +553,429 @@ mod tests {
   }
 
   #[sqlx::test(migrations = "./migrations")]
-  async fn get_requests_with_distance(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+  async fn create_request_deadline_in_past(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user =
+      crate::domains::user::model::User::create(&pool, "past-deadline@example.com", "Past Deadline", "password123")
+        .await?;
+    let token = login_verified_user(app.clone(), &pool, "past-deadline@example.com").await?;
+
+    let payload = CreateRequestRequest {
+      deadline_at: Some(chrono::Utc::now() - chrono::Duration::hours(1)), // Invalid: past
+      ..request_payload(35.6812, 139.7671, "東京", "テスト説明")
+    };
+
+    let request = axum::http::Request::builder()
+      .method("POST")
+      .uri("/api/v1/requests")
+      .header("authorization", format!("Bearer {}", token))
+      .header("content-type", "application/json")
+      .body(axum::body::Body::from(serde_json::to_string(&payload).unwrap()))
+      .unwrap();
+
+    let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_requests_excludes_expired_by_default(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
     let app = app_with_pool(pool.clone()).await;
 
     let user =
-      crate::domains::user::model::User::create(&pool, "distance-test@example.com", "Distance Test", "password123")
+      crate::domains::user::model::User::create(&pool, "expired-list@example.com", "Expired List", "password123")
         .await?;
+    let open =
+      super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "募集中")).await?;
+    let expired =
+      super::super::repository::create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪", "期限切れ")).await?;
+    sqlx::query!("UPDATE requests SET status = 'expired' WHERE id = $1", expired.id)
+      .execute(&pool)
+      .await?;
+
+    let (status, body) = get(app.clone(), "/api/v1/requests").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert!(response.requests.iter().any(|r| r.id == open.id));
+    assert!(response.requests.iter().all(|r| r.id != expired.id));
 
-    // 東京タワー周辺のリクエスト
-    super::super::repository::create(
+    let (status, body) = get(app, "/api/v1/requests?include_expired=true&lat=35.6812&lng=139.7671").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert!(response.requests.iter().any(|r| r.id == expired.id));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_requests_search_with_highlights(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user = crate::domains::user::model::User::create(&pool, "search@example.com", "Search", "password123").await?;
+    let shibuya = super::super::repository::create(
       &pool,
       user.id,
-      35.6812,
-      139.7671,
-      "東京タワー".to_string(),
-      "写真1".to_string(),
+      &request_payload(35.6595, 139.7005, "渋谷駅", "渋谷のスクランブル交差点"),
     )
     .await?;
-    // 大阪城周辺のリクエスト
     super::super::repository::create(
       &pool,
       user.id,
-      34.6937,
-      135.5023,
-      "大阪城".to_string(),
-      "写真2".to_string(),
+      &request_payload(35.6812, 139.7671, "東京駅", "丸の内駅舎"),
     )
     .await?;
-    // 札幌周辺のリクエスト
+
+    let uri = format!("/api/v1/requests?q={}", "%E6%B8%8B%E8%B0%B7"); // 渋谷
+    let (status, body) = get(app, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests.len(), 1);
+    assert_eq!(response.requests[0].id, shibuya.id);
+
+    let highlights = response.requests[0].highlights.as_ref().expect("highlights");
+    assert_eq!(highlights.place_name.as_deref(), Some("<mark>渋谷</mark>駅"));
+    assert_eq!(
+      highlights.description.as_deref(),
+      Some("<mark>渋谷</mark>のスクランブル交差点")
+    );
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_request_resolves_location_and_filters(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user = crate::domains::user::model::User::create(&pool, "geo@example.com", "Geo", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "geo@example.com").await?;
+
+    let (status, body) = post_json_with_auth(
+      app.clone(),
+      "/api/v1/requests",
+      &request_payload(35.6580, 139.7016, "渋谷駅", "ハチ公前"),
+      &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let shibuya: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(shibuya.prefecture.as_deref(), Some("Tokyo"));
+    assert_eq!(shibuya.city.as_deref(), Some("Shibuya"));
+
+    let (status, _) = post_json_with_auth(
+      app.clone(),
+      "/api/v1/requests",
+      &request_payload(34.7025, 135.4959, "大阪駅", "駅ビル"),
+      &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // データセットの範囲外は未設定のまま作成できる
+    let (status, body) = post_json_with_auth(
+      app.clone(),
+      "/api/v1/requests",
+      &request_payload(30.0, 150.0, "太平洋", "海"),
+      &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let ocean: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(ocean.prefecture, None);
+    assert_eq!(ocean.city, None);
+
+    let (status, body) = get(app.clone(), "/api/v1/requests?prefecture=Tokyo").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests.len(), 1);
+    assert_eq!(response.requests[0].id, shibuya.id);
+
+    let (status, body) = get(app.clone(), "/api/v1/requests?prefecture=Tokyo&city=Osaka").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert!(response.requests.is_empty());
+
+    // 位置を変更すると地域も解決し直す
+    let update = UpdateRequestRequest {
+      lat: Some(34.7025),
+      lng: Some(135.4959),
+      ..Default::default()
+    };
+    let (status, body) = patch_json_with_auth(app, &format!("/api/v1/requests/{}", shibuya.id), &update, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let moved: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(moved.prefecture.as_deref(), Some("Osaka"));
+    assert_eq!(moved.city.as_deref(), Some("Osaka"));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_request_with_category_and_tags(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user = crate::domains::user::model::User::create(&pool, "tagged@example.com", "Tagged", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "tagged@example.com").await?;
+
+    let payload = CreateRequestRequest {
+      category: Some("landmark".to_string()),
+      tags: vec!["#Sunset".to_string(), "sunset".to_string(), "Tokyo Tower".to_string()],
+      ..request_payload(35.6586, 139.7454, "東京タワー", "夕焼け")
+    };
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let tower: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(tower.category.as_deref(), Some("landmark"));
+    assert_eq!(tower.tags, vec!["sunset".to_string(), "tokyo-tower".to_string()]);
+
+    let payload = CreateRequestRequest {
+      category: Some("food".to_string()),
+      ..request_payload(35.6812, 139.7671, "東京駅", "駅弁")
+    };
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = get(app.clone(), "/api/v1/requests?tag=%23SUNSET").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests.len(), 1);
+    assert_eq!(response.requests[0].id, tower.id);
+
+    let (status, body) = get(app.clone(), "/api/v1/requests?category=food").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests.len(), 1);
+    assert_eq!(response.requests[0].place_name, "東京駅");
+
+    let (status, _) = get(app.clone(), "/api/v1/requests?category=unknown").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // タグを置き換えて更新できる
+    let update = UpdateRequestRequest {
+      tags: Some(vec!["night".to_string()]),
+      ..Default::default()
+    };
+    let (status, body) =
+      patch_json_with_auth(app.clone(), &format!("/api/v1/requests/{}", tower.id), &update, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let updated: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(updated.tags, vec!["night".to_string()]);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_request_invalid_category_or_tags(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user =
+      crate::domains::user::model::User::create(&pool, "bad-tags@example.com", "Bad Tags", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "bad-tags@example.com").await?;
+
+    let payload = CreateRequestRequest {
+      category: Some("spaceship".to_string()),
+      ..request_payload(35.0, 139.0, "場所", "説明")
+    };
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = CreateRequestRequest {
+      tags: (0..11).map(|i| format!("tag{}", i)).collect(),
+      ..request_payload(35.0, 139.0, "場所", "説明")
+    };
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let payload = CreateRequestRequest {
+      tags: vec!["#".to_string()],
+      ..request_payload(35.0, 139.0, "場所", "説明")
+    };
+    let (status, _) = post_json_with_auth(app, "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_request_detects_possible_duplicates(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "dup-owner@example.com", "Owner", "password123").await?;
+    let _user = crate::domains::user::model::User::create(&pool, "dup@example.com", "Dup", "password123").await?;
+    let existing = super::super::repository::create(
+      &pool,
+      owner.id,
+      &request_payload(35.6586, 139.7454, "東京タワー", "Tokyo Tower at night"),
+    )
+    .await?;
+    let token = login_verified_user(app.clone(), &pool, "dup@example.com").await?;
+
+    let payload = request_payload(35.6590, 139.7450, "東京タワー", "Tokyo Tower at night, please");
+    let (status, body) = post_json_with_auth(app.clone(), "/api/v1/requests", &payload, &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let body: serde_json::Value = serde_json::from_slice(&body).expect("deserialize error");
+    let duplicates = body["details"]["duplicates"].as_array().expect("duplicates");
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0]["id"], existing.id);
+
+    // 離れた場所なら重複とはみなさない
+    let far = request_payload(35.6812, 139.7671, "東京駅", "Tokyo Tower at night");
+    let (status, _) = post_json_with_auth(app.clone(), "/api/v1/requests", &far, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json_with_auth(app, "/api/v1/requests?force=true", &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn fuzzed_location_is_revealed_only_to_owner_and_claimer(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "private-owner@example.com", "Owner", "password123").await?;
+    let _claimer =
+      crate::domains::user::model::User::create(&pool, "private-claimer@example.com", "Claimer", "password123").await?;
+    let _other =
+      crate::domains::user::model::User::create(&pool, "private-other@example.com", "Other", "password123").await?;
+    let payload = CreateRequestRequest {
+      location_precision: Some("100m".to_string()),
+      ..request_payload(35.6812, 139.7671, "自宅前", "玄関の写真")
+    };
+    let created = super::super::repository::create(&pool, owner.id, &payload).await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "private-owner@example.com").await?;
+    let claimer_token = login_verified_user(app.clone(), &pool, "private-claimer@example.com").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "private-other@example.com").await?;
+
+    let uri = format!("/api/v1/requests/{}", created.id);
+    let fetch = |token: Option<String>| {
+      let app = app.clone();
+      let uri = uri.clone();
+      async move {
+        let (status, body) = match token {
+          Some(token) => get_with_auth(app, &uri, &token).await,
+          None => get(app, &uri).await,
+        };
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_slice::<super::super::model::Request>(&body).expect("deserialize request")
+      }
+    };
+
+    // 未ログインでは半径100m以内のぼかした位置になり、何度取得しても同じ
+    let anonymous = fetch(None).await;
+    assert_ne!((anonymous.lat, anonymous.lng), (created.lat, created.lng));
+    let offset = crate::utils::geo::haversine_distance(created.lat, created.lng, anonymous.lat, anonymous.lng);
+    assert!(offset <= 100.5, "offset was {}", offset);
+    let again = fetch(None).await;
+    assert_eq!((again.lat, again.lng), (anonymous.lat, anonymous.lng));
+
+    let as_owner = fetch(Some(owner_token.clone())).await;
+    assert_eq!((as_owner.lat, as_owner.lng), (created.lat, created.lng));
+
+    let (status, body) = get(app.clone(), "/api/v1/requests?lat=35.70&lng=139.77").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::RequestsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests[0].lat, anonymous.lat);
+    let distance = response.requests[0].distance.expect("distance");
+    assert_eq!(distance % 100.0, 0.0);
+
+    // 引き受けた撮影者には正確な位置を返す
+    let claim_uri = format!("/api/v1/requests/{}/claim", created.id);
+    let (status, _) = send_with_auth(app.clone(), "PUT", &claim_uri, &owner_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = send_with_auth(app.clone(), "PUT", &claim_uri, &claimer_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let claimed: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+    assert_eq!(claimed.status, "in-progress");
+    assert_eq!((claimed.lat, claimed.lng), (created.lat, created.lng));
+
+    let as_claimer = fetch(Some(claimer_token.clone())).await;
+    assert_eq!((as_claimer.lat, as_claimer.lng), (created.lat, created.lng));
+    let as_other = fetch(Some(other_token.clone())).await;
+    assert_eq!((as_other.lat, as_other.lng), (anonymous.lat, anonymous.lng));
+
+    let (status, _) = send_with_auth(app.clone(), "PUT", &claim_uri, &other_token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = send_with_auth(app.clone(), "DELETE", &claim_uri, &other_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send_with_auth(app.clone(), "DELETE", &claim_uri, &claimer_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let released = fetch(Some(claimer_token)).await;
+    assert_eq!(released.status, "open");
+    assert_eq!((released.lat, released.lng), (anonymous.lat, anonymous.lng));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn fuzzed_request_matches_watch_areas_by_fuzzed_location(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    use crate::domains::watch_area::{model::CreateWatchAreaRequest, repository as watch_area_repository};
+
+    let app = app_with_pool(pool.clone()).await;
+
+    let _owner =
+      crate::domains::user::model::User::create(&pool, "fuzz-alert-owner@example.com", "Owner", "password123").await?;
+    let watcher =
+      crate::domains::user::model::User::create(&pool, "fuzz-alert-watcher@example.com", "Watcher", "password123")
+        .await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "fuzz-alert-owner@example.com").await?;
+
+    let area = |name: &str, radius_m: f64| CreateWatchAreaRequest {
+      name: name.to_string(),
+      lat: 35.6812,
+      lng: 139.7671,
+      radius_m,
+    };
+    let small = watch_area_repository::create(&pool, watcher.id, &area("玄関前", 50.0), "small-token").await?;
+    let large = watch_area_repository::create(&pool, watcher.id, &area("駅周辺", 5000.0), "large-token").await?;
+
+    let payload = CreateRequestRequest {
+      location_precision: Some("1km".to_string()),
+      ..request_payload(35.6812, 139.7671, "自宅前", "玄関の写真")
+    };
+    let (status, body) = post_json_with_auth(app, "/api/v1/requests", &payload, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    let created: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize request");
+
+    let matched: Vec<i32> = sqlx::query_scalar!(
+      "SELECT watch_area_id FROM watch_area_matches WHERE request_id = $1",
+      created.id
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert!(matched.contains(&large.id));
+    // 正確な位置を囲む小さなエリアは、ぼかした位置が入る場合にしか一致しない
+    let (fuzzed_lat, fuzzed_lng) = super::super::privacy::public_location(&created);
+    let fuzzed_inside = crate::utils::geo::haversine_distance(35.6812, 139.7671, fuzzed_lat, fuzzed_lng) <= 50.0;
+    assert_eq!(matched.contains(&small.id), fuzzed_inside);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_requests_with_distance(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "distance-test@example.com", "Distance Test", "password123")
+        .await?;
+
+    // 東京タワー周辺のリクエスト
     super::super::repository::create(
       &pool,
       user.id,
-      43.0642,
-      141.3469,
-      "札幌".to_string(),
-      "写真3".to_string(),
+      &request_payload(35.6812, 139.7671, "東京タワー", "写真1"),
     )
     .await?;
+    // 大阪城周辺のリクエスト
+    super::super::repository::create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪城", "写真2")).await?;
+    // 札幌周辺のリクエスト
+    super::super::repository::create(&pool, user.id, &request_payload(43.0642, 141.3469, "札幌", "写真3")).await?;
 
     // 東京タワーからの距離を計算
     let (status, body) = get(app, "/api/v1/requests?lat=35.6812&lng=139.7671").await;
@@ -470,15 +1000,7 @@ mod tests {
     let app = app_with_pool(pool.clone()).await;
 
     let user = crate::domains::user::model::User::create(&pool, "no-loc@example.com", "No Loc", "password123").await?;
-    super::super::repository::create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "テスト".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
 
     let (status, body) = get(app, "/api/v1/requests").await;
     assert_eq!(status, StatusCode::OK);
@@ -499,15 +1021,7 @@ mod tests {
 
     let user =
       crate::domains::user::model::User::create(&pool, "only-lat@example.com", "Only Lat", "password123").await?;
-    super::super::repository::create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "テスト".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
 
     // lat のみ指定（lng なし）
     let (status, body) = get(app, "/api/v1/requests?lat=35.6812").await;
@@ -529,15 +1043,7 @@ mod tests {
 
     let user =
       crate::domains::user::model::User::create(&pool, "only-lng@example.com", "Only Lng", "password123").await?;
-    super::super::repository::create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "テスト".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
 
     // lng のみ指定（lat なし）
     let (status, body) = get(app, "/api/v1/requests?lng=139.7671").await;
@@ -560,35 +1066,11 @@ mod tests {
     let user = crate::domains::user::model::User::create(&pool, "sorted@example.com", "Sorted", "password123").await?;
 
     // 札幌（最も遠い）
-    super::super::repository::create(
-      &pool,
-      user.id,
-      43.0642,
-      141.3469,
-      "札幌".to_string(),
-      "写真1".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(43.0642, 141.3469, "札幌", "写真1")).await?;
     // 東京（最も近い）
-    super::super::repository::create(
-      &pool,
-      user.id,
-      35.6812,
-      139.7671,
-      "東京".to_string(),
-      "写真2".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "写真2")).await?;
     // 大阪（中間）
-    super::super::repository::create(
-      &pool,
-      user.id,
-      34.6937,
-      135.5023,
-      "大阪".to_string(),
-      "写真3".to_string(),
-    )
-    .await?;
+    super::super::repository::create(&pool, user.id, &request_payload(34.6937, 135.5023, "大阪", "写真3")).await?;
 
     // 東京タワーからの距離順
     let (status, body) = get(app, "/api/v1/requests?lat=35.6812&lng=139.7671").await;
@@ -616,10 +1098,7 @@ mod tests {
     let created = super::super::repository::create(
       &pool,
       user.id,
-      35.6812,
-      139.7671,
-      "東京タワー".to_string(),
-      "テスト説明".to_string(),
+      &request_payload(35.6812, 139.7671, "東京タワー", "テスト説明"),
     )
     .await?;
 
@@ -647,4 +1126,220 @@ mod tests {
 
     Ok(())
   }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_request_success(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "update-req@example.com", "Update Req", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    let token = login_verified_user(app.clone(), &pool, "update-req@example.com").await?;
+
+    let payload = UpdateRequestRequest {
+      lat: Some(35.6586),
+      lng: Some(139.7454),
+      place_name: Some("東京タワー".to_string()),
+      ..Default::default()
+    };
+    let (status, body) = patch_json_with_auth(app, &format!("/api/v1/requests/{}", created.id), &payload, &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let updated: super::super::model::Request = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.lat, 35.6586);
+    assert_eq!(updated.lng, 139.7454);
+    assert_eq!(updated.place_name, "東京タワー");
+    assert_eq!(updated.description, "テスト");
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_request_forbidden(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "req-owner@example.com", "Owner", "password123").await?;
+    let _other =
+      crate::domains::user::model::User::create(&pool, "req-other@example.com", "Other", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    let token = login_verified_user(app.clone(), &pool, "req-other@example.com").await?;
+
+    let payload = UpdateRequestRequest {
+      description: Some("乗っ取り".to_string()),
+      ..Default::default()
+    };
+    let (status, _) = patch_json_with_auth(app, &format!("/api/v1/requests/{}", created.id), &payload, &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_request_not_open(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "closed-req@example.com", "Closed Req", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    sqlx::query!("UPDATE requests SET status = 'in-progress' WHERE id = $1", created.id)
+      .execute(&pool)
+      .await?;
+    let token = login_verified_user(app.clone(), &pool, "closed-req@example.com").await?;
+
+    let payload = UpdateRequestRequest {
+      description: Some("変更".to_string()),
+      ..Default::default()
+    };
+    let (status, _) = patch_json_with_auth(app, &format!("/api/v1/requests/{}", created.id), &payload, &token).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn update_request_lat_without_lng(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "half-loc@example.com", "Half Loc", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    let token = login_verified_user(app.clone(), &pool, "half-loc@example.com").await?;
+
+    let payload = UpdateRequestRequest {
+      lat: Some(35.0),
+      ..Default::default()
+    };
+    let (status, _) = patch_json_with_auth(app, &format!("/api/v1/requests/{}", created.id), &payload, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_request_success(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "delete-req@example.com", "Delete Req", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, user.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    sqlx::query!(
+      "INSERT INTO pictures (user_id, image_url, request_id) VALUES ($1, $2, $3)",
+      user.id,
+      "http://127.0.0.1:9000/test/pictures/1/test.jpg",
+      created.id
+    )
+    .execute(&pool)
+    .await?;
+    let token = login_verified_user(app.clone(), &pool, "delete-req@example.com").await?;
+
+    let (status, _) = delete_with_auth(app, &format!("/api/v1/requests/{}", created.id), &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let request = super::super::repository::find_by_id(&pool, created.id).await?;
+    assert!(request.is_none());
+
+    let picture_count = sqlx::query_scalar!("SELECT COUNT(*) FROM pictures WHERE request_id = $1", created.id)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(picture_count, Some(0));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_request_forbidden(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "del-owner@example.com", "Owner", "password123").await?;
+    let _other =
+      crate::domains::user::model::User::create(&pool, "del-other@example.com", "Other", "password123").await?;
+    let created =
+      super::super::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "テスト")).await?;
+    let token = login_verified_user(app.clone(), &pool, "del-other@example.com").await?;
+
+    let (status, _) = delete_with_auth(app, &format!("/api/v1/requests/{}", created.id), &token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let request = super::super::repository::find_by_id(&pool, created.id).await?;
+    assert!(request.is_some());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_my_requests_with_counts_and_latest_submission(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner = crate::domains::user::model::User::create(&pool, "my-req@example.com", "Owner", "password123").await?;
+    let photographer =
+      crate::domains::user::model::User::create(&pool, "my-req-photo@example.com", "Photo", "password123").await?;
+    let other =
+      crate::domains::user::model::User::create(&pool, "my-req-other@example.com", "Other", "password123").await?;
+
+    let first =
+      super::super::repository::create(&pool, owner.id, &request_payload(35.6812, 139.7671, "東京", "一つ目")).await?;
+    let second =
+      super::super::repository::create(&pool, owner.id, &request_payload(34.6937, 135.5023, "大阪", "二つ目")).await?;
+    super::super::repository::create(&pool, other.id, &request_payload(43.0642, 141.3469, "札幌", "他人")).await?;
+    sqlx::query!("UPDATE requests SET status = 'completed' WHERE id = $1", first.id)
+      .execute(&pool)
+      .await?;
+
+    for (user_id, url) in [
+      (photographer.id, "http://127.0.0.1:9000/test/pictures/old.jpg"),
+      (other.id, "http://127.0.0.1:9000/test/pictures/new.jpg"),
+    ] {
+      sqlx::query!(
+        "INSERT INTO pictures (user_id, image_url, request_id) VALUES ($1, $2, $3)",
+        user_id,
+        url,
+        first.id
+      )
+      .execute(&pool)
+      .await?;
+    }
+
+    let token = login_verified_user(app.clone(), &pool, "my-req@example.com").await?;
+    let (status, body) = get_with_auth(app.clone(), "/api/v1/users/me/requests", &token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let response: super::super::model::MyRequestsResponse =
+      serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.total, 2);
+    assert_eq!(response.status_counts.open, 1);
+    assert_eq!(response.status_counts.completed, 1);
+
+    let ids: Vec<i32> = response.requests.iter().map(|r| r.request.id).collect();
+    assert_eq!(ids, vec![second.id, first.id]);
+    assert_eq!(response.requests[0].submission_count, 0);
+    assert!(response.requests[0].latest_submission.is_none());
+    assert_eq!(response.requests[1].submission_count, 2);
+    assert_eq!(
+      response.requests[1]
+        .latest_submission
+        .as_ref()
+        .map(|p| p.image_url.as_str()),
+      Some("http://127.0.0.1:9000/test/pictures/new.jpg")
+    );
+
+    let (status, body) = get_with_auth(app.clone(), "/api/v1/users/me/requests?page=2&per_page=1", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let response: super::super::model::MyRequestsResponse =
+      serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.requests.len(), 1);
+    assert_eq!(response.requests[0].request.id, first.id);
+
+    let (status, _) = get_with_auth(app, "/api/v1/users/me/requests?per_page=0", &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
 }
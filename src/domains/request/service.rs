@@ -1,48 +1,445 @@
 use sqlx::PgPool;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::domains::picture::{
+  model::SubmissionSummary, repository as picture_repository, service::resolve_picture_urls,
+};
 use crate::domains::request::{
-  model::{CreateRequestRequest, RequestWithDistance, RequestsResponse},
+  model::{
+    CreateRequestRequest, MyRequest, MyRequestsResponse, Request, RequestFilter, RequestHighlights,
+    RequestWithDistance, RequestsResponse, UpdateRequestRequest,
+  },
+  privacy::{mask_request, mask_request_with_distance, public_location},
   repository,
 };
+use crate::domains::storage_operation::{
+  model::OperationKind, repository as storage_operation_repository, service::StorageOperationService,
+};
+use crate::domains::tag::{model::normalize_tags, repository as tag_repository};
+use crate::domains::watch_area::repository as watch_area_repository;
+use crate::email::EmailService;
+use crate::geocoding::{Geocoder, Location};
+use crate::impl_service_error_conversions;
+use crate::storage::ObjectStorage;
+use crate::utils::pagination::Page;
+use crate::utils::search::highlight_snippet;
+
+/// 検索結果のハイライトで一致箇所の前後に残す文字数
+const HIGHLIGHT_CONTEXT_CHARS: usize = 30;
+
+/// 重複候補とみなす既存リクエストまでの最大距離（メートル）
+const DUPLICATE_RADIUS_M: f64 = 200.0;
+
+/// 重複候補として返す最大件数
+const MAX_DUPLICATE_CANDIDATES: i64 = 5;
+
+#[derive(Debug)]
+pub enum RequestServiceError {
+  InternalServerError(String),
+  BadRequest(String),
+  NotFound(String),
+  Forbidden(String),
+  Conflict(String),
+  /// 近くに似た内容の募集中リクエストがある。`force` を指定すれば作成できる
+  PossibleDuplicates(Vec<RequestWithDistance>),
+}
+
+impl Error for RequestServiceError {}
+
+impl std::fmt::Display for RequestServiceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RequestServiceError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
+      RequestServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+      RequestServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+      RequestServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+      RequestServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+      RequestServiceError::PossibleDuplicates(duplicates) => {
+        write!(f, "Conflict: {} possible duplicate requests", duplicates.len())
+      }
+    }
+  }
+}
+
+impl_service_error_conversions!(RequestServiceError, InternalServerError);
 
 pub struct RequestService {
   pool: PgPool,
+  storage: Arc<dyn ObjectStorage>,
+  storage_operations: StorageOperationService,
+  email_service: EmailService,
+  geocoder: Arc<dyn Geocoder>,
 }
 
 impl RequestService {
-  pub fn new(pool: PgPool) -> Self {
-    Self { pool }
+  pub fn new(
+    pool: PgPool,
+    storage: Arc<dyn ObjectStorage>,
+    email_service: EmailService,
+    geocoder: Arc<dyn Geocoder>,
+  ) -> Self {
+    Self {
+      storage_operations: StorageOperationService::new(pool.clone(), storage.clone()),
+      pool,
+      storage,
+      email_service,
+      geocoder,
+    }
   }
 
   pub async fn get_requests(
     &self,
     user_lat: Option<f64>,
     user_lng: Option<f64>,
-  ) -> Result<RequestsResponse, sqlx::Error> {
-    let requests = if let (Some(lat), Some(lng)) = (user_lat, user_lng) {
-      repository::find_all_with_distance(&self.pool, lat, lng).await?
+    filter: RequestFilter,
+    viewer_id: Option<i32>,
+  ) -> Result<RequestsResponse, RequestServiceError> {
+    let origin = user_lat.zip(user_lng);
+    let mut requests = if let Some((lat, lng)) = origin {
+      repository::find_all_with_distance(&self.pool, lat, lng, &filter).await?
     } else {
-      let reqs = repository::find_all(&self.pool).await?;
-      reqs.into_iter().map(RequestWithDistance::from).collect()
+      repository::find_all(&self.pool, &filter).await?
     };
 
+    for request in &mut requests {
+      mask_request_with_distance(request, viewer_id, origin);
+    }
+    if origin.is_some() {
+      // 正確な距離での並び順から位置を推測されないよう、ぼかした後の距離で並べ直す
+      requests.sort_by(|a, b| {
+        b.rank
+          .partial_cmp(&a.rank)
+          .unwrap_or(Ordering::Equal)
+          .then(a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal))
+          .then(a.id.cmp(&b.id))
+      });
+    }
+
+    if let Some(q) = &filter.q {
+      for request in &mut requests {
+        request.highlights = Some(RequestHighlights {
+          place_name: highlight_snippet(&request.place_name, q, HIGHLIGHT_CONTEXT_CHARS),
+          description: highlight_snippet(&request.description, q, HIGHLIGHT_CONTEXT_CHARS),
+        });
+      }
+    }
+
     Ok(RequestsResponse { requests })
   }
 
+  /// `force` が false の場合、近くに似た内容の募集中リクエストがあれば作成せずに候補を返す
   pub async fn create_request(
     &self,
     user_id: i32,
     req: CreateRequestRequest,
-  ) -> Result<crate::domains::request::model::Request, sqlx::Error> {
-    repository::create(&self.pool, user_id, req.lat, req.lng, req.place_name, req.description).await
+    force: bool,
+  ) -> Result<Request, RequestServiceError> {
+    if let (Some(per_user), Some(total)) = (req.max_submissions_per_user, req.max_total_submissions) {
+      if per_user > total {
+        return Err(RequestServiceError::BadRequest(
+          "max_submissions_per_user must not exceed max_total_submissions".to_string(),
+        ));
+      }
+    }
+
+    if !force {
+      let duplicates = self.find_possible_duplicates(user_id, &req).await?;
+      if !duplicates.is_empty() {
+        return Err(RequestServiceError::PossibleDuplicates(duplicates));
+      }
+    }
+
+    let location = self.resolve_location(req.lat, req.lng).await;
+
+    let mut tx = self.pool.begin().await?;
+    let request = repository::create_with_executor(&mut *tx.as_mut(), user_id, &req).await?;
+    tag_repository::replace_for_request_with_executor(&mut *tx.as_mut(), request.id, &normalize_tags(&req.tags))
+      .await?;
+    let request = repository::update_location_with_executor(
+      &mut *tx.as_mut(),
+      request.id,
+      location.prefecture.as_deref(),
+      location.city.as_deref(),
+    )
+    .await?;
+
+    // 監視エリアへの通知はワーカーがまとめて送るため、ここでは一致の記録だけ行う。
+    // 小さなエリアで正確な位置を絞り込まれないよう、ぼかしたリクエストはぼかした位置で判定する
+    let (match_lat, match_lng) = public_location(&request);
+    watch_area_repository::record_matches_for_request_with_executor(
+      &mut *tx.as_mut(),
+      request.id,
+      user_id,
+      match_lat,
+      match_lng,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(request)
   }
 
   pub async fn get_request_by_id(
     &self,
     request_id: i32,
-  ) -> Result<crate::domains::request::model::Request, sqlx::Error> {
-    repository::find_by_id(&self.pool, request_id)
+    viewer_id: Option<i32>,
+  ) -> Result<Request, RequestServiceError> {
+    let mut request = repository::find_by_id(&self.pool, request_id)
       .await?
-      .ok_or_else(|| sqlx::Error::RowNotFound)
+      .ok_or_else(|| RequestServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    mask_request(&mut request, viewer_id);
+    Ok(request)
+  }
+
+  /// 撮影者としてリクエストを引き受ける。引き受けている間は正確な位置が見られる
+  pub async fn claim_request(&self, request_id: i32, user_id: i32) -> Result<Request, RequestServiceError> {
+    let mut tx = self.pool.begin().await?;
+    let request = repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id)
+      .await?
+      .ok_or_else(|| RequestServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.user_id == user_id {
+      return Err(RequestServiceError::Forbidden(
+        "You cannot claim your own request".to_string(),
+      ));
+    }
+
+    if request.claimed_by == Some(user_id) {
+      return Ok(request);
+    }
+
+    if request.status != "open" || request.claimed_by.is_some() {
+      return Err(RequestServiceError::Conflict(
+        "Only open, unclaimed requests can be claimed".to_string(),
+      ));
+    }
+
+    let claimed =
+      repository::set_claim_with_executor(&mut *tx.as_mut(), request_id, Some(user_id), "in-progress").await?;
+    tx.commit().await?;
+
+    Ok(claimed)
+  }
+
+  /// 引き受けを取り消し、リクエストを募集中に戻す
+  pub async fn unclaim_request(&self, request_id: i32, user_id: i32) -> Result<(), RequestServiceError> {
+    let mut tx = self.pool.begin().await?;
+    let request = repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id)
+      .await?
+      .ok_or_else(|| RequestServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.claimed_by != Some(user_id) {
+      return Err(RequestServiceError::Forbidden(
+        "You have not claimed this request".to_string(),
+      ));
+    }
+
+    // 期限切れなどで既に別のステータスになっている場合はそのまま残す
+    let status = if request.status == "in-progress" {
+      "open"
+    } else {
+      request.status.as_str()
+    };
+    repository::set_claim_with_executor(&mut *tx.as_mut(), request_id, None, status).await?;
+    tx.commit().await?;
+
+    Ok(())
+  }
+
+  pub async fn update_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: UpdateRequestRequest,
+  ) -> Result<Request, RequestServiceError> {
+    if req.lat.is_some() != req.lng.is_some() {
+      return Err(RequestServiceError::BadRequest(
+        "lat and lng must be provided together".to_string(),
+      ));
+    }
+
+    let location = match (req.lat, req.lng) {
+      (Some(lat), Some(lng)) => Some(self.resolve_location(lat, lng).await),
+      _ => None,
+    };
+
+    let mut tx = self.pool.begin().await?;
+    let request = repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id)
+      .await?
+      .ok_or_else(|| RequestServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.user_id != user_id {
+      return Err(RequestServiceError::Forbidden(
+        "You do not have permission to update this request".to_string(),
+      ));
+    }
+
+    if request.status != "open" {
+      return Err(RequestServiceError::Conflict(
+        "Only open requests can be updated".to_string(),
+      ));
+    }
+
+    // 更新後の行を返すときにタグも反映されているよう、先に置き換える
+    if let Some(tags) = &req.tags {
+      tag_repository::replace_for_request_with_executor(&mut *tx.as_mut(), request_id, &normalize_tags(tags)).await?;
+    }
+
+    let updated = repository::update_with_executor(&mut *tx.as_mut(), request_id, &req).await?;
+
+    let updated = match location {
+      Some(location) => {
+        repository::update_location_with_executor(
+          &mut *tx.as_mut(),
+          request_id,
+          location.prefecture.as_deref(),
+          location.city.as_deref(),
+        )
+        .await?
+      }
+      None => updated,
+    };
+
+    tx.commit().await?;
+
+    Ok(updated)
+  }
+
+  pub async fn delete_request(&self, request_id: i32, user_id: i32) -> Result<(), RequestServiceError> {
+    let request = repository::find_by_id(&self.pool, request_id)
+      .await?
+      .ok_or_else(|| RequestServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+
+    if request.user_id != user_id {
+      return Err(RequestServiceError::Forbidden(
+        "You do not have permission to delete this request".to_string(),
+      ));
+    }
+
+    // pictures.request_id は ON DELETE CASCADE のため、リクエストと一緒に消える写真のオブジェクトの参照数を減らして削除を
+    // 同じトランザクションで記録し、ここで消せなかった分は後で片付ける
+    // 削除までの間に投稿された写真を取りこぼさないよう、投稿と同じくリクエスト行をロックしてから写真を取得する
+    let mut tx = self.pool.begin().await?;
+    repository::find_by_id_for_update_with_executor(&mut *tx.as_mut(), request_id).await?;
+    let pictures = picture_repository::find_by_request_id_with_executor(&mut *tx.as_mut(), request_id).await?;
+    let keys: Vec<String> = pictures
+      .iter()
+      .flat_map(|picture| picture.storage_keys(self.storage.as_ref()))
+      .collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    repository::delete_with_executor(&mut *tx.as_mut(), request_id).await?;
+    storage_operation_repository::remove_blob_references_with_executor(&mut *tx.as_mut(), &keys).await?;
+    let deletes = storage_operation_repository::create_with_executor(
+      &mut *tx.as_mut(),
+      OperationKind::Delete,
+      &keys,
+      Duration::ZERO,
+    )
+    .await?;
+    tx.commit().await?;
+
+    self.storage_operations.apply(deletes).await;
+    Ok(())
+  }
+
+  /// 自分のリクエスト一覧を、ステータス別の件数と各リクエストの最新の投稿写真とともに返す
+  pub async fn get_my_requests(&self, user_id: i32, page: Page) -> Result<MyRequestsResponse, RequestServiceError> {
+    let status_counts = repository::count_by_status_for_user(&self.pool, user_id).await?;
+    let requests = repository::find_by_user_id(&self.pool, user_id, page.limit(), page.offset()).await?;
+
+    let request_ids: Vec<i32> = requests.iter().map(|r| r.id).collect();
+    let mut summaries: HashMap<i32, SubmissionSummary> =
+      picture_repository::summarize_by_request_ids(&self.pool, &request_ids)
+        .await?
+        .into_iter()
+        .map(|summary| (summary.request_id, summary))
+        .collect();
+
+    let mut my_requests = Vec::with_capacity(requests.len());
+    for request in requests {
+      let summary = summaries.remove(&request.id);
+      let submission_count = summary.as_ref().map_or(0, |s| s.submission_count);
+      let mut latest_submission = summary.map(|s| s.latest);
+      if let Some(picture) = &mut latest_submission {
+        resolve_picture_urls(self.storage.as_ref(), picture)
+          .await
+          .map_err(|e| RequestServiceError::InternalServerError(format!("Failed to resolve picture URL: {}", e)))?;
+      }
+
+      my_requests.push(MyRequest {
+        request,
+        submission_count,
+        latest_submission,
+      });
+    }
+
+    let total = status_counts.open + status_counts.in_progress + status_counts.completed + status_counts.expired;
+
+    Ok(MyRequestsResponse {
+      requests: my_requests,
+      status_counts,
+      total,
+      page: page.page,
+      per_page: page.per_page,
+    })
+  }
+
+  /// 期限を過ぎた open / in-progress のリクエストを expired にし、作成者へ通知する
+  pub async fn expire_overdue_requests(&self) -> Result<usize, RequestServiceError> {
+    let expired = repository::expire_overdue(&self.pool).await?;
+
+    for request in &expired {
+      let subject = "リクエストの期限が切れました";
+      let body = EmailService::build_request_expired_email_body(request.id, &request.place_name);
+
+      if let Err(e) = self
+        .email_service
+        .send_simple_text_email(&request.email, subject, &body)
+        .await
+      {
+        tracing::error!("Failed to send expiry email for request {}: {:?}", request.id, e);
+      } else {
+        tracing::info!("Expiry email sent for request {}", request.id);
+      }
+    }
+
+    Ok(expired.len())
+  }
+
+  /// 説明文のトライグラム類似度で検索し、近くにある募集中のリクエストを重複候補として返す
+  async fn find_possible_duplicates(
+    &self,
+    user_id: i32,
+    req: &CreateRequestRequest,
+  ) -> Result<Vec<RequestWithDistance>, RequestServiceError> {
+    let duplicates = repository::find_possible_duplicates(
+      &self.pool,
+      user_id,
+      req.lat,
+      req.lng,
+      &req.description,
+      DUPLICATE_RADIUS_M,
+      MAX_DUPLICATE_CANDIDATES,
+    )
+    .await?;
+
+    Ok(duplicates)
+  }
+
+  /// 逆ジオコーディングに失敗してもリクエストの作成・更新は止めず、地域は未設定のままにする
+  async fn resolve_location(&self, lat: f64, lng: f64) -> Location {
+    match self.geocoder.reverse(lat, lng).await {
+      Ok(location) => location.unwrap_or_default(),
+      Err(e) => {
+        tracing::warn!("Failed to reverse geocode ({}, {}): {:?}", lat, lng, e);
+        Location::default()
+      }
+    }
   }
 }
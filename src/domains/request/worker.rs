@@ -0,0 +1,34 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use super::service::RequestService;
+
+const DEFAULT_EXPIRY_INTERVAL_SECS: u64 = 300;
+
+pub fn expiry_interval_from_env() -> Duration {
+  let secs = std::env::var("REQUEST_EXPIRY_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(DEFAULT_EXPIRY_INTERVAL_SECS);
+
+  Duration::from_secs(secs)
+}
+
+/// 期限切れリクエストを定期的に expired へ移行するバックグラウンドタスクを起動する
+pub fn spawn_expiry_worker(request_service: Arc<RequestService>, interval: Duration) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match request_service.expire_overdue_requests().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Expired {} overdue requests", count),
+        Err(e) => tracing::error!("Failed to expire overdue requests: {}", e),
+      }
+    }
+  })
+}
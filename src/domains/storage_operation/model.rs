@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+/// 送信箱に記録したストレージへの操作
+#[derive(Debug, Clone, FromRow)]
+pub struct StorageOperation {
+  pub id: i64,
+  /// `upload` か `delete`。どちらも残っていればオブジェクトを消して片付ける
+  pub operation: String,
+  pub object_key: String,
+  pub attempts: i32,
+  pub last_error: Option<String>,
+  pub next_attempt_at: DateTime<Utc>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// 内容の SHA-256 から決まるキーに置いたオブジェクト。同じ内容の写真や縮小画像で共有する
+#[derive(Debug, Clone, FromRow)]
+pub struct Blob {
+  pub storage_key: String,
+  pub sha256: String,
+  pub size: i64,
+  /// このオブジェクトを指している写真と縮小画像の行の数
+  pub ref_count: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+  /// オブジェクトを置いてから行を作るまで
+  Upload,
+  /// 行を消してからオブジェクトを消すまで
+  Delete,
+}
+
+impl OperationKind {
+  /// `storage_operations.operation` に記録する名前
+  pub fn name(self) -> &'static str {
+    match self {
+      OperationKind::Upload => "upload",
+      OperationKind::Delete => "delete",
+    }
+  }
+}
+
+/// DB の行が指すストレージ上のオブジェクト
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct StorageReference {
+  pub picture_id: i32,
+  /// 縮小画像の行なら、その ID
+  pub variant_id: Option<i32>,
+  pub storage_key: String,
+}
+
+/// キーを持たず、URL だけで画像を指している行
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct LegacyReference {
+  pub picture_id: i32,
+  /// 縮小画像の行なら、その ID
+  pub variant_id: Option<i32>,
+  pub image_url: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BackfillReport {
+  /// キーに置き換えた（dry run では置き換える）行の数
+  pub converted: usize,
+  /// このストレージを指していないため URL のまま残した行
+  pub skipped: Vec<LegacyReference>,
+}
+
+/// GC で見つけた食い違いの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcAction {
+  /// 報告するだけ
+  Report,
+  /// 行のないオブジェクトと、オブジェクトのない行を消す
+  Delete,
+  /// 行のないオブジェクトを隔離用の場所へ移す。オブジェクトのない行は報告するだけ
+  Quarantine,
+}
+
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+  /// 調べるキーの接頭辞
+  pub prefix: String,
+  pub action: GcAction,
+  /// 何をするかを報告するだけで、実際には変更しない
+  pub dry_run: bool,
+  /// これより新しいオブジェクトは、行を作っている途中かもしれないため行がなくても対象にしない
+  pub min_age: std::time::Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+  pub scanned_objects: usize,
+  /// どの行からも指されていないオブジェクトのキー
+  pub orphan_objects: Vec<String>,
+  /// ストレージにないオブジェクトを指している行
+  pub missing_objects: Vec<StorageReference>,
+  /// 新しいか片付け待ちのため、行がなくても対象にしなかったオブジェクトの数
+  pub skipped_objects: usize,
+  /// 消したか移したオブジェクトの数
+  pub removed_objects: usize,
+  /// 消した行の数
+  pub removed_rows: usize,
+}
@@ -0,0 +1,416 @@
+use sqlx::{Executor, PgPool, Postgres};
+use std::time::Duration;
+
+use super::model::{Blob, LegacyReference, OperationKind, StorageOperation, StorageReference};
+
+/// `keys` それぞれへの操作を、`delay` 後から片付けられるものとして記録する
+pub async fn create(
+  db: &PgPool,
+  operation: OperationKind,
+  keys: &[&str],
+  delay: Duration,
+) -> Result<Vec<StorageOperation>, sqlx::Error> {
+  create_with_executor(db, operation, keys, delay).await
+}
+
+pub async fn create_with_executor<'e, E>(
+  executor: E,
+  operation: OperationKind,
+  keys: &[&str],
+  delay: Duration,
+) -> Result<Vec<StorageOperation>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let operations = sqlx::query_as!(
+    StorageOperation,
+    r#"
+      INSERT INTO storage_operations (operation, object_key, next_attempt_at)
+      SELECT $1, key, NOW() + make_interval(secs => $3)
+      FROM UNNEST($2::text[]) AS key
+      RETURNING id, operation, object_key, attempts, last_error, next_attempt_at, created_at
+    "#,
+    operation.name(),
+    keys as &[&str],
+    delay.as_secs_f64()
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(operations)
+}
+
+/// 片付ける時刻を過ぎた操作を古い順に取り出し、`lease` の間はほかの片付けが取り出さないよう先送りする
+pub async fn claim_due(db: &PgPool, limit: i64, lease: Duration) -> Result<Vec<StorageOperation>, sqlx::Error> {
+  claim_due_with_executor(db, limit, lease).await
+}
+
+pub async fn claim_due_with_executor<'e, E>(
+  executor: E,
+  limit: i64,
+  lease: Duration,
+) -> Result<Vec<StorageOperation>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let operations = sqlx::query_as!(
+    StorageOperation,
+    r#"
+      UPDATE storage_operations
+      SET next_attempt_at = NOW() + make_interval(secs => $2)
+      WHERE id IN (
+        SELECT id
+        FROM storage_operations
+        WHERE next_attempt_at <= NOW()
+        ORDER BY next_attempt_at
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+      )
+      RETURNING id, operation, object_key, attempts, last_error, next_attempt_at, created_at
+    "#,
+    limit,
+    lease.as_secs_f64()
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(operations)
+}
+
+/// 片付けに失敗したことを記録し、`retry_after` 後に再試行する
+pub async fn record_failure(db: &PgPool, id: i64, error: &str, retry_after: Duration) -> Result<(), sqlx::Error> {
+  record_failure_with_executor(db, id, error, retry_after).await
+}
+
+pub async fn record_failure_with_executor<'e, E>(
+  executor: E,
+  id: i64,
+  error: &str,
+  retry_after: Duration,
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE storage_operations
+      SET attempts = attempts + 1, last_error = $2, next_attempt_at = NOW() + make_interval(secs => $3)
+      WHERE id = $1
+    "#,
+    id,
+    error,
+    retry_after.as_secs_f64()
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn delete(db: &PgPool, id: i64) -> Result<bool, sqlx::Error> {
+  delete_with_executor(db, id).await
+}
+
+pub async fn delete_with_executor<'e, E>(executor: E, id: i64) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      DELETE FROM storage_operations
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// 片付け待ちの操作が残っているオブジェクトのキー
+pub async fn find_pending_keys(db: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+  find_pending_keys_with_executor(db).await
+}
+
+pub async fn find_pending_keys_with_executor<'e, E>(executor: E) -> Result<Vec<String>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let keys = sqlx::query_scalar!(
+    r#"
+      SELECT DISTINCT object_key
+      FROM storage_operations
+    "#
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(keys)
+}
+
+/// キーが `prefix` で始まるオブジェクトを指している写真と縮小画像の行
+pub async fn find_references(db: &PgPool, prefix: &str) -> Result<Vec<StorageReference>, sqlx::Error> {
+  find_references_with_executor(db, prefix).await
+}
+
+pub async fn find_references_with_executor<'e, E>(
+  executor: E,
+  prefix: &str,
+) -> Result<Vec<StorageReference>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let references = sqlx::query_as!(
+    StorageReference,
+    r#"
+      SELECT id as "picture_id!", NULL::INTEGER as "variant_id?", storage_key as "storage_key!"
+      FROM pictures
+      WHERE starts_with(storage_key, $1)
+      UNION ALL
+      SELECT picture_id, id, storage_key
+      FROM picture_variants
+      WHERE starts_with(storage_key, $1)
+    "#,
+    prefix
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(references)
+}
+
+/// キーを持たず URL だけで画像を指している写真と縮小画像の行
+pub async fn find_legacy_references(db: &PgPool) -> Result<Vec<LegacyReference>, sqlx::Error> {
+  find_legacy_references_with_executor(db).await
+}
+
+pub async fn find_legacy_references_with_executor<'e, E>(executor: E) -> Result<Vec<LegacyReference>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let references = sqlx::query_as!(
+    LegacyReference,
+    r#"
+      SELECT id as "picture_id!", NULL::INTEGER as "variant_id?", image_url as "image_url!"
+      FROM pictures
+      WHERE storage_key IS NULL AND image_url IS NOT NULL
+      UNION ALL
+      SELECT picture_id, id, image_url
+      FROM picture_variants
+      WHERE storage_key IS NULL AND image_url IS NOT NULL
+      ORDER BY 1, 2 NULLS FIRST
+    "#
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(references)
+}
+
+/// URL だけを持つ写真の行をキーに置き換える。すでにキーを持っていれば `false`
+pub async fn set_picture_storage_key(db: &PgPool, id: i32, key: &str, backend: &str) -> Result<bool, sqlx::Error> {
+  set_picture_storage_key_with_executor(db, id, key, backend).await
+}
+
+pub async fn set_picture_storage_key_with_executor<'e, E>(
+  executor: E,
+  id: i32,
+  key: &str,
+  backend: &str,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE pictures
+      SET storage_key = $2, storage_backend = $3, image_url = NULL
+      WHERE id = $1 AND storage_key IS NULL
+    "#,
+    id,
+    key,
+    backend
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// URL だけを持つ縮小画像の行をキーに置き換える。すでにキーを持っていれば `false`
+pub async fn set_variant_storage_key(db: &PgPool, id: i32, key: &str) -> Result<bool, sqlx::Error> {
+  set_variant_storage_key_with_executor(db, id, key).await
+}
+
+pub async fn set_variant_storage_key_with_executor<'e, E>(executor: E, id: i32, key: &str) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE picture_variants
+      SET storage_key = $2, image_url = NULL
+      WHERE id = $1 AND storage_key IS NULL
+    "#,
+    id,
+    key
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// `key` のオブジェクトを別の片付けが消していないことを確かめてから続けられるよう、`blobs` の行を作ってロックする。
+/// 同じキーに別の内容の SHA-256 が記録されていれば、記録を書き換えずに `false` を返す
+pub async fn reserve_blob(db: &PgPool, key: &str, sha256: &str, size: i64) -> Result<bool, sqlx::Error> {
+  reserve_blob_with_executor(db, key, sha256, size).await
+}
+
+pub async fn reserve_blob_with_executor<'e, E>(
+  executor: E,
+  key: &str,
+  sha256: &str,
+  size: i64,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  // 条件に合わず更新しない場合も、既存の行はロックされる
+  let result = sqlx::query!(
+    r#"
+      INSERT INTO blobs (storage_key, sha256, size)
+      VALUES ($1, $2, $3)
+      ON CONFLICT (storage_key) DO UPDATE SET sha256 = EXCLUDED.sha256
+      WHERE blobs.sha256 = EXCLUDED.sha256
+    "#,
+    key,
+    sha256,
+    size
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// `key` の `blobs` の行をロックして取得する。内容から決まるキーでなければ `None`
+pub async fn lock_blob(db: &PgPool, key: &str) -> Result<Option<Blob>, sqlx::Error> {
+  lock_blob_with_executor(db, key).await
+}
+
+pub async fn lock_blob_with_executor<'e, E>(executor: E, key: &str) -> Result<Option<Blob>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let blob = sqlx::query_as!(
+    Blob,
+    r#"
+      SELECT storage_key, sha256, size, ref_count, created_at
+      FROM blobs
+      WHERE storage_key = $1
+      FOR UPDATE
+    "#,
+    key
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(blob)
+}
+
+/// `keys` を指す行を作ったぶん参照数を増やす。同じキーが複数あればその数だけ増やす
+pub async fn add_blob_references(db: &PgPool, keys: &[&str]) -> Result<(), sqlx::Error> {
+  add_blob_references_with_executor(db, keys).await
+}
+
+pub async fn add_blob_references_with_executor<'e, E>(executor: E, keys: &[&str]) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE blobs
+      SET ref_count = ref_count + refs.count
+      FROM (SELECT key, COUNT(*)::INTEGER AS count FROM UNNEST($1::text[]) AS key GROUP BY key) AS refs
+      WHERE blobs.storage_key = refs.key
+    "#,
+    keys as &[&str]
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// `keys` を指す行を消したぶん参照数を減らす。同じキーが複数あればその数だけ減らす
+pub async fn remove_blob_references(db: &PgPool, keys: &[&str]) -> Result<(), sqlx::Error> {
+  remove_blob_references_with_executor(db, keys).await
+}
+
+pub async fn remove_blob_references_with_executor<'e, E>(executor: E, keys: &[&str]) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE blobs
+      SET ref_count = GREATEST(ref_count - refs.count, 0)
+      FROM (SELECT key, COUNT(*)::INTEGER AS count FROM UNNEST($1::text[]) AS key GROUP BY key) AS refs
+      WHERE blobs.storage_key = refs.key
+    "#,
+    keys as &[&str]
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn delete_blob(db: &PgPool, key: &str) -> Result<(), sqlx::Error> {
+  delete_blob_with_executor(db, key).await
+}
+
+pub async fn delete_blob_with_executor<'e, E>(executor: E, key: &str) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      DELETE FROM blobs
+      WHERE storage_key = $1
+    "#,
+    key
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// `id` 以外に、`key` へオブジェクトを置いて行を作ろうとしている途中のアップロードがあるか
+pub async fn has_other_uploads(db: &PgPool, key: &str, id: i64) -> Result<bool, sqlx::Error> {
+  has_other_uploads_with_executor(db, key, id).await
+}
+
+pub async fn has_other_uploads_with_executor<'e, E>(executor: E, key: &str, id: i64) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let exists = sqlx::query_scalar!(
+    r#"
+      SELECT EXISTS (
+        SELECT 1
+        FROM storage_operations
+        WHERE object_key = $1 AND operation = 'upload' AND id <> $2
+      ) as "exists!"
+    "#,
+    key,
+    id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(exists)
+}
@@ -0,0 +1,965 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::domains::picture::repository as picture_repository;
+use crate::storage::ObjectStorage;
+
+use super::model::{BackfillReport, GcAction, GcOptions, GcReport, OperationKind, StorageOperation, StorageReference};
+use super::repository;
+
+/// オブジェクトを置いてから行を作り終えるまでの猶予。これを過ぎても記録が残っていれば、行を作れなかったものとして消す
+const UNRECORDED_UPLOAD_GRACE: Duration = Duration::from_secs(60 * 60);
+/// 片付けのために取り出した操作を、ほかの片付けが取り出さないようにしておく期間
+const CLAIM_LEASE: Duration = Duration::from_secs(5 * 60);
+/// 片付けに失敗したときの最初の再試行までの間隔。失敗するたびに倍にする
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+/// 1回の片付けで扱う操作の上限
+const RECONCILE_BATCH_SIZE: i64 = 100;
+/// GC で行のないオブジェクトを移す先の接頭辞
+pub const QUARANTINE_PREFIX: &str = "quarantine/";
+
+/// `attempts` 回失敗した操作を次に試すまでの間隔
+pub(crate) fn retry_delay(attempts: i32) -> Duration {
+  RETRY_BASE_DELAY
+    .saturating_mul(1 << attempts.clamp(0, 16))
+    .min(RETRY_MAX_DELAY)
+}
+
+/// ストレージ上のオブジェクトと DB の行が、両方あるか両方ないかのどちらかになるよう、送信箱に記録した操作を片付ける
+pub struct StorageOperationService {
+  pool: PgPool,
+  storage: Arc<dyn ObjectStorage>,
+}
+
+impl StorageOperationService {
+  pub fn new(pool: PgPool, storage: Arc<dyn ObjectStorage>) -> Self {
+    Self { pool, storage }
+  }
+
+  /// オブジェクトを置く前に記録する。行を作るトランザクションで記録を取り除かなければ、猶予を過ぎたあとオブジェクトごと消す
+  pub async fn begin_upload(&self, key: &str) -> Result<StorageOperation, sqlx::Error> {
+    let mut operations = repository::create(&self.pool, OperationKind::Upload, &[key], UNRECORDED_UPLOAD_GRACE).await?;
+    Ok(operations.remove(0))
+  }
+
+  /// 内容から決まるキーへオブジェクトを置く前に記録する。
+  /// 同じキーのオブジェクトを消している途中の片付けがあれば、それが終わるのを待ってから記録する。
+  /// 同じキーに別の内容が記録されていれば何も記録せず `None` を返す
+  pub async fn begin_blob_upload(
+    &self,
+    key: &str,
+    sha256: &str,
+    size: i64,
+  ) -> Result<Option<StorageOperation>, sqlx::Error> {
+    let mut tx = self.pool.begin().await?;
+    if !repository::reserve_blob_with_executor(&mut *tx.as_mut(), key, sha256, size).await? {
+      return Ok(None);
+    }
+    let mut operations = repository::create_with_executor(
+      &mut *tx.as_mut(),
+      OperationKind::Upload,
+      &[key],
+      UNRECORDED_UPLOAD_GRACE,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(operations.remove(0)))
+  }
+
+  /// 記録した操作を片付け、片付けたものは記録も取り除いて、その件数を返す。
+  /// 片付けられなかったものは間隔を空けて再試行するよう記録を残す
+  pub async fn apply(&self, operations: Vec<StorageOperation>) -> usize {
+    let mut applied = 0;
+    for operation in &operations {
+      if self.try_apply(operation).await.is_some() {
+        applied += 1;
+      }
+    }
+
+    applied
+  }
+
+  /// 記録した操作を片付け、オブジェクトを消したかどうかを返す。
+  /// 片付けられなかったときは間隔を空けて再試行するよう記録を残して `None` を返す
+  async fn try_apply(&self, operation: &StorageOperation) -> Option<bool> {
+    match self.apply_one(operation).await {
+      Ok(deleted) => Some(deleted),
+      Err(e) => {
+        tracing::error!(
+          "Failed to delete object {} for {} operation (attempt {}): {:?}",
+          operation.object_key,
+          operation.operation,
+          operation.attempts + 1,
+          e
+        );
+        let retry_after = retry_delay(operation.attempts);
+        if let Err(e) = repository::record_failure(&self.pool, operation.id, &e.to_string(), retry_after).await {
+          tracing::error!("Failed to record storage operation {}: {:?}", operation.id, e);
+        }
+        None
+      }
+    }
+  }
+
+  /// オブジェクトを消して記録を取り除き、消したかどうかを返す。内容から決まるキーのオブジェクトは、
+  /// まだほかの行から指されているか、同じ内容を置こうとしている途中のアップロードがあれば消さずに記録だけ取り除く
+  async fn apply_one(&self, operation: &StorageOperation) -> anyhow::Result<bool> {
+    let key = operation.object_key.as_str();
+
+    // 同じキーへ置こうとしているアップロードと入れ違いにならないよう、オブジェクトを消し終えるまで blobs の行をロックしておく
+    let mut tx = self.pool.begin().await?;
+    let blob = repository::lock_blob_with_executor(&mut *tx.as_mut(), key).await?;
+    let in_use = match &blob {
+      Some(blob) => {
+        blob.ref_count > 0 || repository::has_other_uploads_with_executor(&mut *tx.as_mut(), key, operation.id).await?
+      }
+      None => false,
+    };
+
+    if !in_use {
+      self.storage.delete(key).await?;
+      if blob.is_some() {
+        repository::delete_blob_with_executor(&mut *tx.as_mut(), key).await?;
+      }
+    }
+    repository::delete_with_executor(&mut *tx.as_mut(), operation.id).await?;
+    tx.commit().await?;
+
+    Ok(!in_use)
+  }
+
+  /// 片付ける時刻を過ぎた操作を片付け、片付けた件数を返す
+  pub async fn reconcile(&self) -> Result<usize, sqlx::Error> {
+    let operations = repository::claim_due(&self.pool, RECONCILE_BATCH_SIZE, CLAIM_LEASE).await?;
+    Ok(self.apply(operations).await)
+  }
+
+  /// `prefix` 以下のオブジェクトと写真の行を突き合わせ、行のないオブジェクトとオブジェクトのない行を探して `action` のとおり片付ける
+  pub async fn garbage_collect(&self, options: &GcOptions) -> anyhow::Result<GcReport> {
+    // 一覧を取ったあとに作られた行を、オブジェクトのない行と取り違えないよう、行を先に読む
+    let references = repository::find_references(&self.pool, &options.prefix).await?;
+    // キーへの移行がまだの、URL だけを持つ行が指すオブジェクトも、削除と同じ規則でキーを取り出して参照として数える
+    let legacy_keys: Vec<String> = repository::find_legacy_references(&self.pool)
+      .await?
+      .iter()
+      .filter_map(|reference| self.storage.key_for_stored_url(&reference.image_url))
+      .collect();
+    let pending: HashSet<String> = repository::find_pending_keys(&self.pool).await?.into_iter().collect();
+    let objects = self.storage.list(&options.prefix).await?;
+
+    let referenced: HashSet<&str> = references
+      .iter()
+      .map(|r| r.storage_key.as_str())
+      .chain(legacy_keys.iter().map(String::as_str))
+      .collect();
+    let listed: HashSet<&str> = objects.iter().map(|object| object.key.as_str()).collect();
+    let cutoff = Utc::now() - options.min_age;
+
+    let mut report = GcReport {
+      scanned_objects: objects.len(),
+      ..Default::default()
+    };
+    for object in &objects {
+      if referenced.contains(object.key.as_str()) {
+        continue;
+      }
+      // 行を作っている途中のものや、片付け待ちのものは送信箱に任せる
+      if pending.contains(&object.key) || object.last_modified.is_none_or(|modified| modified > cutoff) {
+        report.skipped_objects += 1;
+        continue;
+      }
+      report.orphan_objects.push(object.key.clone());
+    }
+    for reference in &references {
+      if listed.contains(reference.storage_key.as_str()) || pending.contains(&reference.storage_key) {
+        continue;
+      }
+      // 一覧を取るまでの間に消された行かもしれないため、個別に確かめる
+      if self.storage.head(&reference.storage_key).await?.is_none() {
+        report.missing_objects.push(reference.clone());
+      }
+    }
+
+    if options.dry_run {
+      return Ok(report);
+    }
+    match options.action {
+      GcAction::Report => {}
+      GcAction::Delete => {
+        report.removed_objects = self.delete_orphans(&report.orphan_objects).await?;
+        report.removed_rows = self.delete_missing(&report.missing_objects, &references).await?;
+      }
+      GcAction::Quarantine => {
+        for key in &report.orphan_objects {
+          if self.quarantine(key).await? {
+            report.removed_objects += 1;
+          }
+        }
+      }
+    }
+
+    Ok(report)
+  }
+
+  /// キーを持たず URL だけを持つ行のうち、URL がこのストレージを指しているものをキーに置き換える。
+  /// 外部のサイトや別のバケットを指す URL はそのまま残して報告する
+  pub async fn backfill_storage_keys(&self, dry_run: bool) -> anyhow::Result<BackfillReport> {
+    let references = repository::find_legacy_references(&self.pool).await?;
+
+    let mut report = BackfillReport::default();
+    for reference in references {
+      let Some(key) = self.storage.key_for_stored_url(&reference.image_url) else {
+        report.skipped.push(reference);
+        continue;
+      };
+
+      if !dry_run {
+        let updated = match reference.variant_id {
+          None => {
+            repository::set_picture_storage_key(&self.pool, reference.picture_id, &key, self.storage.backend()).await?
+          }
+          Some(variant_id) => repository::set_variant_storage_key(&self.pool, variant_id, &key).await?,
+        };
+        if !updated {
+          continue;
+        }
+      }
+      report.converted += 1;
+    }
+
+    Ok(report)
+  }
+
+  /// 行のないオブジェクトを、送信箱に削除を記録して片付け、消した数を返す。
+  /// 一覧を取ったあとに同じ内容のアップロードが参照を増やしていることがあるため、`blobs` の行をロックして確かめ直してから消す
+  async fn delete_orphans(&self, keys: &[String]) -> Result<usize, sqlx::Error> {
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let deletes = repository::create(&self.pool, OperationKind::Delete, &keys, Duration::ZERO).await?;
+
+    let mut removed = 0;
+    for operation in &deletes {
+      if self.try_apply(operation).await == Some(true) {
+        removed += 1;
+      }
+    }
+    Ok(removed)
+  }
+
+  /// 行のないオブジェクトを隔離用の場所へ移し、移したかどうかを返す。元のオブジェクトは `delete_orphans` と同じく確かめ直してから消し、
+  /// その間に使われ始めていれば写しを消す。元のオブジェクトを消せなかったときは、送信箱が消し直すまで写しを残す
+  async fn quarantine(&self, key: &str) -> anyhow::Result<bool> {
+    let content_type = self
+      .storage
+      .head(key)
+      .await?
+      .and_then(|info| info.content_type)
+      .unwrap_or_else(|| "application/octet-stream".to_string());
+    let data = self.storage.get(key).await?;
+    let quarantined = format!("{}{}", QUARANTINE_PREFIX, key);
+    self.storage.put(&quarantined, data, &content_type).await?;
+
+    let deletes = repository::create(&self.pool, OperationKind::Delete, &[key], Duration::ZERO).await?;
+    match self.try_apply(&deletes[0]).await {
+      Some(true) => Ok(true),
+      Some(false) => {
+        self.storage.delete(&quarantined).await?;
+        Ok(false)
+      }
+      None => Ok(false),
+    }
+  }
+
+  /// オブジェクトのない行を消し、消した行の数を返す。元画像がない写真は、残っている縮小画像のオブジェクトごと消す
+  async fn delete_missing(
+    &self,
+    missing: &[StorageReference],
+    references: &[StorageReference],
+  ) -> Result<usize, sqlx::Error> {
+    let missing_pictures: HashSet<i32> = missing
+      .iter()
+      .filter(|reference| reference.variant_id.is_none())
+      .map(|reference| reference.picture_id)
+      .collect();
+
+    let mut picture_keys: BTreeMap<i32, Vec<&str>> = BTreeMap::new();
+    for reference in references {
+      if missing_pictures.contains(&reference.picture_id) {
+        picture_keys
+          .entry(reference.picture_id)
+          .or_default()
+          .push(&reference.storage_key);
+      }
+    }
+
+    let mut removed = 0;
+    for (picture_id, keys) in picture_keys {
+      let mut tx = self.pool.begin().await?;
+      picture_repository::delete_with_executor(&mut *tx.as_mut(), picture_id).await?;
+      let deletes = self.record_deletes(&mut tx, &keys).await?;
+      tx.commit().await?;
+
+      self.apply(deletes).await;
+      removed += 1;
+    }
+    for reference in missing {
+      match reference.variant_id {
+        Some(variant_id) if !missing_pictures.contains(&reference.picture_id) => {
+          let mut tx = self.pool.begin().await?;
+          picture_repository::delete_variant_with_executor(&mut *tx.as_mut(), variant_id).await?;
+          let deletes = self.record_deletes(&mut tx, &[&reference.storage_key]).await?;
+          tx.commit().await?;
+
+          self.apply(deletes).await;
+          removed += 1;
+        }
+        _ => {}
+      }
+    }
+
+    Ok(removed)
+  }
+
+  /// 行を消すトランザクションの中で、消した行が指していたオブジェクトの参照数を減らして削除を記録する
+  async fn record_deletes(
+    &self,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    keys: &[&str],
+  ) -> Result<Vec<StorageOperation>, sqlx::Error> {
+    repository::remove_blob_references_with_executor(&mut *tx.as_mut(), keys).await?;
+    repository::create_with_executor(&mut *tx.as_mut(), OperationKind::Delete, keys, Duration::ZERO).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::picture::{
+    format::{tests::sample_png, ImageLimits},
+    model::CreatePictureUploadRequest,
+    service::{FileChunks, PictureService, PictureServiceError, PictureServiceImpl},
+  };
+  use crate::domains::user::model::User;
+  use crate::storage::MemoryStorage;
+  use async_trait::async_trait;
+  use axum::body::Bytes;
+  use sha2::{Digest, Sha256};
+
+  struct CompleteBody(Option<Vec<u8>>);
+
+  #[async_trait]
+  impl FileChunks for CompleteBody {
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError> {
+      Ok(self.0.take().map(Bytes::from))
+    }
+  }
+
+  fn create_services(pool: &PgPool, storage: &MemoryStorage) -> (PictureServiceImpl, StorageOperationService) {
+    let storage: Arc<dyn ObjectStorage> = Arc::new(storage.clone());
+    (
+      PictureServiceImpl::new(pool.clone(), storage.clone(), ImageLimits::from_env()),
+      StorageOperationService::new(pool.clone(), storage),
+    )
+  }
+
+  async fn upload_picture(
+    service: &PictureServiceImpl,
+    user_id: i32,
+    file: Vec<u8>,
+  ) -> Result<crate::domains::picture::model::Picture, PictureServiceError> {
+    let staged = service.stage_upload(user_id, &mut CompleteBody(Some(file))).await?;
+    service.upload_and_create_picture(user_id, staged, None, None).await
+  }
+
+  /// `pictures` への `event` を DB の障害として失敗させる
+  async fn fail_pictures_on(pool: &PgPool, event: &str) -> Result<(), sqlx::Error> {
+    sqlx::raw_sql(&format!(
+      r#"
+        CREATE FUNCTION fail_pictures() RETURNS trigger AS $$
+        BEGIN
+          RAISE EXCEPTION 'injected failure';
+        END
+        $$ LANGUAGE plpgsql;
+        CREATE TRIGGER fail_pictures BEFORE {} ON pictures FOR EACH ROW EXECUTE FUNCTION fail_pictures();
+      "#,
+      event
+    ))
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  async fn object_count(storage: &MemoryStorage) -> usize {
+    storage.list("pictures/").await.unwrap().len() + storage.list("uploads/").await.unwrap().len()
+  }
+
+  async fn operation_count(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM storage_operations")
+      .fetch_one(pool)
+      .await
+  }
+
+  async fn make_operations_due(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE storage_operations SET next_attempt_at = NOW()")
+      .execute(pool)
+      .await?;
+    Ok(())
+  }
+
+  #[test]
+  fn test_retry_delay_doubles_up_to_max() {
+    assert_eq!(retry_delay(0), RETRY_BASE_DELAY);
+    assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 4);
+    assert_eq!(retry_delay(100), RETRY_MAX_DELAY);
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn failed_picture_insert_leaves_no_object(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "insert@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, _) = create_services(&pool, &storage);
+    fail_pictures_on(&pool, "INSERT").await?;
+
+    assert!(upload_picture(&pictures, user.id, sample_png(40, 30)).await.is_err());
+
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn object_left_by_failed_insert_is_reconciled(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "orphan@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    fail_pictures_on(&pool, "INSERT").await?;
+    storage.fail_deletes(true);
+
+    assert!(upload_picture(&pictures, user.id, sample_png(40, 30)).await.is_err());
+
+    // 行のない写真が残るが、送信箱に記録されている
+    assert_eq!(object_count(&storage).await, 1);
+    assert_eq!(operation_count(&pool).await?, 1);
+
+    storage.fail_deletes(false);
+    assert_eq!(operations.reconcile().await?, 0);
+    make_operations_due(&pool).await?;
+    assert_eq!(operations.reconcile().await?, 1);
+
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn failed_row_delete_keeps_objects(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "rowdelete@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, _) = create_services(&pool, &storage);
+    let picture = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let objects = object_count(&storage).await;
+    fail_pictures_on(&pool, "DELETE").await?;
+
+    assert!(pictures.delete_picture(picture.id, user.id).await.is_err());
+
+    // 行が残っている間は、行が指すオブジェクトも消さない
+    assert!(pictures.get_picture(picture.id, None).await.is_ok());
+    assert_eq!(object_count(&storage).await, objects);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn failed_object_delete_is_retried(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "objectdelete@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let picture = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let keys = picture.storage_keys(&storage).len();
+    storage.fail_deletes(true);
+
+    pictures.delete_picture(picture.id, user.id).await.unwrap();
+
+    assert!(pictures.get_picture(picture.id, None).await.is_err());
+    assert_eq!(object_count(&storage).await, keys);
+    let attempts: Vec<i32> = sqlx::query_scalar(
+      "SELECT attempts FROM storage_operations WHERE operation = 'delete' AND next_attempt_at > NOW()",
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(attempts, vec![1; keys]);
+
+    // 再試行でも失敗したものは、次の再試行まで間隔を空ける
+    make_operations_due(&pool).await?;
+    assert_eq!(operations.reconcile().await?, 0);
+    let attempts: Vec<i32> = sqlx::query_scalar("SELECT attempts FROM storage_operations")
+      .fetch_all(&pool)
+      .await?;
+    assert_eq!(attempts, vec![2; keys]);
+
+    storage.fail_deletes(false);
+    make_operations_due(&pool).await?;
+    assert_eq!(operations.reconcile().await?, keys);
+
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn expired_uploads_are_deleted_through_outbox(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "expired@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    for file in [sample_png(40, 30), sample_png(30, 20)] {
+      let upload = pictures
+        .create_upload(
+          user.id,
+          CreatePictureUploadRequest {
+            content_type: "image/png".to_string(),
+            content_length: file.len() as i64,
+            request_id: None,
+          },
+        )
+        .await
+        .unwrap();
+      let object_key: String = sqlx::query_scalar("SELECT object_key FROM picture_uploads WHERE id = $1")
+        .bind(upload.upload_id)
+        .fetch_one(&pool)
+        .await?;
+      storage.put(&object_key, file, "image/png").await.unwrap();
+    }
+    sqlx::query("UPDATE picture_uploads SET expires_at = NOW() - INTERVAL '1 minute'")
+      .execute(&pool)
+      .await?;
+    storage.fail_deletes(true);
+
+    // ファイルを消せなくても行は消え、削除は送信箱から再試行される
+    assert_eq!(pictures.cleanup_expired_uploads().await.unwrap(), 2);
+    let uploads: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM picture_uploads")
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(uploads, 0);
+    assert_eq!(object_count(&storage).await, 2);
+    assert_eq!(operation_count(&pool).await?, 2);
+
+    storage.fail_deletes(false);
+    make_operations_due(&pool).await?;
+    assert_eq!(operations.reconcile().await?, 2);
+
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  fn gc_options(action: GcAction, dry_run: bool) -> GcOptions {
+    GcOptions {
+      prefix: "pictures/".to_string(),
+      action,
+      dry_run,
+      min_age: Duration::ZERO,
+    }
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn garbage_collect_finds_and_fixes_inconsistencies(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "gc@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let kept = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let broken = upload_picture(&pictures, user.id, sample_png(30, 20)).await.unwrap();
+    let broken_key = broken.storage_key.clone().unwrap();
+    storage.delete(&broken_key).await.unwrap();
+    storage
+      .put("pictures/1/orphan.png", sample_png(4, 4), "image/png")
+      .await
+      .unwrap();
+    // 行を作っている途中のオブジェクトは対象にしない
+    let in_flight = operations.begin_upload("pictures/1/in-flight.png").await?;
+    storage
+      .put(&in_flight.object_key, sample_png(4, 4), "image/png")
+      .await
+      .unwrap();
+
+    let report = operations
+      .garbage_collect(&gc_options(GcAction::Delete, true))
+      .await
+      .unwrap();
+    assert_eq!(report.orphan_objects, vec!["pictures/1/orphan.png".to_string()]);
+    assert_eq!(report.skipped_objects, 1);
+    assert_eq!(
+      report.missing_objects,
+      vec![StorageReference {
+        picture_id: broken.id,
+        variant_id: None,
+        storage_key: broken_key,
+      }]
+    );
+    assert_eq!(report.removed_objects + report.removed_rows, 0);
+    assert!(storage.head("pictures/1/orphan.png").await.unwrap().is_some());
+
+    let report = operations
+      .garbage_collect(&gc_options(GcAction::Quarantine, false))
+      .await
+      .unwrap();
+    assert_eq!(report.removed_objects, 1);
+    assert!(storage.head("pictures/1/orphan.png").await.unwrap().is_none());
+    assert!(storage
+      .head("quarantine/pictures/1/orphan.png")
+      .await
+      .unwrap()
+      .is_some());
+    assert!(pictures.get_picture(broken.id, None).await.is_ok());
+
+    let report = operations
+      .garbage_collect(&gc_options(GcAction::Delete, false))
+      .await
+      .unwrap();
+    assert_eq!(report.removed_rows, 1);
+    assert!(pictures.get_picture(broken.id, None).await.is_err());
+    assert!(pictures.get_picture(kept.id, None).await.is_ok());
+    // 消した写真の縮小画像も残さない
+    let remaining: Vec<String> = storage
+      .list("pictures/")
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|object| object.key)
+      .collect();
+    let mut expected = kept.storage_keys(&storage);
+    expected.push(in_flight.object_key.clone());
+    expected.sort();
+    assert_eq!(remaining, expected);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn garbage_collect_keeps_objects_reused_after_listing(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "gc-race@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let uploads = StorageOperationService::new(pool.clone(), Arc::new(storage.clone()));
+    let png = sample_png(40, 30);
+    let reused = upload_picture(&pictures, user.id, png.clone()).await.unwrap();
+    let abandoned = upload_picture(&pictures, user.id, sample_png(30, 20)).await.unwrap();
+    let in_flight_key = abandoned.storage_key.clone().unwrap();
+    // 行が消えて参照数も 0 になり、オブジェクトだけが残った状態にする
+    sqlx::query("DELETE FROM pictures WHERE id = ANY($1)")
+      .bind(vec![reused.id, abandoned.id])
+      .execute(&pool)
+      .await?;
+    sqlx::query("UPDATE blobs SET ref_count = 0").execute(&pool).await?;
+    let (sha256, size): (String, i64) = sqlx::query_as("SELECT sha256, size FROM blobs WHERE storage_key = $1")
+      .bind(&in_flight_key)
+      .fetch_one(&pool)
+      .await?;
+
+    let pause = storage.pause_next_list();
+    let gc = tokio::spawn(async move {
+      operations
+        .garbage_collect(&gc_options(GcAction::Delete, false))
+        .await
+        .unwrap()
+    });
+    pause.listed.notified().await;
+    // 一覧を取ったあとで、同じ内容の写真が作られ、別のオブジェクトへのアップロードが始まる
+    let recreated = upload_picture(&pictures, user.id, png).await.unwrap();
+    let in_flight = uploads.begin_blob_upload(&in_flight_key, &sha256, size).await?.unwrap();
+    pause.resume.notify_one();
+    let report = gc.await.unwrap();
+
+    let mut orphans = [reused.storage_keys(&storage), abandoned.storage_keys(&storage)].concat();
+    orphans.sort();
+    assert_eq!(report.orphan_objects, orphans);
+    // 作られた写真のオブジェクトと、アップロード中のオブジェクトは消さない
+    for key in recreated.storage_keys(&storage) {
+      assert!(storage.head(&key).await.unwrap().is_some(), "{}", key);
+    }
+    assert!(pictures.get_picture(recreated.id, None).await.is_ok());
+    assert!(storage.head(&in_flight_key).await.unwrap().is_some());
+    for key in abandoned.storage_keys(&storage).iter().skip(1) {
+      assert!(storage.head(key).await.unwrap().is_none(), "{}", key);
+    }
+    assert_eq!(report.removed_objects, abandoned.storage_keys(&storage).len() - 1);
+    let remaining: Vec<i64> = sqlx::query_scalar("SELECT id FROM storage_operations")
+      .fetch_all(&pool)
+      .await?;
+    assert_eq!(remaining, vec![in_flight.id]);
+
+    Ok(())
+  }
+
+  async fn ref_count(pool: &PgPool, key: &str) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT ref_count FROM blobs WHERE storage_key = $1")
+      .bind(key)
+      .fetch_optional(pool)
+      .await
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn variant_blobs_record_their_own_content(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "variantblob@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let picture = upload_picture(&pictures, user.id, sample_png(600, 400)).await.unwrap();
+    assert_eq!(picture.variants.len(), 2);
+
+    // 縮小画像のキーも、記録した SHA-256 も、置いた内容から決まる
+    for variant in picture.variants.values() {
+      let key = variant.storage_key.clone().unwrap();
+      let data = storage.get(&key).await.unwrap();
+      let sha256: String = sqlx::query_scalar("SELECT sha256 FROM blobs WHERE storage_key = $1")
+        .bind(&key)
+        .fetch_one(&pool)
+        .await?;
+      assert_eq!(sha256, format!("{:x}", Sha256::digest(&data)));
+      assert!(key.starts_with(&format!("pictures/{}.", sha256)));
+    }
+
+    // 別の内容として記録しようとしても、記録済みの SHA-256 は書き換えない
+    let key = picture.storage_key.clone().unwrap();
+    assert!(operations
+      .begin_blob_upload(&key, "0".repeat(64).as_str(), 1)
+      .await?
+      .is_none());
+    let sha256: String = sqlx::query_scalar("SELECT sha256 FROM blobs WHERE storage_key = $1")
+      .bind(&key)
+      .fetch_one(&pool)
+      .await?;
+    assert!(key.contains(&sha256));
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn identical_uploads_share_one_object_until_last_delete(pool: PgPool) -> Result<(), sqlx::Error> {
+    let alice = User::create(&pool, "alice@example.com", "Alice", "password123").await?;
+    let bob = User::create(&pool, "bob@example.com", "Bob", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, _) = create_services(&pool, &storage);
+
+    let first = upload_picture(&pictures, alice.id, sample_png(40, 30)).await.unwrap();
+    let objects = object_count(&storage).await;
+    let second = upload_picture(&pictures, bob.id, sample_png(40, 30)).await.unwrap();
+
+    let key = first.storage_key.clone().unwrap();
+    assert_eq!(second.storage_key.as_deref(), Some(key.as_str()));
+    assert_eq!(object_count(&storage).await, objects);
+    assert_eq!(ref_count(&pool, &key).await?, Some(2));
+
+    // ほかの写真が指しているうちはオブジェクトを消さない
+    pictures.delete_picture(first.id, alice.id).await.unwrap();
+    assert_eq!(ref_count(&pool, &key).await?, Some(1));
+    assert!(storage.head(&key).await.unwrap().is_some());
+    assert!(pictures.get_picture(second.id, None).await.is_ok());
+
+    pictures.delete_picture(second.id, bob.id).await.unwrap();
+    assert_eq!(ref_count(&pool, &key).await?, None);
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn concurrent_uploads_stay_within_buffer_budget(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "buffered@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let sizes = [(400, 300), (300, 200), (200, 100)];
+
+    // 1ファイル分・1枚分の予算しかなければ、同時に届いても読み込みとデコードは順番に待つ
+    let (pictures, _) = create_services(&pool, &storage);
+    let pictures = pictures.with_buffer_budget(1024).with_decode_budget(400 * 300 * 4 * 3);
+    let (first, second, third) = tokio::join!(
+      upload_picture(&pictures, user.id, sample_png(sizes[0].0, sizes[0].1)),
+      upload_picture(&pictures, user.id, sample_png(sizes[1].0, sizes[1].1)),
+      upload_picture(&pictures, user.id, sample_png(sizes[2].0, sizes[2].1)),
+    );
+    assert_eq!(pictures.peak_buffered_bytes(), 1024);
+    // デコードしたピクセルの分も、縮小のためのコピーを含めて予算から確保する
+    assert_eq!(pictures.peak_decoded_bytes(), 400 * 300 * 4 * 3);
+
+    // メタデータを取り除いたファイルを保存先のキーへ一度だけ置き、一時的な場所には置かない
+    let written = storage.written_keys();
+    assert!(written.iter().all(|key| key.starts_with("pictures/")));
+    for picture in [first.unwrap(), second.unwrap(), third.unwrap()] {
+      let key = picture.storage_key.unwrap();
+      assert_eq!(written.iter().filter(|written| **written == key).count(), 1);
+    }
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn failed_insert_keeps_shared_object(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "shared@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, _) = create_services(&pool, &storage);
+    let picture = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let key = picture.storage_key.clone().unwrap();
+    let objects = object_count(&storage).await;
+    fail_pictures_on(&pool, "INSERT").await?;
+
+    assert!(upload_picture(&pictures, user.id, sample_png(40, 30)).await.is_err());
+
+    // 行を作れなかったアップロードを片付けても、すでにある写真のオブジェクトは残す
+    assert!(storage.head(&key).await.unwrap().is_some());
+    assert_eq!(object_count(&storage).await, objects);
+    assert_eq!(ref_count(&pool, &key).await?, Some(1));
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  /// 写真と縮小画像の行を、キーの代わりに `prefix` を付けた URL を持つ古い形に戻す
+  #[sqlx::test(migrations = "./migrations")]
+  async fn legacy_rows_are_deleted_before_backfill(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "legacy@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, _) = create_services(&pool, &storage);
+    let public = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let private = upload_picture(&pictures, user.id, sample_png(30, 20)).await.unwrap();
+    make_legacy(&pool, public.id, "http://storage.invalid/").await?;
+    make_legacy(&pool, private.id, "").await?;
+    // キーを持つ前の行には blobs の行もない
+    sqlx::query("DELETE FROM blobs").execute(&pool).await?;
+
+    // URL しか持たない行を消しても、オブジェクトを残さない
+    pictures.delete_picture(public.id, user.id).await.unwrap();
+    pictures.delete_picture(private.id, user.id).await.unwrap();
+    assert_eq!(object_count(&storage).await, 0);
+    assert_eq!(operation_count(&pool).await?, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn garbage_collect_keeps_objects_of_legacy_rows(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "legacygc@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let public = upload_picture(&pictures, user.id, sample_png(600, 400)).await.unwrap();
+    let private = upload_picture(&pictures, user.id, sample_png(30, 20)).await.unwrap();
+    make_legacy(&pool, public.id, "http://storage.invalid/").await?;
+    make_legacy(&pool, private.id, "").await?;
+    // キーを持つ前の行には blobs の行もない
+    sqlx::query("DELETE FROM blobs").execute(&pool).await?;
+    storage
+      .put("pictures/1/orphan.png", sample_png(4, 4), "image/png")
+      .await
+      .unwrap();
+    let objects = object_count(&storage).await;
+
+    // キーへの移行の前でも、URL だけを持つ行が指すオブジェクトは消さない
+    let report = operations
+      .garbage_collect(&gc_options(GcAction::Delete, false))
+      .await
+      .unwrap();
+    assert_eq!(report.orphan_objects, vec!["pictures/1/orphan.png".to_string()]);
+    assert!(report.missing_objects.is_empty());
+    assert_eq!(object_count(&storage).await, objects - 1);
+    for key in [public.storage_keys(&storage), private.storage_keys(&storage)].concat() {
+      assert!(storage.head(&key).await.unwrap().is_some());
+    }
+
+    Ok(())
+  }
+
+  async fn make_legacy(pool: &PgPool, picture_id: i32, prefix: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE picture_variants SET image_url = $2 || storage_key, storage_key = NULL WHERE picture_id = $1")
+      .bind(picture_id)
+      .bind(prefix)
+      .execute(pool)
+      .await?;
+    sqlx::query(
+      "UPDATE pictures SET image_url = $2 || storage_key, storage_key = NULL, storage_backend = NULL WHERE id = $1",
+    )
+    .bind(picture_id)
+    .bind(prefix)
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  async fn stored_keys(pool: &PgPool, picture_id: i32) -> Result<Vec<(Option<String>, Option<String>)>, sqlx::Error> {
+    sqlx::query_as(
+      r#"
+      SELECT storage_key, image_url FROM pictures WHERE id = $1
+      UNION ALL
+      (SELECT storage_key, image_url FROM picture_variants WHERE picture_id = $1 ORDER BY id)
+      "#,
+    )
+    .bind(picture_id)
+    .fetch_all(pool)
+    .await
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn backfill_converts_only_urls_of_this_storage(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = User::create(&pool, "backfill@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new();
+    let (pictures, operations) = create_services(&pool, &storage);
+    let public = upload_picture(&pictures, user.id, sample_png(40, 30)).await.unwrap();
+    let private = upload_picture(&pictures, user.id, sample_png(30, 20)).await.unwrap();
+    let external = upload_picture(&pictures, user.id, sample_png(20, 10)).await.unwrap();
+    let uploaded = upload_picture(&pictures, user.id, sample_png(10, 10)).await.unwrap();
+    let public_keys = stored_keys(&pool, public.id).await?;
+    let private_keys = stored_keys(&pool, private.id).await?;
+    make_legacy(&pool, public.id, "http://storage.invalid/").await?;
+    // 非公開バケットの頃はキーそのものを保存していた
+    make_legacy(&pool, private.id, "").await?;
+    make_legacy(&pool, external.id, "https://cdn.example.com/").await?;
+    sqlx::query("UPDATE pictures SET image_url = $2, storage_key = NULL, storage_backend = NULL WHERE id = $1")
+      .bind(uploaded.id)
+      .bind("http://storage.invalid/uploads/1/legacy.png?v=1")
+      .execute(&pool)
+      .await?;
+    let external_rows = stored_keys(&pool, external.id).await?;
+
+    let report = operations.backfill_storage_keys(true).await.unwrap();
+    assert_eq!(report.converted, public_keys.len() + private_keys.len() + 1);
+    assert_eq!(report.skipped.len(), external_rows.len());
+    assert!(report
+      .skipped
+      .iter()
+      .all(|reference| reference.picture_id == external.id));
+    assert_eq!(stored_keys(&pool, external.id).await?, external_rows);
+    assert!(stored_keys(&pool, public.id)
+      .await?
+      .iter()
+      .all(|(key, _)| key.is_none()));
+
+    let report = operations.backfill_storage_keys(false).await.unwrap();
+    assert_eq!(report.converted, public_keys.len() + private_keys.len() + 1);
+    assert_eq!(stored_keys(&pool, public.id).await?, public_keys);
+    assert_eq!(stored_keys(&pool, private.id).await?, private_keys);
+    assert_eq!(
+      stored_keys(&pool, uploaded.id).await?[0],
+      (Some("uploads/1/legacy.png".to_string()), None)
+    );
+    let backend: Option<String> = sqlx::query_scalar("SELECT storage_backend FROM pictures WHERE id = $1")
+      .bind(public.id)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(backend.as_deref(), Some(storage.backend()));
+    // このストレージを指していない URL は残す
+    assert_eq!(stored_keys(&pool, external.id).await?, external_rows);
+    assert_eq!(report.skipped.len(), external_rows.len());
+
+    // 置き換え済みの行は二度目には対象にならない
+    let report = operations.backfill_storage_keys(false).await.unwrap();
+    assert_eq!(report.converted, 0);
+
+    Ok(())
+  }
+}
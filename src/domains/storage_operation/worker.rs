@@ -0,0 +1,37 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use super::service::StorageOperationService;
+
+const DEFAULT_STORAGE_RECONCILE_INTERVAL_SECS: u64 = 60;
+
+pub fn reconcile_interval_from_env() -> Duration {
+  let secs = std::env::var("STORAGE_RECONCILE_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(DEFAULT_STORAGE_RECONCILE_INTERVAL_SECS);
+
+  Duration::from_secs(secs)
+}
+
+/// 送信箱に残ったストレージへの操作を定期的に片付けるバックグラウンドタスクを起動する
+pub fn spawn_storage_reconcile_worker(
+  storage_operation_service: Arc<StorageOperationService>,
+  interval: Duration,
+) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match storage_operation_service.reconcile().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Reconciled {} storage operations", count),
+        Err(e) => tracing::error!("Failed to reconcile storage operations: {}", e),
+      }
+    }
+  })
+}
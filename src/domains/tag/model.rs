@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::ValidationError;
+
+/// 正規化後のタグの最大文字数
+pub const MAX_TAG_CHARS: usize = 30;
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct PopularTag {
+  pub name: String,
+  pub request_count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PopularTagsResponse {
+  pub tags: Vec<PopularTag>,
+}
+
+/// タグを正規化する。前後の空白と先頭の `#` を除き、小文字化し、途中の空白は `-` にまとめる。空になる場合は `None`
+pub fn normalize_tag(raw: &str) -> Option<String> {
+  let trimmed = raw.trim().trim_start_matches('#');
+  let normalized = trimmed.split_whitespace().collect::<Vec<_>>().join("-").to_lowercase();
+
+  if normalized.is_empty() {
+    None
+  } else {
+    Some(normalized)
+  }
+}
+
+/// タグを正規化し、入力順を保ったまま重複を取り除く
+pub fn normalize_tags(raw: &[String]) -> Vec<String> {
+  let mut tags: Vec<String> = Vec::new();
+  for tag in raw.iter().filter_map(|t| normalize_tag(t)) {
+    if !tags.contains(&tag) {
+      tags.push(tag);
+    }
+  }
+  tags
+}
+
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+  for tag in tags {
+    match normalize_tag(tag) {
+      None => return Err(ValidationError::new("タグは空にできません")),
+      Some(tag) if tag.chars().count() > MAX_TAG_CHARS => {
+        return Err(ValidationError::new("タグは30文字以内である必要があります"));
+      }
+      Some(_) => {}
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalize_tag() {
+    assert_eq!(normalize_tag("  #Sunset "), Some("sunset".to_string()));
+    assert_eq!(normalize_tag("Tokyo  Tower"), Some("tokyo-tower".to_string()));
+    assert_eq!(normalize_tag("夜景"), Some("夜景".to_string()));
+    assert_eq!(normalize_tag(" # "), None);
+  }
+
+  #[test]
+  fn test_normalize_tags_deduplicates() {
+    let raw = vec![
+      "Sunset".to_string(),
+      "#sunset".to_string(),
+      "".to_string(),
+      "夜景".to_string(),
+    ];
+    assert_eq!(normalize_tags(&raw), vec!["sunset".to_string(), "夜景".to_string()]);
+  }
+
+  #[test]
+  fn test_validate_tags() {
+    assert!(validate_tags(&["sunset".to_string(), "#夜景".to_string()]).is_ok());
+
+    let result = validate_tags(&["  ".to_string()]);
+    assert!(format!("{:?}", result.unwrap_err()).contains("タグは空にできません"));
+
+    let result = validate_tags(&["a".repeat(MAX_TAG_CHARS + 1)]);
+    assert!(format!("{:?}", result.unwrap_err()).contains("タグは30文字以内である必要があります"));
+  }
+}
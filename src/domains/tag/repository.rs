@@ -0,0 +1,106 @@
+use sqlx::{Executor, PgPool, Postgres};
+
+use super::model::PopularTag;
+
+/// リクエストのタグを `names`（正規化・重複除去済み）で置き換える。未登録のタグは作成する
+pub async fn replace_for_request(db: &PgPool, request_id: i32, names: &[String]) -> Result<(), sqlx::Error> {
+  replace_for_request_with_executor(db, request_id, names).await
+}
+
+pub async fn replace_for_request_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  names: &[String],
+) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  // 既存タグの id も RETURNING で受け取るため、衝突時は同じ値で更新する
+  sqlx::query!(
+    r#"
+      WITH tag_ids AS (
+        INSERT INTO tags (name)
+        SELECT unnest($2::text[])
+        ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+        RETURNING id
+      ), removed AS (
+        DELETE FROM request_tags
+        WHERE request_id = $1
+          AND tag_id NOT IN (SELECT id FROM tag_ids)
+      )
+      INSERT INTO request_tags (request_id, tag_id)
+      SELECT $1, id FROM tag_ids
+      ON CONFLICT DO NOTHING
+    "#,
+    request_id,
+    names
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// 期限切れでないリクエストに付いている数が多い順にタグを返す
+pub async fn find_popular(db: &PgPool, limit: i64) -> Result<Vec<PopularTag>, sqlx::Error> {
+  find_popular_with_executor(db, limit).await
+}
+
+pub async fn find_popular_with_executor<'e, E>(executor: E, limit: i64) -> Result<Vec<PopularTag>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let tags = sqlx::query_as!(
+    PopularTag,
+    r#"
+      SELECT t.name, COUNT(*) as "request_count!"
+      FROM tags t
+      JOIN request_tags rt ON rt.tag_id = t.id
+      JOIN requests r ON r.id = rt.request_id
+      WHERE r.status <> 'expired'
+      GROUP BY t.id, t.name
+      ORDER BY COUNT(*) DESC, t.name ASC
+      LIMIT $1
+    "#,
+    limit
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::request::repository as request_repository;
+  use crate::test_support::request_payload;
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn replace_for_request_and_find_popular(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let user = crate::domains::user::model::User::create(&pool, "tags@example.com", "Tags", "password123").await?;
+    let first = request_repository::create(&pool, user.id, &request_payload(35.0, 139.0, "一つ目", "説明")).await?;
+    let second = request_repository::create(&pool, user.id, &request_payload(35.0, 139.0, "二つ目", "説明")).await?;
+
+    replace_for_request(&pool, first.id, &["sunset".to_string(), "夜景".to_string()]).await?;
+    replace_for_request(&pool, second.id, &["sunset".to_string()]).await?;
+
+    let popular = find_popular(&pool, 10).await?;
+    assert_eq!(popular.len(), 2);
+    assert_eq!(popular[0].name, "sunset");
+    assert_eq!(popular[0].request_count, 2);
+    assert_eq!(popular[1].name, "夜景");
+
+    // 置き換えで外したタグの関連は消え、タグ自体は再利用される
+    replace_for_request(&pool, first.id, &["夜景".to_string(), "tower".to_string()]).await?;
+    let first = request_repository::find_by_id(&pool, first.id).await?.expect("request");
+    assert_eq!(first.tags, vec!["tower".to_string(), "夜景".to_string()]);
+
+    let tag_count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM tags"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(tag_count, 3);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,78 @@
+use axum::{
+  extract::{Query, State},
+  response::Json as JsonResponse,
+  routing::get,
+  Router,
+};
+use serde::Deserialize;
+
+use super::model::PopularTagsResponse;
+use crate::{
+  state::{AppState, SharedAppState},
+  AppError,
+};
+
+const DEFAULT_POPULAR_TAGS_LIMIT: i64 = 20;
+const MAX_POPULAR_TAGS_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct PopularTagsQuery {
+  pub limit: Option<i64>,
+}
+
+pub fn tag_routes() -> Router<SharedAppState> {
+  Router::new().route("/tags/popular", get(get_popular_tags_handler))
+}
+
+pub async fn get_popular_tags_handler(
+  State(state): State<SharedAppState>,
+  Query(query): Query<PopularTagsQuery>,
+) -> Result<JsonResponse<PopularTagsResponse>, AppError> {
+  let limit = query.limit.unwrap_or(DEFAULT_POPULAR_TAGS_LIMIT);
+  if !(1..=MAX_POPULAR_TAGS_LIMIT).contains(&limit) {
+    return Err(AppError::bad_request(format!(
+      "limit must be between 1 and {}",
+      MAX_POPULAR_TAGS_LIMIT
+    )));
+  }
+
+  state
+    .get_popular_tags(limit)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::model::PopularTagsResponse;
+  use crate::test_support::{app_with_pool, get};
+  use axum::http::StatusCode;
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn get_popular_tags_success(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let user =
+      crate::domains::user::model::User::create(&pool, "popular@example.com", "Popular", "password123").await?;
+    let request = crate::domains::request::repository::create(
+      &pool,
+      user.id,
+      &crate::test_support::request_payload(35.0, 139.0, "東京", "説明"),
+    )
+    .await?;
+    super::super::repository::replace_for_request(&pool, request.id, &["sunset".to_string()]).await?;
+
+    let (status, body) = get(app.clone(), "/api/v1/tags/popular?limit=5").await;
+    assert_eq!(status, StatusCode::OK);
+    let response: PopularTagsResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.tags.len(), 1);
+    assert_eq!(response.tags[0].name, "sunset");
+    assert_eq!(response.tags[0].request_count, 1);
+
+    let (status, _) = get(app, "/api/v1/tags/popular?limit=0").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,39 @@
+use sqlx::PgPool;
+use std::error::Error;
+
+use crate::domains::tag::{model::PopularTagsResponse, repository};
+use crate::impl_service_error_conversions;
+
+#[derive(Debug)]
+pub enum TagServiceError {
+  InternalServerError(String),
+  BadRequest(String),
+}
+
+impl Error for TagServiceError {}
+
+impl std::fmt::Display for TagServiceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      TagServiceError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
+      TagServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+    }
+  }
+}
+
+impl_service_error_conversions!(TagServiceError, InternalServerError);
+
+pub struct TagService {
+  pool: PgPool,
+}
+
+impl TagService {
+  pub fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  pub async fn get_popular_tags(&self, limit: i64) -> Result<PopularTagsResponse, TagServiceError> {
+    let tags = repository::find_popular(&self.pool, limit).await?;
+    Ok(PopularTagsResponse { tags })
+  }
+}
@@ -0,0 +1,5 @@
+pub mod model;
+pub mod repository;
+pub mod rest;
+pub mod service;
+pub mod worker;
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, FromRow};
+use uuid::Uuid;
+
+use crate::storage::UploadedPart;
+
+/// tus プロトコルで受け取り中のアップロード
+#[derive(Debug, Clone, FromRow)]
+pub struct ResumableUpload {
+  pub id: Uuid,
+  pub user_id: i32,
+  pub request_id: Option<i32>,
+  pub reported_lat: Option<f64>,
+  pub reported_lng: Option<f64>,
+  pub object_key: String,
+  pub content_type: String,
+  pub upload_length: i64,
+  pub upload_offset: i64,
+  pub multipart_upload_id: String,
+  pub part_size: i64,
+  /// ストレージへ送り終えたパート。最後以外はすべて `part_size` バイト
+  pub parts: Json<Vec<UploadedPart>>,
+  /// パートをつなげ終えた日時。つなげたあとはマルチパートアップロードではなく `object_key` のオブジェクトになる
+  pub multipart_completed_at: Option<DateTime<Utc>>,
+  pub picture_id: Option<i32>,
+  pub completed_at: Option<DateTime<Utc>>,
+  pub expires_at: DateTime<Utc>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl ResumableUpload {
+  /// パートの大きさに満たないため、パートとは別に置いている末尾のバイト数。
+  /// 最後まで届いたあとは最後のパートも `part_size` に満たないため、末尾はない
+  pub fn tail_length(&self) -> i64 {
+    (self.upload_offset - self.parts.len() as i64 * self.part_size).max(0)
+  }
+
+  /// 末尾を置くオブジェクトのキー
+  pub fn tail_key(&self) -> String {
+    format!("{}.tail", self.object_key)
+  }
+
+  pub fn reported_location(&self) -> Option<(f64, f64)> {
+    self.reported_lat.zip(self.reported_lng)
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.upload_offset == self.upload_length
+  }
+}
+
+/// アップロードの作成時に `Upload-Length` と `Upload-Metadata` から読み取る内容
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CreateResumableUploadRequest {
+  pub upload_length: i64,
+  /// JPEG・PNG・WebP・HEIC の MIME タイプ
+  pub content_type: String,
+  pub request_id: Option<i32>,
+  pub reported_location: Option<(f64, f64)>,
+}
+
+/// HEAD と PATCH でクライアントに返すアップロードの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumableUploadStatus {
+  pub upload_offset: i64,
+  pub upload_length: i64,
+  /// 受け取り終えて写真として保存した場合の写真の ID
+  pub picture_id: Option<i32>,
+}
+
+impl From<&ResumableUpload> for ResumableUploadStatus {
+  fn from(upload: &ResumableUpload) -> Self {
+    Self {
+      upload_offset: upload.upload_offset,
+      upload_length: upload.upload_length,
+      picture_id: upload.picture_id,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_tail_length_excludes_uploaded_parts() {
+    let part = UploadedPart {
+      part_number: 1,
+      etag: "etag".to_string(),
+    };
+    let upload = ResumableUpload {
+      id: Uuid::new_v4(),
+      user_id: 1,
+      request_id: None,
+      reported_lat: None,
+      reported_lng: None,
+      object_key: "uploads/1/a.png".to_string(),
+      content_type: "image/png".to_string(),
+      upload_length: 100,
+      upload_offset: 25,
+      multipart_upload_id: "upload".to_string(),
+      part_size: 10,
+      parts: Json(vec![part.clone(), UploadedPart { part_number: 2, ..part }]),
+      multipart_completed_at: None,
+      picture_id: None,
+      completed_at: None,
+      expires_at: Utc::now(),
+      created_at: Utc::now(),
+    };
+
+    assert_eq!(upload.tail_length(), 5);
+    assert_eq!(upload.tail_key(), "uploads/1/a.png.tail");
+    assert!(!upload.is_complete());
+
+    // 最後のパートを送り終えたあとは末尾がない
+    let last = UploadedPart {
+      part_number: 3,
+      ..upload.parts[0].clone()
+    };
+    let upload = ResumableUpload {
+      upload_length: 25,
+      parts: Json(vec![upload.parts[0].clone(), upload.parts[1].clone(), last]),
+      ..upload
+    };
+    assert_eq!(upload.tail_length(), 0);
+    assert!(upload.is_complete());
+  }
+}
@@ -0,0 +1,279 @@
+use sqlx::{types::Json, Executor, PgPool, Postgres};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::storage::UploadedPart;
+
+use super::model::ResumableUpload;
+
+pub async fn create(db: &PgPool, upload: &ResumableUpload) -> Result<(), sqlx::Error> {
+  create_with_executor(db, upload).await
+}
+
+pub async fn create_with_executor<'e, E>(executor: E, upload: &ResumableUpload) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      INSERT INTO resumable_uploads (
+        id, user_id, request_id, reported_lat, reported_lng, object_key, content_type,
+        upload_length, multipart_upload_id, part_size, expires_at
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+    "#,
+    upload.id,
+    upload.user_id,
+    upload.request_id,
+    upload.reported_lat,
+    upload.reported_lng,
+    upload.object_key,
+    upload.content_type,
+    upload.upload_length,
+    upload.multipart_upload_id,
+    upload.part_size,
+    upload.expires_at
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn find_by_id(db: &PgPool, id: Uuid) -> Result<Option<ResumableUpload>, sqlx::Error> {
+  find_by_id_with_executor(db, id).await
+}
+
+pub async fn find_by_id_with_executor<'e, E>(executor: E, id: Uuid) -> Result<Option<ResumableUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let upload = sqlx::query_as!(
+    ResumableUpload,
+    r#"
+      SELECT id, user_id, request_id, reported_lat, reported_lng, object_key, content_type,
+        upload_length, upload_offset, multipart_upload_id, part_size,
+        parts as "parts!: Json<Vec<UploadedPart>>", multipart_completed_at, picture_id, completed_at, expires_at, created_at
+      FROM resumable_uploads
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(upload)
+}
+
+/// オフセットが `offset` のまま誰も受け取り中でなければ、`lock_token` で `lease` の間ロックする。
+/// 同じアップロードへ同時に送られたデータを混ぜないよう、受け取りはこのロックを持つリクエストだけが行う
+pub async fn lock(
+  db: &PgPool,
+  id: Uuid,
+  offset: i64,
+  lock_token: Uuid,
+  lease: Duration,
+) -> Result<Option<ResumableUpload>, sqlx::Error> {
+  lock_with_executor(db, id, offset, lock_token, lease).await
+}
+
+pub async fn lock_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  offset: i64,
+  lock_token: Uuid,
+  lease: Duration,
+) -> Result<Option<ResumableUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let upload = sqlx::query_as!(
+    ResumableUpload,
+    r#"
+      UPDATE resumable_uploads
+      SET lock_token = $3, locked_until = NOW() + make_interval(secs => $4)
+      WHERE id = $1
+        AND upload_offset = $2
+        AND completed_at IS NULL
+        AND expires_at > NOW()
+        AND (locked_until IS NULL OR locked_until < NOW())
+      RETURNING id, user_id, request_id, reported_lat, reported_lng, object_key, content_type,
+        upload_length, upload_offset, multipart_upload_id, part_size,
+        parts as "parts!: Json<Vec<UploadedPart>>", multipart_completed_at, picture_id, completed_at, expires_at, created_at
+    "#,
+    id,
+    offset,
+    lock_token,
+    lease.as_secs_f64()
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(upload)
+}
+
+/// 受け取りを終えた位置と送り終えたパートを記録し、ロックを延長する。ロックを失っていたら `false`
+pub async fn save_progress(
+  db: &PgPool,
+  id: Uuid,
+  lock_token: Uuid,
+  offset: i64,
+  parts: &[UploadedPart],
+  lease: Duration,
+) -> Result<bool, sqlx::Error> {
+  save_progress_with_executor(db, id, lock_token, offset, parts, lease).await
+}
+
+pub async fn save_progress_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  lock_token: Uuid,
+  offset: i64,
+  parts: &[UploadedPart],
+  lease: Duration,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE resumable_uploads
+      SET upload_offset = $3, parts = $4, locked_until = NOW() + make_interval(secs => $5)
+      WHERE id = $1 AND lock_token = $2
+    "#,
+    id,
+    lock_token,
+    offset,
+    Json(parts) as _,
+    lease.as_secs_f64()
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+pub async fn unlock(db: &PgPool, id: Uuid, lock_token: Uuid) -> Result<(), sqlx::Error> {
+  unlock_with_executor(db, id, lock_token).await
+}
+
+pub async fn unlock_with_executor<'e, E>(executor: E, id: Uuid, lock_token: Uuid) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE resumable_uploads
+      SET lock_token = NULL, locked_until = NULL
+      WHERE id = $1 AND lock_token = $2
+    "#,
+    id,
+    lock_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// パートをつなげ終えたことを記録する。ロックを失っていたら `false`
+pub async fn mark_multipart_completed(db: &PgPool, id: Uuid, lock_token: Uuid) -> Result<bool, sqlx::Error> {
+  mark_multipart_completed_with_executor(db, id, lock_token).await
+}
+
+pub async fn mark_multipart_completed_with_executor<'e, E>(
+  executor: E,
+  id: Uuid,
+  lock_token: Uuid,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE resumable_uploads
+      SET multipart_completed_at = NOW()
+      WHERE id = $1 AND lock_token = $2
+    "#,
+    id,
+    lock_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// 写真として保存し終えたことを記録してロックを外す
+pub async fn complete(db: &PgPool, id: Uuid, picture_id: i32) -> Result<(), sqlx::Error> {
+  complete_with_executor(db, id, picture_id).await
+}
+
+pub async fn complete_with_executor<'e, E>(executor: E, id: Uuid, picture_id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE resumable_uploads
+      SET picture_id = $2, completed_at = NOW(), lock_token = NULL, locked_until = NULL
+      WHERE id = $1
+    "#,
+    id,
+    picture_id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// 期限が切れ、受け取り中でもないアップロードを古い順に取得する
+pub async fn find_expired(db: &PgPool, limit: i64) -> Result<Vec<ResumableUpload>, sqlx::Error> {
+  find_expired_with_executor(db, limit).await
+}
+
+pub async fn find_expired_with_executor<'e, E>(executor: E, limit: i64) -> Result<Vec<ResumableUpload>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let uploads = sqlx::query_as!(
+    ResumableUpload,
+    r#"
+      SELECT id, user_id, request_id, reported_lat, reported_lng, object_key, content_type,
+        upload_length, upload_offset, multipart_upload_id, part_size,
+        parts as "parts!: Json<Vec<UploadedPart>>", multipart_completed_at, picture_id, completed_at, expires_at, created_at
+      FROM resumable_uploads
+      WHERE expires_at <= NOW()
+        AND (locked_until IS NULL OR locked_until < NOW())
+      ORDER BY expires_at
+      LIMIT $1
+    "#,
+    limit
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(uploads)
+}
+
+pub async fn delete(db: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+  delete_with_executor(db, id).await
+}
+
+pub async fn delete_with_executor<'e, E>(executor: E, id: Uuid) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      DELETE FROM resumable_uploads
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,485 @@
+use async_trait::async_trait;
+use axum::{
+  body::{Body, Bytes, HttpBody},
+  extract::{Path, State},
+  http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+  middleware,
+  response::{IntoResponse, Response},
+  routing::{options, patch},
+  Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{collections::HashMap, pin::Pin};
+use uuid::Uuid;
+
+use crate::{
+  domains::picture::{
+    format::ImageLimits,
+    rest::{parse_coordinate, reported_location},
+    service::{FileChunks, PictureServiceError},
+  },
+  middleware::auth::auth_middleware,
+  state::{AppState, SharedAppState},
+  AppError,
+};
+
+use super::model::{CreateResumableUploadRequest, ResumableUploadStatus};
+
+/// 対応している tus プロトコルのバージョン
+const TUS_VERSION: &str = "1.0.0";
+/// 対応している tus プロトコルの拡張
+const TUS_EXTENSIONS: &str = "creation";
+/// PATCH で送るデータの Content-Type
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+/// 送り終えて写真として保存した場合に、その写真の ID を返すヘッダー
+const PICTURE_ID: HeaderName = HeaderName::from_static("picture-id");
+
+/// tus プロトコル（コアと creation 拡張）で、途中で切れても続きから送れるアップロードを受け付ける
+pub fn upload_routes() -> Router<SharedAppState> {
+  Router::new()
+    .route("/uploads", options(tus_options_handler).post(create_upload_handler))
+    .route(
+      "/uploads/{upload_id}",
+      patch(append_upload_handler).head(get_upload_handler),
+    )
+    .layer(middleware::map_response(add_tus_headers))
+}
+
+/// tus のレスポンスにはすべて `Tus-Resumable` を付ける。バージョンが合わずに断った場合は対応しているバージョンも伝える
+async fn add_tus_headers(mut response: Response) -> Response {
+  let precondition_failed = response.status() == StatusCode::PRECONDITION_FAILED;
+  let headers = response.headers_mut();
+  headers.insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+  if precondition_failed {
+    headers.insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+  }
+  response
+}
+
+/// PATCH の本文を、全体をメモリに載せずに少しずつ読む
+struct RequestBody(Body);
+
+#[async_trait]
+impl FileChunks for RequestBody {
+  async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError> {
+    loop {
+      match std::future::poll_fn(|cx| Pin::new(&mut self.0).poll_frame(cx)).await {
+        None => return Ok(None),
+        Some(Ok(frame)) => {
+          if let Ok(data) = frame.into_data() {
+            return Ok(Some(data));
+          }
+        }
+        Some(Err(e)) => {
+          return Err(PictureServiceError::BadRequest(format!(
+            "Failed to read upload data: {}",
+            e
+          )))
+        }
+      }
+    }
+  }
+}
+
+async fn tus_options_handler() -> impl IntoResponse {
+  (
+    StatusCode::NO_CONTENT,
+    [
+      (TUS_VERSION_HEADER, TUS_VERSION.to_string()),
+      (TUS_EXTENSION, TUS_EXTENSIONS.to_string()),
+      (TUS_MAX_SIZE, ImageLimits::from_env().max_bytes.to_string()),
+    ],
+  )
+}
+
+async fn create_upload_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+  check_tus_resumable(&headers)?;
+  let claims = auth_middleware(headers.clone()).await?;
+
+  let upload_length = parse_offset_header(&headers, &UPLOAD_LENGTH)?;
+  let metadata = match headers.get(&UPLOAD_METADATA) {
+    Some(value) => parse_metadata(
+      value
+        .to_str()
+        .map_err(|_| AppError::bad_request("Upload-Metadata must be ASCII"))?,
+    )?,
+    None => HashMap::new(),
+  };
+  let req = create_request(upload_length, &metadata)?;
+
+  let upload = state.create_resumable_upload(claims.user_id, req).await?;
+
+  Ok((
+    StatusCode::CREATED,
+    [(header::LOCATION, format!("/api/v1/uploads/{}", upload.id))],
+  ))
+}
+
+async fn get_upload_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(upload_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+  check_tus_resumable(&headers)?;
+  let claims = auth_middleware(headers).await?;
+
+  let status = state.get_resumable_upload(claims.user_id, upload_id).await?;
+
+  let mut response = status_response(StatusCode::OK, status);
+  response
+    .headers_mut()
+    .insert(UPLOAD_LENGTH, HeaderValue::from(status.upload_length));
+  response
+    .headers_mut()
+    .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+  Ok(response)
+}
+
+async fn append_upload_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(upload_id): Path<Uuid>,
+  body: Body,
+) -> Result<Response, AppError> {
+  check_tus_resumable(&headers)?;
+  let claims = auth_middleware(headers.clone()).await?;
+
+  if headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()) != Some(OFFSET_OCTET_STREAM) {
+    return Err(AppError::new(
+      StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      format!("Content-Type must be {}", OFFSET_OCTET_STREAM),
+    ));
+  }
+  let offset = parse_offset_header(&headers, &UPLOAD_OFFSET)?;
+
+  let status = state
+    .append_resumable_upload(claims.user_id, upload_id, offset, &mut RequestBody(body))
+    .await?;
+
+  Ok(status_response(StatusCode::NO_CONTENT, status))
+}
+
+fn check_tus_resumable(headers: &HeaderMap) -> Result<(), AppError> {
+  if headers.get(&TUS_RESUMABLE).map(HeaderValue::as_bytes) != Some(TUS_VERSION.as_bytes()) {
+    return Err(AppError::new(
+      StatusCode::PRECONDITION_FAILED,
+      format!("Tus-Resumable must be {}", TUS_VERSION),
+    ));
+  }
+  Ok(())
+}
+
+/// `Upload-Length` や `Upload-Offset` のような0以上の整数のヘッダーを読む
+fn parse_offset_header(headers: &HeaderMap, name: &HeaderName) -> Result<i64, AppError> {
+  headers
+    .get(name)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| value.parse::<i64>().ok())
+    .filter(|value| *value >= 0)
+    .ok_or_else(|| AppError::bad_request(format!("{} must be a non-negative integer", name)))
+}
+
+/// `key base64値` をカンマで区切って並べた `Upload-Metadata` を読む。値のないキーは空文字列にする
+fn parse_metadata(value: &str) -> Result<HashMap<String, String>, AppError> {
+  let mut metadata = HashMap::new();
+
+  for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+    let (key, encoded) = pair.split_once(' ').unwrap_or((pair, ""));
+    let decoded = STANDARD
+      .decode(encoded.trim())
+      .ok()
+      .and_then(|decoded| String::from_utf8(decoded).ok())
+      .ok_or_else(|| AppError::bad_request(format!("Upload-Metadata value for {} is not valid base64", key)))?;
+    if metadata.insert(key.to_string(), decoded).is_some() {
+      return Err(AppError::bad_request(format!(
+        "Upload-Metadata has duplicate key {}",
+        key
+      )));
+    }
+  }
+
+  Ok(metadata)
+}
+
+/// メタデータの `filetype` を MIME タイプ、`request_id` を投稿先のリクエスト、`lat` と `lng` を撮影時の現在地として読む
+fn create_request(
+  upload_length: i64,
+  metadata: &HashMap<String, String>,
+) -> Result<CreateResumableUploadRequest, AppError> {
+  let content_type = metadata
+    .get("filetype")
+    .ok_or_else(|| AppError::bad_request("Upload-Metadata must include filetype"))?;
+  let request_id = metadata
+    .get("request_id")
+    .map(|value| {
+      value
+        .trim()
+        .parse()
+        .map_err(|_| AppError::bad_request("request_id must be an integer"))
+    })
+    .transpose()?;
+  let lat = metadata
+    .get("lat")
+    .map(|value| parse_coordinate(value.trim(), "lat", 90.0))
+    .transpose()?;
+  let lng = metadata
+    .get("lng")
+    .map(|value| parse_coordinate(value.trim(), "lng", 180.0))
+    .transpose()?;
+
+  Ok(CreateResumableUploadRequest {
+    upload_length,
+    content_type: content_type.clone(),
+    request_id,
+    reported_location: reported_location(lat, lng)?,
+  })
+}
+
+fn status_response(status_code: StatusCode, status: ResumableUploadStatus) -> Response {
+  let mut response = status_code.into_response();
+  let headers = response.headers_mut();
+  headers.insert(UPLOAD_OFFSET, HeaderValue::from(status.upload_offset));
+  if let Some(picture_id) = status.picture_id {
+    headers.insert(PICTURE_ID, HeaderValue::from(picture_id));
+  }
+  response
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::picture::format::tests::sample_png;
+  use crate::storage::{MemoryStorage, ObjectStorage};
+  use crate::test_support::{app_with_storage, get, login_verified_user};
+  use std::sync::Arc;
+
+  /// tus のリクエストを送り、ステータスとヘッダーを返す
+  async fn send_tus(
+    app: Router,
+    method: &str,
+    uri: &str,
+    token: &str,
+    headers: &[(&str, String)],
+    body: Vec<u8>,
+  ) -> (StatusCode, HeaderMap) {
+    let mut request = axum::http::Request::builder()
+      .method(method)
+      .uri(uri)
+      .header("authorization", format!("Bearer {}", token))
+      .header("tus-resumable", TUS_VERSION);
+    for (name, value) in headers {
+      request = request.header(*name, value);
+    }
+
+    let response = tower::ServiceExt::oneshot(app, request.body(Body::from(body)).unwrap())
+      .await
+      .unwrap();
+    (response.status(), response.headers().clone())
+  }
+
+  async fn create_upload(app: Router, token: &str, length: usize, metadata: &str) -> (StatusCode, HeaderMap) {
+    send_tus(
+      app,
+      "POST",
+      "/api/v1/uploads",
+      token,
+      &[
+        ("upload-length", length.to_string()),
+        ("upload-metadata", metadata.to_string()),
+      ],
+      Vec::new(),
+    )
+    .await
+  }
+
+  async fn patch_upload(
+    app: Router,
+    location: &str,
+    token: &str,
+    offset: usize,
+    data: &[u8],
+  ) -> (StatusCode, HeaderMap) {
+    send_tus(
+      app,
+      "PATCH",
+      location,
+      token,
+      &[
+        ("content-type", OFFSET_OCTET_STREAM.to_string()),
+        ("upload-offset", offset.to_string()),
+      ],
+      data.to_vec(),
+    )
+    .await
+  }
+
+  fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers.get(name).unwrap().to_str().unwrap()
+  }
+
+  fn metadata(filetype: &str) -> String {
+    format!(
+      "filetype {},filename {}",
+      STANDARD.encode(filetype),
+      STANDARD.encode("photo.png")
+    )
+  }
+
+  #[test]
+  fn test_parse_metadata() {
+    let metadata = parse_metadata(&format!("filetype {}, is_confidential", STANDARD.encode("image/png"))).unwrap();
+    assert_eq!(metadata["filetype"], "image/png");
+    assert_eq!(metadata["is_confidential"], "");
+
+    assert!(parse_metadata("filetype !!!").is_err());
+    assert!(parse_metadata(&format!("a {},a {}", STANDARD.encode("1"), STANDARD.encode("2"))).is_err());
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn tus_upload_in_chunks_creates_picture(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let app = app_with_storage(pool.clone(), Arc::new(storage.clone())).await;
+
+    crate::domains::user::model::User::create(&pool, "tus@example.com", "Uploader", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "tus@example.com").await?;
+
+    let request = axum::http::Request::builder()
+      .method("OPTIONS")
+      .uri("/api/v1/uploads")
+      .body(Body::empty())
+      .unwrap();
+    let response = tower::ServiceExt::oneshot(app.clone(), request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(header_str(response.headers(), "tus-version"), TUS_VERSION);
+    assert_eq!(header_str(response.headers(), "tus-extension"), "creation");
+
+    let file = sample_png(40, 30);
+    let (status, headers) = create_upload(app.clone(), &token, file.len(), &metadata("image/png")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(header_str(&headers, "tus-resumable"), TUS_VERSION);
+    let location = header_str(&headers, "location").to_string();
+    assert!(location.starts_with("/api/v1/uploads/"));
+
+    let (status, headers) = send_tus(app.clone(), "HEAD", &location, &token, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header_str(&headers, "upload-offset"), "0");
+    assert_eq!(header_str(&headers, "upload-length"), file.len().to_string());
+    assert_eq!(header_str(&headers, "cache-control"), "no-store");
+
+    // パートの大きさに満たない末尾も記録され、続きから送れる
+    let (status, headers) = patch_upload(app.clone(), &location, &token, 0, &file[..21]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(header_str(&headers, "upload-offset"), "21");
+    assert!(headers.get("picture-id").is_none());
+    let (status, headers) = patch_upload(app.clone(), &location, &token, 21, &file[21..30]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(header_str(&headers, "upload-offset"), "30");
+
+    let (_, headers) = send_tus(app.clone(), "HEAD", &location, &token, &[], Vec::new()).await;
+    assert_eq!(header_str(&headers, "upload-offset"), "30");
+
+    let (status, headers) = patch_upload(app.clone(), &location, &token, 30, &file[30..]).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(header_str(&headers, "upload-offset"), file.len().to_string());
+    let picture_id = header_str(&headers, "picture-id").to_string();
+
+    let (status, _) = get(app.clone(), &format!("/api/v1/pictures/{}", picture_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, headers) = send_tus(app.clone(), "HEAD", &location, &token, &[], Vec::new()).await;
+    assert_eq!(header_str(&headers, "picture-id"), picture_id);
+
+    // 送り終えたアップロードにはもう書き込めず、一時的なファイルも残らない
+    let (status, _) = patch_upload(app, &location, &token, file.len(), b"").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+    assert_eq!(storage.pending_multipart_count(), 0);
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM picture_uploads"#)
+      .fetch_one(&pool)
+      .await?;
+    assert_eq!(count, 0);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn tus_rejects_invalid_requests(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let app = app_with_storage(pool.clone(), Arc::new(storage.clone())).await;
+
+    crate::domains::user::model::User::create(&pool, "tus-owner@example.com", "Owner", "password123").await?;
+    crate::domains::user::model::User::create(&pool, "tus-other@example.com", "Other", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "tus-owner@example.com").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "tus-other@example.com").await?;
+
+    let (status, _) = create_upload(app.clone(), &token, 100, &metadata("application/pdf")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = create_upload(app.clone(), &token, 100, "filename cGhvdG8=").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = create_upload(app.clone(), &token, usize::MAX / 2, &metadata("image/png")).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let (status, headers) = create_upload(app.clone(), &token, 100, &metadata("image/png")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let location = header_str(&headers, "location").to_string();
+
+    // 対応していないバージョン
+    let request = axum::http::Request::builder()
+      .method("HEAD")
+      .uri(&location)
+      .header("authorization", format!("Bearer {}", token))
+      .header("tus-resumable", "0.2.2")
+      .body(Body::empty())
+      .unwrap();
+    let response = tower::ServiceExt::oneshot(app.clone(), request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(header_str(response.headers(), "tus-version"), TUS_VERSION);
+
+    let (status, _) = send_tus(
+      app.clone(),
+      "PATCH",
+      &location,
+      &token,
+      &[
+        ("content-type", "application/octet-stream".to_string()),
+        ("upload-offset", "0".to_string()),
+      ],
+      vec![0; 10],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let (status, _) = patch_upload(app.clone(), &location, &token, 5, &[0; 10]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = patch_upload(app.clone(), &location, &other_token, 0, &[0; 10]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send_tus(app.clone(), "HEAD", &location, &other_token, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Upload-Length を超えたデータは受け取らない
+    let (status, _) = patch_upload(app.clone(), &location, &token, 0, &[0; 101]).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let (_, headers) = send_tus(app.clone(), "HEAD", &location, &token, &[], Vec::new()).await;
+    assert_eq!(header_str(&headers, "upload-offset"), "0");
+
+    // 画像でなかったファイルは最後まで受け取ってから断り、アップロードごと消す
+    let (status, _) = patch_upload(app.clone(), &location, &token, 0, &[0; 100]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send_tus(app, "HEAD", &location, &token, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+    assert_eq!(storage.pending_multipart_count(), 0);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,764 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::domains::picture::{
+  format::{ImageError, ImageFormat, ImageLimits},
+  model::PictureUpload,
+  service::{FileChunks, PictureService, PictureServiceError, PictureServiceImpl},
+};
+use crate::domains::request::repository as request_repository;
+use crate::impl_service_error_conversions;
+use crate::storage::ObjectStorage;
+
+use super::model::{CreateResumableUploadRequest, ResumableUpload, ResumableUploadStatus};
+use super::repository;
+
+/// 作成からこの期間内に送り終えなかったアップロードは破棄する
+const RESUMABLE_UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// 受け取り中のロックの期間。パートを送り終えるたびに延長し、途中でプロセスが落ちた場合はこの期間が過ぎれば再開できる
+const LOCK_LEASE: Duration = Duration::from_secs(5 * 60);
+/// 1回の掃除で削除する期限切れアップロードの上限
+const EXPIRED_UPLOAD_BATCH_SIZE: i64 = 100;
+
+#[derive(Debug)]
+pub enum UploadServiceError {
+  InternalServerError(String),
+  BadRequest(String),
+  NotFound(String),
+  Forbidden(String),
+  Conflict(String),
+  PayloadTooLarge(String),
+}
+
+impl Error for UploadServiceError {}
+
+impl std::fmt::Display for UploadServiceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      UploadServiceError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
+      UploadServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+      UploadServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+      UploadServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+      UploadServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+      UploadServiceError::PayloadTooLarge(msg) => write!(f, "Payload Too Large: {}", msg),
+    }
+  }
+}
+
+impl_service_error_conversions!(UploadServiceError, InternalServerError);
+
+impl From<PictureServiceError> for UploadServiceError {
+  fn from(error: PictureServiceError) -> Self {
+    match error {
+      PictureServiceError::InternalServerError(msg) => UploadServiceError::InternalServerError(msg),
+      PictureServiceError::BadRequest(msg) => UploadServiceError::BadRequest(msg),
+      PictureServiceError::NotFound(msg) => UploadServiceError::NotFound(msg),
+      PictureServiceError::Forbidden(msg) => UploadServiceError::Forbidden(msg),
+      PictureServiceError::Conflict(msg) => UploadServiceError::Conflict(msg),
+      PictureServiceError::PayloadTooLarge(msg) => UploadServiceError::PayloadTooLarge(msg),
+    }
+  }
+}
+
+impl From<ImageError> for UploadServiceError {
+  fn from(error: ImageError) -> Self {
+    PictureServiceError::from(error).into()
+  }
+}
+
+/// tus プロトコルで分割して届く写真を受け取る。
+/// 受け取ったデータはストレージのマルチパートアップロードのパートとして送り、送り終えたら写真のアップロードと同じ処理で保存する
+pub struct UploadService {
+  pool: PgPool,
+  storage: Arc<dyn ObjectStorage>,
+  picture_service: Arc<PictureServiceImpl>,
+  limits: ImageLimits,
+}
+
+impl UploadService {
+  pub fn new(
+    pool: PgPool,
+    storage: Arc<dyn ObjectStorage>,
+    picture_service: Arc<PictureServiceImpl>,
+    limits: ImageLimits,
+  ) -> Self {
+    Self {
+      pool,
+      storage,
+      picture_service,
+      limits,
+    }
+  }
+
+  pub async fn create_upload(
+    &self,
+    user_id: i32,
+    req: CreateResumableUploadRequest,
+  ) -> Result<ResumableUpload, UploadServiceError> {
+    let format = ImageFormat::from_mime_type(&req.content_type)
+      .ok_or_else(|| UploadServiceError::BadRequest(ImageError::UnsupportedFormat.to_string()))?;
+    if req.upload_length <= 0 {
+      return Err(UploadServiceError::BadRequest(
+        "Upload-Length must be a positive integer".to_string(),
+      ));
+    }
+    if req.upload_length as u64 > self.limits.max_bytes as u64 {
+      return Err(
+        ImageError::TooLarge {
+          max_bytes: self.limits.max_bytes,
+        }
+        .into(),
+      );
+    }
+    if let Some(request_id) = req.request_id {
+      request_repository::find_by_id(&self.pool, request_id)
+        .await?
+        .ok_or_else(|| UploadServiceError::NotFound(format!("Request with id {} not found", request_id)))?;
+    }
+
+    let id = Uuid::new_v4();
+    let object_key = format!("uploads/{}/{}.{}", user_id, id, format.extension());
+    let multipart_upload_id = self
+      .storage
+      .create_multipart(&object_key, format.mime_type())
+      .await
+      .map_err(|e| UploadServiceError::InternalServerError(format!("Failed to start upload: {}", e)))?;
+
+    let now = Utc::now();
+    let upload = ResumableUpload {
+      id,
+      user_id,
+      request_id: req.request_id,
+      reported_lat: req.reported_location.map(|(lat, _)| lat),
+      reported_lng: req.reported_location.map(|(_, lng)| lng),
+      object_key,
+      content_type: format.mime_type().to_string(),
+      upload_length: req.upload_length,
+      upload_offset: 0,
+      multipart_upload_id,
+      part_size: self.storage.multipart_part_size() as i64,
+      parts: Default::default(),
+      multipart_completed_at: None,
+      picture_id: None,
+      completed_at: None,
+      expires_at: now + RESUMABLE_UPLOAD_TTL,
+      created_at: now,
+    };
+    if let Err(e) = repository::create(&self.pool, &upload).await {
+      if let Err(abort_error) = self
+        .storage
+        .abort_multipart(&upload.object_key, &upload.multipart_upload_id)
+        .await
+      {
+        tracing::error!(
+          "Failed to abort unrecorded upload {}: {:?}",
+          upload.object_key,
+          abort_error
+        );
+      }
+      return Err(e.into());
+    }
+
+    Ok(upload)
+  }
+
+  pub async fn get_status(&self, user_id: i32, upload_id: Uuid) -> Result<ResumableUploadStatus, UploadServiceError> {
+    let upload = self.find_own_upload(user_id, upload_id).await?;
+    Ok(ResumableUploadStatus::from(&upload))
+  }
+
+  /// `offset` から続くデータを受け取る。途中で接続が切れても、受け取れた分は次の PATCH で続きから送れるよう記録する。
+  /// 送り終えたのに写真として保存できなかったアップロードは、`Upload-Length` の位置への空の PATCH で保存をやり直せる
+  pub async fn append(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    offset: i64,
+    body: &mut dyn FileChunks,
+  ) -> Result<ResumableUploadStatus, UploadServiceError> {
+    let upload = self.find_own_upload(user_id, upload_id).await?;
+    if upload.completed_at.is_some() {
+      return Err(UploadServiceError::Conflict(
+        "The upload is already complete".to_string(),
+      ));
+    }
+    if upload.upload_offset != offset {
+      return Err(UploadServiceError::Conflict(format!(
+        "Upload-Offset must be {}",
+        upload.upload_offset
+      )));
+    }
+
+    let lock_token = Uuid::new_v4();
+    let mut upload = repository::lock(&self.pool, upload_id, offset, lock_token, LOCK_LEASE)
+      .await?
+      .ok_or_else(|| UploadServiceError::Conflict("The upload is being written by another request".to_string()))?;
+
+    let received = self.receive(&mut upload, lock_token, body).await;
+    if received.is_ok() && upload.is_complete() {
+      return self.complete(upload, lock_token).await;
+    }
+
+    self.release(&upload, lock_token).await;
+    received.map(|_| ResumableUploadStatus::from(&upload))
+  }
+
+  /// 期限までに保存し終えなかったアップロードを、送り済みのパートやつなげたファイルごと削除し、削除した件数を返す
+  pub async fn cleanup_expired_uploads(&self) -> Result<usize, UploadServiceError> {
+    let uploads = repository::find_expired(&self.pool, EXPIRED_UPLOAD_BATCH_SIZE).await?;
+
+    let mut deleted = 0;
+    for upload in uploads {
+      // パートやファイルを消せなかったものは次回に再試行する
+      if upload.completed_at.is_none() {
+        if let Err(e) = self.discard_assembled_or_parts(&upload).await {
+          tracing::error!("Failed to discard expired upload {}: {:?}", upload.object_key, e);
+          continue;
+        }
+        if let Err(e) = self.storage.delete(&upload.tail_key()).await {
+          tracing::error!("Failed to delete expired upload {}: {:?}", upload.tail_key(), e);
+          continue;
+        }
+      }
+      if repository::delete(&self.pool, upload.id).await? {
+        deleted += 1;
+      }
+    }
+
+    Ok(deleted)
+  }
+
+  async fn find_own_upload(&self, user_id: i32, upload_id: Uuid) -> Result<ResumableUpload, UploadServiceError> {
+    let upload = repository::find_by_id(&self.pool, upload_id)
+      .await?
+      .filter(|upload| upload.completed_at.is_some() || upload.expires_at > Utc::now())
+      .ok_or_else(|| UploadServiceError::NotFound(format!("Upload with id {} not found", upload_id)))?;
+
+    if upload.user_id != user_id {
+      return Err(UploadServiceError::Forbidden(
+        "You do not have permission to access this upload".to_string(),
+      ));
+    }
+
+    Ok(upload)
+  }
+
+  /// 届いたデータをパートの大きさごとにストレージへ送る。最後まで届いていなければ、パートに満たない末尾は別のオブジェクトに置く
+  async fn receive(
+    &self,
+    upload: &mut ResumableUpload,
+    lock_token: Uuid,
+    body: &mut dyn FileChunks,
+  ) -> Result<(), UploadServiceError> {
+    let part_size = upload.part_size as usize;
+    let tail_length = upload.tail_length() as usize;
+    let mut buffer = match tail_length {
+      0 => Vec::new(),
+      _ => self
+        .storage
+        .get(&upload.tail_key())
+        .await
+        .map_err(|e| UploadServiceError::InternalServerError(format!("Failed to read upload: {}", e)))?,
+    };
+    // 末尾を置いたあと位置を記録する前に止まった場合、記録より長い末尾が残っている
+    if buffer.len() < tail_length {
+      return Err(UploadServiceError::InternalServerError(
+        "Stored upload is shorter than its offset".to_string(),
+      ));
+    }
+    buffer.truncate(tail_length);
+
+    let received = loop {
+      let chunk = match body.next_chunk().await {
+        Ok(Some(chunk)) => chunk,
+        Ok(None) => break Ok(()),
+        Err(e) => break Err(e.into()),
+      };
+      if upload.upload_offset + chunk.len() as i64 > upload.upload_length {
+        break Err(UploadServiceError::PayloadTooLarge(
+          "The data exceeds Upload-Length".to_string(),
+        ));
+      }
+      buffer.extend_from_slice(&chunk);
+      upload.upload_offset += chunk.len() as i64;
+
+      while buffer.len() >= part_size {
+        let rest = buffer.split_off(part_size);
+        let part = std::mem::replace(&mut buffer, rest);
+        self.upload_part(upload, part).await?;
+        let offset = upload.parts.len() as i64 * upload.part_size;
+        self.save_progress(upload, lock_token, offset).await?;
+      }
+    };
+
+    if !buffer.is_empty() {
+      if upload.is_complete() {
+        self.upload_part(upload, buffer).await?;
+      } else {
+        self
+          .storage
+          .put(&upload.tail_key(), buffer, &upload.content_type)
+          .await
+          .map_err(|e| UploadServiceError::InternalServerError(format!("Failed to store upload: {}", e)))?;
+      }
+      self.save_progress(upload, lock_token, upload.upload_offset).await?;
+    }
+
+    received
+  }
+
+  async fn upload_part(&self, upload: &mut ResumableUpload, data: Vec<u8>) -> Result<(), UploadServiceError> {
+    let part_number = upload.parts.len() as i32 + 1;
+    let part = self
+      .storage
+      .upload_part(&upload.object_key, &upload.multipart_upload_id, part_number, data)
+      .await
+      .map_err(|e| UploadServiceError::InternalServerError(format!("Failed to store upload: {}", e)))?;
+    upload.parts.push(part);
+    Ok(())
+  }
+
+  async fn save_progress(
+    &self,
+    upload: &ResumableUpload,
+    lock_token: Uuid,
+    offset: i64,
+  ) -> Result<(), UploadServiceError> {
+    if !repository::save_progress(&self.pool, upload.id, lock_token, offset, &upload.parts, LOCK_LEASE).await? {
+      return Err(UploadServiceError::Conflict(
+        "The upload is being written by another request".to_string(),
+      ));
+    }
+    Ok(())
+  }
+
+  /// 期限切れのアップロードのパートを消す。パートをつなげ終えていれば、つなげたファイルを消す
+  async fn discard_assembled_or_parts(&self, upload: &ResumableUpload) -> anyhow::Result<()> {
+    if upload.multipart_completed_at.is_none() {
+      let aborted = self
+        .storage
+        .abort_multipart(&upload.object_key, &upload.multipart_upload_id)
+        .await;
+      // つなげたことを記録する前に止まった場合、マルチパートアップロードはもうないがファイルはある
+      if let Err(e) = aborted {
+        if self.storage.head(&upload.object_key).await?.is_none() {
+          return Err(e);
+        }
+      }
+    }
+
+    self.storage.delete(&upload.object_key).await
+  }
+
+  /// パートをつなげてファイルにし、写真として保存する。
+  /// 画像として受け付けられなかったアップロードは残さず、ストレージや DB の一時的な障害ではロックを外して再試行できるようにする
+  async fn complete(
+    &self,
+    mut upload: ResumableUpload,
+    lock_token: Uuid,
+  ) -> Result<ResumableUploadStatus, UploadServiceError> {
+    if let Err(e) = self.assemble(&mut upload, lock_token).await {
+      self.release(&upload, lock_token).await;
+      return Err(e);
+    }
+
+    let now = Utc::now();
+    let assembled = PictureUpload {
+      id: upload.id,
+      user_id: upload.user_id,
+      request_id: upload.request_id,
+      object_key: upload.object_key.clone(),
+      content_type: upload.content_type.clone(),
+      content_length: upload.upload_length,
+      expires_at: now,
+      created_at: now,
+    };
+    match self
+      .picture_service
+      .finalize_assembled_upload(assembled, upload.reported_location())
+      .await
+    {
+      Ok(picture) => {
+        if let Err(e) = repository::complete(&self.pool, upload.id, picture.id).await {
+          // 保存した写真は、再試行したときに同じアップロードの写真として見つかる
+          self.release(&upload, lock_token).await;
+          return Err(e.into());
+        }
+        Ok(ResumableUploadStatus {
+          picture_id: Some(picture.id),
+          ..ResumableUploadStatus::from(&upload)
+        })
+      }
+      Err(e @ PictureServiceError::InternalServerError(_)) => {
+        self.release(&upload, lock_token).await;
+        Err(e.into())
+      }
+      Err(e) => {
+        repository::delete(&self.pool, upload.id).await?;
+        Err(e.into())
+      }
+    }
+  }
+
+  /// パートをつなげ終えていなければつなげ、つなげたことを記録する
+  async fn assemble(&self, upload: &mut ResumableUpload, lock_token: Uuid) -> Result<(), UploadServiceError> {
+    if upload.multipart_completed_at.is_some() {
+      return Ok(());
+    }
+
+    self.complete_multipart(upload).await?;
+    if !repository::mark_multipart_completed(&self.pool, upload.id, lock_token).await? {
+      return Err(UploadServiceError::Conflict(
+        "The upload is being written by another request".to_string(),
+      ));
+    }
+    upload.multipart_completed_at = Some(Utc::now());
+    if let Err(e) = self.storage.delete(&upload.tail_key()).await {
+      tracing::error!("Failed to delete {}: {:?}", upload.tail_key(), e);
+    }
+
+    Ok(())
+  }
+
+  /// 続きの受け取りや保存のやり直しができるようロックを外す
+  async fn release(&self, upload: &ResumableUpload, lock_token: Uuid) {
+    if let Err(e) = repository::unlock(&self.pool, upload.id, lock_token).await {
+      tracing::error!("Failed to unlock upload {}: {:?}", upload.id, e);
+    }
+  }
+
+  /// パートをつなげる。つなげたことを記録する前に止まっていた場合は、すでにつなげたファイルをそのまま使う
+  async fn complete_multipart(&self, upload: &ResumableUpload) -> Result<(), UploadServiceError> {
+    let completed = self
+      .storage
+      .complete_multipart(&upload.object_key, &upload.multipart_upload_id, &upload.parts)
+      .await;
+    let Err(e) = completed else {
+      return Ok(());
+    };
+
+    match self.storage.head(&upload.object_key).await {
+      Ok(Some(object)) if object.size == upload.upload_length => Ok(()),
+      _ => Err(UploadServiceError::InternalServerError(format!(
+        "Failed to complete upload: {}",
+        e
+      ))),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::picture::format::tests::sample_png;
+  use crate::storage::MemoryStorage;
+  use async_trait::async_trait;
+  use axum::body::Bytes;
+
+  /// 決まったチャンクを返したあと、接続が切れたことにする本文
+  struct InterruptedBody(Vec<Vec<u8>>);
+
+  #[async_trait]
+  impl FileChunks for InterruptedBody {
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError> {
+      if self.0.is_empty() {
+        return Err(PictureServiceError::BadRequest("connection reset".to_string()));
+      }
+      Ok(Some(Bytes::from(self.0.remove(0))))
+    }
+  }
+
+  struct CompleteBody(Option<Vec<u8>>);
+
+  #[async_trait]
+  impl FileChunks for CompleteBody {
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>, PictureServiceError> {
+      Ok(self.0.take().map(Bytes::from))
+    }
+  }
+
+  fn create_service(pool: PgPool, storage: MemoryStorage) -> UploadService {
+    let storage: Arc<dyn ObjectStorage> = Arc::new(storage);
+    let limits = ImageLimits::from_env();
+    let picture_service = Arc::new(PictureServiceImpl::new(pool.clone(), storage.clone(), limits));
+    UploadService::new(pool, storage, picture_service, limits)
+  }
+
+  fn upload_request(upload_length: usize) -> CreateResumableUploadRequest {
+    CreateResumableUploadRequest {
+      upload_length: upload_length as i64,
+      content_type: "image/png".to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn append_keeps_data_received_before_disconnect(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "resume@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    let file = sample_png(40, 30);
+    let upload = service
+      .create_upload(user.id, upload_request(file.len()))
+      .await
+      .unwrap();
+
+    let mut body = InterruptedBody(vec![file[..10].to_vec(), file[10..25].to_vec()]);
+    assert!(service.append(user.id, upload.id, 0, &mut body).await.is_err());
+
+    // 切断までに届いた分は記録され、ロックも外れている
+    let status = service.get_status(user.id, upload.id).await.unwrap();
+    assert_eq!(status.upload_offset, 25);
+    let status = service
+      .append(user.id, upload.id, 25, &mut CompleteBody(Some(file[25..].to_vec())))
+      .await
+      .unwrap();
+    assert_eq!(status.upload_offset, file.len() as i64);
+    assert!(status.picture_id.is_some());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn failed_completion_is_retried(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user = crate::domains::user::model::User::create(&pool, "retry@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    let file = sample_png(40, 30);
+    let length = file.len() as i64;
+    let uploads = [
+      service
+        .create_upload(user.id, upload_request(file.len()))
+        .await
+        .unwrap(),
+      service
+        .create_upload(user.id, upload_request(file.len()))
+        .await
+        .unwrap(),
+    ];
+
+    // 最後のパートまで送れても、つなげられなければ写真はまだない
+    storage.fail_completions(true);
+    for upload in &uploads {
+      let result = service
+        .append(user.id, upload.id, 0, &mut CompleteBody(Some(file.clone())))
+        .await;
+      assert!(matches!(result, Err(UploadServiceError::InternalServerError(_))));
+    }
+    storage.fail_completions(false);
+
+    // HEAD は状態を返すだけで保存をやり直さない
+    let status = service.get_status(user.id, uploads[0].id).await.unwrap();
+    assert_eq!(status.upload_offset, length);
+    assert_eq!(status.picture_id, None);
+    assert_eq!(storage.pending_multipart_count(), 2);
+
+    // 空の PATCH で保存をやり直せる
+    for upload in &uploads {
+      let status = service
+        .append(user.id, upload.id, length, &mut CompleteBody(None))
+        .await
+        .unwrap();
+      assert_eq!(status.upload_offset, length);
+      assert!(status.picture_id.is_some());
+
+      // 保存し終えたあとの HEAD は同じ写真を返す
+      assert_eq!(service.get_status(user.id, upload.id).await.unwrap(), status);
+    }
+    assert_eq!(storage.pending_multipart_count(), 0);
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn completion_is_retried_without_assembling_again(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "assembled@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    let file = sample_png(40, 30);
+    let length = file.len() as i64;
+    let uploads = [
+      service
+        .create_upload(user.id, upload_request(file.len()))
+        .await
+        .unwrap(),
+      service
+        .create_upload(user.id, upload_request(file.len()))
+        .await
+        .unwrap(),
+    ];
+
+    // パートをつなげたあと、写真の行を作れない一時的な障害で失敗する
+    sqlx::raw_sql(
+      r#"
+        CREATE FUNCTION fail_pictures() RETURNS trigger AS $$
+        BEGIN
+          RAISE EXCEPTION 'injected failure';
+        END
+        $$ LANGUAGE plpgsql;
+        CREATE TRIGGER fail_pictures BEFORE INSERT ON pictures FOR EACH ROW EXECUTE FUNCTION fail_pictures();
+      "#,
+    )
+    .execute(&pool)
+    .await?;
+    let result = service
+      .append(user.id, uploads[0].id, 0, &mut CompleteBody(Some(file.clone())))
+      .await;
+    assert!(matches!(result, Err(UploadServiceError::InternalServerError(_))));
+
+    // 行もつなげたファイルも残り、ロックも外れている
+    let assembled = repository::find_by_id(&pool, uploads[0].id).await?.unwrap();
+    assert!(assembled.multipart_completed_at.is_some());
+    assert!(storage.head(&assembled.object_key).await.unwrap().is_some());
+    assert_eq!(storage.pending_multipart_count(), 1);
+
+    // つなげたことを記録する前に止まったアップロードは、記録がなくてもつなげたファイルを使う
+    storage.fail_completions(true);
+    let result = service
+      .append(user.id, uploads[1].id, 0, &mut CompleteBody(Some(file.clone())))
+      .await;
+    assert!(matches!(result, Err(UploadServiceError::InternalServerError(_))));
+    storage.fail_completions(false);
+    let unrecorded = repository::find_by_id(&pool, uploads[1].id).await?.unwrap();
+    storage
+      .complete_multipart(
+        &unrecorded.object_key,
+        &unrecorded.multipart_upload_id,
+        &unrecorded.parts,
+      )
+      .await
+      .unwrap();
+    assert_eq!(storage.pending_multipart_count(), 0);
+
+    sqlx::raw_sql("DROP TRIGGER fail_pictures ON pictures")
+      .execute(&pool)
+      .await?;
+    for upload in &uploads {
+      let status = service
+        .append(user.id, upload.id, length, &mut CompleteBody(None))
+        .await
+        .unwrap();
+      assert!(status.picture_id.is_some());
+    }
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn rejected_image_is_not_kept(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "rejected@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    let mut file = sample_png(40, 30);
+    file.truncate(60);
+    let upload = service
+      .create_upload(user.id, upload_request(file.len()))
+      .await
+      .unwrap();
+
+    let result = service
+      .append(user.id, upload.id, 0, &mut CompleteBody(Some(file)))
+      .await;
+    assert!(matches!(result, Err(UploadServiceError::BadRequest(_))));
+    assert!(repository::find_by_id(&pool, upload.id).await?.is_none());
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn cleanup_removes_expired_uploads_and_their_parts(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "expired@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    let expired = service.create_upload(user.id, upload_request(100)).await.unwrap();
+    let active = service.create_upload(user.id, upload_request(100)).await.unwrap();
+    service
+      .append(user.id, expired.id, 0, &mut CompleteBody(Some(vec![0; 40])))
+      .await
+      .unwrap();
+    sqlx::query!(
+      "UPDATE resumable_uploads SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1",
+      expired.id
+    )
+    .execute(&pool)
+    .await?;
+
+    assert_eq!(service.cleanup_expired_uploads().await.unwrap(), 1);
+    assert!(matches!(
+      service.get_status(user.id, expired.id).await,
+      Err(UploadServiceError::NotFound(_))
+    ));
+    assert!(service.get_status(user.id, active.id).await.is_ok());
+    assert_eq!(storage.pending_multipart_count(), 1);
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn cleanup_removes_assembled_uploads(pool: PgPool) -> Result<(), sqlx::Error> {
+    let user =
+      crate::domains::user::model::User::create(&pool, "leftover@example.com", "Uploader", "password123").await?;
+    let storage = MemoryStorage::new().with_multipart_part_size(16);
+    let service = create_service(pool.clone(), storage.clone());
+
+    // パートをつなげたあと写真として保存する前に止まり、つなげたことを記録できたものとできなかったもの
+    let file = sample_png(40, 30);
+    let mut ids = Vec::new();
+    for mark in [true, false] {
+      let upload = service
+        .create_upload(user.id, upload_request(file.len()))
+        .await
+        .unwrap();
+      storage.fail_completions(true);
+      assert!(service
+        .append(user.id, upload.id, 0, &mut CompleteBody(Some(file.clone())))
+        .await
+        .is_err());
+      storage.fail_completions(false);
+      let upload = repository::find_by_id(&pool, upload.id).await?.unwrap();
+      storage
+        .complete_multipart(&upload.object_key, &upload.multipart_upload_id, &upload.parts)
+        .await
+        .unwrap();
+      if mark {
+        sqlx::query!(
+          "UPDATE resumable_uploads SET multipart_completed_at = NOW() WHERE id = $1",
+          upload.id
+        )
+        .execute(&pool)
+        .await?;
+      }
+      ids.push(upload.id);
+    }
+    sqlx::query!("UPDATE resumable_uploads SET expires_at = NOW() - INTERVAL '1 minute'")
+      .execute(&pool)
+      .await?;
+    assert_eq!(storage.list("uploads/").await.unwrap().len(), 2);
+
+    // マルチパートアップロードはもう中断できないが、つなげたファイルを消して行も消す
+    assert_eq!(service.cleanup_expired_uploads().await.unwrap(), 2);
+    for id in ids {
+      assert!(repository::find_by_id(&pool, id).await?.is_none());
+    }
+    assert!(storage.list("uploads/").await.unwrap().is_empty());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,23 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use super::service::UploadService;
+
+/// 期限までに送り終えなかった再開可能アップロードを定期的に削除するバックグラウンドタスクを起動する
+pub fn spawn_resumable_upload_cleanup_worker(upload_service: Arc<UploadService>, interval: Duration) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match upload_service.cleanup_expired_uploads().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Deleted {} expired resumable uploads", count),
+        Err(e) => tracing::error!("Failed to delete expired resumable uploads: {}", e),
+      }
+    }
+  })
+}
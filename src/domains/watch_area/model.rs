@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct WatchArea {
+  pub id: i32,
+  pub user_id: i32,
+  pub name: String,
+  pub lat: f64,
+  pub lng: f64,
+  pub radius_m: f64,
+  pub unsubscribed_at: Option<DateTime<Utc>>,
+  pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchAreasResponse {
+  pub watch_areas: Vec<WatchArea>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Validate)]
+pub struct CreateWatchAreaRequest {
+  #[validate(length(min = 1, max = 100, message = "エリア名は1文字以上100文字以内である必要があります"))]
+  pub name: String,
+  #[validate(range(min = -90.0, max = 90.0, message = "緯度は-90から90の範囲である必要があります"))]
+  pub lat: f64,
+  #[validate(range(min = -180.0, max = 180.0, message = "経度は-180から180の範囲である必要があります"))]
+  pub lng: f64,
+  #[validate(range(
+    min = 100.0,
+    max = 50000.0,
+    message = "半径は100mから50000mの範囲である必要があります"
+  ))]
+  pub radius_m: f64,
+}
+
+/// 未通知のエリア一致。ユーザー・エリアごとにまとめてメールにする
+#[derive(Debug, Clone, FromRow)]
+pub struct PendingAreaAlert {
+  pub match_id: i32,
+  pub user_id: i32,
+  pub email: String,
+  pub watch_area_id: i32,
+  pub area_name: String,
+  pub unsubscribe_token: String,
+  pub request_id: i32,
+  pub place_name: String,
+}
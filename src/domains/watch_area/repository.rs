@@ -0,0 +1,434 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+
+use super::model::{CreateWatchAreaRequest, PendingAreaAlert, WatchArea};
+
+pub async fn find_by_user_id(db: &PgPool, user_id: i32) -> Result<Vec<WatchArea>, sqlx::Error> {
+  find_by_user_id_with_executor(db, user_id).await
+}
+
+pub async fn find_by_user_id_with_executor<'e, E>(executor: E, user_id: i32) -> Result<Vec<WatchArea>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let areas = sqlx::query_as!(
+    WatchArea,
+    r#"
+      SELECT id, user_id, name, lat, lng, radius_m, unsubscribed_at, created_at
+      FROM watch_areas
+      WHERE user_id = $1
+      ORDER BY created_at ASC, id ASC
+    "#,
+    user_id
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(areas)
+}
+
+pub async fn count_by_user_id_with_executor<'e, E>(executor: E, user_id: i32) -> Result<i64, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let count = sqlx::query_scalar!(
+    r#"SELECT COUNT(*) as "count!" FROM watch_areas WHERE user_id = $1"#,
+    user_id
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(count)
+}
+
+pub async fn create(
+  db: &PgPool,
+  user_id: i32,
+  req: &CreateWatchAreaRequest,
+  unsubscribe_token: &str,
+) -> Result<WatchArea, sqlx::Error> {
+  create_with_executor(db, user_id, req, unsubscribe_token).await
+}
+
+pub async fn create_with_executor<'e, E>(
+  executor: E,
+  user_id: i32,
+  req: &CreateWatchAreaRequest,
+  unsubscribe_token: &str,
+) -> Result<WatchArea, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let area = sqlx::query_as!(
+    WatchArea,
+    r#"
+      INSERT INTO watch_areas (user_id, name, lat, lng, radius_m, unsubscribe_token)
+      VALUES ($1, $2, $3, $4, $5, $6)
+      RETURNING id, user_id, name, lat, lng, radius_m, unsubscribed_at, created_at
+    "#,
+    user_id,
+    req.name,
+    req.lat,
+    req.lng,
+    req.radius_m,
+    unsubscribe_token
+  )
+  .fetch_one(executor)
+  .await?;
+
+  Ok(area)
+}
+
+pub async fn find_by_id(db: &PgPool, id: i32) -> Result<Option<WatchArea>, sqlx::Error> {
+  find_by_id_with_executor(db, id).await
+}
+
+pub async fn find_by_id_with_executor<'e, E>(executor: E, id: i32) -> Result<Option<WatchArea>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let area = sqlx::query_as!(
+    WatchArea,
+    r#"
+      SELECT id, user_id, name, lat, lng, radius_m, unsubscribed_at, created_at
+      FROM watch_areas
+      WHERE id = $1
+    "#,
+    id
+  )
+  .fetch_optional(executor)
+  .await?;
+
+  Ok(area)
+}
+
+pub async fn delete(db: &PgPool, id: i32) -> Result<(), sqlx::Error> {
+  delete_with_executor(db, id).await
+}
+
+pub async fn delete_with_executor<'e, E>(executor: E, id: i32) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      DELETE FROM watch_areas
+      WHERE id = $1
+    "#,
+    id
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+pub async fn unsubscribe_by_token(db: &PgPool, unsubscribe_token: &str) -> Result<bool, sqlx::Error> {
+  unsubscribe_by_token_with_executor(db, unsubscribe_token).await
+}
+
+pub async fn unsubscribe_by_token_with_executor<'e, E>(
+  executor: E,
+  unsubscribe_token: &str,
+) -> Result<bool, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let result = sqlx::query!(
+    r#"
+      UPDATE watch_areas
+      SET unsubscribed_at = COALESCE(unsubscribed_at, NOW())
+      WHERE unsubscribe_token = $1
+    "#,
+    unsubscribe_token
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected() > 0)
+}
+
+/// 新しいリクエストの位置を含む監視エリアを一致として記録する（リクエスト作成者自身のエリアは除く）
+pub async fn record_matches_for_request_with_executor<'e, E>(
+  executor: E,
+  request_id: i32,
+  requester_id: i32,
+  lat: f64,
+  lng: f64,
+) -> Result<u64, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  // ハヴァサイン公式をSQLで実装（丸め誤差で acos の定義域を外れないよう LEAST で抑える）
+  let result = sqlx::query!(
+    r#"
+      INSERT INTO watch_area_matches (watch_area_id, request_id)
+      SELECT id, $1
+      FROM watch_areas
+      WHERE user_id <> $2
+        AND unsubscribed_at IS NULL
+        AND 6371000 * acos(LEAST(1.0,
+          cos(radians($3)) * cos(radians(lat)) *
+          cos(radians(lng) - radians($4)) +
+          sin(radians($3)) * sin(radians(lat))
+        )) <= radius_m
+      ON CONFLICT (watch_area_id, request_id) DO NOTHING
+    "#,
+    request_id,
+    requester_id,
+    lat,
+    lng
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(result.rows_affected())
+}
+
+/// 未通知の一致を取得する。`since` 以降に通知メールを受け取ったユーザーの分は次のバッチまで保留する。
+/// トランザクション内で呼ぶと取得した一致をロックし、他のインスタンスがロック中の一致は飛ばす
+pub async fn find_pending_alerts(db: &PgPool, since: DateTime<Utc>) -> Result<Vec<PendingAreaAlert>, sqlx::Error> {
+  find_pending_alerts_with_executor(db, since).await
+}
+
+pub async fn find_pending_alerts_with_executor<'e, E>(
+  executor: E,
+  since: DateTime<Utc>,
+) -> Result<Vec<PendingAreaAlert>, sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  let alerts = sqlx::query_as!(
+    PendingAreaAlert,
+    r#"
+      SELECT
+        m.id as match_id,
+        a.user_id,
+        u.email,
+        a.id as watch_area_id,
+        a.name as area_name,
+        a.unsubscribe_token,
+        r.id as request_id,
+        r.place_name
+      FROM watch_area_matches m
+      JOIN watch_areas a ON a.id = m.watch_area_id
+      JOIN users u ON u.id = a.user_id
+      JOIN requests r ON r.id = m.request_id
+      WHERE m.notified_at IS NULL
+        AND a.unsubscribed_at IS NULL
+        AND r.status = 'open'
+        AND NOT EXISTS (
+          SELECT 1
+          FROM watch_area_matches sent
+          JOIN watch_areas sent_area ON sent_area.id = sent.watch_area_id
+          WHERE sent_area.user_id = a.user_id
+            AND sent.notified_at >= $1
+        )
+      ORDER BY a.user_id, a.id, r.created_at, r.id
+      FOR UPDATE OF m SKIP LOCKED
+    "#,
+    since
+  )
+  .fetch_all(executor)
+  .await?;
+
+  Ok(alerts)
+}
+
+pub async fn mark_notified(db: &PgPool, match_ids: &[i32]) -> Result<(), sqlx::Error> {
+  mark_notified_with_executor(db, match_ids).await
+}
+
+pub async fn mark_notified_with_executor<'e, E>(executor: E, match_ids: &[i32]) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE watch_area_matches
+      SET notified_at = NOW()
+      WHERE id = ANY($1)
+    "#,
+    match_ids
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+/// 送信に失敗した一致を未通知に戻す
+pub async fn unmark_notified(db: &PgPool, match_ids: &[i32]) -> Result<(), sqlx::Error> {
+  unmark_notified_with_executor(db, match_ids).await
+}
+
+pub async fn unmark_notified_with_executor<'e, E>(executor: E, match_ids: &[i32]) -> Result<(), sqlx::Error>
+where
+  E: Executor<'e, Database = Postgres>,
+{
+  sqlx::query!(
+    r#"
+      UPDATE watch_area_matches
+      SET notified_at = NULL
+      WHERE id = ANY($1)
+    "#,
+    match_ids
+  )
+  .execute(executor)
+  .await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::domains::request::repository as request_repository;
+  use crate::test_support::request_payload;
+
+  fn area(name: &str, lat: f64, lng: f64, radius_m: f64) -> CreateWatchAreaRequest {
+    CreateWatchAreaRequest {
+      name: name.to_string(),
+      lat,
+      lng,
+      radius_m,
+    }
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn record_matches_only_inside_radius(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let watcher =
+      crate::domains::user::model::User::create(&pool, "watcher@example.com", "Watcher", "password123").await?;
+    let requester =
+      crate::domains::user::model::User::create(&pool, "requester@example.com", "Requester", "password123").await?;
+
+    // 東京駅から半径5km と 大阪駅から半径3km
+    let tokyo = create(
+      &pool,
+      watcher.id,
+      &area("東京駅周辺", 35.6812, 139.7671, 5000.0),
+      "tokyo-token",
+    )
+    .await?;
+    create(
+      &pool,
+      watcher.id,
+      &area("大阪駅周辺", 34.7025, 135.4959, 3000.0),
+      "osaka-token",
+    )
+    .await?;
+    // 作成者自身のエリアには通知しない
+    create(
+      &pool,
+      requester.id,
+      &area("自分のエリア", 35.6812, 139.7671, 3000.0),
+      "own-token",
+    )
+    .await?;
+
+    // 東京タワー（東京駅から約3.2km）
+    let request = request_repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6586, 139.7454, "東京タワー", "夜景"),
+    )
+    .await?;
+    let matched = record_matches_for_request_with_executor(&pool, request.id, requester.id, 35.6586, 139.7454).await?;
+    assert_eq!(matched, 1);
+
+    let alerts = find_pending_alerts(&pool, Utc::now() - chrono::Duration::hours(1)).await?;
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].watch_area_id, tokyo.id);
+    assert_eq!(alerts[0].request_id, request.id);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn pending_alerts_are_held_within_batch_window(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let watcher =
+      crate::domains::user::model::User::create(&pool, "batch-watcher@example.com", "Watcher", "password123").await?;
+    let requester =
+      crate::domains::user::model::User::create(&pool, "batch-req@example.com", "Requester", "password123").await?;
+    create(
+      &pool,
+      watcher.id,
+      &area("東京駅周辺", 35.6812, 139.7671, 3000.0),
+      "batch-token",
+    )
+    .await?;
+
+    let first = request_repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "一つ目"),
+    )
+    .await?;
+    record_matches_for_request_with_executor(&pool, first.id, requester.id, 35.6812, 139.7671).await?;
+
+    let window_start = Utc::now() - chrono::Duration::hours(1);
+    let alerts = find_pending_alerts(&pool, window_start).await?;
+    assert_eq!(alerts.len(), 1);
+    mark_notified(&pool, &[alerts[0].match_id]).await?;
+
+    let second = request_repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "二つ目"),
+    )
+    .await?;
+    record_matches_for_request_with_executor(&pool, second.id, requester.id, 35.6812, 139.7671).await?;
+
+    // 1時間以内に通知済みなので保留される
+    assert!(find_pending_alerts(&pool, window_start).await?.is_empty());
+    // 時間が経てば次のバッチで送られる
+    assert_eq!(
+      find_pending_alerts(&pool, Utc::now() + chrono::Duration::seconds(1))
+        .await?
+        .len(),
+      1
+    );
+
+    // 購読解除したエリアの一致は送らない
+    assert!(unsubscribe_by_token(&pool, "batch-token").await?);
+    assert!(find_pending_alerts(&pool, Utc::now() + chrono::Duration::seconds(1))
+      .await?
+      .is_empty());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn pending_alerts_locked_by_another_worker_are_skipped(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let watcher =
+      crate::domains::user::model::User::create(&pool, "lock-watcher@example.com", "Watcher", "password123").await?;
+    let requester =
+      crate::domains::user::model::User::create(&pool, "lock-req@example.com", "Requester", "password123").await?;
+    create(
+      &pool,
+      watcher.id,
+      &area("東京駅周辺", 35.6812, 139.7671, 3000.0),
+      "lock-token",
+    )
+    .await?;
+    let request = request_repository::create(
+      &pool,
+      requester.id,
+      &request_payload(35.6812, 139.7671, "東京駅", "駅舎"),
+    )
+    .await?;
+    record_matches_for_request_with_executor(&pool, request.id, requester.id, 35.6812, 139.7671).await?;
+
+    let since = Utc::now() - chrono::Duration::hours(1);
+    let mut tx = pool.begin().await?;
+    assert_eq!(find_pending_alerts_with_executor(&mut *tx, since).await?.len(), 1);
+
+    // 別のワーカーはロック中の一致を取得しない
+    assert!(find_pending_alerts(&pool, since).await?.is_empty());
+
+    tx.rollback().await?;
+    assert_eq!(find_pending_alerts(&pool, since).await?.len(), 1);
+
+    Ok(())
+  }
+}
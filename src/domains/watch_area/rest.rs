@@ -0,0 +1,234 @@
+use axum::{
+  extract::{Json, Path, State},
+  http::HeaderMap,
+  response::Json as JsonResponse,
+  routing::{delete, get},
+  Router,
+};
+use validator::Validate;
+
+use super::model::{CreateWatchAreaRequest, WatchArea, WatchAreasResponse};
+use crate::{
+  middleware::auth::auth_middleware,
+  state::{AppState, SharedAppState},
+  AppError,
+};
+
+pub fn watch_area_routes() -> Router<SharedAppState> {
+  Router::new()
+    .route(
+      "/users/me/watch-areas",
+      get(list_watch_areas_handler).post(create_watch_area_handler),
+    )
+    .route(
+      "/users/me/watch-areas/{watch_area_id}",
+      delete(delete_watch_area_handler),
+    )
+    .route(
+      "/watch-areas/unsubscribe/{token}",
+      get(unsubscribe_watch_area_by_token_handler),
+    )
+}
+
+pub async fn list_watch_areas_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+) -> Result<JsonResponse<WatchAreasResponse>, AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .list_watch_areas(user_id)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn create_watch_area_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Json(payload): Json<CreateWatchAreaRequest>,
+) -> Result<JsonResponse<WatchArea>, AppError> {
+  payload
+    .validate()
+    .map_err(|e| AppError::bad_request(format!("Validation failed: {}", e)))?;
+
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state
+    .create_watch_area(user_id, payload)
+    .await
+    .map(JsonResponse)
+    .map_err(Into::into)
+}
+
+pub async fn delete_watch_area_handler(
+  State(state): State<SharedAppState>,
+  headers: HeaderMap,
+  Path(watch_area_id): Path<i32>,
+) -> Result<(), AppError> {
+  let claims = auth_middleware(headers).await?;
+  let user_id = claims.user_id;
+
+  state.delete_watch_area(watch_area_id, user_id).await?;
+
+  Ok(())
+}
+
+pub async fn unsubscribe_watch_area_by_token_handler(
+  State(state): State<SharedAppState>,
+  Path(token): Path<String>,
+) -> Result<(), AppError> {
+  state.unsubscribe_watch_area_by_token(token).await?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::super::{
+    model::{CreateWatchAreaRequest, WatchAreasResponse},
+    service::MAX_WATCH_AREAS_PER_USER,
+  };
+  use crate::test_support::{
+    app_with_pool, delete_with_auth, get, get_with_auth, login_verified_user, post_json_with_auth, request_payload,
+  };
+  use axum::http::StatusCode;
+
+  fn area_payload(name: &str) -> CreateWatchAreaRequest {
+    CreateWatchAreaRequest {
+      name: name.to_string(),
+      lat: 35.6812,
+      lng: 139.7671,
+      radius_m: 3000.0,
+    }
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_and_list_watch_areas(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user = crate::domains::user::model::User::create(&pool, "area@example.com", "Area", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "area@example.com").await?;
+
+    for i in 0..MAX_WATCH_AREAS_PER_USER {
+      let (status, _) = post_json_with_auth(
+        app.clone(),
+        "/api/v1/users/me/watch-areas",
+        &area_payload(&format!("エリア{}", i)),
+        &token,
+      )
+      .await;
+      assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _) = post_json_with_auth(
+      app.clone(),
+      "/api/v1/users/me/watch-areas",
+      &area_payload("上限超え"),
+      &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = get_with_auth(app, "/api/v1/users/me/watch-areas", &token).await;
+    assert_eq!(status, StatusCode::OK);
+    let response: WatchAreasResponse = serde_json::from_slice(&body).expect("deserialize response");
+    assert_eq!(response.watch_areas.len() as i64, MAX_WATCH_AREAS_PER_USER);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn create_watch_area_invalid_radius(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let _user =
+      crate::domains::user::model::User::create(&pool, "area-radius@example.com", "Area", "password123").await?;
+    let token = login_verified_user(app.clone(), &pool, "area-radius@example.com").await?;
+
+    let payload = CreateWatchAreaRequest {
+      radius_m: 10.0,
+      ..area_payload("狭すぎる")
+    };
+    let (status, _) = post_json_with_auth(app, "/api/v1/users/me/watch-areas", &payload, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn delete_watch_area_forbidden(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let owner =
+      crate::domains::user::model::User::create(&pool, "area-owner@example.com", "Owner", "password123").await?;
+    let _other =
+      crate::domains::user::model::User::create(&pool, "area-other@example.com", "Other", "password123").await?;
+    let area = super::super::repository::create(&pool, owner.id, &area_payload("東京"), "area-del-token").await?;
+    let other_token = login_verified_user(app.clone(), &pool, "area-other@example.com").await?;
+    let owner_token = login_verified_user(app.clone(), &pool, "area-owner@example.com").await?;
+
+    let uri = format!("/api/v1/users/me/watch-areas/{}", area.id);
+    let (status, _) = delete_with_auth(app.clone(), &uri, &other_token).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = delete_with_auth(app, &uri, &owner_token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(super::super::repository::find_by_id(&pool, area.id).await?.is_none());
+
+    Ok(())
+  }
+
+  #[sqlx::test(migrations = "./migrations")]
+  async fn new_request_in_area_is_recorded_and_unsubscribe_stops_it(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+    let app = app_with_pool(pool.clone()).await;
+
+    let watcher =
+      crate::domains::user::model::User::create(&pool, "area-watch@example.com", "Watcher", "password123").await?;
+    let _requester =
+      crate::domains::user::model::User::create(&pool, "area-req@example.com", "Requester", "password123").await?;
+    let area = super::super::repository::create(&pool, watcher.id, &area_payload("東京駅"), "area-flow-token").await?;
+    let requester_token = login_verified_user(app.clone(), &pool, "area-req@example.com").await?;
+
+    let (status, _) = post_json_with_auth(
+      app.clone(),
+      "/api/v1/requests",
+      &request_payload(35.6800, 139.7690, "丸の内", "駅前の写真"),
+      &requester_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let matches = sqlx::query_scalar!(
+      "SELECT COUNT(*) FROM watch_area_matches WHERE watch_area_id = $1",
+      area.id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(matches, Some(1));
+
+    let (status, _) = get(app.clone(), "/api/v1/watch-areas/unsubscribe/area-flow-token").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json_with_auth(
+      app,
+      "/api/v1/requests",
+      &request_payload(35.6800, 139.7690, "丸の内", "もう一枚"),
+      &requester_token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let matches = sqlx::query_scalar!(
+      "SELECT COUNT(*) FROM watch_area_matches WHERE watch_area_id = $1",
+      area.id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(matches, Some(1));
+
+    Ok(())
+  }
+}
@@ -0,0 +1,162 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::error::Error;
+use uuid::Uuid;
+
+use crate::domains::watch_area::{
+  model::{CreateWatchAreaRequest, PendingAreaAlert, WatchArea, WatchAreasResponse},
+  repository,
+};
+use crate::email::{AreaAlertRequest, AreaAlertSection, EmailService};
+use crate::impl_service_error_conversions;
+
+/// 1ユーザーが登録できる監視エリアの上限
+pub const MAX_WATCH_AREAS_PER_USER: i64 = 5;
+
+/// 1ユーザーに送るエリア通知メールの最短間隔
+const ALERT_BATCH_WINDOW_HOURS: i64 = 1;
+
+#[derive(Debug)]
+pub enum WatchAreaServiceError {
+  InternalServerError(String),
+  BadRequest(String),
+  NotFound(String),
+  Forbidden(String),
+  Conflict(String),
+}
+
+impl Error for WatchAreaServiceError {}
+
+impl std::fmt::Display for WatchAreaServiceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      WatchAreaServiceError::InternalServerError(msg) => write!(f, "Internal Server Error: {}", msg),
+      WatchAreaServiceError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+      WatchAreaServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+      WatchAreaServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+      WatchAreaServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+    }
+  }
+}
+
+impl_service_error_conversions!(WatchAreaServiceError, InternalServerError);
+
+pub struct WatchAreaService {
+  pool: PgPool,
+  email_service: EmailService,
+}
+
+impl WatchAreaService {
+  pub fn new(pool: PgPool, email_service: EmailService) -> Self {
+    Self { pool, email_service }
+  }
+
+  pub async fn list_watch_areas(&self, user_id: i32) -> Result<WatchAreasResponse, WatchAreaServiceError> {
+    let watch_areas = repository::find_by_user_id(&self.pool, user_id).await?;
+    Ok(WatchAreasResponse { watch_areas })
+  }
+
+  pub async fn create_watch_area(
+    &self,
+    user_id: i32,
+    req: CreateWatchAreaRequest,
+  ) -> Result<WatchArea, WatchAreaServiceError> {
+    let mut tx = self.pool.begin().await?;
+
+    // 同じユーザーの同時登録で上限を超えないよう、ユーザー行をロックしてから数える
+    sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+      .fetch_one(&mut *tx.as_mut())
+      .await?;
+
+    let count = repository::count_by_user_id_with_executor(&mut *tx.as_mut(), user_id).await?;
+    if count >= MAX_WATCH_AREAS_PER_USER {
+      return Err(WatchAreaServiceError::Conflict(format!(
+        "You can register at most {} watch areas",
+        MAX_WATCH_AREAS_PER_USER
+      )));
+    }
+
+    let area = repository::create_with_executor(&mut *tx.as_mut(), user_id, &req, &Uuid::new_v4().to_string()).await?;
+
+    tx.commit().await?;
+
+    Ok(area)
+  }
+
+  pub async fn delete_watch_area(&self, watch_area_id: i32, user_id: i32) -> Result<(), WatchAreaServiceError> {
+    let area = repository::find_by_id(&self.pool, watch_area_id)
+      .await?
+      .ok_or_else(|| WatchAreaServiceError::NotFound(format!("Watch area with id {} not found", watch_area_id)))?;
+
+    if area.user_id != user_id {
+      return Err(WatchAreaServiceError::Forbidden(
+        "You do not have permission to delete this watch area".to_string(),
+      ));
+    }
+
+    repository::delete(&self.pool, watch_area_id).await?;
+    Ok(())
+  }
+
+  pub async fn unsubscribe_by_token(&self, token: &str) -> Result<(), WatchAreaServiceError> {
+    if !repository::unsubscribe_by_token(&self.pool, token).await? {
+      return Err(WatchAreaServiceError::NotFound("Watch area not found".to_string()));
+    }
+    Ok(())
+  }
+
+  /// 未通知のエリア一致をユーザーごとに1通のメールにまとめて送る。送信したメールの数を返す
+  pub async fn send_pending_alerts(&self) -> Result<usize, WatchAreaServiceError> {
+    let alerts = self.claim_pending_alerts().await?;
+
+    let mut sent = 0;
+    for user_alerts in alerts.chunk_by(|a, b| a.user_id == b.user_id) {
+      let email = &user_alerts[0].email;
+      let subject = "監視エリアに新しいリクエストがあります";
+      let body = EmailService::build_area_alert_email_body(&group_by_area(user_alerts));
+
+      if let Err(e) = self.email_service.send_simple_text_email(email, subject, &body).await {
+        // 未通知に戻し、次回のバッチで再送する
+        tracing::error!("Failed to send area alert to user {}: {:?}", user_alerts[0].user_id, e);
+        let match_ids: Vec<i32> = user_alerts.iter().map(|a| a.match_id).collect();
+        repository::unmark_notified(&self.pool, &match_ids).await?;
+        continue;
+      }
+
+      sent += 1;
+    }
+
+    Ok(sent)
+  }
+
+  /// 送信前に未通知の一致を通知済みにしてコミットし、複数のインスタンスが同じメールを送らないようにする
+  async fn claim_pending_alerts(&self) -> Result<Vec<PendingAreaAlert>, WatchAreaServiceError> {
+    let since = Utc::now() - Duration::hours(ALERT_BATCH_WINDOW_HOURS);
+
+    let mut tx = self.pool.begin().await?;
+    let alerts = repository::find_pending_alerts_with_executor(&mut *tx.as_mut(), since).await?;
+    let match_ids: Vec<i32> = alerts.iter().map(|a| a.match_id).collect();
+    repository::mark_notified_with_executor(&mut *tx.as_mut(), &match_ids).await?;
+    tx.commit().await?;
+
+    Ok(alerts)
+  }
+}
+
+/// 同じユーザーの一致（エリア順に並んでいる前提）をエリアごとのセクションにまとめる
+fn group_by_area(alerts: &[PendingAreaAlert]) -> Vec<AreaAlertSection> {
+  alerts
+    .chunk_by(|a, b| a.watch_area_id == b.watch_area_id)
+    .map(|area_alerts| AreaAlertSection {
+      area_name: area_alerts[0].area_name.clone(),
+      unsubscribe_token: area_alerts[0].unsubscribe_token.clone(),
+      requests: area_alerts
+        .iter()
+        .map(|a| AreaAlertRequest {
+          id: a.request_id,
+          place_name: a.place_name.clone(),
+        })
+        .collect(),
+    })
+    .collect()
+}
@@ -0,0 +1,34 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+
+use super::service::WatchAreaService;
+
+const DEFAULT_ALERT_INTERVAL_SECS: u64 = 300;
+
+pub fn alert_interval_from_env() -> Duration {
+  let secs = std::env::var("AREA_ALERT_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(DEFAULT_ALERT_INTERVAL_SECS);
+
+  Duration::from_secs(secs)
+}
+
+/// 監視エリアの新着リクエストを定期的にメールで通知するバックグラウンドタスクを起動する
+pub fn spawn_alert_worker(watch_area_service: Arc<WatchAreaService>, interval: Duration) -> JoinHandle<()> {
+  tokio::spawn(async move {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+      ticker.tick().await;
+
+      match watch_area_service.send_pending_alerts().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Sent {} area alert emails", count),
+        Err(e) => tracing::error!("Failed to send area alerts: {}", e),
+      }
+    }
+  })
+}
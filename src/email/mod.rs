@@ -7,4 +7,4 @@ mod service;
 mod types;
 
 pub use service::EmailService;
-pub use types::{EmailMessage, SmtpConfig};
+pub use types::{AreaAlertRequest, AreaAlertSection, EmailMessage, SmtpConfig};
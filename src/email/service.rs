@@ -1,10 +1,11 @@
-use crate::email::types::{EmailMessage, SmtpConfig};
+use crate::email::types::{AreaAlertSection, EmailMessage, SmtpConfig};
 use anyhow::Result;
 use lettre::{
   message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport,
   Message, Tokio1Executor,
 };
 
+#[derive(Clone)]
 pub struct EmailService {
   smtp_config: SmtpConfig,
   transporter: AsyncSmtpTransport<Tokio1Executor>,
@@ -61,6 +62,54 @@ impl EmailService {
       verification_url
     )
   }
+
+  pub fn build_request_expired_email_body(request_id: i32, place_name: &str) -> String {
+    let frontend_url = std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:1420".to_string());
+    let request_url = format!("{}/requests/{}", frontend_url, request_id);
+
+    format!(
+      "こんにちは、\n\nあなたのリクエスト「{}」は期限を過ぎたため終了しました。\n\n{}\n\n引き続き写真が必要な場合は、新しいリクエストを作成してください。\n\nよろしくお願いします。",
+      place_name, request_url
+    )
+  }
+
+  pub fn build_comment_notification_email_body(
+    request_id: i32,
+    place_name: &str,
+    comment_body: &str,
+    unsubscribe_token: &str,
+  ) -> String {
+    let frontend_url = std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:1420".to_string());
+    let request_url = format!("{}/requests/{}", frontend_url, request_id);
+    let unsubscribe_url = format!("{}/unsubscribe/comments/{}", frontend_url, unsubscribe_token);
+
+    format!(
+      "こんにちは、\n\nリクエスト「{}」に新しいコメントがありました:\n\n{}\n\n{}\n\nこのリクエストのコメント通知を停止するには、以下のリンクをクリックしてください:\n{}\n\nよろしくお願いします。",
+      place_name, comment_body, request_url, unsubscribe_url
+    )
+  }
+
+  pub fn build_area_alert_email_body(sections: &[AreaAlertSection]) -> String {
+    let frontend_url = std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:1420".to_string());
+
+    let mut body = "こんにちは、\n\n登録した監視エリアの近くに新しいリクエストが作成されました。\n".to_string();
+    for section in sections {
+      body.push_str(&format!("\n■ {}\n", section.area_name));
+      for request in &section.requests {
+        body.push_str(&format!(
+          "・{}\n  {}/requests/{}\n",
+          request.place_name, frontend_url, request.id
+        ));
+      }
+      body.push_str(&format!(
+        "このエリアの通知を停止する: {}/unsubscribe/watch-areas/{}\n",
+        frontend_url, section.unsubscribe_token
+      ));
+    }
+    body.push_str("\nよろしくお願いします。");
+
+    body
+  }
 }
 
 #[cfg(test)]
@@ -123,6 +172,7 @@ mod tests {
   }
 
   #[test]
+  #[serial_test::serial]
   fn test_build_verification_email_body() {
     env::set_var("FRONTEND_URL", "https://example.com");
 
@@ -136,6 +186,7 @@ mod tests {
   }
 
   #[test]
+  #[serial_test::serial]
   fn test_build_verification_email_body_default_url() {
     env::remove_var("FRONTEND_URL");
 
@@ -146,6 +197,63 @@ mod tests {
     assert_eq!(actual_body, expected_body);
   }
 
+  #[test]
+  #[serial_test::serial]
+  fn test_build_request_expired_email_body() {
+    env::set_var("FRONTEND_URL", "https://example.com");
+
+    let expected_body = "こんにちは、\n\nあなたのリクエスト「東京タワー」は期限を過ぎたため終了しました。\n\nhttps://example.com/requests/42\n\n引き続き写真が必要な場合は、新しいリクエストを作成してください。\n\nよろしくお願いします。";
+
+    let actual_body = EmailService::build_request_expired_email_body(42, "東京タワー");
+    assert_eq!(actual_body, expected_body);
+
+    env::remove_var("FRONTEND_URL");
+  }
+
+  #[test]
+  #[serial_test::serial]
+  fn test_build_comment_notification_email_body() {
+    env::set_var("FRONTEND_URL", "https://example.com");
+
+    let expected_body = "こんにちは、\n\nリクエスト「東京タワー」に新しいコメントがありました:\n\n北側からでも大丈夫ですか？\n\nhttps://example.com/requests/42\n\nこのリクエストのコメント通知を停止するには、以下のリンクをクリックしてください:\nhttps://example.com/unsubscribe/comments/tok123\n\nよろしくお願いします。";
+
+    let actual_body =
+      EmailService::build_comment_notification_email_body(42, "東京タワー", "北側からでも大丈夫ですか？", "tok123");
+    assert_eq!(actual_body, expected_body);
+
+    env::remove_var("FRONTEND_URL");
+  }
+
+  #[test]
+  #[serial_test::serial]
+  fn test_build_area_alert_email_body() {
+    use crate::email::types::AreaAlertRequest;
+
+    env::set_var("FRONTEND_URL", "https://example.com");
+
+    let sections = vec![AreaAlertSection {
+      area_name: "東京駅周辺".to_string(),
+      unsubscribe_token: "area-token".to_string(),
+      requests: vec![
+        AreaAlertRequest {
+          id: 1,
+          place_name: "東京タワー".to_string(),
+        },
+        AreaAlertRequest {
+          id: 2,
+          place_name: "皇居".to_string(),
+        },
+      ],
+    }];
+
+    let expected_body = "こんにちは、\n\n登録した監視エリアの近くに新しいリクエストが作成されました。\n\n■ 東京駅周辺\n・東京タワー\n  https://example.com/requests/1\n・皇居\n  https://example.com/requests/2\nこのエリアの通知を停止する: https://example.com/unsubscribe/watch-areas/area-token\n\nよろしくお願いします。";
+
+    let actual_body = EmailService::build_area_alert_email_body(&sections);
+    assert_eq!(actual_body, expected_body);
+
+    env::remove_var("FRONTEND_URL");
+  }
+
   #[tokio::test]
   async fn test_email_service_new_with_localhost_smtp() -> Result<()> {
     let smtp_config = SmtpConfig {
@@ -33,3 +33,17 @@ impl EmailMessage {
     EmailMessage { to, subject, body }
   }
 }
+
+/// エリア通知メールに載せる、監視エリアごとの新着リクエスト
+#[derive(Debug, Clone)]
+pub struct AreaAlertSection {
+  pub area_name: String,
+  pub unsubscribe_token: String,
+  pub requests: Vec<AreaAlertRequest>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AreaAlertRequest {
+  pub id: i32,
+  pub place_name: String,
+}
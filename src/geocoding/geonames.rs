@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use super::{Geocoder, Location};
+use crate::utils::geo::haversine_distance;
+
+/// 最寄りの地点として採用する最大距離（メートル）。これより遠い場合は海上などとみなして解決しない
+const MAX_DISTANCE_M: f64 = 30_000.0;
+
+/// 近傍探索用のグリッドの1マスの大きさ（度）
+const GRID_CELL_DEG: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+struct Place {
+  name: String,
+  lat: f64,
+  lng: f64,
+  prefecture: Option<String>,
+}
+
+/// GeoNames 形式（`cities1000.txt` などのタブ区切り）のデータセットから最寄りの市区町村を引くオフライン実装
+pub struct GeoNamesGeocoder {
+  places: Vec<Place>,
+  grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl GeoNamesGeocoder {
+  /// `cities` は GeoNames の cities ファイル、`admin1` は `admin1CodesASCII.txt`（都道府県名の解決に使う）
+  pub fn from_readers(cities: impl BufRead, admin1: Option<impl BufRead>) -> Result<Self> {
+    let admin1_names = match admin1 {
+      Some(reader) => parse_admin1(reader)?,
+      None => HashMap::new(),
+    };
+
+    let mut places = Vec::new();
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+
+    for (i, line) in cities.lines().enumerate() {
+      let line = line?;
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let columns: Vec<&str> = line.split('\t').collect();
+      if columns.len() < 11 {
+        return Err(anyhow!("Invalid GeoNames cities line {}: too few columns", i + 1));
+      }
+
+      let lat: f64 = columns[4]
+        .parse()
+        .with_context(|| format!("Invalid latitude on line {}", i + 1))?;
+      let lng: f64 = columns[5]
+        .parse()
+        .with_context(|| format!("Invalid longitude on line {}", i + 1))?;
+      let prefecture = admin1_names.get(&format!("{}.{}", columns[8], columns[10])).cloned();
+
+      grid.entry(cell_of(lat, lng)).or_default().push(places.len());
+      places.push(Place {
+        name: columns[1].to_string(),
+        lat,
+        lng,
+        prefecture,
+      });
+    }
+
+    Ok(Self { places, grid })
+  }
+
+  pub fn len(&self) -> usize {
+    self.places.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.places.is_empty()
+  }
+
+  fn nearest(&self, lat: f64, lng: f64) -> Option<&Place> {
+    let (cell_lat, cell_lng) = cell_of(lat, lng);
+
+    // 周囲のマスも含めて探す（1マスは MAX_DISTANCE_M より十分大きい）
+    let mut best: Option<(&Place, f64)> = None;
+    for d_lat in -1..=1 {
+      for d_lng in -1..=1 {
+        let cell = (cell_lat + d_lat, wrap_lng_cell(cell_lng + d_lng));
+        for &index in self.grid.get(&cell).into_iter().flatten() {
+          let place = &self.places[index];
+          let distance = haversine_distance(lat, lng, place.lat, place.lng);
+          if distance <= MAX_DISTANCE_M && best.is_none_or(|(_, d)| distance < d) {
+            best = Some((place, distance));
+          }
+        }
+      }
+    }
+
+    best.map(|(place, _)| place)
+  }
+}
+
+#[async_trait]
+impl Geocoder for GeoNamesGeocoder {
+  async fn reverse(&self, lat: f64, lng: f64) -> Result<Option<Location>> {
+    Ok(self.nearest(lat, lng).map(|place| Location {
+      prefecture: place.prefecture.clone(),
+      city: Some(place.name.clone()),
+    }))
+  }
+}
+
+fn parse_admin1(reader: impl BufRead) -> Result<HashMap<String, String>> {
+  let mut names = HashMap::new();
+
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let mut columns = line.split('\t');
+    match (columns.next(), columns.next()) {
+      (Some(code), Some(name)) => {
+        names.insert(code.to_string(), name.to_string());
+      }
+      _ => return Err(anyhow!("Invalid GeoNames admin1 line {}", i + 1)),
+    }
+  }
+
+  Ok(names)
+}
+
+fn cell_of(lat: f64, lng: f64) -> (i32, i32) {
+  (
+    (lat / GRID_CELL_DEG).floor() as i32,
+    wrap_lng_cell((lng / GRID_CELL_DEG).floor() as i32),
+  )
+}
+
+/// 日付変更線をまたいでも隣のマスを辿れるよう経度方向のマスを折り返す
+fn wrap_lng_cell(cell: i32) -> i32 {
+  let cells = (360.0 / GRID_CELL_DEG) as i32;
+  (cell + cells / 2).rem_euclid(cells) - cells / 2
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  pub(crate) const SAMPLE_CITIES: &str = "\
+1850147\tTokyo\tTokyo\t\t35.6895\t139.69171\tP\tPPLC\tJP\t\t40\t\t\t\t8336599\t\t44\tAsia/Tokyo\t2024-01-01
+1850692\tShibuya\tShibuya\t\t35.66361\t139.69694\tP\tPPLX\tJP\t\t40\t\t\t\t224533\t\t35\tAsia/Tokyo\t2024-01-01
+1853909\tOsaka\tOsaka\t\t34.69374\t135.50218\tP\tPPLA\tJP\t\t32\t\t\t\t2592413\t\t24\tAsia/Tokyo\t2024-01-01
+";
+
+  pub(crate) const SAMPLE_ADMIN1: &str = "\
+JP.40\tTokyo\tTokyo\t1850144
+JP.32\tOsaka\tOsaka\t1853904
+";
+
+  pub(crate) fn sample_geocoder() -> GeoNamesGeocoder {
+    GeoNamesGeocoder::from_readers(SAMPLE_CITIES.as_bytes(), Some(SAMPLE_ADMIN1.as_bytes()))
+      .expect("load sample dataset")
+  }
+
+  #[tokio::test]
+  async fn reverse_resolves_nearest_city() {
+    let geocoder = sample_geocoder();
+    assert_eq!(geocoder.len(), 3);
+
+    // 渋谷駅付近
+    let location = geocoder.reverse(35.6580, 139.7016).await.unwrap();
+    assert_eq!(
+      location,
+      Some(Location {
+        prefecture: Some("Tokyo".to_string()),
+        city: Some("Shibuya".to_string()),
+      })
+    );
+
+    // 大阪駅付近
+    let location = geocoder.reverse(34.7025, 135.4959).await.unwrap().unwrap();
+    assert_eq!(location.prefecture.as_deref(), Some("Osaka"));
+    assert_eq!(location.city.as_deref(), Some("Osaka"));
+  }
+
+  #[tokio::test]
+  async fn reverse_returns_none_when_too_far() {
+    let geocoder = sample_geocoder();
+
+    // 太平洋上
+    assert_eq!(geocoder.reverse(30.0, 150.0).await.unwrap(), None);
+  }
+
+  #[test]
+  fn from_readers_without_admin1_leaves_prefecture_empty() {
+    let geocoder = GeoNamesGeocoder::from_readers(SAMPLE_CITIES.as_bytes(), None::<&[u8]>).unwrap();
+    let place = geocoder.nearest(35.6895, 139.69171).unwrap();
+    assert_eq!(place.name, "Tokyo");
+    assert_eq!(place.prefecture, None);
+  }
+
+  #[test]
+  fn from_readers_rejects_malformed_lines() {
+    let result = GeoNamesGeocoder::from_readers("1\tBroken\t35.0".as_bytes(), None::<&[u8]>);
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn wrap_lng_cell_crosses_antimeridian() {
+    assert_eq!(wrap_lng_cell(180), -180);
+    assert_eq!(wrap_lng_cell(-181), 179);
+    assert_eq!(wrap_lng_cell(139), 139);
+  }
+}
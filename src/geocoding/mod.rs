@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{env, fs::File, io::BufReader, sync::Arc};
+
+pub mod geonames;
+
+pub use geonames::GeoNamesGeocoder;
+
+/// 逆ジオコーディングの結果
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+  pub prefecture: Option<String>,
+  pub city: Option<String>,
+}
+
+/// 緯度経度から都道府県・市区町村を引く。オフラインのデータセットや外部の HTTP API など実装を差し替えられるようにする
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+  /// 該当する地域が見つからない場合は `Ok(None)` を返す
+  async fn reverse(&self, lat: f64, lng: f64) -> Result<Option<Location>>;
+}
+
+/// データセットが設定されていない環境向けの、何も解決しない実装
+pub struct NoopGeocoder;
+
+#[async_trait]
+impl Geocoder for NoopGeocoder {
+  async fn reverse(&self, _lat: f64, _lng: f64) -> Result<Option<Location>> {
+    Ok(None)
+  }
+}
+
+/// 環境変数からジオコーダーを組み立てる。`GEONAMES_CITIES_PATH` が未設定なら逆ジオコーディングは行わない
+pub fn init_geocoder() -> Result<Arc<dyn Geocoder>> {
+  let Ok(cities_path) = env::var("GEONAMES_CITIES_PATH") else {
+    tracing::warn!("GEONAMES_CITIES_PATH not set; reverse geocoding is disabled");
+    return Ok(Arc::new(NoopGeocoder));
+  };
+
+  let cities = File::open(&cities_path).with_context(|| format!("Failed to open {}", cities_path))?;
+  let admin1 = match env::var("GEONAMES_ADMIN1_PATH") {
+    Ok(path) => Some(BufReader::new(
+      File::open(&path).with_context(|| format!("Failed to open {}", path))?,
+    )),
+    Err(_) => None,
+  };
+
+  let geocoder = GeoNamesGeocoder::from_readers(BufReader::new(cities), admin1)?;
+  tracing::info!("Loaded {} places for reverse geocoding", geocoder.len());
+
+  Ok(Arc::new(geocoder))
+}
@@ -3,6 +3,7 @@ pub mod db;
 pub mod domains;
 pub mod email;
 pub mod error;
+pub mod geocoding;
 pub mod middleware;
 pub mod state;
 pub mod storage;
@@ -1,12 +1,21 @@
-use axum::http::Method;
+use axum::http::{HeaderName, Method};
 use dotenvy::dotenv;
 use tokio::signal;
 use tower_http::cors::{Any, CorsLayer};
 
 use koko_pic_api::app::create_app;
 use koko_pic_api::db::pool::create_pool;
+use koko_pic_api::domains::picture::worker::{
+  spawn_upload_cleanup_worker, spawn_upload_processing_worker, upload_cleanup_interval_from_env,
+  upload_processing_interval_from_env,
+};
+use koko_pic_api::domains::request::worker::{expiry_interval_from_env, spawn_expiry_worker};
+use koko_pic_api::domains::storage_operation::worker::{reconcile_interval_from_env, spawn_storage_reconcile_worker};
+use koko_pic_api::domains::upload::worker::spawn_resumable_upload_cleanup_worker;
+use koko_pic_api::domains::watch_area::worker::{alert_interval_from_env, spawn_alert_worker};
+use koko_pic_api::geocoding::init_geocoder;
 use koko_pic_api::state::SharedAppState;
-use koko_pic_api::storage::S3Storage;
+use koko_pic_api::storage::init_storage;
 use koko_pic_api::utils::init_email_service;
 
 #[tokio::main]
@@ -22,14 +31,44 @@ async fn main() -> anyhow::Result<()> {
   println!("Database migrations applied successfully");
 
   let email_service = init_email_service().await?;
-  let storage = S3Storage::new().await?;
-  let app_state = SharedAppState::new(pool, email_service, storage).await;
+  let storage = init_storage().await?;
+  let geocoder = init_geocoder()?;
+  let app_state = SharedAppState::new(pool, email_service, storage, geocoder).await;
+
+  spawn_expiry_worker(app_state.request_service.clone(), expiry_interval_from_env());
+  spawn_alert_worker(app_state.watch_area_service.clone(), alert_interval_from_env());
+  spawn_upload_cleanup_worker(app_state.picture_service.clone(), upload_cleanup_interval_from_env());
+  spawn_upload_processing_worker(app_state.picture_service.clone(), upload_processing_interval_from_env());
+  spawn_resumable_upload_cleanup_worker(app_state.upload_service.clone(), upload_cleanup_interval_from_env());
+  spawn_storage_reconcile_worker(
+    app_state.storage_operation_service.clone(),
+    reconcile_interval_from_env(),
+  );
 
   let app = create_app(app_state).layer(
     CorsLayer::new()
-      .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
+      .allow_methods([
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::OPTIONS,
+        Method::HEAD,
+      ])
       .allow_origin(Any)
-      .allow_headers(Any),
+      .allow_headers(Any)
+      // ブラウザの tus クライアントが読めるよう、再開可能アップロードのヘッダーを公開する
+      .expose_headers([
+        HeaderName::from_static("location"),
+        HeaderName::from_static("tus-resumable"),
+        HeaderName::from_static("tus-version"),
+        HeaderName::from_static("tus-extension"),
+        HeaderName::from_static("tus-max-size"),
+        HeaderName::from_static("upload-offset"),
+        HeaderName::from_static("upload-length"),
+        HeaderName::from_static("picture-id"),
+      ]),
   );
 
   let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
@@ -18,3 +18,12 @@ pub async fn auth_middleware(headers: HeaderMap) -> Result<Claims, AppError> {
 
   Ok(claims)
 }
+
+/// Authorization ヘッダーがあれば検証し、なければ未ログインとして `None` を返す
+pub async fn optional_auth_middleware(headers: HeaderMap) -> Result<Option<Claims>, AppError> {
+  if !headers.contains_key(axum::http::header::AUTHORIZATION) {
+    return Ok(None);
+  }
+
+  auth_middleware(headers).await.map(Some)
+}
@@ -1,25 +1,51 @@
 use std::sync::Arc;
 
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
   domains::{
+    comment::{
+      model::{Comment, CommentsResponse, CreateCommentRequest, UpdateCommentRequest},
+      service::{CommentService, CommentServiceError},
+    },
     picture::{
-      model::Picture,
-      service::{PictureService, PictureServiceError, PictureServiceImpl},
+      format::ImageLimits,
+      model::{
+        CreatePictureUploadRequest, Picture, PictureFeedResponse, PictureUploadResponse, PictureUploadStatus,
+        PictureWithAuthor, StagedUpload, SubmissionsResponse,
+      },
+      service::{FileChunks, PictureService, PictureServiceError, PictureServiceImpl},
     },
     request::{
-      model::{CreateRequestRequest, Request, RequestsResponse},
-      service::RequestService,
+      model::{
+        CreateRequestRequest, MyRequestsResponse, Request, RequestFilter, RequestsResponse, UpdateRequestRequest,
+      },
+      service::{RequestService, RequestServiceError},
+    },
+    storage_operation::service::StorageOperationService,
+    tag::{
+      model::PopularTagsResponse,
+      service::{TagService, TagServiceError},
+    },
+    upload::{
+      model::{CreateResumableUploadRequest, ResumableUpload, ResumableUploadStatus},
+      service::{UploadService, UploadServiceError},
     },
     user::{
       model::{CreateUserRequest, LoginRequest, LoginResponse, User, VerifyEmailResponse},
       repository::{SqlxUserRepository, SqlxVerificationTokenRepository},
       service::{UserService, UserServiceError, UserServiceImpl},
     },
+    watch_area::{
+      model::{CreateWatchAreaRequest, WatchArea, WatchAreasResponse},
+      service::{WatchAreaService, WatchAreaServiceError},
+    },
   },
   email::EmailService,
-  storage::S3Storage,
+  geocoding::Geocoder,
+  storage::ObjectStorage,
+  utils::pagination::{CursorPage, Page},
 };
 
 pub trait AppState: Clone + Send + Sync + 'static {
@@ -49,58 +75,268 @@ pub trait AppState: Clone + Send + Sync + 'static {
     user_id: i32,
     image_url: String,
   ) -> impl std::future::Future<Output = Result<Picture, PictureServiceError>> + Send;
+  fn stage_picture_upload(
+    &self,
+    user_id: i32,
+    file: &mut dyn FileChunks,
+  ) -> impl std::future::Future<Output = Result<StagedUpload, PictureServiceError>> + Send;
   fn upload_and_create_picture(
     &self,
     user_id: i32,
-    file_data: Vec<u8>,
-    file_name: String,
-    content_type: String,
+    staged: StagedUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
   ) -> impl std::future::Future<Output = Result<Picture, PictureServiceError>> + Send;
+  fn create_picture_upload(
+    &self,
+    user_id: i32,
+    req: CreatePictureUploadRequest,
+  ) -> impl std::future::Future<Output = Result<PictureUploadResponse, PictureServiceError>> + Send;
+  fn complete_picture_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    reported_location: Option<(f64, f64)>,
+  ) -> impl std::future::Future<Output = Result<PictureUploadStatus, PictureServiceError>> + Send;
+  fn get_picture_upload_status(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+  ) -> impl std::future::Future<Output = Result<PictureUploadStatus, PictureServiceError>> + Send;
+  fn create_resumable_upload(
+    &self,
+    user_id: i32,
+    req: CreateResumableUploadRequest,
+  ) -> impl std::future::Future<Output = Result<ResumableUpload, UploadServiceError>> + Send;
+  fn get_resumable_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+  ) -> impl std::future::Future<Output = Result<ResumableUploadStatus, UploadServiceError>> + Send;
+  fn append_resumable_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    offset: i64,
+    body: &mut dyn FileChunks,
+  ) -> impl std::future::Future<Output = Result<ResumableUploadStatus, UploadServiceError>> + Send;
   fn delete_picture(
     &self,
     picture_id: i32,
     user_id: i32,
   ) -> impl std::future::Future<Output = Result<(), PictureServiceError>> + Send;
+  fn get_my_submissions(
+    &self,
+    user_id: i32,
+    page: Page,
+  ) -> impl std::future::Future<Output = Result<SubmissionsResponse, PictureServiceError>> + Send;
+  fn get_pictures(
+    &self,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<PictureFeedResponse, PictureServiceError>> + Send;
+  fn get_user_pictures(
+    &self,
+    user_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<PictureFeedResponse, PictureServiceError>> + Send;
+  fn get_request_pictures(
+    &self,
+    request_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<PictureFeedResponse, PictureServiceError>> + Send;
+  fn get_picture(
+    &self,
+    picture_id: i32,
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<PictureWithAuthor, PictureServiceError>> + Send;
+  fn review_submission(
+    &self,
+    request_id: i32,
+    picture_id: i32,
+    user_id: i32,
+    review_status: &str,
+  ) -> impl std::future::Future<Output = Result<PictureWithAuthor, PictureServiceError>> + Send;
   fn get_requests(
     &self,
     user_lat: Option<f64>,
     user_lng: Option<f64>,
-  ) -> impl std::future::Future<Output = Result<RequestsResponse, sqlx::Error>> + Send;
+    filter: RequestFilter,
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<RequestsResponse, RequestServiceError>> + Send;
   fn create_request(
     &self,
     user_id: i32,
     req: CreateRequestRequest,
-  ) -> impl std::future::Future<Output = Result<Request, sqlx::Error>> + Send;
+    force: bool,
+  ) -> impl std::future::Future<Output = Result<Request, RequestServiceError>> + Send;
   fn get_request_by_id(
     &self,
     request_id: i32,
-  ) -> impl std::future::Future<Output = Result<Request, sqlx::Error>> + Send;
+    viewer_id: Option<i32>,
+  ) -> impl std::future::Future<Output = Result<Request, RequestServiceError>> + Send;
+  fn claim_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<Request, RequestServiceError>> + Send;
+  fn unclaim_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), RequestServiceError>> + Send;
+  fn update_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: UpdateRequestRequest,
+  ) -> impl std::future::Future<Output = Result<Request, RequestServiceError>> + Send;
+  fn delete_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), RequestServiceError>> + Send;
+  fn get_my_requests(
+    &self,
+    user_id: i32,
+    page: Page,
+  ) -> impl std::future::Future<Output = Result<MyRequestsResponse, RequestServiceError>> + Send;
+  fn list_comments(
+    &self,
+    request_id: i32,
+    page: Page,
+  ) -> impl std::future::Future<Output = Result<CommentsResponse, CommentServiceError>> + Send;
+  fn create_comment(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: CreateCommentRequest,
+  ) -> impl std::future::Future<Output = Result<Comment, CommentServiceError>> + Send;
+  fn update_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+    req: UpdateCommentRequest,
+  ) -> impl std::future::Future<Output = Result<Comment, CommentServiceError>> + Send;
+  fn delete_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), CommentServiceError>> + Send;
+  fn pin_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<Comment, CommentServiceError>> + Send;
+  fn unpin_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<Comment, CommentServiceError>> + Send;
+  fn subscribe_comments(
+    &self,
+    request_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), CommentServiceError>> + Send;
+  fn unsubscribe_comments(
+    &self,
+    request_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), CommentServiceError>> + Send;
+  fn unsubscribe_comments_by_token(
+    &self,
+    token: String,
+  ) -> impl std::future::Future<Output = Result<(), CommentServiceError>> + Send;
+  fn list_watch_areas(
+    &self,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<WatchAreasResponse, WatchAreaServiceError>> + Send;
+  fn create_watch_area(
+    &self,
+    user_id: i32,
+    req: CreateWatchAreaRequest,
+  ) -> impl std::future::Future<Output = Result<WatchArea, WatchAreaServiceError>> + Send;
+  fn delete_watch_area(
+    &self,
+    watch_area_id: i32,
+    user_id: i32,
+  ) -> impl std::future::Future<Output = Result<(), WatchAreaServiceError>> + Send;
+  fn unsubscribe_watch_area_by_token(
+    &self,
+    token: String,
+  ) -> impl std::future::Future<Output = Result<(), WatchAreaServiceError>> + Send;
+  fn get_popular_tags(
+    &self,
+    limit: i64,
+  ) -> impl std::future::Future<Output = Result<PopularTagsResponse, TagServiceError>> + Send;
 }
 
 #[derive(Clone)]
 pub struct SharedAppState {
   pub user_service: Arc<UserServiceImpl<SqlxUserRepository, SqlxVerificationTokenRepository>>,
   pub picture_service: Arc<PictureServiceImpl>,
+  pub upload_service: Arc<UploadService>,
   pub request_service: Arc<RequestService>,
+  pub comment_service: Arc<CommentService>,
+  pub watch_area_service: Arc<WatchAreaService>,
+  pub tag_service: Arc<TagService>,
+  pub storage_operation_service: Arc<StorageOperationService>,
+  pub storage: Arc<dyn ObjectStorage>,
 }
 
 impl SharedAppState {
-  pub async fn new(pool: PgPool, email_service: EmailService, storage: S3Storage) -> Self {
+  pub async fn new(
+    pool: PgPool,
+    email_service: EmailService,
+    storage: Arc<dyn ObjectStorage>,
+    geocoder: Arc<dyn Geocoder>,
+  ) -> Self {
     let user_repository = SqlxUserRepository::new(pool.clone());
     let verification_token_repository = SqlxVerificationTokenRepository::new(pool.clone());
     let user_service = Arc::new(UserServiceImpl::new(
       user_repository,
       verification_token_repository,
-      email_service,
+      email_service.clone(),
     ));
 
-    let picture_service = Arc::new(PictureServiceImpl::new(pool.clone(), storage));
-    let request_service = Arc::new(RequestService::new(pool));
+    let picture_service = Arc::new(PictureServiceImpl::new(
+      pool.clone(),
+      storage.clone(),
+      ImageLimits::from_env(),
+    ));
+    let upload_service = Arc::new(UploadService::new(
+      pool.clone(),
+      storage.clone(),
+      picture_service.clone(),
+      ImageLimits::from_env(),
+    ));
+    let request_service = Arc::new(RequestService::new(
+      pool.clone(),
+      storage.clone(),
+      email_service.clone(),
+      geocoder,
+    ));
+    let comment_service = Arc::new(CommentService::new(pool.clone(), email_service.clone()));
+    let watch_area_service = Arc::new(WatchAreaService::new(pool.clone(), email_service));
+    let storage_operation_service = Arc::new(StorageOperationService::new(pool.clone(), storage.clone()));
+    let tag_service = Arc::new(TagService::new(pool));
 
     Self {
       user_service,
       picture_service,
+      upload_service,
       request_service,
+      comment_service,
+      watch_area_service,
+      tag_service,
+      storage_operation_service,
+      storage,
     }
   }
 }
@@ -134,32 +370,272 @@ impl AppState for SharedAppState {
     self.picture_service.create_picture(user_id, image_url).await
   }
 
+  async fn stage_picture_upload(
+    &self,
+    user_id: i32,
+    file: &mut dyn FileChunks,
+  ) -> Result<StagedUpload, PictureServiceError> {
+    self.picture_service.stage_upload(user_id, file).await
+  }
+
   async fn upload_and_create_picture(
     &self,
     user_id: i32,
-    file_data: Vec<u8>,
-    file_name: String,
-    content_type: String,
+    staged: StagedUpload,
+    request_id: Option<i32>,
+    reported_location: Option<(f64, f64)>,
   ) -> Result<Picture, PictureServiceError> {
     self
       .picture_service
-      .upload_and_create_picture(user_id, file_data, file_name, content_type)
+      .upload_and_create_picture(user_id, staged, request_id, reported_location)
+      .await
+  }
+
+  async fn create_picture_upload(
+    &self,
+    user_id: i32,
+    req: CreatePictureUploadRequest,
+  ) -> Result<PictureUploadResponse, PictureServiceError> {
+    self.picture_service.create_upload(user_id, req).await
+  }
+
+  async fn complete_picture_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    reported_location: Option<(f64, f64)>,
+  ) -> Result<PictureUploadStatus, PictureServiceError> {
+    self
+      .picture_service
+      .complete_upload(user_id, upload_id, reported_location)
       .await
   }
 
+  async fn get_picture_upload_status(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+  ) -> Result<PictureUploadStatus, PictureServiceError> {
+    self.picture_service.get_upload_status(user_id, upload_id).await
+  }
+
+  async fn create_resumable_upload(
+    &self,
+    user_id: i32,
+    req: CreateResumableUploadRequest,
+  ) -> Result<ResumableUpload, UploadServiceError> {
+    self.upload_service.create_upload(user_id, req).await
+  }
+
+  async fn get_resumable_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+  ) -> Result<ResumableUploadStatus, UploadServiceError> {
+    self.upload_service.get_status(user_id, upload_id).await
+  }
+
+  async fn append_resumable_upload(
+    &self,
+    user_id: i32,
+    upload_id: Uuid,
+    offset: i64,
+    body: &mut dyn FileChunks,
+  ) -> Result<ResumableUploadStatus, UploadServiceError> {
+    self.upload_service.append(user_id, upload_id, offset, body).await
+  }
+
   async fn delete_picture(&self, picture_id: i32, user_id: i32) -> Result<(), PictureServiceError> {
     self.picture_service.delete_picture(picture_id, user_id).await
   }
 
-  async fn get_requests(&self, user_lat: Option<f64>, user_lng: Option<f64>) -> Result<RequestsResponse, sqlx::Error> {
-    self.request_service.get_requests(user_lat, user_lng).await
+  async fn get_my_submissions(&self, user_id: i32, page: Page) -> Result<SubmissionsResponse, PictureServiceError> {
+    self.picture_service.get_my_submissions(user_id, page).await
+  }
+
+  async fn get_pictures(
+    &self,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    self.picture_service.get_pictures(page, viewer_id).await
+  }
+
+  async fn get_user_pictures(
+    &self,
+    user_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    self.picture_service.get_user_pictures(user_id, page, viewer_id).await
+  }
+
+  async fn get_request_pictures(
+    &self,
+    request_id: i32,
+    page: CursorPage,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureFeedResponse, PictureServiceError> {
+    self
+      .picture_service
+      .get_request_pictures(request_id, page, viewer_id)
+      .await
+  }
+
+  async fn get_picture(
+    &self,
+    picture_id: i32,
+    viewer_id: Option<i32>,
+  ) -> Result<PictureWithAuthor, PictureServiceError> {
+    self.picture_service.get_picture(picture_id, viewer_id).await
+  }
+
+  async fn review_submission(
+    &self,
+    request_id: i32,
+    picture_id: i32,
+    user_id: i32,
+    review_status: &str,
+  ) -> Result<PictureWithAuthor, PictureServiceError> {
+    self
+      .picture_service
+      .review_submission(request_id, picture_id, user_id, review_status)
+      .await
+  }
+
+  async fn get_requests(
+    &self,
+    user_lat: Option<f64>,
+    user_lng: Option<f64>,
+    filter: RequestFilter,
+    viewer_id: Option<i32>,
+  ) -> Result<RequestsResponse, RequestServiceError> {
+    self
+      .request_service
+      .get_requests(user_lat, user_lng, filter, viewer_id)
+      .await
+  }
+
+  async fn create_request(
+    &self,
+    user_id: i32,
+    req: CreateRequestRequest,
+    force: bool,
+  ) -> Result<Request, RequestServiceError> {
+    self.request_service.create_request(user_id, req, force).await
+  }
+
+  async fn get_request_by_id(&self, request_id: i32, viewer_id: Option<i32>) -> Result<Request, RequestServiceError> {
+    self.request_service.get_request_by_id(request_id, viewer_id).await
+  }
+
+  async fn claim_request(&self, request_id: i32, user_id: i32) -> Result<Request, RequestServiceError> {
+    self.request_service.claim_request(request_id, user_id).await
+  }
+
+  async fn unclaim_request(&self, request_id: i32, user_id: i32) -> Result<(), RequestServiceError> {
+    self.request_service.unclaim_request(request_id, user_id).await
+  }
+
+  async fn update_request(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: UpdateRequestRequest,
+  ) -> Result<Request, RequestServiceError> {
+    self.request_service.update_request(request_id, user_id, req).await
+  }
+
+  async fn delete_request(&self, request_id: i32, user_id: i32) -> Result<(), RequestServiceError> {
+    self.request_service.delete_request(request_id, user_id).await
+  }
+
+  async fn get_my_requests(&self, user_id: i32, page: Page) -> Result<MyRequestsResponse, RequestServiceError> {
+    self.request_service.get_my_requests(user_id, page).await
+  }
+
+  async fn list_comments(&self, request_id: i32, page: Page) -> Result<CommentsResponse, CommentServiceError> {
+    self.comment_service.list_comments(request_id, page).await
+  }
+
+  async fn create_comment(
+    &self,
+    request_id: i32,
+    user_id: i32,
+    req: CreateCommentRequest,
+  ) -> Result<Comment, CommentServiceError> {
+    self.comment_service.create_comment(request_id, user_id, req).await
+  }
+
+  async fn update_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+    req: UpdateCommentRequest,
+  ) -> Result<Comment, CommentServiceError> {
+    self
+      .comment_service
+      .update_comment(request_id, comment_id, user_id, req)
+      .await
+  }
+
+  async fn delete_comment(&self, request_id: i32, comment_id: i32, user_id: i32) -> Result<(), CommentServiceError> {
+    self
+      .comment_service
+      .delete_comment(request_id, comment_id, user_id)
+      .await
+  }
+
+  async fn pin_comment(&self, request_id: i32, comment_id: i32, user_id: i32) -> Result<Comment, CommentServiceError> {
+    self.comment_service.pin_comment(request_id, comment_id, user_id).await
+  }
+
+  async fn unpin_comment(
+    &self,
+    request_id: i32,
+    comment_id: i32,
+    user_id: i32,
+  ) -> Result<Comment, CommentServiceError> {
+    self
+      .comment_service
+      .unpin_comment(request_id, comment_id, user_id)
+      .await
+  }
+
+  async fn subscribe_comments(&self, request_id: i32, user_id: i32) -> Result<(), CommentServiceError> {
+    self.comment_service.subscribe(request_id, user_id).await
+  }
+
+  async fn unsubscribe_comments(&self, request_id: i32, user_id: i32) -> Result<(), CommentServiceError> {
+    self.comment_service.unsubscribe(request_id, user_id).await
+  }
+
+  async fn unsubscribe_comments_by_token(&self, token: String) -> Result<(), CommentServiceError> {
+    self.comment_service.unsubscribe_by_token(&token).await
+  }
+
+  async fn list_watch_areas(&self, user_id: i32) -> Result<WatchAreasResponse, WatchAreaServiceError> {
+    self.watch_area_service.list_watch_areas(user_id).await
+  }
+
+  async fn create_watch_area(
+    &self,
+    user_id: i32,
+    req: CreateWatchAreaRequest,
+  ) -> Result<WatchArea, WatchAreaServiceError> {
+    self.watch_area_service.create_watch_area(user_id, req).await
+  }
+
+  async fn delete_watch_area(&self, watch_area_id: i32, user_id: i32) -> Result<(), WatchAreaServiceError> {
+    self.watch_area_service.delete_watch_area(watch_area_id, user_id).await
   }
 
-  async fn create_request(&self, user_id: i32, req: CreateRequestRequest) -> Result<Request, sqlx::Error> {
-    self.request_service.create_request(user_id, req).await
+  async fn unsubscribe_watch_area_by_token(&self, token: String) -> Result<(), WatchAreaServiceError> {
+    self.watch_area_service.unsubscribe_by_token(&token).await
   }
 
-  async fn get_request_by_id(&self, request_id: i32) -> Result<Request, sqlx::Error> {
-    self.request_service.get_request_by_id(request_id).await
+  async fn get_popular_tags(&self, limit: i64) -> Result<PopularTagsResponse, TagServiceError> {
+    self.tag_service.get_popular_tags(limit).await
   }
 }
@@ -0,0 +1,673 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use axum::{
+  body::Bytes,
+  extract::{DefaultBodyLimit, Path as UrlPath, Query, State},
+  http::{header, HeaderMap, StatusCode},
+  response::IntoResponse,
+  routing::get,
+  Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{
+  collections::BTreeMap,
+  env,
+  io::{ErrorKind, SeekFrom},
+  path::{Component, Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+  s3::presigned_url_ttl_from_env, signing::UrlSigner, ObjectInfo, ObjectStorage, ObjectSummary, ObjectWriter,
+  PresignedRequest, UploadedPart,
+};
+
+/// ファイルを配信するルートのパス
+const ROUTE_PREFIX: &str = "/storage";
+/// 署名付き URL で受け付けるファイルの大きさの上限。アップロードの上限は完了時に写真側で確かめる
+const MAX_PUT_BYTES: usize = 256 * 1024 * 1024;
+/// 書き込み中のファイルを置くディレクトリ。一覧には含めない
+const PARTIAL_DIR: &str = ".partial";
+/// 配信するのは写真として確定したファイルだけで、検証前のアップロードや隔離したファイルは返さない
+const SERVED_PREFIX: &str = "pictures/";
+
+/// ローカルのディレクトリに置くストレージ。開発用で、ファイルはアプリ自身が `/storage` 以下で配信する
+#[derive(Clone)]
+pub struct LocalStorage {
+  root: Arc<PathBuf>,
+  signer: UrlSigner,
+  /// 非公開では画像 URL を署名付きにし、署名のない取得を拒む
+  private: bool,
+  presigned_url_ttl: Duration,
+}
+
+#[derive(Deserialize)]
+struct SignedQuery {
+  expires: Option<i64>,
+  signature: Option<String>,
+}
+
+impl LocalStorage {
+  /// `STORAGE_BACKEND` と `pictures.storage_backend` に使う名前
+  pub const BACKEND: &'static str = "local";
+
+  /// `public_url` はクライアントから届くアプリの URL
+  pub fn new(root: impl Into<PathBuf>, public_url: &str, secret: &str) -> Result<Self> {
+    let root = root.into();
+    std::fs::create_dir_all(&root).with_context(|| format!("Failed to create {}", root.display()))?;
+
+    Ok(Self {
+      root: Arc::new(root),
+      signer: UrlSigner::new(format!("{}{}", public_url.trim_end_matches('/'), ROUTE_PREFIX), secret),
+      private: false,
+      presigned_url_ttl: Duration::from_secs(super::s3::DEFAULT_PRESIGNED_URL_TTL_SECS),
+    })
+  }
+
+  /// 非公開のストレージとして扱う
+  pub(crate) fn into_private(mut self, presigned_url_ttl: Duration) -> Self {
+    self.private = true;
+    self.presigned_url_ttl = presigned_url_ttl;
+    self
+  }
+
+  pub fn from_env() -> Result<Self> {
+    let root = env::var("LOCAL_STORAGE_DIR").unwrap_or_else(|_| "./local-storage".to_string());
+    let public_url = env::var("LOCAL_STORAGE_PUBLIC_URL").unwrap_or_else(|_| "http://127.0.0.1:8000".to_string());
+    // JWT の鍵を使い回すと、片方が漏れたときにもう片方も偽造できてしまう
+    let secret = env::var("STORAGE_SIGNING_SECRET").context("STORAGE_SIGNING_SECRET not set")?;
+    let private = env::var("LOCAL_STORAGE_PRIVATE").is_ok_and(|value| value == "true" || value == "1");
+
+    let storage = Self::new(root, &public_url, &secret)?;
+    if private {
+      return Ok(storage.into_private(presigned_url_ttl_from_env("LOCAL_STORAGE_PRESIGNED_URL_TTL_SECS")));
+    }
+    Ok(storage)
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  async fn create_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+      tokio::fs::create_dir_all(parent)
+        .await
+        .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    Ok(())
+  }
+
+  /// ディレクトリの外を指すキーは受け付けない
+  fn path_for(&self, key: &str) -> Result<PathBuf> {
+    let relative = Path::new(key);
+    if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+      bail!("Invalid object key: {}", key);
+    }
+
+    Ok(self.root.join(relative))
+  }
+
+  /// マルチパートアップロードのパートを置くディレクトリ
+  fn multipart_dir(&self, upload_id: &str) -> Result<PathBuf> {
+    let upload_id = uuid::Uuid::parse_str(upload_id).with_context(|| format!("Invalid upload id: {}", upload_id))?;
+    Ok(self.root.join(PARTIAL_DIR).join(upload_id.to_string()))
+  }
+}
+
+/// 別の場所に書き込んでから移動し、書きかけのファイルが見えないようにする
+struct LocalWriter {
+  /// 確定か中断をしたら `None`
+  file: Option<tokio::fs::File>,
+  partial_path: PathBuf,
+  path: PathBuf,
+}
+
+#[async_trait]
+impl ObjectWriter for LocalWriter {
+  async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+    let file = self.file.as_mut().context("Writer is already closed")?;
+    file
+      .write_all(chunk)
+      .await
+      .with_context(|| format!("Failed to write {}", self.partial_path.display()))
+  }
+
+  async fn finish(mut self: Box<Self>) -> Result<()> {
+    let mut file = self.file.take().context("Writer is already closed")?;
+    file.flush().await?;
+    drop(file);
+
+    LocalStorage::create_parent_dir(&self.path).await?;
+    tokio::fs::rename(&self.partial_path, &self.path)
+      .await
+      .with_context(|| {
+        format!(
+          "Failed to move {} to {}",
+          self.partial_path.display(),
+          self.path.display()
+        )
+      })
+  }
+
+  async fn abort(mut self: Box<Self>) -> Result<()> {
+    self.file.take();
+    tokio::fs::remove_file(&self.partial_path)
+      .await
+      .with_context(|| format!("Failed to delete {}", self.partial_path.display()))
+  }
+}
+
+impl Drop for LocalWriter {
+  fn drop(&mut self) {
+    if self.file.take().is_some() {
+      let _ = std::fs::remove_file(&self.partial_path);
+    }
+  }
+}
+
+/// 拡張子から Content-Type を推測する。ファイルには Content-Type を保存しないため
+fn content_type_for(key: &str) -> Option<&'static str> {
+  let extension = Path::new(key).extension()?.to_str()?.to_ascii_lowercase();
+  match extension.as_str() {
+    "jpg" | "jpeg" => Some("image/jpeg"),
+    "png" => Some("image/png"),
+    "webp" => Some("image/webp"),
+    "heic" => Some("image/heic"),
+    "heif" => Some("image/heif"),
+    _ => None,
+  }
+}
+
+#[async_trait]
+impl ObjectStorage for LocalStorage {
+  fn backend(&self) -> &'static str {
+    Self::BACKEND
+  }
+
+  async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<()> {
+    let path = self.path_for(key)?;
+    Self::create_parent_dir(&path).await?;
+    tokio::fs::write(&path, data)
+      .await
+      .with_context(|| format!("Failed to write {}", path.display()))
+  }
+
+  async fn put_stream(&self, key: &str, _content_type: &str) -> Result<Box<dyn ObjectWriter>> {
+    let path = self.path_for(key)?;
+    let partial_path = self.root.join(PARTIAL_DIR).join(uuid::Uuid::new_v4().to_string());
+    Self::create_parent_dir(&partial_path).await?;
+    let file = tokio::fs::File::create(&partial_path)
+      .await
+      .with_context(|| format!("Failed to create {}", partial_path.display()))?;
+
+    Ok(Box::new(LocalWriter {
+      file: Some(file),
+      partial_path,
+      path,
+    }))
+  }
+
+  async fn create_multipart(&self, key: &str, _content_type: &str) -> Result<String> {
+    self.path_for(key)?;
+    let upload_id = uuid::Uuid::new_v4().to_string();
+    let directory = self.multipart_dir(&upload_id)?;
+    tokio::fs::create_dir_all(&directory)
+      .await
+      .with_context(|| format!("Failed to create {}", directory.display()))?;
+    Ok(upload_id)
+  }
+
+  async fn upload_part(&self, _key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<UploadedPart> {
+    let directory = self.multipart_dir(upload_id)?;
+    if !tokio::fs::try_exists(&directory).await? {
+      bail!("Multipart upload {} not found", upload_id);
+    }
+
+    let etag = format!("{:x}", Sha256::digest(&data));
+    let path = directory.join(part_number.to_string());
+    tokio::fs::write(&path, data)
+      .await
+      .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(UploadedPart { part_number, etag })
+  }
+
+  async fn complete_multipart(&self, key: &str, upload_id: &str, parts: &[UploadedPart]) -> Result<()> {
+    let path = self.path_for(key)?;
+    let directory = self.multipart_dir(upload_id)?;
+
+    let mut writer = self.put_stream(key, "").await?;
+    for part in parts {
+      let part_path = directory.join(part.part_number.to_string());
+      let data = tokio::fs::read(&part_path)
+        .await
+        .with_context(|| format!("Failed to read {}", part_path.display()))?;
+      if format!("{:x}", Sha256::digest(&data)) != part.etag {
+        bail!("Part {} of {} does not match", part.part_number, upload_id);
+      }
+      writer.write(&data).await?;
+    }
+    writer
+      .finish()
+      .await
+      .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    self.abort_multipart(key, upload_id).await
+  }
+
+  async fn abort_multipart(&self, _key: &str, upload_id: &str) -> Result<()> {
+    let directory = self.multipart_dir(upload_id)?;
+    match tokio::fs::remove_dir_all(&directory).await {
+      Err(e) if e.kind() != ErrorKind::NotFound => {
+        Err(e).with_context(|| format!("Failed to delete {}", directory.display()))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  async fn get(&self, key: &str) -> Result<Vec<u8>> {
+    let path = self.path_for(key)?;
+    tokio::fs::read(&path)
+      .await
+      .with_context(|| format!("Failed to read {}", path.display()))
+  }
+
+  async fn get_range(&self, key: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+    let path = self.path_for(key)?;
+    let mut file = tokio::fs::File::open(&path)
+      .await
+      .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    file
+      .seek(SeekFrom::Start(offset))
+      .await
+      .with_context(|| format!("Failed to seek {}", path.display()))?;
+    let mut data = Vec::new();
+    file
+      .take(length)
+      .read_to_end(&mut data)
+      .await
+      .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    Ok(data)
+  }
+
+  async fn head(&self, key: &str) -> Result<Option<ObjectInfo>> {
+    let path = self.path_for(key)?;
+    match tokio::fs::metadata(&path).await {
+      Ok(metadata) if metadata.is_file() => Ok(Some(ObjectInfo {
+        size: metadata.len() as i64,
+        content_type: content_type_for(key).map(str::to_string),
+      })),
+      Ok(_) => Ok(None),
+      Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e).with_context(|| format!("Failed to stat {}", path.display())),
+    }
+  }
+
+  async fn delete(&self, key: &str) -> Result<()> {
+    let path = self.path_for(key)?;
+    match tokio::fs::remove_file(&path).await {
+      Err(e) if e.kind() != ErrorKind::NotFound => {
+        Err(e).with_context(|| format!("Failed to delete {}", path.display()))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>> {
+    let mut objects = Vec::new();
+    let mut directories = vec![self.root.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+      let mut entries = tokio::fs::read_dir(&directory)
+        .await
+        .with_context(|| format!("Failed to list {}", directory.display()))?;
+      while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_dir() && entry.file_name() == PARTIAL_DIR {
+          continue;
+        }
+        if metadata.is_dir() {
+          directories.push(entry.path());
+          continue;
+        }
+
+        let Ok(relative) = entry.path().strip_prefix(self.root.as_path()).map(Path::to_path_buf) else {
+          continue;
+        };
+        let key = relative
+          .components()
+          .map(|component| component.as_os_str().to_string_lossy())
+          .collect::<Vec<_>>()
+          .join("/");
+        if !key.starts_with(prefix) {
+          continue;
+        }
+
+        objects.push(ObjectSummary {
+          key,
+          size: metadata.len() as i64,
+          last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        });
+      }
+    }
+
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(objects)
+  }
+
+  async fn presign_put(&self, key: &str, content_type: &str, expires_in: Duration) -> Result<PresignedRequest> {
+    self.path_for(key)?;
+
+    Ok(PresignedRequest {
+      method: "PUT".to_string(),
+      url: self.signer.presign("PUT", key, expires_in),
+      headers: BTreeMap::from([("content-type".to_string(), content_type.to_string())]),
+    })
+  }
+
+  async fn url_for(&self, key: &str) -> Result<String> {
+    if self.private {
+      return Ok(self.signer.presign("GET", key, self.presigned_url_ttl));
+    }
+    Ok(self.signer.url(key))
+  }
+
+  fn key_for_url(&self, url: &str) -> Option<String> {
+    self.signer.key_for_url(url)
+  }
+
+  fn routes(&self) -> Option<Router> {
+    Some(
+      Router::new()
+        .route(
+          &format!("{}/{{*key}}", ROUTE_PREFIX),
+          get(serve_object)
+            .put(receive_object)
+            .layer(DefaultBodyLimit::max(MAX_PUT_BYTES)),
+        )
+        .with_state(self.clone()),
+    )
+  }
+}
+
+async fn serve_object(
+  State(storage): State<LocalStorage>,
+  UrlPath(key): UrlPath<String>,
+  Query(query): Query<SignedQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+  if !key.starts_with(SERVED_PREFIX) {
+    return Err(StatusCode::NOT_FOUND);
+  }
+  if storage.private {
+    let (Some(expires), Some(signature)) = (query.expires, query.signature) else {
+      return Err(StatusCode::FORBIDDEN);
+    };
+    if !storage.signer.verify("GET", &key, expires, &signature) {
+      return Err(StatusCode::FORBIDDEN);
+    }
+  }
+  if storage.head(&key).await.map_err(|_| StatusCode::NOT_FOUND)?.is_none() {
+    return Err(StatusCode::NOT_FOUND);
+  }
+  let data = storage.get(&key).await.map_err(|e| {
+    tracing::error!("Failed to serve {}: {:?}", key, e);
+    StatusCode::INTERNAL_SERVER_ERROR
+  })?;
+  let content_type = content_type_for(&key).unwrap_or("application/octet-stream");
+
+  Ok(([(header::CONTENT_TYPE, content_type)], data))
+}
+
+/// 署名付き URL へのアップロードを受け取る
+async fn receive_object(
+  State(storage): State<LocalStorage>,
+  UrlPath(key): UrlPath<String>,
+  Query(query): Query<SignedQuery>,
+  headers: HeaderMap,
+  body: Bytes,
+) -> Result<StatusCode, StatusCode> {
+  let (Some(expires), Some(signature)) = (query.expires, query.signature) else {
+    return Err(StatusCode::FORBIDDEN);
+  };
+  if !storage.signer.verify("PUT", &key, expires, &signature) {
+    return Err(StatusCode::FORBIDDEN);
+  }
+
+  let content_type = headers
+    .get(header::CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or("application/octet-stream");
+  storage.put(&key, body.to_vec(), content_type).await.map_err(|e| {
+    tracing::error!("Failed to store {}: {:?}", key, e);
+    StatusCode::BAD_REQUEST
+  })?;
+
+  Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use axum::body::Body;
+  use tower::ServiceExt;
+
+  fn get(uri: &str) -> axum::http::Request<Body> {
+    axum::http::Request::builder().uri(uri).body(Body::empty()).unwrap()
+  }
+
+  fn create_test_storage() -> (LocalStorage, PathBuf) {
+    let root = env::temp_dir().join(format!("koko-pic-local-storage-{}", uuid::Uuid::new_v4()));
+    let storage = LocalStorage::new(&root, "http://localhost:8000/", "secret").unwrap();
+    (storage, root)
+  }
+
+  #[tokio::test]
+  async fn test_put_get_list_delete() {
+    let (storage, root) = create_test_storage();
+
+    storage
+      .put("pictures/1/a.png", vec![1, 2, 3], "image/png")
+      .await
+      .unwrap();
+    storage.put("pictures/2/b.jpg", vec![4], "image/jpeg").await.unwrap();
+    storage.put("uploads/1/c.png", vec![5], "image/png").await.unwrap();
+
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+    assert_eq!(storage.get_range("pictures/1/a.png", 0, 2).await.unwrap(), vec![1, 2]);
+    assert_eq!(storage.get_range("pictures/1/a.png", 2, 10).await.unwrap(), vec![3]);
+    assert_eq!(
+      storage.head("pictures/2/b.jpg").await.unwrap(),
+      Some(ObjectInfo {
+        size: 1,
+        content_type: Some("image/jpeg".to_string()),
+      })
+    );
+    let keys: Vec<String> = storage
+      .list("pictures/")
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|object| object.key)
+      .collect();
+    assert_eq!(keys, vec!["pictures/1/a.png", "pictures/2/b.jpg"]);
+
+    storage.delete("pictures/1/a.png").await.unwrap();
+    storage.delete("pictures/1/a.png").await.unwrap();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_put_stream_moves_file_into_place_on_finish() {
+    let (storage, root) = create_test_storage();
+
+    let mut writer = storage.put_stream("pictures/1/a.png", "image/png").await.unwrap();
+    writer.write(&[1, 2]).await.unwrap();
+    writer.write(&[3]).await.unwrap();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+    assert!(storage.list("").await.unwrap().is_empty());
+    writer.finish().await.unwrap();
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+
+    // 中断したものも、確定せずに破棄したものも残らない
+    let mut writer = storage.put_stream("pictures/1/b.png", "image/png").await.unwrap();
+    writer.write(&[1]).await.unwrap();
+    writer.abort().await.unwrap();
+    let mut writer = storage.put_stream("pictures/1/c.png", "image/png").await.unwrap();
+    writer.write(&[1]).await.unwrap();
+    drop(writer);
+    assert_eq!(std::fs::read_dir(root.join(PARTIAL_DIR)).unwrap().count(), 0);
+    let keys: Vec<String> = storage.list("").await.unwrap().into_iter().map(|o| o.key).collect();
+    assert_eq!(keys, vec!["pictures/1/a.png"]);
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_multipart_upload() {
+    let (storage, root) = create_test_storage();
+
+    let upload_id = storage.create_multipart("pictures/1/a.png", "image/png").await.unwrap();
+    let first = storage
+      .upload_part("pictures/1/a.png", &upload_id, 1, vec![1, 2])
+      .await
+      .unwrap();
+    let second = storage
+      .upload_part("pictures/1/a.png", &upload_id, 2, vec![3])
+      .await
+      .unwrap();
+    assert!(storage.list("").await.unwrap().is_empty());
+
+    storage
+      .complete_multipart("pictures/1/a.png", &upload_id, &[first, second])
+      .await
+      .unwrap();
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+
+    let upload_id = storage.create_multipart("pictures/1/b.png", "image/png").await.unwrap();
+    storage
+      .upload_part("pictures/1/b.png", &upload_id, 1, vec![1])
+      .await
+      .unwrap();
+    storage.abort_multipart("pictures/1/b.png", &upload_id).await.unwrap();
+    assert_eq!(std::fs::read_dir(root.join(PARTIAL_DIR)).unwrap().count(), 0);
+    assert!(storage
+      .upload_part("pictures/1/b.png", "../../a", 1, vec![1])
+      .await
+      .is_err());
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_rejects_keys_outside_root() {
+    let (storage, root) = create_test_storage();
+
+    for key in ["../escape.png", "/etc/passwd", "pictures/../../escape.png", ""] {
+      assert!(storage.put(key, vec![0], "image/png").await.is_err(), "{}", key);
+      assert!(storage.head(key).await.is_err(), "{}", key);
+    }
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_routes_accept_presigned_put_and_serve_files() {
+    let (storage, root) = create_test_storage();
+    let app = storage.routes().unwrap();
+
+    let presigned = storage
+      .presign_put("uploads/1/a.png", "image/png", Duration::from_secs(60))
+      .await
+      .unwrap();
+    let path = presigned.url.trim_start_matches("http://localhost:8000").to_string();
+    assert!(path.starts_with("/storage/uploads/1/a.png?"));
+
+    let put = |uri: &str| {
+      axum::http::Request::builder()
+        .method("PUT")
+        .uri(uri)
+        .header("content-type", "image/png")
+        .body(Body::from(vec![7, 8, 9]))
+        .unwrap()
+    };
+    let response = app.clone().oneshot(put("/storage/uploads/1/a.png")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app
+      .clone()
+      .oneshot(put(&path.replace("uploads/1/a.png", "uploads/1/b.png")))
+      .await
+      .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = app.clone().oneshot(put(&path)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(storage.get("uploads/1/a.png").await.unwrap(), vec![7, 8, 9]);
+    // 検証前のアップロードは配信しない
+    assert_eq!(
+      app.clone().oneshot(get(&path)).await.unwrap().status(),
+      StatusCode::NOT_FOUND
+    );
+
+    storage
+      .put("pictures/1/a.heic", vec![7, 8, 9], "image/heic")
+      .await
+      .unwrap();
+    let url = storage.url_for("pictures/1/a.heic").await.unwrap();
+    assert_eq!(url, "http://localhost:8000/storage/pictures/1/a.heic");
+    let response = app.clone().oneshot(get("/storage/pictures/1/a.heic")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/heic");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.as_ref(), [7, 8, 9]);
+
+    let response = app.oneshot(get("/storage/pictures/1/missing.png")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_private_storage_serves_only_signed_urls() {
+    let (storage, root) = create_test_storage();
+    let storage = storage.into_private(Duration::from_secs(60));
+    let app = storage.routes().unwrap();
+    storage
+      .put("pictures/1/a.png", vec![7, 8, 9], "image/png")
+      .await
+      .unwrap();
+    storage.put("pictures/1/b.png", vec![1], "image/png").await.unwrap();
+
+    let url = storage.url_for("pictures/1/a.png").await.unwrap();
+    let path = url.trim_start_matches("http://localhost:8000").to_string();
+    assert!(path.starts_with("/storage/pictures/1/a.png?expires="));
+    assert_eq!(app.clone().oneshot(get(&path)).await.unwrap().status(), StatusCode::OK);
+
+    for path in [
+      "/storage/pictures/1/a.png".to_string(),
+      path.replace("pictures/1/a.png", "pictures/1/b.png"),
+      path.replace("signature=", "signature=0"),
+    ] {
+      let response = app.clone().oneshot(get(&path)).await.unwrap();
+      assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    // アップロード用の署名では取得できない
+    let presigned = storage
+      .presign_put("pictures/1/a.png", "image/png", Duration::from_secs(60))
+      .await
+      .unwrap();
+    let path = presigned.url.trim_start_matches("http://localhost:8000").to_string();
+    assert_eq!(app.oneshot(get(&path)).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+    std::fs::remove_dir_all(root).unwrap();
+  }
+}
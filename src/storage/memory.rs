@@ -0,0 +1,456 @@
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::BTreeMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  time::Duration,
+};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use super::{
+  signing::UrlSigner, ObjectInfo, ObjectStorage, ObjectSummary, ObjectWriter, PresignedRequest, UploadedPart,
+};
+
+/// 実際には配信されない URL のホスト
+const BASE_URL: &str = "http://storage.invalid";
+
+struct StoredObject {
+  data: Vec<u8>,
+  content_type: String,
+  last_modified: DateTime<Utc>,
+}
+
+struct PendingMultipart {
+  key: String,
+  content_type: String,
+  parts: BTreeMap<i32, Vec<u8>>,
+}
+
+/// プロセスのメモリ上に置くストレージ。テストで S3 を立ち上げずに済むようにする。
+/// クローンしたものは同じオブジェクトを共有する
+#[derive(Clone)]
+pub struct MemoryStorage {
+  objects: Arc<RwLock<BTreeMap<String, StoredObject>>>,
+  multipart_uploads: Arc<RwLock<BTreeMap<String, PendingMultipart>>>,
+  signer: UrlSigner,
+  private: bool,
+  presigned_url_ttl: Duration,
+  multipart_part_size: usize,
+  /// テストでストレージの障害を再現するため、削除を失敗させる
+  failing_deletes: Arc<AtomicBool>,
+  /// テストでストレージの障害を再現するため、マルチパートアップロードの確定を失敗させる
+  failing_completions: Arc<AtomicBool>,
+  /// テストで一覧を取った直後の入れ違いを再現するため、次の一覧を返す前に止める
+  list_pause: Arc<Mutex<Option<ListPause>>>,
+  /// テストで何度オブジェクトを置いたかを確かめるため、置いたキーを順に記録する
+  written_keys: Arc<Mutex<Vec<String>>>,
+}
+
+/// 一覧を取ったことを `listed` で知らせ、`resume` で知らされるまで一覧を返さない
+#[derive(Clone, Default)]
+pub(crate) struct ListPause {
+  pub(crate) listed: Arc<Notify>,
+  pub(crate) resume: Arc<Notify>,
+}
+
+impl MemoryStorage {
+  /// `STORAGE_BACKEND` と `pictures.storage_backend` に使う名前
+  pub const BACKEND: &'static str = "memory";
+
+  pub fn new() -> Self {
+    Self {
+      objects: Arc::default(),
+      multipart_uploads: Arc::default(),
+      signer: UrlSigner::new(BASE_URL, Uuid::new_v4().to_string()),
+      private: false,
+      presigned_url_ttl: Duration::from_secs(super::s3::DEFAULT_PRESIGNED_URL_TTL_SECS),
+      multipart_part_size: super::s3::DEFAULT_MULTIPART_PART_SIZE_BYTES,
+      failing_deletes: Arc::default(),
+      failing_completions: Arc::default(),
+      list_pause: Arc::default(),
+      written_keys: Arc::default(),
+    }
+  }
+
+  /// 非公開のストレージとして扱う
+  #[cfg(test)]
+  pub(crate) fn into_private(mut self, presigned_url_ttl: Duration) -> Self {
+    self.private = true;
+    self.presigned_url_ttl = presigned_url_ttl;
+    self
+  }
+
+  /// 小さなファイルでも複数のパートに分かれるようにする
+  #[cfg(test)]
+  pub(crate) fn with_multipart_part_size(mut self, multipart_part_size: usize) -> Self {
+    self.multipart_part_size = multipart_part_size;
+    self
+  }
+
+  /// `true` にしている間は削除を失敗させる。クローンしたものにも効く
+  #[cfg(test)]
+  pub(crate) fn fail_deletes(&self, failing: bool) {
+    self.failing_deletes.store(failing, Ordering::SeqCst);
+  }
+
+  /// `true` にしている間はマルチパートアップロードの確定を失敗させる。クローンしたものにも効く
+  #[cfg(test)]
+  pub(crate) fn fail_completions(&self, failing: bool) {
+    self.failing_completions.store(failing, Ordering::SeqCst);
+  }
+
+  /// 次の一覧を、取った直後に止める。クローンしたものにも効く
+  #[cfg(test)]
+  pub(crate) fn pause_next_list(&self) -> ListPause {
+    let pause = ListPause::default();
+    *self.list_pause.lock().unwrap() = Some(pause.clone());
+    pause
+  }
+
+  /// これまでにオブジェクトを置いたキー。同じキーへ何度も置けばその回数だけ並ぶ
+  #[cfg(test)]
+  pub(crate) fn written_keys(&self) -> Vec<String> {
+    self.written_keys.lock().unwrap().clone()
+  }
+
+  /// 確定も中断もされていないマルチパートアップロードの数
+  #[cfg(test)]
+  pub(crate) fn pending_multipart_count(&self) -> usize {
+    self.multipart_uploads.read().unwrap().len()
+  }
+}
+
+/// 確定するまで書き込んだ内容を手元に持っておく
+struct MemoryWriter {
+  storage: MemoryStorage,
+  key: String,
+  content_type: String,
+  buffer: Vec<u8>,
+}
+
+#[async_trait]
+impl ObjectWriter for MemoryWriter {
+  async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+    self.buffer.extend_from_slice(chunk);
+    Ok(())
+  }
+
+  async fn finish(self: Box<Self>) -> Result<()> {
+    self.storage.put(&self.key, self.buffer, &self.content_type).await
+  }
+
+  async fn abort(self: Box<Self>) -> Result<()> {
+    Ok(())
+  }
+}
+
+impl Default for MemoryStorage {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[async_trait]
+impl ObjectStorage for MemoryStorage {
+  fn backend(&self) -> &'static str {
+    Self::BACKEND
+  }
+
+  async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()> {
+    let object = StoredObject {
+      data,
+      content_type: content_type.to_string(),
+      last_modified: Utc::now(),
+    };
+    self.objects.write().unwrap().insert(key.to_string(), object);
+    self.written_keys.lock().unwrap().push(key.to_string());
+    Ok(())
+  }
+
+  async fn put_stream(&self, key: &str, content_type: &str) -> Result<Box<dyn ObjectWriter>> {
+    Ok(Box::new(MemoryWriter {
+      storage: self.clone(),
+      key: key.to_string(),
+      content_type: content_type.to_string(),
+      buffer: Vec::new(),
+    }))
+  }
+
+  async fn create_multipart(&self, key: &str, content_type: &str) -> Result<String> {
+    let upload_id = Uuid::new_v4().to_string();
+    let upload = PendingMultipart {
+      key: key.to_string(),
+      content_type: content_type.to_string(),
+      parts: BTreeMap::new(),
+    };
+    self
+      .multipart_uploads
+      .write()
+      .unwrap()
+      .insert(upload_id.clone(), upload);
+    Ok(upload_id)
+  }
+
+  async fn upload_part(&self, key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<UploadedPart> {
+    let etag = format!("{:x}", Sha256::digest(&data));
+    let mut uploads = self.multipart_uploads.write().unwrap();
+    let upload = uploads
+      .get_mut(upload_id)
+      .filter(|upload| upload.key == key)
+      .ok_or_else(|| anyhow!("Multipart upload {} not found", upload_id))?;
+    upload.parts.insert(part_number, data);
+    Ok(UploadedPart { part_number, etag })
+  }
+
+  async fn complete_multipart(&self, key: &str, upload_id: &str, parts: &[UploadedPart]) -> Result<()> {
+    if self.failing_completions.load(Ordering::SeqCst) {
+      bail!("Failed to complete {}: storage is unavailable", key);
+    }
+    let upload = {
+      let mut uploads = self.multipart_uploads.write().unwrap();
+      match uploads.get(upload_id) {
+        Some(upload) if upload.key == key => uploads.remove(upload_id).unwrap(),
+        _ => bail!("Multipart upload {} not found", upload_id),
+      }
+    };
+
+    let mut data = Vec::new();
+    for part in parts {
+      let chunk = upload
+        .parts
+        .get(&part.part_number)
+        .filter(|chunk| format!("{:x}", Sha256::digest(chunk)) == part.etag)
+        .ok_or_else(|| anyhow!("Part {} of {} does not match", part.part_number, upload_id))?;
+      data.extend_from_slice(chunk);
+    }
+    self.put(key, data, &upload.content_type).await
+  }
+
+  async fn abort_multipart(&self, _key: &str, upload_id: &str) -> Result<()> {
+    // S3 と同じく、確定や中断を終えたマルチパートアップロードは中断できない
+    match self.multipart_uploads.write().unwrap().remove(upload_id) {
+      Some(_) => Ok(()),
+      None => bail!("Multipart upload {} not found", upload_id),
+    }
+  }
+
+  fn multipart_part_size(&self) -> usize {
+    self.multipart_part_size
+  }
+
+  async fn get(&self, key: &str) -> Result<Vec<u8>> {
+    self
+      .objects
+      .read()
+      .unwrap()
+      .get(key)
+      .map(|object| object.data.clone())
+      .ok_or_else(|| anyhow!("Object {} not found", key))
+  }
+
+  async fn get_range(&self, key: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+    let objects = self.objects.read().unwrap();
+    let data = &objects
+      .get(key)
+      .ok_or_else(|| anyhow!("Object {} not found", key))?
+      .data;
+    let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+    let end = usize::try_from(length)
+      .unwrap_or(usize::MAX)
+      .saturating_add(start)
+      .min(data.len());
+    Ok(data[start..end].to_vec())
+  }
+
+  async fn head(&self, key: &str) -> Result<Option<ObjectInfo>> {
+    Ok(self.objects.read().unwrap().get(key).map(|object| ObjectInfo {
+      size: object.data.len() as i64,
+      content_type: Some(object.content_type.clone()),
+    }))
+  }
+
+  async fn delete(&self, key: &str) -> Result<()> {
+    if self.failing_deletes.load(Ordering::SeqCst) {
+      bail!("Failed to delete {}: storage is unavailable", key);
+    }
+    self.objects.write().unwrap().remove(key);
+    Ok(())
+  }
+
+  async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>> {
+    let objects: Vec<ObjectSummary> = self
+      .objects
+      .read()
+      .unwrap()
+      .range(prefix.to_string()..)
+      .take_while(|(key, _)| key.starts_with(prefix))
+      .map(|(key, object)| ObjectSummary {
+        key: key.clone(),
+        size: object.data.len() as i64,
+        last_modified: Some(object.last_modified),
+      })
+      .collect();
+
+    let pause = self.list_pause.lock().unwrap().take();
+    if let Some(pause) = pause {
+      pause.listed.notify_one();
+      pause.resume.notified().await;
+    }
+    Ok(objects)
+  }
+
+  async fn presign_put(&self, key: &str, content_type: &str, expires_in: Duration) -> Result<PresignedRequest> {
+    Ok(PresignedRequest {
+      method: "PUT".to_string(),
+      url: self.signer.presign("PUT", key, expires_in),
+      headers: BTreeMap::from([("content-type".to_string(), content_type.to_string())]),
+    })
+  }
+
+  async fn url_for(&self, key: &str) -> Result<String> {
+    if self.private {
+      return Ok(self.signer.presign("GET", key, self.presigned_url_ttl));
+    }
+    Ok(self.signer.url(key))
+  }
+
+  fn key_for_url(&self, url: &str) -> Option<String> {
+    self.signer.key_for_url(url)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_put_get_head_delete() {
+    let storage = MemoryStorage::new();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+    assert!(storage.get("pictures/1/a.png").await.is_err());
+
+    storage
+      .put("pictures/1/a.png", vec![1, 2, 3], "image/png")
+      .await
+      .unwrap();
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+    assert_eq!(storage.get_range("pictures/1/a.png", 1, 1).await.unwrap(), vec![2]);
+    assert_eq!(storage.get_range("pictures/1/a.png", 1, 10).await.unwrap(), vec![2, 3]);
+    assert_eq!(
+      storage.head("pictures/1/a.png").await.unwrap(),
+      Some(ObjectInfo {
+        size: 3,
+        content_type: Some("image/png".to_string()),
+      })
+    );
+
+    // クローンは同じオブジェクトを見る
+    storage.clone().delete("pictures/1/a.png").await.unwrap();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+    storage.delete("pictures/1/a.png").await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_put_stream_stores_only_finished_objects() {
+    let storage = MemoryStorage::new();
+
+    let mut writer = storage.put_stream("pictures/1/a.png", "image/png").await.unwrap();
+    writer.write(&[1, 2]).await.unwrap();
+    writer.write(&[3]).await.unwrap();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+    writer.finish().await.unwrap();
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+
+    let mut writer = storage.put_stream("pictures/1/b.png", "image/png").await.unwrap();
+    writer.write(&[1]).await.unwrap();
+    writer.abort().await.unwrap();
+    assert!(storage.head("pictures/1/b.png").await.unwrap().is_none());
+  }
+
+  #[tokio::test]
+  async fn test_multipart_upload() {
+    let storage = MemoryStorage::new();
+
+    let upload_id = storage.create_multipart("pictures/1/a.png", "image/png").await.unwrap();
+    let second = storage
+      .upload_part("pictures/1/a.png", &upload_id, 2, vec![3])
+      .await
+      .unwrap();
+    let first = storage
+      .upload_part("pictures/1/a.png", &upload_id, 1, vec![1, 2])
+      .await
+      .unwrap();
+    assert!(storage.head("pictures/1/a.png").await.unwrap().is_none());
+
+    storage
+      .complete_multipart("pictures/1/a.png", &upload_id, &[first, second])
+      .await
+      .unwrap();
+    assert_eq!(storage.get("pictures/1/a.png").await.unwrap(), vec![1, 2, 3]);
+    assert_eq!(storage.pending_multipart_count(), 0);
+
+    let upload_id = storage.create_multipart("pictures/1/b.png", "image/png").await.unwrap();
+    storage
+      .upload_part("pictures/1/b.png", &upload_id, 1, vec![1])
+      .await
+      .unwrap();
+    storage.abort_multipart("pictures/1/b.png", &upload_id).await.unwrap();
+    assert_eq!(storage.pending_multipart_count(), 0);
+    assert!(storage
+      .upload_part("pictures/1/b.png", &upload_id, 2, vec![2])
+      .await
+      .is_err());
+  }
+
+  #[tokio::test]
+  async fn test_list_by_prefix() {
+    let storage = MemoryStorage::new();
+    for key in [
+      "pictures/1/a.png",
+      "pictures/2/b.png",
+      "picturesque.png",
+      "uploads/1/c.png",
+    ] {
+      storage.put(key, vec![0; 4], "image/png").await.unwrap();
+    }
+
+    let keys: Vec<String> = storage
+      .list("pictures/")
+      .await
+      .unwrap()
+      .into_iter()
+      .map(|object| object.key)
+      .collect();
+    assert_eq!(keys, vec!["pictures/1/a.png", "pictures/2/b.png"]);
+  }
+
+  #[tokio::test]
+  async fn test_urls() {
+    let storage = MemoryStorage::new();
+    assert_eq!(
+      storage.url_for("pictures/1/a.png").await.unwrap(),
+      "http://storage.invalid/pictures/1/a.png"
+    );
+
+    let presigned = storage
+      .presign_put("uploads/1/a.png", "image/png", Duration::from_secs(60))
+      .await
+      .unwrap();
+    assert_eq!(presigned.method, "PUT");
+    assert!(presigned.url.contains("/uploads/1/a.png?expires="));
+    assert_eq!(presigned.headers["content-type"], "image/png");
+
+    let storage = storage.into_private(Duration::from_secs(60));
+    assert!(storage
+      .url_for("pictures/1/a.png")
+      .await
+      .unwrap()
+      .contains("&signature="));
+  }
+}
@@ -1,310 +1,150 @@
-use anyhow::{Context, Result};
-use aws_config::{BehaviorVersion, Region};
-use aws_sdk_s3::{
-  config::{Credentials, SharedCredentialsProvider},
-  primitives::ByteStream,
-  Client as S3Client,
-};
-use aws_smithy_runtime::client::http::hyper_014::HyperClientBuilder;
-use std::env;
-
-#[derive(Clone)]
-pub struct S3Storage {
-  client: S3Client,
-  bucket: String,
-  endpoint: Option<String>,
-  public_endpoint: Option<String>,
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, sync::Arc, time::Duration};
+
+pub mod local;
+pub mod memory;
+pub mod s3;
+mod signing;
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::S3Storage;
+
+/// クライアントがストレージへ直接送るための署名付きリクエスト
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+  pub method: String,
+  pub url: String,
+  /// 署名に含まれているため、クライアントがそのまま送る必要があるヘッダー
+  pub headers: BTreeMap<String, String>,
 }
 
-impl S3Storage {
-  pub async fn new() -> Result<Self> {
-    let endpoint = env::var("S3_ENDPOINT").ok();
-    let public_endpoint = env::var("S3_PUBLIC_ENDPOINT").ok();
-    let access_key = env::var("S3_ACCESS_KEY").context("S3_ACCESS_KEY not set")?;
-    let secret_key = env::var("S3_SECRET_KEY").context("S3_SECRET_KEY not set")?;
-    let region = env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
-    let bucket = env::var("S3_BUCKET").context("S3_BUCKET not set")?;
-
-    let credentials = Credentials::new(access_key, secret_key, None, None, "custom");
-    let credentials_provider = SharedCredentialsProvider::new(credentials);
-
-    let mut config_builder = aws_config::defaults(BehaviorVersion::latest())
-      .region(Region::new(region))
-      .credentials_provider(credentials_provider);
-
-    if let Some(ref endpoint_url) = endpoint {
-      config_builder = config_builder.endpoint_url(endpoint_url);
-    }
-
-    let config = config_builder.load().await;
-    let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&config);
-
-    let is_supabase = endpoint.as_ref().map(|e| e.contains("supabase")).unwrap_or(false);
-
-    if is_supabase {
-      let http_client = HyperClientBuilder::new().build_https();
-      s3_config_builder = s3_config_builder.http_client(http_client).force_path_style(true);
-    } else {
-      s3_config_builder = s3_config_builder.force_path_style(true);
-    }
-
-    if let Some(ref endpoint_url) = endpoint {
-      s3_config_builder = s3_config_builder.endpoint_url(endpoint_url);
-    }
-
-    let client = S3Client::from_conf(s3_config_builder.build());
-
-    Ok(Self {
-      client,
-      bucket,
-      endpoint,
-      public_endpoint,
-    })
-  }
-
-  pub async fn upload_file(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<String> {
-    let byte_stream = ByteStream::from(data);
-
-    self
-      .client
-      .put_object()
-      .bucket(&self.bucket)
-      .key(key)
-      .body(byte_stream)
-      .content_type(content_type)
-      .send()
-      .await
-      .map_err(|e| anyhow::anyhow!("Failed to upload file to S3: {:?}", e))?;
-
-    let endpoint_for_url = self.public_endpoint.as_ref().or(self.endpoint.as_ref());
-
-    let url = if let Some(endpoint) = endpoint_for_url {
-      format!("{}/{}/{}", endpoint, self.bucket, key)
-    } else {
-      format!("https://{}.s3.amazonaws.com/{}", self.bucket, key)
-    };
-
-    Ok(url)
-  }
-
-  pub async fn delete_file(&self, key: &str) -> Result<()> {
-    self
-      .client
-      .delete_object()
-      .bucket(&self.bucket)
-      .key(key)
-      .send()
-      .await
-      .map_err(|e| anyhow::anyhow!("Failed to delete file from S3: {:?}", e))?;
-
-    Ok(())
-  }
-
-  pub fn extract_key_from_url(&self, url: &str) -> Option<String> {
-    let endpoint_for_url = self.public_endpoint.as_ref().or(self.endpoint.as_ref());
-
-    if let Some(endpoint) = endpoint_for_url {
-      let prefix = format!("{}/{}/", endpoint, self.bucket);
-      if url.starts_with(&prefix) {
-        return Some(url[prefix.len()..].to_string());
-      }
-    } else {
-      let prefix = format!("https://{}.s3.amazonaws.com/", self.bucket);
-      if url.starts_with(&prefix) {
-        return Some(url[prefix.len()..].to_string());
-      }
-    }
-
-    None
-  }
+/// HEAD で分かるオブジェクトの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+  pub size: i64,
+  pub content_type: Option<String>,
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+/// 一覧で返すオブジェクトの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSummary {
+  pub key: String,
+  pub size: i64,
+  pub last_modified: Option<DateTime<Utc>>,
+}
 
-  fn create_test_storage(endpoint: Option<String>, public_endpoint: Option<String>, bucket: &str) -> S3Storage {
-    let credentials = Credentials::new("test_key", "test_secret", None, None, "test");
-    let credentials_provider = SharedCredentialsProvider::new(credentials);
+/// マルチパートアップロードで送り終えたパート
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UploadedPart {
+  pub part_number: i32,
+  pub etag: String,
+}
 
-    let config_builder = aws_config::defaults(BehaviorVersion::latest())
-      .region(Region::new("us-east-1"))
-      .credentials_provider(credentials_provider);
+/// 少しずつ書き込むオブジェクト。`finish` も `abort` もせずに捨てた場合も、書きかけのデータは残さない
+#[async_trait]
+pub trait ObjectWriter: Send {
+  async fn write(&mut self, chunk: &[u8]) -> Result<()>;
 
-    let config = tokio::runtime::Runtime::new().unwrap().block_on(config_builder.load());
+  /// 書き込んだ内容をオブジェクトとして確定する
+  async fn finish(self: Box<Self>) -> Result<()>;
 
-    let s3_config_builder = aws_sdk_s3::config::Builder::from(&config);
-    let client = S3Client::from_conf(s3_config_builder.build());
+  /// 書きかけのデータを破棄する
+  async fn abort(self: Box<Self>) -> Result<()>;
+}
 
-    S3Storage {
-      client,
-      bucket: bucket.to_string(),
-      endpoint,
-      public_endpoint,
-    }
-  }
+/// 写真を置くオブジェクトストレージ。S3 互換のサービス、開発用のローカルディレクトリ、テスト用のメモリ上の実装を差し替えられるようにする
+#[async_trait]
+pub trait ObjectStorage: Send + Sync {
+  /// `pictures.storage_backend` に記録する名前
+  fn backend(&self) -> &'static str;
 
-  #[test]
-  fn test_extract_key_from_url_with_endpoint() {
-    let storage = create_test_storage(Some("http://localhost:9000".to_string()), None, "dev");
+  async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()>;
 
-    let url = "http://localhost:9000/dev/pictures/test.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, Some("pictures/test.jpg".to_string()));
-  }
+  /// 全体をメモリに載せずに `key` へ書き込む
+  async fn put_stream(&self, key: &str, content_type: &str) -> Result<Box<dyn ObjectWriter>>;
 
-  #[test]
-  fn test_extract_key_from_url_with_public_endpoint() {
-    let storage = create_test_storage(
-      Some("http://rustfs:9000".to_string()),
-      Some("http://127.0.0.1:9000".to_string()),
-      "dev",
-    );
+  /// 複数のリクエストにまたがって書き込むマルチパートアップロードを始め、その ID を返す
+  async fn create_multipart(&self, key: &str, content_type: &str) -> Result<String>;
 
-    let url = "http://127.0.0.1:9000/dev/pictures/test.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, Some("pictures/test.jpg".to_string()));
-  }
-
-  #[test]
-  fn test_extract_key_from_url_with_supabase() {
-    let storage = create_test_storage(
-      Some("https://project.storage.supabase.co/storage/v1/s3".to_string()),
-      None,
-      "koko-pic",
-    );
+  /// `part_number` は1から。同じ番号で送り直すと置き換わる
+  async fn upload_part(&self, key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<UploadedPart>;
 
-    let url = "https://project.storage.supabase.co/storage/v1/s3/koko-pic/pictures/7/test.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, Some("pictures/7/test.jpg".to_string()));
-  }
+  /// `parts` を番号順につなげてオブジェクトとして確定する
+  async fn complete_multipart(&self, key: &str, upload_id: &str, parts: &[UploadedPart]) -> Result<()>;
 
-  #[test]
-  fn test_extract_key_from_url_with_cloudflare_r2() {
-    let storage = create_test_storage(
-      Some("https://3eee0f3be0c1d2517ddd0a5acd4486e7.r2.cloudflarestorage.com".to_string()),
-      None,
-      "koko-pic",
-    );
+  async fn abort_multipart(&self, key: &str, upload_id: &str) -> Result<()>;
 
-    let url = "https://3eee0f3be0c1d2517ddd0a5acd4486e7.r2.cloudflarestorage.com/koko-pic/pictures/test.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, Some("pictures/test.jpg".to_string()));
+  /// 最後以外のパートの大きさ
+  fn multipart_part_size(&self) -> usize {
+    s3::DEFAULT_MULTIPART_PART_SIZE_BYTES
   }
 
-  #[test]
-  fn test_extract_key_from_url_aws_s3() {
-    let storage = create_test_storage(None, None, "my-bucket");
+  async fn get(&self, key: &str) -> Result<Vec<u8>>;
 
-    let url = "https://my-bucket.s3.amazonaws.com/uploads/image.png";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, Some("uploads/image.png".to_string()));
-  }
+  /// 先頭から `offset` バイト目以降を最大 `length` バイト読む。オブジェクトが短ければ読めた分だけ返す
+  async fn get_range(&self, key: &str, offset: u64, length: u64) -> Result<Vec<u8>>;
 
-  #[test]
-  fn test_extract_key_from_url_invalid() {
-    let storage = create_test_storage(Some("http://localhost:9000".to_string()), None, "dev");
+  /// オブジェクトがなければ `None`
+  async fn head(&self, key: &str) -> Result<Option<ObjectInfo>>;
 
-    let url = "http://example.com/invalid/path.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, None);
-  }
-
-  #[test]
-  fn test_extract_key_from_url_wrong_bucket() {
-    let storage = create_test_storage(Some("http://localhost:9000".to_string()), None, "dev");
+  /// オブジェクトがなくてもエラーにしない
+  async fn delete(&self, key: &str) -> Result<()>;
 
-    let url = "http://localhost:9000/production/pictures/test.jpg";
-    let key = storage.extract_key_from_url(url);
-    assert_eq!(key, None);
-  }
+  /// キーが `prefix` で始まるオブジェクトをすべて返す
+  async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>>;
 
-  #[tokio::test]
-  #[serial_test::serial]
-  async fn test_new_with_supabase_endpoint() {
-    std::env::set_var("S3_ENDPOINT", "https://test.storage.supabase.co/storage/v1/s3");
-    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
-    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
-    std::env::set_var("S3_REGION", "us-east-1");
-    std::env::set_var("S3_BUCKET", "test-bucket");
+  /// `content_type` のファイルを `key` に PUT するための署名付きリクエストを作る
+  async fn presign_put(&self, key: &str, content_type: &str, expires_in: Duration) -> Result<PresignedRequest>;
 
-    let result = S3Storage::new().await;
-    assert!(result.is_ok());
+  /// クライアントに返す `key` の URL。設定から毎回組み立てるため、エンドポイントを変えても古いファイルを指せる。
+  /// 非公開のストレージでは有効期限付きの署名付き URL を発行する
+  async fn url_for(&self, key: &str) -> Result<String>;
 
-    let storage = result.unwrap();
-    assert_eq!(storage.bucket, "test-bucket");
-    assert_eq!(
-      storage.endpoint,
-      Some("https://test.storage.supabase.co/storage/v1/s3".to_string())
-    );
+  /// このストレージが `url_for` で返していた URL からオブジェクトのキーを取り出す。ほかの場所を指す URL なら `None`
+  fn key_for_url(&self, url: &str) -> Option<String>;
 
-    std::env::remove_var("S3_ENDPOINT");
-    std::env::remove_var("S3_ACCESS_KEY");
-    std::env::remove_var("S3_SECRET_KEY");
-    std::env::remove_var("S3_REGION");
-    std::env::remove_var("S3_BUCKET");
+  /// キーを保存する前の行の `image_url` からキーを取り出す。非公開バケットの頃は URL ではなくキーそのものを保存していた
+  fn key_for_stored_url(&self, image_url: &str) -> Option<String> {
+    if image_url.contains("://") {
+      self.key_for_url(image_url)
+    } else {
+      Some(image_url.to_string())
+    }
   }
 
-  #[tokio::test]
-  #[serial_test::serial]
-  async fn test_new_with_cloudflare_r2_endpoint() {
-    std::env::set_var("S3_ENDPOINT", "https://abc123.r2.cloudflarestorage.com");
-    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
-    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
-    std::env::set_var("S3_REGION", "auto");
-    std::env::set_var("S3_BUCKET", "my-bucket");
-
-    let result = S3Storage::new().await;
-    assert!(result.is_ok());
-
-    let storage = result.unwrap();
-    assert_eq!(storage.bucket, "my-bucket");
-    assert_eq!(
-      storage.endpoint,
-      Some("https://abc123.r2.cloudflarestorage.com".to_string())
-    );
-
-    std::env::remove_var("S3_ENDPOINT");
-    std::env::remove_var("S3_ACCESS_KEY");
-    std::env::remove_var("S3_SECRET_KEY");
-    std::env::remove_var("S3_REGION");
-    std::env::remove_var("S3_BUCKET");
+  /// ストレージ自身がファイルを配信する場合に、アプリに追加するルート
+  fn routes(&self) -> Option<Router> {
+    None
   }
+}
 
-  #[tokio::test]
-  #[serial_test::serial]
-  async fn test_new_with_public_endpoint() {
-    std::env::set_var("S3_ENDPOINT", "http://rustfs:9000");
-    std::env::set_var("S3_PUBLIC_ENDPOINT", "http://127.0.0.1:9000");
-    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
-    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
-    std::env::set_var("S3_REGION", "us-east-1");
-    std::env::set_var("S3_BUCKET", "dev");
-
-    let result = S3Storage::new().await;
-    assert!(result.is_ok());
-
-    let storage = result.unwrap();
-    assert_eq!(storage.bucket, "dev");
-    assert_eq!(storage.endpoint, Some("http://rustfs:9000".to_string()));
-    assert_eq!(storage.public_endpoint, Some("http://127.0.0.1:9000".to_string()));
-
-    std::env::remove_var("S3_ENDPOINT");
-    std::env::remove_var("S3_PUBLIC_ENDPOINT");
-    std::env::remove_var("S3_ACCESS_KEY");
-    std::env::remove_var("S3_SECRET_KEY");
-    std::env::remove_var("S3_REGION");
-    std::env::remove_var("S3_BUCKET");
-  }
+/// `url` が `prefix` で始まっていれば、その後ろからクエリとフラグメントを除いたものをキーとして返す
+fn key_after_prefix(url: &str, prefix: &str) -> Option<String> {
+  let rest = url.strip_prefix(prefix)?;
+  let key = rest.split(['?', '#']).next().unwrap_or_default();
+  (!key.is_empty()).then(|| key.to_string())
+}
 
-  #[tokio::test]
-  #[serial_test::serial]
-  async fn test_new_missing_credentials() {
-    std::env::remove_var("S3_ACCESS_KEY");
-    std::env::remove_var("S3_SECRET_KEY");
-    std::env::remove_var("S3_BUCKET");
+/// 環境変数 `STORAGE_BACKEND` からストレージを組み立てる。未設定なら S3 を使う
+pub async fn init_storage() -> Result<Arc<dyn ObjectStorage>> {
+  let backend = env::var("STORAGE_BACKEND").unwrap_or_else(|_| S3Storage::BACKEND.to_string());
 
-    let result = S3Storage::new().await;
-    assert!(result.is_err());
+  match backend.as_str() {
+    S3Storage::BACKEND => Ok(Arc::new(S3Storage::new().await?)),
+    LocalStorage::BACKEND => {
+      let storage = LocalStorage::from_env()?;
+      tracing::info!("Storing pictures in {}", storage.root().display());
+      Ok(Arc::new(storage))
+    }
+    MemoryStorage::BACKEND => {
+      tracing::warn!("STORAGE_BACKEND is memory; uploaded pictures are lost on restart");
+      Ok(Arc::new(MemoryStorage::new()))
+    }
+    other => bail!("Unknown STORAGE_BACKEND: {}", other),
   }
 }
@@ -0,0 +1,785 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_s3::{
+  config::{Credentials, SharedCredentialsProvider},
+  presigning::PresigningConfig,
+  primitives::ByteStream,
+  types::{CompletedMultipartUpload, CompletedPart},
+  Client as S3Client,
+};
+use aws_smithy_runtime::client::http::hyper_014::HyperClientBuilder;
+use chrono::DateTime;
+use std::{env, time::Duration};
+
+use super::{key_after_prefix, ObjectInfo, ObjectStorage, ObjectSummary, ObjectWriter, PresignedRequest, UploadedPart};
+
+pub const DEFAULT_PRESIGNED_URL_TTL_SECS: u64 = 15 * 60;
+/// S3 の署名付き URL の有効期間の上限（7日）
+const MAX_PRESIGNED_URL_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_MULTIPART_PART_SIZE_BYTES: usize = 8 * 1024 * 1024;
+/// S3 のマルチパートアップロードで、最後以外のパートに必要な最小サイズ
+const MIN_MULTIPART_PART_SIZE_BYTES: usize = 5 * 1024 * 1024;
+/// S3 のマルチパートアップロードのパートの最大サイズ
+const MAX_MULTIPART_PART_SIZE_BYTES: usize = 5 * 1024 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct S3Storage {
+  client: S3Client,
+  /// 署名付き URL はクライアントから届くホスト名で署名する必要があるため、公開エンドポイント向けに別に持つ
+  presign_client: S3Client,
+  bucket: String,
+  endpoint: Option<String>,
+  public_endpoint: Option<String>,
+  /// 非公開バケットでは DB にオブジェクトのキーだけを保存し、レスポンスごとに署名付き URL を発行する
+  private: bool,
+  presigned_url_ttl: Duration,
+  /// R2 は最後以外のパートがすべて同じサイズである必要があるため、固定のサイズで分割する
+  multipart_part_size: usize,
+}
+
+/// 環境変数 `name` から署名付き URL の有効期間を読む。未設定か範囲外なら既定値を使う
+pub(crate) fn presigned_url_ttl_from_env(name: &str) -> Duration {
+  Duration::from_secs(
+    env::var(name)
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|secs| (1..=MAX_PRESIGNED_URL_TTL_SECS).contains(secs))
+      .unwrap_or(DEFAULT_PRESIGNED_URL_TTL_SECS),
+  )
+}
+
+impl S3Storage {
+  /// `STORAGE_BACKEND` と `pictures.storage_backend` に使う名前
+  pub const BACKEND: &'static str = "s3";
+
+  pub async fn new() -> Result<Self> {
+    let endpoint = env::var("S3_ENDPOINT").ok();
+    let public_endpoint = env::var("S3_PUBLIC_ENDPOINT").ok();
+    let access_key = env::var("S3_ACCESS_KEY").context("S3_ACCESS_KEY not set")?;
+    let secret_key = env::var("S3_SECRET_KEY").context("S3_SECRET_KEY not set")?;
+    let region = env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+    let bucket = env::var("S3_BUCKET").context("S3_BUCKET not set")?;
+    let private = env::var("S3_PRIVATE_BUCKET").is_ok_and(|value| value == "true" || value == "1");
+    let presigned_url_ttl = presigned_url_ttl_from_env("S3_PRESIGNED_URL_TTL_SECS");
+    let multipart_part_size = env::var("S3_MULTIPART_PART_SIZE_BYTES")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .filter(|bytes| (MIN_MULTIPART_PART_SIZE_BYTES..=MAX_MULTIPART_PART_SIZE_BYTES).contains(bytes))
+      .unwrap_or(DEFAULT_MULTIPART_PART_SIZE_BYTES);
+
+    let credentials = Credentials::new(access_key, secret_key, None, None, "custom");
+    let credentials_provider = SharedCredentialsProvider::new(credentials);
+
+    let mut config_builder = aws_config::defaults(BehaviorVersion::latest())
+      .region(Region::new(region))
+      .credentials_provider(credentials_provider);
+
+    if let Some(ref endpoint_url) = endpoint {
+      config_builder = config_builder.endpoint_url(endpoint_url);
+    }
+
+    let config = config_builder.load().await;
+    let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&config);
+
+    let is_supabase = endpoint.as_ref().map(|e| e.contains("supabase")).unwrap_or(false);
+
+    if is_supabase {
+      let http_client = HyperClientBuilder::new().build_https();
+      s3_config_builder = s3_config_builder.http_client(http_client).force_path_style(true);
+    } else {
+      s3_config_builder = s3_config_builder.force_path_style(true);
+    }
+
+    if let Some(ref endpoint_url) = endpoint {
+      s3_config_builder = s3_config_builder.endpoint_url(endpoint_url);
+    }
+
+    let s3_config = s3_config_builder.build();
+    let presign_client = match public_endpoint.as_ref() {
+      Some(public_endpoint_url) => {
+        S3Client::from_conf(s3_config.to_builder().endpoint_url(public_endpoint_url).build())
+      }
+      None => S3Client::from_conf(s3_config.clone()),
+    };
+    let client = S3Client::from_conf(s3_config);
+
+    Ok(Self {
+      client,
+      presign_client,
+      bucket,
+      endpoint,
+      public_endpoint,
+      private,
+      presigned_url_ttl,
+      multipart_part_size,
+    })
+  }
+
+  /// 非公開バケットとして扱う
+  #[cfg(test)]
+  pub(crate) fn into_private(mut self, presigned_url_ttl: Duration) -> Self {
+    self.private = true;
+    self.presigned_url_ttl = presigned_url_ttl;
+    self
+  }
+
+  fn public_url(&self, key: &str) -> String {
+    let endpoint_for_url = self.public_endpoint.as_ref().or(self.endpoint.as_ref());
+
+    if let Some(endpoint) = endpoint_for_url {
+      format!("{}/{}/{}", endpoint, self.bucket, key)
+    } else {
+      format!("https://{}.s3.amazonaws.com/{}", self.bucket, key)
+    }
+  }
+}
+
+#[async_trait]
+impl ObjectStorage for S3Storage {
+  fn backend(&self) -> &'static str {
+    Self::BACKEND
+  }
+
+  async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()> {
+    let byte_stream = ByteStream::from(data);
+
+    self
+      .client
+      .put_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .body(byte_stream)
+      .content_type(content_type)
+      .send()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to upload file to S3: {:?}", e))?;
+
+    Ok(())
+  }
+
+  async fn put_stream(&self, key: &str, content_type: &str) -> Result<Box<dyn ObjectWriter>> {
+    Ok(Box::new(S3MultipartWriter {
+      client: self.client.clone(),
+      bucket: self.bucket.clone(),
+      key: key.to_string(),
+      content_type: content_type.to_string(),
+      part_size: self.multipart_part_size,
+      buffer: Vec::new(),
+      upload_id: None,
+      parts: Vec::new(),
+      done: false,
+    }))
+  }
+
+  async fn create_multipart(&self, key: &str, content_type: &str) -> Result<String> {
+    create_multipart_upload(&self.client, &self.bucket, key, content_type).await
+  }
+
+  async fn upload_part(&self, key: &str, upload_id: &str, part_number: i32, data: Vec<u8>) -> Result<UploadedPart> {
+    upload_part(&self.client, &self.bucket, key, upload_id, part_number, data).await
+  }
+
+  async fn complete_multipart(&self, key: &str, upload_id: &str, parts: &[UploadedPart]) -> Result<()> {
+    complete_multipart_upload(&self.client, &self.bucket, key, upload_id, parts).await
+  }
+
+  async fn abort_multipart(&self, key: &str, upload_id: &str) -> Result<()> {
+    abort_multipart_upload(&self.client, &self.bucket, key, upload_id).await
+  }
+
+  fn multipart_part_size(&self) -> usize {
+    self.multipart_part_size
+  }
+
+  async fn presign_put(&self, key: &str, content_type: &str, expires_in: Duration) -> Result<PresignedRequest> {
+    let presigning_config = PresigningConfig::expires_in(expires_in)?;
+    let presigned = self
+      .presign_client
+      .put_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .content_type(content_type)
+      .presigned(presigning_config)
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to presign upload: {:?}", e))?;
+
+    Ok(PresignedRequest {
+      method: presigned.method().to_string(),
+      url: presigned.uri().to_string(),
+      headers: presigned
+        .headers()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect(),
+    })
+  }
+
+  async fn head(&self, key: &str) -> Result<Option<ObjectInfo>> {
+    match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+      Ok(output) => Ok(Some(ObjectInfo {
+        size: output.content_length().unwrap_or(0),
+        content_type: output.content_type().map(str::to_string),
+      })),
+      Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+      Err(e) => Err(anyhow::anyhow!("Failed to head file in S3: {:?}", e)),
+    }
+  }
+
+  async fn get(&self, key: &str) -> Result<Vec<u8>> {
+    let output = self
+      .client
+      .get_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .send()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to download file from S3: {:?}", e))?;
+
+    let data = output
+      .body
+      .collect()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to read file from S3: {:?}", e))?;
+
+    Ok(data.into_bytes().to_vec())
+  }
+
+  async fn get_range(&self, key: &str, offset: u64, length: u64) -> Result<Vec<u8>> {
+    if length == 0 {
+      return Ok(Vec::new());
+    }
+
+    let output = self
+      .client
+      .get_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .range(format!("bytes={}-{}", offset, offset.saturating_add(length - 1)))
+      .send()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to download file from S3: {:?}", e))?;
+
+    let data = output
+      .body
+      .collect()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to read file from S3: {:?}", e))?;
+
+    Ok(data.into_bytes().to_vec())
+  }
+
+  /// 非公開バケットでは有効期限付きの署名付き URL を発行する
+  async fn url_for(&self, key: &str) -> Result<String> {
+    if !self.private {
+      return Ok(self.public_url(key));
+    }
+
+    let presigned = self
+      .presign_client
+      .get_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .presigned(PresigningConfig::expires_in(self.presigned_url_ttl)?)
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to presign download: {:?}", e))?;
+
+    Ok(presigned.uri().to_string())
+  }
+
+  /// 公開エンドポイントと内部のエンドポイントのどちらで組み立てた URL でも、このバケットを指していればキーを返す
+  fn key_for_url(&self, url: &str) -> Option<String> {
+    let endpoints = [self.public_endpoint.as_ref(), self.endpoint.as_ref()];
+    if endpoints.iter().all(Option::is_none) {
+      return key_after_prefix(url, &format!("https://{}.s3.amazonaws.com/", self.bucket));
+    }
+
+    endpoints
+      .into_iter()
+      .flatten()
+      .find_map(|endpoint| key_after_prefix(url, &format!("{}/{}/", endpoint.trim_end_matches('/'), self.bucket)))
+  }
+
+  async fn delete(&self, key: &str) -> Result<()> {
+    self
+      .client
+      .delete_object()
+      .bucket(&self.bucket)
+      .key(key)
+      .send()
+      .await
+      .map_err(|e| anyhow::anyhow!("Failed to delete file from S3: {:?}", e))?;
+
+    Ok(())
+  }
+
+  async fn list(&self, prefix: &str) -> Result<Vec<ObjectSummary>> {
+    let mut objects = Vec::new();
+    let mut pages = self
+      .client
+      .list_objects_v2()
+      .bucket(&self.bucket)
+      .prefix(prefix)
+      .into_paginator()
+      .send();
+
+    while let Some(page) = pages.next().await {
+      let page = page.map_err(|e| anyhow::anyhow!("Failed to list files in S3: {:?}", e))?;
+      for object in page.contents() {
+        let Some(key) = object.key() else {
+          continue;
+        };
+        objects.push(ObjectSummary {
+          key: key.to_string(),
+          size: object.size().unwrap_or(0),
+          last_modified: object
+            .last_modified()
+            .and_then(|modified| DateTime::from_timestamp(modified.secs(), modified.subsec_nanos())),
+        });
+      }
+    }
+
+    Ok(objects)
+  }
+}
+
+/// パートのサイズまでたまったらマルチパートアップロードで送る。
+/// 1パートに満たない小さなファイルはマルチパートアップロードを使わずに1回の PUT で送る
+struct S3MultipartWriter {
+  client: S3Client,
+  bucket: String,
+  key: String,
+  content_type: String,
+  part_size: usize,
+  buffer: Vec<u8>,
+  /// 最初のパートを送るときに始める
+  upload_id: Option<String>,
+  parts: Vec<UploadedPart>,
+  /// 確定か中断をしたら、破棄するときに中断しない
+  done: bool,
+}
+
+impl S3MultipartWriter {
+  async fn upload_part(&mut self, data: Vec<u8>) -> Result<()> {
+    let upload_id = match &self.upload_id {
+      Some(upload_id) => upload_id.clone(),
+      None => {
+        let upload_id = create_multipart_upload(&self.client, &self.bucket, &self.key, &self.content_type).await?;
+        self.upload_id = Some(upload_id.clone());
+        upload_id
+      }
+    };
+    let part_number = self.parts.len() as i32 + 1;
+
+    let part = upload_part(&self.client, &self.bucket, &self.key, &upload_id, part_number, data).await?;
+    self.parts.push(part);
+
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl ObjectWriter for S3MultipartWriter {
+  async fn write(&mut self, chunk: &[u8]) -> Result<()> {
+    self.buffer.extend_from_slice(chunk);
+    while self.buffer.len() >= self.part_size {
+      let rest = self.buffer.split_off(self.part_size);
+      let part = std::mem::replace(&mut self.buffer, rest);
+      self.upload_part(part).await?;
+    }
+
+    Ok(())
+  }
+
+  async fn finish(mut self: Box<Self>) -> Result<()> {
+    let Some(upload_id) = self.upload_id.clone() else {
+      let data = std::mem::take(&mut self.buffer);
+      self
+        .client
+        .put_object()
+        .bucket(&self.bucket)
+        .key(&self.key)
+        .body(ByteStream::from(data))
+        .content_type(&self.content_type)
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to upload file to S3: {:?}", e))?;
+      self.done = true;
+      return Ok(());
+    };
+
+    if !self.buffer.is_empty() {
+      let part = std::mem::take(&mut self.buffer);
+      self.upload_part(part).await?;
+    }
+
+    complete_multipart_upload(&self.client, &self.bucket, &self.key, &upload_id, &self.parts).await?;
+    self.done = true;
+
+    Ok(())
+  }
+
+  async fn abort(mut self: Box<Self>) -> Result<()> {
+    self.done = true;
+    let Some(upload_id) = self.upload_id.take() else {
+      return Ok(());
+    };
+
+    abort_multipart_upload(&self.client, &self.bucket, &self.key, &upload_id).await
+  }
+}
+
+/// クライアントが切断してハンドラーごと破棄された場合も、送りかけのパートが課金され続けないよう中断する
+impl Drop for S3MultipartWriter {
+  fn drop(&mut self) {
+    if self.done {
+      return;
+    }
+    let (Some(upload_id), Ok(runtime)) = (self.upload_id.take(), tokio::runtime::Handle::try_current()) else {
+      return;
+    };
+
+    let (client, bucket, key) = (self.client.clone(), self.bucket.clone(), self.key.clone());
+    runtime.spawn(async move {
+      if let Err(e) = abort_multipart_upload(&client, &bucket, &key, &upload_id).await {
+        tracing::error!("Failed to abort abandoned multipart upload {}: {:?}", key, e);
+      }
+    });
+  }
+}
+
+async fn create_multipart_upload(client: &S3Client, bucket: &str, key: &str, content_type: &str) -> Result<String> {
+  let output = client
+    .create_multipart_upload()
+    .bucket(bucket)
+    .key(key)
+    .content_type(content_type)
+    .send()
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to start multipart upload to S3: {:?}", e))?;
+
+  Ok(
+    output
+      .upload_id()
+      .context("S3 did not return a multipart upload id")?
+      .to_string(),
+  )
+}
+
+async fn upload_part(
+  client: &S3Client,
+  bucket: &str,
+  key: &str,
+  upload_id: &str,
+  part_number: i32,
+  data: Vec<u8>,
+) -> Result<UploadedPart> {
+  let output = client
+    .upload_part()
+    .bucket(bucket)
+    .key(key)
+    .upload_id(upload_id)
+    .part_number(part_number)
+    .body(ByteStream::from(data))
+    .send()
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to upload part {} to S3: {:?}", part_number, e))?;
+
+  Ok(UploadedPart {
+    part_number,
+    etag: output
+      .e_tag()
+      .context("S3 did not return an ETag for the part")?
+      .to_string(),
+  })
+}
+
+async fn complete_multipart_upload(
+  client: &S3Client,
+  bucket: &str,
+  key: &str,
+  upload_id: &str,
+  parts: &[UploadedPart],
+) -> Result<()> {
+  let parts = parts
+    .iter()
+    .map(|part| {
+      CompletedPart::builder()
+        .part_number(part.part_number)
+        .e_tag(&part.etag)
+        .build()
+    })
+    .collect();
+
+  client
+    .complete_multipart_upload()
+    .bucket(bucket)
+    .key(key)
+    .upload_id(upload_id)
+    .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+    .send()
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to complete multipart upload to S3: {:?}", e))?;
+
+  Ok(())
+}
+
+async fn abort_multipart_upload(client: &S3Client, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+  client
+    .abort_multipart_upload()
+    .bucket(bucket)
+    .key(key)
+    .upload_id(upload_id)
+    .send()
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to abort multipart upload to S3: {:?}", e))?;
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn create_test_storage(endpoint: Option<String>, public_endpoint: Option<String>, bucket: &str) -> S3Storage {
+    let credentials = Credentials::new("test_key", "test_secret", None, None, "test");
+    let credentials_provider = SharedCredentialsProvider::new(credentials);
+
+    let config_builder = aws_config::defaults(BehaviorVersion::latest())
+      .region(Region::new("us-east-1"))
+      .credentials_provider(credentials_provider);
+
+    let config = tokio::runtime::Runtime::new().unwrap().block_on(config_builder.load());
+
+    let s3_config_builder = aws_sdk_s3::config::Builder::from(&config);
+    let client = S3Client::from_conf(s3_config_builder.build());
+
+    S3Storage {
+      presign_client: client.clone(),
+      client,
+      bucket: bucket.to_string(),
+      endpoint,
+      public_endpoint,
+      private: false,
+      presigned_url_ttl: Duration::from_secs(DEFAULT_PRESIGNED_URL_TTL_SECS),
+      multipart_part_size: DEFAULT_MULTIPART_PART_SIZE_BYTES,
+    }
+  }
+
+  fn url_for(storage: &S3Storage, key: &str) -> String {
+    tokio::runtime::Runtime::new()
+      .unwrap()
+      .block_on(storage.url_for(key))
+      .unwrap()
+  }
+
+  #[test]
+  fn test_url_for_with_endpoint() {
+    let storage = create_test_storage(Some("http://localhost:9000".to_string()), None, "dev");
+
+    assert_eq!(
+      url_for(&storage, "pictures/test.jpg"),
+      "http://localhost:9000/dev/pictures/test.jpg"
+    );
+  }
+
+  #[test]
+  fn test_url_for_with_public_endpoint() {
+    let storage = create_test_storage(
+      Some("http://rustfs:9000".to_string()),
+      Some("http://127.0.0.1:9000".to_string()),
+      "dev",
+    );
+
+    assert_eq!(
+      url_for(&storage, "pictures/test.jpg"),
+      "http://127.0.0.1:9000/dev/pictures/test.jpg"
+    );
+  }
+
+  #[test]
+  fn test_url_for_with_supabase() {
+    let storage = create_test_storage(
+      Some("https://project.storage.supabase.co/storage/v1/s3".to_string()),
+      None,
+      "koko-pic",
+    );
+
+    assert_eq!(
+      url_for(&storage, "pictures/7/test.jpg"),
+      "https://project.storage.supabase.co/storage/v1/s3/koko-pic/pictures/7/test.jpg"
+    );
+  }
+
+  #[test]
+  fn test_url_for_aws_s3() {
+    let storage = create_test_storage(None, None, "my-bucket");
+
+    assert_eq!(
+      url_for(&storage, "uploads/image.png"),
+      "https://my-bucket.s3.amazonaws.com/uploads/image.png"
+    );
+  }
+
+  #[test]
+  fn test_key_for_url_matches_only_this_bucket() {
+    let storage = create_test_storage(
+      Some("http://rustfs:9000".to_string()),
+      Some("http://127.0.0.1:9000".to_string()),
+      "dev",
+    );
+
+    for url in [
+      "http://127.0.0.1:9000/dev/pictures/7/a.jpg",
+      "http://rustfs:9000/dev/pictures/7/a.jpg",
+    ] {
+      assert_eq!(storage.key_for_url(url).as_deref(), Some("pictures/7/a.jpg"));
+    }
+    assert_eq!(
+      storage
+        .key_for_url("http://127.0.0.1:9000/dev/uploads/7/b.png")
+        .as_deref(),
+      Some("uploads/7/b.png")
+    );
+    // 別のバケットや外部のサイトを指す URL は取り出さない
+    assert_eq!(
+      storage.key_for_url("http://127.0.0.1:9000/other/pictures/7/a.jpg"),
+      None
+    );
+    assert_eq!(storage.key_for_url("https://example.com/dev/pictures/7/a.jpg"), None);
+    assert_eq!(storage.key_for_url("http://127.0.0.1:9000/dev/"), None);
+
+    let storage = create_test_storage(None, None, "my-bucket");
+    assert_eq!(
+      storage
+        .key_for_url("https://my-bucket.s3.amazonaws.com/pictures/1/a.png")
+        .as_deref(),
+      Some("pictures/1/a.png")
+    );
+    assert_eq!(
+      storage.key_for_url("https://other.s3.amazonaws.com/pictures/1/a.png"),
+      None
+    );
+  }
+
+  #[test]
+  fn test_url_for_presigns_in_private_bucket() {
+    let storage =
+      create_test_storage(Some("http://localhost:9000".to_string()), None, "dev").into_private(Duration::from_secs(60));
+
+    let url = url_for(&storage, "pictures/7/a.jpg");
+    assert!(url.contains("/pictures/7/a.jpg?"));
+    assert!(url.contains("X-Amz-Expires=60"));
+  }
+
+  #[tokio::test]
+  #[serial_test::serial]
+  async fn test_new_with_supabase_endpoint() {
+    std::env::set_var("S3_ENDPOINT", "https://test.storage.supabase.co/storage/v1/s3");
+    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
+    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
+    std::env::set_var("S3_REGION", "us-east-1");
+    std::env::set_var("S3_BUCKET", "test-bucket");
+
+    let result = S3Storage::new().await;
+    assert!(result.is_ok());
+
+    let storage = result.unwrap();
+    assert_eq!(storage.bucket, "test-bucket");
+    assert_eq!(
+      storage.endpoint,
+      Some("https://test.storage.supabase.co/storage/v1/s3".to_string())
+    );
+
+    std::env::remove_var("S3_ENDPOINT");
+    std::env::remove_var("S3_ACCESS_KEY");
+    std::env::remove_var("S3_SECRET_KEY");
+    std::env::remove_var("S3_REGION");
+    std::env::remove_var("S3_BUCKET");
+  }
+
+  #[tokio::test]
+  #[serial_test::serial]
+  async fn test_new_with_cloudflare_r2_endpoint() {
+    std::env::set_var("S3_ENDPOINT", "https://abc123.r2.cloudflarestorage.com");
+    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
+    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
+    std::env::set_var("S3_REGION", "auto");
+    std::env::set_var("S3_BUCKET", "my-bucket");
+
+    let result = S3Storage::new().await;
+    assert!(result.is_ok());
+
+    let storage = result.unwrap();
+    assert_eq!(storage.bucket, "my-bucket");
+    assert_eq!(
+      storage.endpoint,
+      Some("https://abc123.r2.cloudflarestorage.com".to_string())
+    );
+
+    std::env::remove_var("S3_ENDPOINT");
+    std::env::remove_var("S3_ACCESS_KEY");
+    std::env::remove_var("S3_SECRET_KEY");
+    std::env::remove_var("S3_REGION");
+    std::env::remove_var("S3_BUCKET");
+  }
+
+  #[tokio::test]
+  #[serial_test::serial]
+  async fn test_new_with_public_endpoint() {
+    std::env::set_var("S3_ENDPOINT", "http://rustfs:9000");
+    std::env::set_var("S3_PUBLIC_ENDPOINT", "http://127.0.0.1:9000");
+    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
+    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
+    std::env::set_var("S3_REGION", "us-east-1");
+    std::env::set_var("S3_BUCKET", "dev");
+
+    let result = S3Storage::new().await;
+    assert!(result.is_ok());
+
+    let storage = result.unwrap();
+    assert_eq!(storage.bucket, "dev");
+    assert_eq!(storage.endpoint, Some("http://rustfs:9000".to_string()));
+    assert_eq!(storage.public_endpoint, Some("http://127.0.0.1:9000".to_string()));
+
+    std::env::remove_var("S3_ENDPOINT");
+    std::env::remove_var("S3_PUBLIC_ENDPOINT");
+    std::env::remove_var("S3_ACCESS_KEY");
+    std::env::remove_var("S3_SECRET_KEY");
+    std::env::remove_var("S3_REGION");
+    std::env::remove_var("S3_BUCKET");
+  }
+
+  #[tokio::test]
+  #[serial_test::serial]
+  async fn test_new_reads_multipart_part_size() {
+    std::env::set_var("S3_ACCESS_KEY", "test_access_key");
+    std::env::set_var("S3_SECRET_KEY", "test_secret_key");
+    std::env::set_var("S3_BUCKET", "dev");
+
+    std::env::set_var("S3_MULTIPART_PART_SIZE_BYTES", "16777216");
+    assert_eq!(S3Storage::new().await.unwrap().multipart_part_size, 16 * 1024 * 1024);
+
+    // S3 の最小サイズに満たない値は使わない
+    std::env::set_var("S3_MULTIPART_PART_SIZE_BYTES", "1024");
+    assert_eq!(
+      S3Storage::new().await.unwrap().multipart_part_size,
+      DEFAULT_MULTIPART_PART_SIZE_BYTES
+    );
+
+    std::env::remove_var("S3_MULTIPART_PART_SIZE_BYTES");
+    std::env::remove_var("S3_ACCESS_KEY");
+    std::env::remove_var("S3_SECRET_KEY");
+    std::env::remove_var("S3_BUCKET");
+  }
+
+  #[tokio::test]
+  #[serial_test::serial]
+  async fn test_new_missing_credentials() {
+    std::env::remove_var("S3_ACCESS_KEY");
+    std::env::remove_var("S3_SECRET_KEY");
+    std::env::remove_var("S3_BUCKET");
+
+    let result = S3Storage::new().await;
+    assert!(result.is_err());
+  }
+}
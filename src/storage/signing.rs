@@ -0,0 +1,123 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// S3 を使わないストレージで、署名付き URL の代わりに発行する URL を作って検証する。
+/// 署名は `method`・`key`・`expires` に対する HMAC-SHA256 で、JWT とは別の鍵を使う
+#[derive(Clone)]
+pub(crate) struct UrlSigner {
+  base_url: String,
+  secret: String,
+}
+
+impl UrlSigner {
+  pub(crate) fn new(base_url: impl Into<String>, secret: impl Into<String>) -> Self {
+    Self {
+      base_url: base_url.into().trim_end_matches('/').to_string(),
+      secret: secret.into(),
+    }
+  }
+
+  pub(crate) fn url(&self, key: &str) -> String {
+    format!("{}/{}", self.base_url, key)
+  }
+
+  /// `url` で組み立てた URL からキーを取り出す
+  pub(crate) fn key_for_url(&self, url: &str) -> Option<String> {
+    super::key_after_prefix(url, &format!("{}/", self.base_url))
+  }
+
+  /// `expires_in` の間だけ `method` で `key` にアクセスできる URL
+  pub(crate) fn presign(&self, method: &str, key: &str, expires_in: Duration) -> String {
+    let expires = Utc::now().timestamp() + expires_in.as_secs() as i64;
+    format!(
+      "{}?expires={}&signature={}",
+      self.url(key),
+      expires,
+      self.signature(method, key, expires)
+    )
+  }
+
+  /// 署名の比較は一致するまでの時間から署名を推測されないよう、定数時間で行う
+  pub(crate) fn verify(&self, method: &str, key: &str, expires: i64, signature: &str) -> bool {
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+      return false;
+    };
+    expires > Utc::now().timestamp() && self.mac(method, key, expires).verify_slice(&signature).is_ok()
+  }
+
+  fn signature(&self, method: &str, key: &str, expires: i64) -> String {
+    URL_SAFE_NO_PAD.encode(self.mac(method, key, expires).finalize().into_bytes())
+  }
+
+  fn mac(&self, method: &str, key: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts keys of any length");
+    // キーに `:` を含めても区切りがずれないよう、キーは最後に置く
+    mac.update(format!("{}:{}:", method, expires).as_bytes());
+    mac.update(key.as_bytes());
+    mac
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn presigned_query(signer: &UrlSigner, method: &str, key: &str) -> (i64, String) {
+    let url = signer.presign(method, key, Duration::from_secs(60));
+    let query = url.split_once('?').unwrap().1;
+    let (expires, signature) = query.split_once("&signature=").unwrap();
+    (
+      expires.trim_start_matches("expires=").parse().unwrap(),
+      signature.to_string(),
+    )
+  }
+
+  #[test]
+  fn test_presigned_url_verifies_only_for_same_request() {
+    let signer = UrlSigner::new("http://localhost:8000/storage/", "secret");
+    let url = signer.presign("PUT", "uploads/1/a.png", Duration::from_secs(60));
+    assert!(url.starts_with("http://localhost:8000/storage/uploads/1/a.png?expires="));
+
+    let (expires, signature) = presigned_query(&signer, "PUT", "uploads/1/a.png");
+    assert!(signer.verify("PUT", "uploads/1/a.png", expires, &signature));
+    assert!(!UrlSigner::new("http://localhost:8000/storage", "other").verify(
+      "PUT",
+      "uploads/1/a.png",
+      expires,
+      &signature
+    ));
+  }
+
+  #[test]
+  fn test_tampered_request_is_rejected() {
+    let signer = UrlSigner::new("http://localhost:8000/storage", "secret");
+    let (expires, signature) = presigned_query(&signer, "PUT", "uploads/1/a.png");
+
+    // 方法・キー・期限のどれを変えても通らない
+    assert!(!signer.verify("GET", "uploads/1/a.png", expires, &signature));
+    assert!(!signer.verify("PUT", "uploads/1/b.png", expires, &signature));
+    assert!(!signer.verify("PUT", "uploads/1/a.png.tail", expires, &signature));
+    assert!(!signer.verify("PUT", "uploads/1/a.png", expires + 3600, &signature));
+
+    // 署名そのものを変えたものや、署名として読めないものも通らない
+    let mut flipped = signature.clone().into_bytes();
+    flipped[0] = if flipped[0] == b'A' { b'B' } else { b'A' };
+    assert!(!signer.verify("PUT", "uploads/1/a.png", expires, &String::from_utf8(flipped).unwrap()));
+    assert!(!signer.verify("PUT", "uploads/1/a.png", expires, &signature[..signature.len() - 2]));
+    assert!(!signer.verify("PUT", "uploads/1/a.png", expires, "not a signature"));
+    assert!(!signer.verify("PUT", "uploads/1/a.png", expires, ""));
+  }
+
+  #[test]
+  fn test_expired_url_is_rejected() {
+    let signer = UrlSigner::new("http://localhost:8000/storage", "secret");
+    let expires = Utc::now().timestamp() - 1;
+
+    assert!(!signer.verify("PUT", "a.png", expires, &signer.signature("PUT", "a.png", expires)));
+  }
+}
@@ -5,9 +5,19 @@ use axum::{
 };
 use serde::Serialize;
 use sqlx::PgPool;
+use std::sync::Arc;
 use tower::ServiceExt;
 
-use crate::{app::create_app, email::EmailService, state::SharedAppState, storage::S3Storage};
+use crate::{
+  app::create_app,
+  domains::{
+    request::model::CreateRequestRequest,
+    user::model::{LoginRequest, LoginResponse},
+  },
+  email::EmailService,
+  state::SharedAppState,
+  storage::{MemoryStorage, ObjectStorage},
+};
 
 async fn create_test_email_service() -> EmailService {
   crate::utils::init_email_service()
@@ -15,20 +25,46 @@ async fn create_test_email_service() -> EmailService {
     .expect("Failed to create test email service")
 }
 
-async fn create_test_storage() -> S3Storage {
-  std::env::set_var("S3_ENDPOINT", "http://rustfs:9000");
-  std::env::set_var("S3_PUBLIC_ENDPOINT", "http://127.0.0.1:9000");
-  std::env::set_var("S3_ACCESS_KEY", "rustfs");
-  std::env::set_var("S3_SECRET_KEY", "rustfssecret");
-  std::env::set_var("S3_REGION", "us-east-1");
-  std::env::set_var("S3_BUCKET", "test");
-  S3Storage::new().await.expect("Failed to create test storage")
+/// テスト用のメモリ上のストレージ。同じオブジェクトを見るには、同じものをアプリと共有する
+pub fn create_test_storage() -> Arc<dyn ObjectStorage> {
+  Arc::new(MemoryStorage::new())
+}
+
+pub fn request_payload(lat: f64, lng: f64, place_name: &str, description: &str) -> CreateRequestRequest {
+  CreateRequestRequest {
+    lat,
+    lng,
+    place_name: place_name.to_string(),
+    description: description.to_string(),
+    ..Default::default()
+  }
+}
+
+/// メール確認済みにしたうえでログインし、JWT を返す（パスワードは "password123" 固定）
+pub async fn login_verified_user(app: Router, pool: &PgPool, email: &str) -> Result<String, sqlx::Error> {
+  sqlx::query!("UPDATE users SET email_verified = true WHERE email = $1", email)
+    .execute(pool)
+    .await?;
+
+  let login_payload = LoginRequest {
+    email: email.to_string(),
+    password: "password123".to_string(),
+  };
+  let (login_status, login_body) = post_json(app, "/api/v1/login", &login_payload).await;
+  assert_eq!(login_status, StatusCode::OK);
+
+  let login_response: LoginResponse = serde_json::from_slice(&login_body).expect("deserialize login response");
+  Ok(login_response.token)
 }
 
 pub async fn app_with_pool(pool: PgPool) -> Router {
+  app_with_storage(pool, create_test_storage()).await
+}
+
+pub async fn app_with_storage(pool: PgPool, storage: Arc<dyn ObjectStorage>) -> Router {
   let email_service = create_test_email_service().await;
-  let storage = create_test_storage().await;
-  let state = SharedAppState::new(pool, email_service, storage).await;
+  let geocoder = Arc::new(crate::geocoding::geonames::tests::sample_geocoder());
+  let state = SharedAppState::new(pool, email_service, storage, geocoder).await;
   create_app(state)
 }
 
@@ -48,6 +84,56 @@ pub async fn post_json<T: Serialize>(app: Router, uri: &str, body: &T) -> (Statu
   (status, body)
 }
 
+pub async fn patch_json_with_auth<T: Serialize>(app: Router, uri: &str, body: &T, token: &str) -> (StatusCode, Bytes) {
+  let request = Request::builder()
+    .method("PATCH")
+    .uri(uri)
+    .header("content-type", "application/json")
+    .header("authorization", format!("Bearer {}", token))
+    .body(Body::from(serde_json::to_vec(body).expect("serialize request body")))
+    .expect("build request");
+
+  let response = app.oneshot(request).await.expect("handle request");
+  let status = response.status();
+  let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+    .await
+    .expect("read response body");
+  (status, body)
+}
+
+pub async fn send_with_auth(app: Router, method: &str, uri: &str, token: &str) -> (StatusCode, Bytes) {
+  let request = Request::builder()
+    .method(method)
+    .uri(uri)
+    .header("authorization", format!("Bearer {}", token))
+    .body(Body::empty())
+    .expect("build request");
+
+  let response = app.oneshot(request).await.expect("handle request");
+  let status = response.status();
+  let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+    .await
+    .expect("read response body");
+  (status, body)
+}
+
+pub async fn post_json_with_auth<T: Serialize>(app: Router, uri: &str, body: &T, token: &str) -> (StatusCode, Bytes) {
+  let request = Request::builder()
+    .method("POST")
+    .uri(uri)
+    .header("content-type", "application/json")
+    .header("authorization", format!("Bearer {}", token))
+    .body(Body::from(serde_json::to_vec(body).expect("serialize request body")))
+    .expect("build request");
+
+  let response = app.oneshot(request).await.expect("handle request");
+  let status = response.status();
+  let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+    .await
+    .expect("read response body");
+  (status, body)
+}
+
 pub async fn get(app: Router, uri: &str) -> (StatusCode, Bytes) {
   let request = Request::builder()
     .method("GET")
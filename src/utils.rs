@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use sha2::{Digest, Sha256};
 use validator::ValidationError;
@@ -5,6 +6,8 @@ use validator::ValidationError;
 pub mod error;
 pub mod geo;
 pub mod jwt;
+pub mod pagination;
+pub mod search;
 
 pub fn hash_password(password: &str) -> String {
   let mut hasher = Sha256::new();
@@ -28,6 +31,14 @@ pub fn validate_password(password: &str) -> Result<(), ValidationError> {
   Ok(())
 }
 
+pub fn validate_future_datetime(value: &DateTime<Utc>) -> Result<(), ValidationError> {
+  if *value <= Utc::now() {
+    return Err(ValidationError::new("期限は未来の日時である必要があります"));
+  }
+
+  Ok(())
+}
+
 pub async fn init_email_service() -> anyhow::Result<crate::email::EmailService> {
   use crate::email::{EmailService, SmtpConfig};
   use std::env;
@@ -90,4 +101,13 @@ mod tests {
     let err = result.unwrap_err();
     assert!(format!("{:?}", err).contains("パスワードには英字を含める必要があります"));
   }
+
+  #[test]
+  fn test_validate_future_datetime() {
+    assert!(validate_future_datetime(&(Utc::now() + chrono::Duration::hours(1))).is_ok());
+
+    let result = validate_future_datetime(&(Utc::now() - chrono::Duration::hours(1)));
+    assert!(result.is_err());
+    assert!(format!("{:?}", result.unwrap_err()).contains("期限は未来の日時である必要があります"));
+  }
 }
@@ -9,6 +9,8 @@ use serde_json::json;
 pub struct AppError {
   pub status_code: StatusCode,
   pub message: String,
+  /// クライアントがエラーに対処するための追加情報（重複候補など）
+  pub details: Option<serde_json::Value>,
 }
 
 impl AppError {
@@ -16,9 +18,15 @@ impl AppError {
     Self {
       status_code,
       message: message.into(),
+      details: None,
     }
   }
 
+  pub fn with_details(mut self, details: serde_json::Value) -> Self {
+    self.details = Some(details);
+    self
+  }
+
   pub fn bad_request(message: impl Into<String>) -> Self {
     Self::new(StatusCode::BAD_REQUEST, message)
   }
@@ -42,12 +50,15 @@ impl AppError {
 
 impl IntoResponse for AppError {
   fn into_response(self) -> Response {
-    let body = Json(json!({
+    let mut body = json!({
       "error": self.message,
       "status_code": self.status_code.as_u16(),
-    }));
+    });
+    if let Some(details) = self.details {
+      body["details"] = details;
+    }
 
-    (self.status_code, body).into_response()
+    (self.status_code, Json(body)).into_response()
   }
 }
 
@@ -109,6 +120,74 @@ impl From<crate::domains::picture::service::PictureServiceError> for AppError {
       PictureServiceError::BadRequest(msg) => AppError::bad_request(msg),
       PictureServiceError::NotFound(msg) => AppError::not_found(msg),
       PictureServiceError::Forbidden(msg) => AppError::forbidden(msg),
+      PictureServiceError::Conflict(msg) => AppError::new(StatusCode::CONFLICT, msg),
+      PictureServiceError::PayloadTooLarge(msg) => AppError::new(StatusCode::PAYLOAD_TOO_LARGE, msg),
+    }
+  }
+}
+
+impl From<crate::domains::request::service::RequestServiceError> for AppError {
+  fn from(error: crate::domains::request::service::RequestServiceError) -> Self {
+    use crate::domains::request::service::RequestServiceError;
+    match error {
+      RequestServiceError::InternalServerError(msg) => AppError::internal_server_error(msg),
+      RequestServiceError::BadRequest(msg) => AppError::bad_request(msg),
+      RequestServiceError::NotFound(msg) => AppError::not_found(msg),
+      RequestServiceError::Forbidden(msg) => AppError::forbidden(msg),
+      RequestServiceError::Conflict(msg) => AppError::new(StatusCode::CONFLICT, msg),
+      RequestServiceError::PossibleDuplicates(duplicates) => {
+        AppError::new(StatusCode::CONFLICT, "Possible duplicate requests found")
+          .with_details(json!({ "duplicates": duplicates }))
+      }
+    }
+  }
+}
+
+impl From<crate::domains::comment::service::CommentServiceError> for AppError {
+  fn from(error: crate::domains::comment::service::CommentServiceError) -> Self {
+    use crate::domains::comment::service::CommentServiceError;
+    match error {
+      CommentServiceError::InternalServerError(msg) => AppError::internal_server_error(msg),
+      CommentServiceError::BadRequest(msg) => AppError::bad_request(msg),
+      CommentServiceError::NotFound(msg) => AppError::not_found(msg),
+      CommentServiceError::Forbidden(msg) => AppError::forbidden(msg),
+    }
+  }
+}
+
+impl From<crate::domains::watch_area::service::WatchAreaServiceError> for AppError {
+  fn from(error: crate::domains::watch_area::service::WatchAreaServiceError) -> Self {
+    use crate::domains::watch_area::service::WatchAreaServiceError;
+    match error {
+      WatchAreaServiceError::InternalServerError(msg) => AppError::internal_server_error(msg),
+      WatchAreaServiceError::BadRequest(msg) => AppError::bad_request(msg),
+      WatchAreaServiceError::NotFound(msg) => AppError::not_found(msg),
+      WatchAreaServiceError::Forbidden(msg) => AppError::forbidden(msg),
+      WatchAreaServiceError::Conflict(msg) => AppError::new(StatusCode::CONFLICT, msg),
+    }
+  }
+}
+
+impl From<crate::domains::tag::service::TagServiceError> for AppError {
+  fn from(error: crate::domains::tag::service::TagServiceError) -> Self {
+    use crate::domains::tag::service::TagServiceError;
+    match error {
+      TagServiceError::InternalServerError(msg) => AppError::internal_server_error(msg),
+      TagServiceError::BadRequest(msg) => AppError::bad_request(msg),
+    }
+  }
+}
+
+impl From<crate::domains::upload::service::UploadServiceError> for AppError {
+  fn from(error: crate::domains::upload::service::UploadServiceError) -> Self {
+    use crate::domains::upload::service::UploadServiceError;
+    match error {
+      UploadServiceError::InternalServerError(msg) => AppError::internal_server_error(msg),
+      UploadServiceError::BadRequest(msg) => AppError::bad_request(msg),
+      UploadServiceError::NotFound(msg) => AppError::not_found(msg),
+      UploadServiceError::Forbidden(msg) => AppError::forbidden(msg),
+      UploadServiceError::Conflict(msg) => AppError::new(StatusCode::CONFLICT, msg),
+      UploadServiceError::PayloadTooLarge(msg) => AppError::new(StatusCode::PAYLOAD_TOO_LARGE, msg),
     }
   }
 }
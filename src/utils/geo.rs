@@ -16,6 +16,15 @@ pub fn haversine_distance(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
   EARTH_RADIUS_KM * c * 1000.0 // km を m に変換
 }
 
+/// 指定した方位（ラジアン、北が0で時計回り）と距離（メートル）だけ移動した地点を返す。数km程度までの近似
+pub fn offset_point(lat: f64, lng: f64, distance_m: f64, bearing_rad: f64) -> (f64, f64) {
+  let distance_rad = distance_m / (EARTH_RADIUS_KM * 1000.0);
+  let delta_lat = distance_rad * bearing_rad.cos();
+  let delta_lng = distance_rad * bearing_rad.sin() / (lat * PI / 180.0).cos();
+
+  (lat + delta_lat * 180.0 / PI, lng + delta_lng * 180.0 / PI)
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -56,4 +65,13 @@ mod tests {
     // 約 5,570km
     assert!(distance > 5500000.0 && distance < 5600000.0);
   }
+
+  #[test]
+  fn test_offset_point_keeps_distance() {
+    for bearing in [0.0, PI / 3.0, PI, 1.5 * PI] {
+      let (lat, lng) = offset_point(35.6812, 139.7671, 1000.0, bearing);
+      let distance = haversine_distance(35.6812, 139.7671, lat, lng);
+      assert!((distance - 1000.0).abs() < 1.0, "distance was {}", distance);
+    }
+  }
 }
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+pub const DEFAULT_PER_PAGE: i64 = 20;
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// ページ番号（1始まり）と1ページあたりの件数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+  pub page: i64,
+  pub per_page: i64,
+}
+
+impl Page {
+  /// クエリパラメータから検証済みのページ指定を作る。未指定の場合は既定値を使う
+  pub fn new(page: Option<i64>, per_page: Option<i64>) -> Result<Self, String> {
+    let page = page.unwrap_or(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE);
+
+    if page < 1 {
+      return Err("page must be at least 1".to_string());
+    }
+    if !(1..=MAX_PER_PAGE).contains(&per_page) {
+      return Err(format!("per_page must be between 1 and {}", MAX_PER_PAGE));
+    }
+
+    Ok(Self { page, per_page })
+  }
+
+  pub fn limit(&self) -> i64 {
+    self.per_page
+  }
+
+  pub fn offset(&self) -> i64 {
+    (self.page - 1).saturating_mul(self.per_page)
+  }
+}
+
+/// `?page=&per_page=` のみを受け取る一覧エンドポイント用のクエリ
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+  pub page: Option<i64>,
+  pub per_page: Option<i64>,
+}
+
+impl TryFrom<PageQuery> for Page {
+  type Error = String;
+
+  fn try_from(query: PageQuery) -> Result<Self, Self::Error> {
+    Page::new(query.page, query.per_page)
+  }
+}
+
+impl Default for Page {
+  fn default() -> Self {
+    Self {
+      page: 1,
+      per_page: DEFAULT_PER_PAGE,
+    }
+  }
+}
+
+/// キーセットページネーションの位置。前のページの最後の行の作成日時とID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+  pub created_at: DateTime<Utc>,
+  pub id: i32,
+}
+
+impl Cursor {
+  /// `{作成日時のUNIXマイクロ秒}_{ID}` の形式でクライアントに返す
+  pub fn encode(&self) -> String {
+    format!("{}_{}", self.created_at.timestamp_micros(), self.id)
+  }
+
+  pub fn decode(value: &str) -> Result<Self, String> {
+    let invalid = || "cursor is invalid".to_string();
+
+    let (micros, id) = value.split_once('_').ok_or_else(invalid)?;
+    let micros: i64 = micros.parse().map_err(|_| invalid())?;
+    let id: i32 = id.parse().map_err(|_| invalid())?;
+    let created_at = DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?;
+
+    Ok(Self { created_at, id })
+  }
+}
+
+/// 検証済みのカーソルと1ページあたりの件数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorPage {
+  pub after: Option<Cursor>,
+  pub limit: i64,
+}
+
+impl CursorPage {
+  pub fn new(cursor: Option<&str>, limit: Option<i64>) -> Result<Self, String> {
+    let limit = limit.unwrap_or(DEFAULT_PER_PAGE);
+    if !(1..=MAX_PER_PAGE).contains(&limit) {
+      return Err(format!("limit must be between 1 and {}", MAX_PER_PAGE));
+    }
+
+    let after = cursor.map(Cursor::decode).transpose()?;
+
+    Ok(Self { after, limit })
+  }
+
+  /// 次のページがあるかを判定するため、1件多く取得する
+  pub fn fetch_limit(&self) -> i64 {
+    self.limit + 1
+  }
+}
+
+impl Default for CursorPage {
+  fn default() -> Self {
+    Self {
+      after: None,
+      limit: DEFAULT_PER_PAGE,
+    }
+  }
+}
+
+/// `?cursor=&limit=` を受け取るキーセットページネーション用のクエリ
+#[derive(Debug, Deserialize)]
+pub struct CursorQuery {
+  pub cursor: Option<String>,
+  pub limit: Option<i64>,
+}
+
+impl TryFrom<CursorQuery> for CursorPage {
+  type Error = String;
+
+  fn try_from(query: CursorQuery) -> Result<Self, Self::Error> {
+    CursorPage::new(query.cursor.as_deref(), query.limit)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_page_defaults() {
+    let page = Page::new(None, None).unwrap();
+    assert_eq!(page, Page::default());
+    assert_eq!(page.offset(), 0);
+  }
+
+  #[test]
+  fn test_page_offset() {
+    let page = Page::new(Some(3), Some(10)).unwrap();
+    assert_eq!(page.limit(), 10);
+    assert_eq!(page.offset(), 20);
+  }
+
+  #[test]
+  fn test_page_rejects_out_of_range() {
+    assert!(Page::new(Some(0), None).is_err());
+    assert!(Page::new(None, Some(0)).is_err());
+    assert!(Page::new(None, Some(MAX_PER_PAGE + 1)).is_err());
+  }
+
+  #[test]
+  fn test_cursor_round_trip() {
+    let cursor = Cursor {
+      created_at: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+      id: 42,
+    };
+    assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+  }
+
+  #[test]
+  fn test_cursor_page_rejects_invalid_input() {
+    assert!(CursorPage::new(Some("garbage"), None).is_err());
+    assert!(CursorPage::new(Some("123_abc"), None).is_err());
+    assert!(CursorPage::new(None, Some(0)).is_err());
+    assert!(CursorPage::new(None, Some(MAX_PER_PAGE + 1)).is_err());
+    assert_eq!(CursorPage::new(None, None).unwrap(), CursorPage::default());
+  }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+const HIGHLIGHT_OPEN: &str = "<mark>";
+const HIGHLIGHT_CLOSE: &str = "</mark>";
+const ELLIPSIS: char = '…';
+
+/// 完全一致がないときに、綴りの揺れを含む箇所とみなすトライグラム類似度の下限
+const FUZZY_MATCH_THRESHOLD: f64 = 0.3;
+/// 綴りの揺れを探すとき、`query` より何文字まで短い・長い箇所を比べるか
+const FUZZY_LENGTH_SLACK: usize = 2;
+
+/// `text` 中の `query` の一致箇所（大文字小文字を区別しない）を `<mark>` で囲み、
+/// 最初の一致の前後 `context` 文字を切り出したスニペットを返す。
+/// 完全に一致する箇所がなければトライグラム類似度が最も高い箇所を囲み、それも十分似ていなければ `None`。
+/// `text` はユーザーの入力なので、HTML として解釈されないようエスケープしてから `<mark>` を付ける
+pub fn highlight_snippet(text: &str, query: &str, context: usize) -> Option<String> {
+  let text_chars: Vec<char> = text.chars().collect();
+  let query_chars: Vec<char> = query.trim().chars().collect();
+
+  if query_chars.is_empty() || text_chars.is_empty() {
+    return None;
+  }
+
+  let mut ranges = exact_matches(&text_chars, &query_chars);
+  if ranges.is_empty() {
+    ranges.push(closest_match(&text_chars, &query_chars)?);
+  }
+
+  let (first_start, first_end) = ranges[0];
+  let start = first_start.saturating_sub(context);
+  let end = (first_end + context).min(text_chars.len());
+
+  let mut snippet = String::new();
+  if start > 0 {
+    snippet.push(ELLIPSIS);
+  }
+
+  let mut i = start;
+  for &(match_start, match_end) in ranges.iter().filter(|&&(_, match_end)| match_end <= end) {
+    push_escaped(&mut snippet, &text_chars[i..match_start]);
+    snippet.push_str(HIGHLIGHT_OPEN);
+    push_escaped(&mut snippet, &text_chars[match_start..match_end]);
+    snippet.push_str(HIGHLIGHT_CLOSE);
+    i = match_end;
+  }
+  push_escaped(&mut snippet, &text_chars[i..end]);
+
+  if end < text_chars.len() {
+    snippet.push(ELLIPSIS);
+  }
+
+  Some(snippet)
+}
+
+/// 大文字小文字を区別せずに `query` と一致する、互いに重ならない範囲
+fn exact_matches(text: &[char], query: &[char]) -> Vec<(usize, usize)> {
+  let mut ranges = Vec::new();
+  if query.len() > text.len() {
+    return ranges;
+  }
+
+  let mut i = 0;
+  while i + query.len() <= text.len() {
+    let matched = text[i..i + query.len()]
+      .iter()
+      .zip(query)
+      .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()));
+    if matched {
+      ranges.push((i, i + query.len()));
+      i += query.len();
+    } else {
+      i += 1;
+    }
+  }
+
+  ranges
+}
+
+/// `query` と長さの近い範囲のうち、トライグラム類似度が最も高いもの
+fn closest_match(text: &[char], query: &[char]) -> Option<(usize, usize)> {
+  let query_trigrams = trigrams(query);
+  let min_len = query.len().saturating_sub(FUZZY_LENGTH_SLACK).max(1);
+  let max_len = (query.len() + FUZZY_LENGTH_SLACK).min(text.len());
+
+  let mut best: Option<(f64, usize, usize)> = None;
+  for len in min_len..=max_len {
+    for start in 0..=text.len() - len {
+      let score = similarity(&trigrams(&text[start..start + len]), &query_trigrams);
+      if score >= FUZZY_MATCH_THRESHOLD && best.is_none_or(|(best_score, _, _)| score > best_score) {
+        best = Some((score, start, start + len));
+      }
+    }
+  }
+
+  // 英単語の途中で切れないよう、前後に続く英数字まで広げる
+  let (_, mut start, mut end) = best?;
+  while start > 0 && text[start - 1].is_ascii_alphanumeric() && text[start].is_ascii_alphanumeric() {
+    start -= 1;
+  }
+  while end < text.len() && text[end - 1].is_ascii_alphanumeric() && text[end].is_ascii_alphanumeric() {
+    end += 1;
+  }
+
+  Some((start, end))
+}
+
+/// pg_trgm と同じく小文字にして前に空白2つ、後ろに空白1つを足し、3文字ずつ取り出す
+fn trigrams(chars: &[char]) -> HashSet<[char; 3]> {
+  let padded: Vec<char> = "  "
+    .chars()
+    .chain(chars.iter().flat_map(|c| c.to_lowercase()))
+    .chain(" ".chars())
+    .collect();
+  padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+fn similarity(a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f64 {
+  let common = a.intersection(b).count();
+  let union = a.len() + b.len() - common;
+  if union == 0 {
+    0.0
+  } else {
+    common as f64 / union as f64
+  }
+}
+
+fn push_escaped(out: &mut String, chars: &[char]) {
+  for &c in chars {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&#39;"),
+      _ => out.push(c),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_highlight_snippet_japanese() {
+    let snippet = highlight_snippet("東京スカイツリーの夜景", "スカイツリー", 10);
+    assert_eq!(snippet, Some("東京<mark>スカイツリー</mark>の夜景".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_case_insensitive() {
+    let snippet = highlight_snippet("Tokyo Tower at night", "tower", 20);
+    assert_eq!(snippet, Some("Tokyo <mark>Tower</mark> at night".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_truncates_context() {
+    let snippet = highlight_snippet("あいうえお渋谷かきくけこ", "渋谷", 2);
+    assert_eq!(snippet, Some("…えお<mark>渋谷</mark>かき…".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_multiple_matches() {
+    let snippet = highlight_snippet("渋谷から渋谷へ", "渋谷", 10);
+    assert_eq!(snippet, Some("<mark>渋谷</mark>から<mark>渋谷</mark>へ".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_escapes_html() {
+    let snippet = highlight_snippet("<script>alert('x')</script> & \"渋谷\"", "渋谷", 50);
+    assert_eq!(
+      snippet,
+      Some("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt; &amp; &quot;<mark>渋谷</mark>&quot;".to_string())
+    );
+
+    let snippet = highlight_snippet("a<b>c", "<b>", 10);
+    assert_eq!(snippet, Some("a<mark>&lt;b&gt;</mark>c".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_fuzzy_match() {
+    let snippet = highlight_snippet("Tokyo Tower at night", "tokyo towr", 20);
+    assert_eq!(snippet, Some("<mark>Tokyo Tower</mark> at night".to_string()));
+  }
+
+  #[test]
+  fn test_highlight_snippet_no_match() {
+    assert_eq!(highlight_snippet("新宿駅", "渋谷", 10), None);
+    assert_eq!(highlight_snippet("新宿駅", "", 10), None);
+  }
+}